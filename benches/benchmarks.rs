@@ -1,5 +1,7 @@
-use chirp8::{Chirp8, Chirp8Mode};
-use criterion::{criterion_group, criterion_main, Criterion};
+use chirp8::{
+    Chirp8, Chirp8Mode, QuirkFlags, DISPLAY_HEIGHT, DISPLAY_WIDTH, PIXEL_OFF, RAM_SIZE,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 
 fn criterion_benchmark(c: &mut Criterion) {
 
@@ -71,5 +73,102 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, criterion_benchmark);
+/// Benchmarks the execution of test roms from the beginning, run with and without the
+/// `decode_cache` feature to compare the interpretation speed.
+fn roms_benchmark(c: &mut Criterion) {
+    /// Number of steps taken by the emulator, enough for these roms to complete.
+    const STEPS: usize = 1000;
+
+    let roms: [(&str, &[u8]); 4] = [
+        (
+            "chip8-logo",
+            include_bytes!("../submodules/chip8-test-suite/bin/1-chip8-logo.ch8"),
+        ),
+        (
+            "ibm-logo",
+            include_bytes!("../submodules/chip8-test-suite/bin/2-ibm-logo.ch8"),
+        ),
+        (
+            "corax+",
+            include_bytes!("../submodules/chip8-test-suite/bin/3-corax+.ch8"),
+        ),
+        (
+            "flags",
+            include_bytes!("../submodules/chip8-test-suite/bin/4-flags.ch8"),
+        ),
+    ];
+
+    #[rustfmt::skip]
+    let arithmetic_loop: &[u8] = &[
+        0x60, 0x00, // 200 : v0 = 0
+        0x70, 0x01, // 202 : v0 += 1
+        0x81, 0x04, // 204 : v1 += v0
+        0x82, 0x13, // 206 : v2 ^= v1
+        0x30, 0x00, // 208 : skip if v0 == 0
+        0x12, 0x02, // 20A : jump 202
+        0x12, 0x00, // 20C : jump 200
+    ];
+    // Does not depend on the test suite, so that it can always be compared.
+    let roms = roms.into_iter().chain([("arithmetic-loop", arithmetic_loop)]);
+
+    for (name, rom) in roms {
+        c.bench_function(&format!("Run rom {} {} steps", name, STEPS), move |b| {
+            b.iter_batched(
+                || {
+                    // No "display wait" quirk, so that the roms run at full speed.
+                    let mut emulator = Chirp8::new(Chirp8Mode::SuperChipModern);
+                    emulator.load_rom(rom);
+                    emulator
+                },
+                |mut emulator| emulator.take_steps(STEPS),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+}
+
+/// Benchmarks scrolls and sprites on the contiguous display buffer of the emulator, and on rows
+/// of pixels allocated separately to compare both layouts.
+fn display_layout_benchmark(c: &mut Criterion) {
+    let programs: [(&str, &[u8]); 4] = [
+        // Enable hi-res, then scroll or draw a 15-high sprite.
+        ("Scroll right", &[0x00, 0xFF, 0x00, 0xFB]),
+        ("Scroll left", &[0x00, 0xFF, 0x00, 0xFC]),
+        ("Scroll down 4", &[0x00, 0xFF, 0x00, 0xC4]),
+        ("Draw h15 high-res", &[0x00, 0xFF, 0xD0, 0x1F]),
+    ];
+
+    for (name, program) in programs {
+        let mut emulator = Chirp8::new(Chirp8Mode::SuperChipModern);
+        emulator.load_rom(program);
+        c.bench_function(&format!("{} contiguous display", name), move |b| {
+            b.iter(|| {
+                emulator.take_steps(2);
+                emulator.reset();
+            })
+        });
+
+        let mode = Chirp8Mode::SuperChipModern;
+        let mut emulator = Chirp8::with_bus_and_display(
+            mode,
+            QuirkFlags::from_mode(mode),
+            vec![0; RAM_SIZE],
+            vec![vec![PIXEL_OFF; DISPLAY_WIDTH]; DISPLAY_HEIGHT],
+        );
+        emulator.load_rom(program);
+        c.bench_function(&format!("{} display rows", name), move |b| {
+            b.iter(|| {
+                emulator.take_steps(2);
+                emulator.reset();
+            })
+        });
+    }
+}
+
+criterion_group!(
+    benches,
+    criterion_benchmark,
+    roms_benchmark,
+    display_layout_benchmark
+);
 criterion_main!(benches);
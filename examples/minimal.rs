@@ -20,7 +20,7 @@ fn main() {
     // The display buffer, the pixels array, can be accessed as follows.
     // Also try `display_changed()` to know if the screen needs to be redrawn.
     let screen = chirp8.get_display_buffer();
-    for pixel_row in screen {
+    for pixel_row in screen.rows() {
         for pixel in pixel_row {
             // If a pixel is ON, printing a black square.
             if *pixel == chirp8::PIXEL_ON {
@@ -114,7 +114,7 @@ async fn main() {
             COLOR_OFF
         };
         clear_background(background);
-        for (i, row) in emulator.get_display_buffer().iter().enumerate() {
+        for (i, row) in emulator.get_display_buffer().rows().enumerate() {
             for (j, pixel) in row.iter().enumerate() {
                 if *pixel != 0 {
                     let color = (*pixel as f32) / (u8::MAX as f32);
@@ -0,0 +1,222 @@
+#ifndef CHIRP8_H
+#define CHIRP8_H
+
+/* Generated with cbindgen from src/ffi.rs, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Original Cosmac VIP chip-8 mode, see [Chirp8Mode::CosmacChip8].
+ */
+#define CHIRP8_MODE_COSMAC_CHIP8 0
+
+/**
+ * Super-Chip 1.1 mode, see [Chirp8Mode::SuperChip1_1].
+ */
+#define CHIRP8_MODE_SUPER_CHIP_1_1 1
+
+/**
+ * Modern Super-Chip mode, see [Chirp8Mode::SuperChipModern].
+ */
+#define CHIRP8_MODE_SUPER_CHIP_MODERN 2
+
+/**
+ * XO-Chip mode, see [Chirp8Mode::XOChip].
+ */
+#define CHIRP8_MODE_XO_CHIP 3
+
+/**
+ * Number of pixels of each row of the display, see [chirp8_display_buffer].
+ */
+#define CHIRP8_DISPLAY_WIDTH 128
+
+/**
+ * Number of rows of the display, see [chirp8_display_buffer].
+ */
+#define CHIRP8_DISPLAY_HEIGHT 64
+
+/**
+ * Opaque handle to an emulator, as seen from C.
+ *
+ * With the `packed_display` feature, the display is copied to a contiguous buffer when
+ * requested with [chirp8_display_buffer].
+ */
+typedef struct Chirp8Handle Chirp8Handle;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+#if defined(CHIRP8_ALLOC)
+/**
+ * Creates an emulator running in given `mode`, one of the `CHIRP8_MODE_*` constants.
+ * Returns null if the mode is unknown. The emulator must be freed with [chirp8_free].
+ */
+struct Chirp8Handle *chirp8_new(uint32_t mode);
+#endif
+
+#if defined(CHIRP8_ALLOC)
+/**
+ * Frees an emulator created with [chirp8_new]. Does nothing if `handle` is null.
+ *
+ * # Safety
+ * `handle` must come from [chirp8_new] and must not be used afterwards.
+ */
+void chirp8_free(struct Chirp8Handle *handle);
+#endif
+
+/**
+ * Returns the number of bytes needed by [chirp8_init].
+ */
+size_t chirp8_handle_size(void);
+
+/**
+ * Returns the alignment needed by [chirp8_init].
+ */
+size_t chirp8_handle_align(void);
+
+/**
+ * Creates an emulator running in given `mode` in caller-provided `storage` of `size` bytes,
+ * without allocating. Returns null if the mode is unknown or needs more memory than the default
+ * [crate::Ram], on XO-Chip without `alloc`, or if the storage is too small or misaligned, see
+ * [chirp8_handle_size] and [chirp8_handle_align]. The emulator must be destroyed with
+ * [chirp8_deinit].
+ *
+ * # Safety
+ * `storage` must be valid for writes of `size` bytes for as long as the emulator is used.
+ */
+struct Chirp8Handle *chirp8_init(void *storage, size_t size, uint32_t mode);
+
+/**
+ * Destroys an emulator created with [chirp8_init], its storage can be reused afterwards.
+ * Does nothing if `handle` is null.
+ *
+ * # Safety
+ * `handle` must come from [chirp8_init] and must not be used afterwards.
+ */
+void chirp8_deinit(struct Chirp8Handle *handle);
+
+/**
+ * Loads the `length` bytes long `rom`, see [Chirp8::load_rom].
+ * Returns true if the ROM has been loaded.
+ *
+ * # Safety
+ * `handle` must be a valid emulator or null, `rom` must be valid for reads of `length` bytes.
+ */
+bool chirp8_load_rom(struct Chirp8Handle *handle, const uint8_t *rom, size_t length);
+
+/**
+ * Resets the emulator to the beginning of the program, see [Chirp8::reset].
+ *
+ * # Safety
+ * `handle` must be a valid emulator or null.
+ */
+void chirp8_reset(struct Chirp8Handle *handle);
+
+/**
+ * Presses or releases given `key`, between 0x0 and 0xF, see [Chirp8::key_set].
+ *
+ * # Safety
+ * `handle` must be a valid emulator or null.
+ */
+void chirp8_key_set(struct Chirp8Handle *handle, uint8_t key, bool pressed);
+
+/**
+ * Runs as many instructions as necessary to generate a frame, see [Chirp8::run_frame].
+ *
+ * # Safety
+ * `handle` must be a valid emulator or null.
+ */
+void chirp8_run_frame(struct Chirp8Handle *handle);
+
+/**
+ * Indicates if the display changed since the last call, see [Chirp8::display_changed].
+ *
+ * # Safety
+ * `handle` must be a valid emulator or null.
+ */
+bool chirp8_display_changed(struct Chirp8Handle *handle);
+
+/**
+ * Returns the display, `CHIRP8_DISPLAY_HEIGHT` rows of `CHIRP8_DISPLAY_WIDTH` pixels, one byte
+ * per pixel. Rows are `*stride` bytes apart. Returns null if `handle` is null.
+ *
+ * The buffer is valid until the next call to any function with this handle. It is the display
+ * of the emulator itself, or a copy of it with the `packed_display` feature.
+ *
+ * # Safety
+ * `handle` must be a valid emulator or null, `stride` must be valid for writes or null.
+ */
+const uint8_t *chirp8_display_buffer(struct Chirp8Handle *handle, size_t *stride);
+
+/**
+ * Indicates whether the sound is on, see [Chirp8::is_sounding].
+ *
+ * # Safety
+ * `handle` must be a valid emulator or null.
+ */
+bool chirp8_is_sounding(const struct Chirp8Handle *handle);
+
+/**
+ * Indicates whether the sound is made of the audio buffer, rather than a simple buzzer.
+ * See [Chirp8::has_sound_wave].
+ *
+ * # Safety
+ * `handle` must be a valid emulator or null.
+ */
+bool chirp8_has_sound_wave(const struct Chirp8Handle *handle);
+
+/**
+ * Returns the 16 bytes long XO-Chip audio buffer, each bit being played at the rate given by
+ * [chirp8_audio_bit_rate_log2_hz]. Returns null if `handle` is null.
+ *
+ * # Safety
+ * `handle` must be a valid emulator or null.
+ */
+const uint8_t *chirp8_audio_buffer(const struct Chirp8Handle *handle);
+
+/**
+ * Returns the base 2 logarithm of the XO-Chip audio bit rate in Hertz,
+ * see [Chirp8::get_audio_bit_rate_log2_hz].
+ *
+ * # Safety
+ * `handle` must be a valid emulator or null.
+ */
+float chirp8_audio_bit_rate_log2_hz(const struct Chirp8Handle *handle);
+
+/**
+ * Returns the number of bytes of a save state of the emulator, see [Chirp8::state_size].
+ * Returns 0 if `handle` is null.
+ *
+ * # Safety
+ * `handle` must be a valid emulator or null.
+ */
+size_t chirp8_state_size(const struct Chirp8Handle *handle);
+
+/**
+ * Saves the emulator state to `state`, `length` bytes long, see [Chirp8::save_state].
+ * Returns true on success, false if the state is too short.
+ *
+ * # Safety
+ * `handle` must be a valid emulator or null, `state` must be valid for writes of `length` bytes.
+ */
+bool chirp8_save_state(struct Chirp8Handle *handle, uint8_t *state, size_t length);
+
+/**
+ * Restores the emulator state from `state`, `length` bytes long, see [Chirp8::load_state].
+ * Returns true on success, false if the state is invalid.
+ *
+ * # Safety
+ * `handle` must be a valid emulator or null, `state` must be valid for reads of `length` bytes.
+ */
+bool chirp8_load_state(struct Chirp8Handle *handle, const uint8_t *state, size_t length);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIRP8_H */
@@ -0,0 +1,693 @@
+//! libretro core running the chirp8 emulator, to be loaded by RetroArch and other frontends.
+//!
+//! The 16 keys of the keypad are mapped onto the RetroPad, the mode, quirks, speed and palette are
+//! core options, and save states are supported.
+
+// The entry points are called by the frontend, following the contracts of `libretro.h`.
+#![allow(clippy::missing_safety_doc)]
+
+mod retro;
+
+use std::ffi::{c_char, c_uint, c_void, CStr, CString};
+use std::sync::{Mutex, OnceLock};
+
+use chirp8::{
+    Chirp8, Chirp8Mode, DisplayPixels, Palette, QuirkFlags, DISPLAY_HEIGHT, DISPLAY_WIDTH,
+};
+use retro::*;
+
+/// Rate of the audio samples sent to the frontend.
+const SAMPLE_RATE_HZ: f64 = 44100.0;
+/// Number of stereo audio samples sent every frame.
+const SAMPLES_PER_FRAME: usize = SAMPLE_RATE_HZ as usize / chirp8::REFRESH_RATE_HZ;
+/// Frequency of the buzzer of interpreters without audio buffer.
+const BUZZER_FREQUENCY_HZ: f64 = 440.0;
+/// Amplitude of the audio samples.
+const VOLUME: i16 = 0x1000;
+/// Number of bits in the XO-Chip audio buffer.
+const AUDIO_BUFFER_BITS: f64 = 128.0;
+
+/// Keypad key of every RetroPad button, along with its description.
+const KEY_MAP: [(c_uint, u8, &CStr); 16] = [
+    (RETRO_DEVICE_ID_JOYPAD_UP, 0x2, c"Key 2 (up)"),
+    (RETRO_DEVICE_ID_JOYPAD_DOWN, 0x8, c"Key 8 (down)"),
+    (RETRO_DEVICE_ID_JOYPAD_LEFT, 0x4, c"Key 4 (left)"),
+    (RETRO_DEVICE_ID_JOYPAD_RIGHT, 0x6, c"Key 6 (right)"),
+    (RETRO_DEVICE_ID_JOYPAD_A, 0x5, c"Key 5 (action)"),
+    (RETRO_DEVICE_ID_JOYPAD_B, 0x0, c"Key 0"),
+    (RETRO_DEVICE_ID_JOYPAD_X, 0x1, c"Key 1"),
+    (RETRO_DEVICE_ID_JOYPAD_Y, 0x3, c"Key 3"),
+    (RETRO_DEVICE_ID_JOYPAD_L, 0x7, c"Key 7"),
+    (RETRO_DEVICE_ID_JOYPAD_R, 0x9, c"Key 9"),
+    (RETRO_DEVICE_ID_JOYPAD_L2, 0xA, c"Key A"),
+    (RETRO_DEVICE_ID_JOYPAD_R2, 0xB, c"Key B"),
+    (RETRO_DEVICE_ID_JOYPAD_L3, 0xC, c"Key C"),
+    (RETRO_DEVICE_ID_JOYPAD_R3, 0xD, c"Key D"),
+    (RETRO_DEVICE_ID_JOYPAD_SELECT, 0xE, c"Key E"),
+    (RETRO_DEVICE_ID_JOYPAD_START, 0xF, c"Key F"),
+];
+
+/// The modes selectable in the core options, the first one being the default.
+const MODES: [(&str, Chirp8Mode); 4] = [
+    ("Super-Chip modern", Chirp8Mode::SuperChipModern),
+    ("Chip-8", Chirp8Mode::CosmacChip8),
+    ("Super-Chip 1.1", Chirp8Mode::SuperChip1_1),
+    ("XO-Chip", Chirp8Mode::XOChip),
+];
+
+/// The speeds, in instructions per frame, selectable in the core options besides the default
+/// speed of the mode.
+const SPEEDS: [usize; 10] = [5, 10, 15, 20, 30, 50, 100, 200, 500, 1000];
+
+/// The palettes selectable in the core options, the first one being the default.
+const PALETTES: [(&str, Palette); 4] = [
+    ("High contrast", Palette::HIGH_CONTRAST),
+    ("Octo", Palette::OCTO),
+    ("LCD green", Palette::LCD_GREEN),
+    ("Amber", Palette::AMBER),
+];
+
+const OPTION_MODE: &str = "chirp8_mode";
+const OPTION_SPEED: &str = "chirp8_speed";
+const OPTION_PALETTE: &str = "chirp8_palette";
+/// Prefix of the options of every quirk, followed by the lower case name of the quirk.
+const OPTION_QUIRK_PREFIX: &str = "chirp8_quirk_";
+
+/// The configuration of the core, from its options.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Options {
+    mode: Chirp8Mode,
+    quirks: QuirkFlags,
+    /// Instructions per frame, the default of the mode if absent.
+    speed: Option<usize>,
+    /// Colors of the pixels, see [PALETTES].
+    palette: Palette,
+}
+
+impl Options {
+    /// Reads the options, with `get` returning the value of an option from its key.
+    /// Unset or unknown values are replaced with defaults.
+    fn read(get: impl Fn(&str) -> Option<String>) -> Self {
+        let mode = get(OPTION_MODE)
+            .and_then(|value| MODES.iter().find(|(name, _)| *name == value))
+            .map_or(MODES[0].1, |(_, mode)| *mode);
+
+        let mut quirks = QuirkFlags::from_mode(mode);
+        for (name, quirk) in QuirkFlags::all().iter_names() {
+            let key = std::format!("{}{}", OPTION_QUIRK_PREFIX, name.to_lowercase());
+            match get(&key).as_deref() {
+                Some("enabled") => quirks.insert(quirk),
+                Some("disabled") => quirks.remove(quirk),
+                _ => {}
+            }
+        }
+
+        Self {
+            mode,
+            quirks,
+            speed: get(OPTION_SPEED).and_then(|value| value.parse().ok()),
+            palette: get(OPTION_PALETTE)
+                .and_then(|value| PALETTES.iter().find(|(name, _)| *name == value))
+                .map_or(PALETTES[0].1, |(_, palette)| *palette),
+        }
+    }
+}
+
+/// Returns the option definitions given to the frontend, as keys and `Description; value|value`.
+fn variables() -> &'static [(CString, CString)] {
+    static VARIABLES: OnceLock<Vec<(CString, CString)>> = OnceLock::new();
+    VARIABLES.get_or_init(|| {
+        let join = |values: Vec<String>| values.join("|");
+        let mut variables = vec![
+            (
+                OPTION_MODE.to_string(),
+                std::format!(
+                    "Mode; {}",
+                    join(MODES.iter().map(|(name, _)| name.to_string()).collect())
+                ),
+            ),
+            (
+                OPTION_SPEED.to_string(),
+                std::format!(
+                    "Instructions per frame; default|{}",
+                    join(SPEEDS.iter().map(|speed| speed.to_string()).collect())
+                ),
+            ),
+            (
+                OPTION_PALETTE.to_string(),
+                std::format!(
+                    "Palette; {}",
+                    join(PALETTES.iter().map(|(name, _)| name.to_string()).collect())
+                ),
+            ),
+        ];
+        for (name, _) in QuirkFlags::all().iter_names() {
+            variables.push((
+                std::format!("{}{}", OPTION_QUIRK_PREFIX, name.to_lowercase()),
+                std::format!("Quirk {}; default|enabled|disabled", name),
+            ));
+        }
+        variables
+            .into_iter()
+            .map(|(key, value)| (CString::new(key).unwrap(), CString::new(value).unwrap()))
+            .collect()
+    })
+}
+
+/// A loaded game.
+struct Core {
+    emulator: Chirp8,
+    rom: Vec<u8>,
+    options: Options,
+    /// XRGB8888 pixels sent to the frontend.
+    frame: Vec<u32>,
+    /// Interleaved stereo samples sent to the frontend.
+    audio: Vec<i16>,
+    /// Position in the sound wave, in periods of the buzzer or in bits of the audio buffer.
+    audio_phase: f64,
+}
+
+impl Core {
+    /// Creates the emulator and loads `rom`, returns `None` if the ROM is too large.
+    fn new(rom: &[u8], options: Options) -> Option<Self> {
+        let mut core = Self {
+            emulator: Chirp8::new(options.mode),
+            rom: rom.to_vec(),
+            options,
+            frame: vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            audio: vec![0; SAMPLES_PER_FRAME * 2],
+            audio_phase: 0.0,
+        };
+        core.restart().then_some(core)
+    }
+
+    /// Creates a new emulator running the ROM from the beginning.
+    /// Returns false, keeping the current emulator, if the ROM does not fit in its memory.
+    fn restart(&mut self) -> bool {
+        let mut emulator = Chirp8::with_custom_quirks(self.options.mode, self.options.quirks);
+        if let Some(speed) = self.options.speed {
+            emulator.set_steps_per_frame(speed);
+        }
+        if !emulator.load_rom(&self.rom) {
+            return false;
+        }
+        self.emulator = emulator;
+        true
+    }
+
+    /// Applies new `options`, restarting the game if the mode or quirks changed.
+    /// Returns false, keeping the previous options and the game running, if the ROM does not fit
+    /// in the memory of the new mode.
+    fn configure(&mut self, options: Options) -> bool {
+        let previous = core::mem::replace(&mut self.options, options);
+        if previous.mode != options.mode || previous.quirks != options.quirks {
+            if !self.restart() {
+                self.options = previous;
+                return false;
+            }
+        } else if previous.speed != options.speed {
+            let speed = options
+                .speed
+                .unwrap_or_else(|| Chirp8::new(options.mode).steps_per_frame());
+            self.emulator.set_steps_per_frame(speed);
+        }
+        true
+    }
+
+    /// Runs a frame with the keys reported by `pressed` for every RetroPad button,
+    /// then renders the video frame and audio samples.
+    fn run(&mut self, pressed: impl Fn(c_uint) -> bool) {
+        for (button, key, _) in KEY_MAP {
+            self.emulator.key_set(key, pressed(button));
+        }
+        self.emulator.run_frame();
+        self.render_video();
+        self.render_audio();
+    }
+
+    fn render_video(&mut self) {
+        let display = self.emulator.get_display_buffer();
+        let palette = self
+            .options
+            .palette
+            .with_planes(self.emulator.display_planes());
+        for (y, output) in self.frame.chunks_exact_mut(DISPLAY_WIDTH).enumerate() {
+            for (x, color) in output.iter_mut().enumerate() {
+                // XRGB8888 is the 0xRRGGBB color of the palette.
+                *color = palette.color(display.pixel(x, y));
+            }
+        }
+    }
+
+    fn render_audio(&mut self) {
+        if !self.emulator.is_sounding() {
+            self.audio.fill(0);
+            self.audio_phase = 0.0;
+            return;
+        }
+
+        let wave = *self.emulator.get_audio_buffer();
+        let (step, period) = if self.emulator.has_sound_wave() {
+            let rate = self.emulator.get_audio_bit_rate_hz() as f64;
+            (rate / SAMPLE_RATE_HZ, AUDIO_BUFFER_BITS)
+        } else {
+            (BUZZER_FREQUENCY_HZ / SAMPLE_RATE_HZ, 1.0)
+        };
+        let has_sound_wave = self.emulator.has_sound_wave();
+        for sample in self.audio.chunks_exact_mut(2) {
+            let high = if has_sound_wave {
+                let bit = self.audio_phase as usize;
+                wave[bit / 8] & (0x80 >> (bit % 8)) != 0
+            } else {
+                self.audio_phase < 0.5
+            };
+            sample.fill(if high { VOLUME } else { -VOLUME });
+            self.audio_phase = (self.audio_phase + step) % period;
+        }
+    }
+}
+
+/// The callbacks given by the frontend.
+#[derive(Default)]
+struct Frontend {
+    environment: Option<retro_environment_t>,
+    video_refresh: Option<retro_video_refresh_t>,
+    audio_sample_batch: Option<retro_audio_sample_batch_t>,
+    input_poll: Option<retro_input_poll_t>,
+    input_state: Option<retro_input_state_t>,
+}
+
+static FRONTEND: Mutex<Frontend> = Mutex::new(Frontend {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+fn frontend() -> std::sync::MutexGuard<'static, Frontend> {
+    FRONTEND.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+fn core() -> std::sync::MutexGuard<'static, Option<Core>> {
+    CORE.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+/// Reads the core options through the `environment` callback.
+unsafe fn read_options(environment: retro_environment_t) -> Options {
+    Options::read(|key| {
+        let key = CString::new(key).ok()?;
+        let mut variable = retro_variable {
+            key: key.as_ptr(),
+            value: std::ptr::null(),
+        };
+        let found = environment(
+            RETRO_ENVIRONMENT_GET_VARIABLE,
+            &mut variable as *mut _ as *mut c_void,
+        );
+        (found && !variable.value.is_null()).then(|| {
+            CStr::from_ptr(variable.value)
+                .to_string_lossy()
+                .into_owned()
+        })
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_environment(environment: retro_environment_t) {
+    frontend().environment = Some(environment);
+
+    let mut definitions: Vec<retro_variable> = variables()
+        .iter()
+        .map(|(key, value)| retro_variable {
+            key: key.as_ptr(),
+            value: value.as_ptr(),
+        })
+        .collect();
+    definitions.push(retro_variable {
+        key: std::ptr::null(),
+        value: std::ptr::null(),
+    });
+    environment(
+        RETRO_ENVIRONMENT_SET_VARIABLES,
+        definitions.as_mut_ptr() as *mut c_void,
+    );
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(video_refresh: retro_video_refresh_t) {
+    frontend().video_refresh = Some(video_refresh);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_audio_sample: retro_audio_sample_t) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(audio_sample_batch: retro_audio_sample_batch_t) {
+    frontend().audio_sample_batch = Some(audio_sample_batch);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(input_poll: retro_input_poll_t) {
+    frontend().input_poll = Some(input_poll);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(input_state: retro_input_state_t) {
+    frontend().input_state = Some(input_state);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *core() = None;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut retro_system_info) {
+    if let Some(info) = info.as_mut() {
+        *info = retro_system_info {
+            library_name: c"Chirp8".as_ptr(),
+            library_version: c"0.1.0".as_ptr(),
+            valid_extensions: c"ch8|c8|sc8|xo8".as_ptr(),
+            need_fullpath: false,
+            block_extract: false,
+        };
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut retro_system_av_info) {
+    if let Some(info) = info.as_mut() {
+        *info = retro_system_av_info {
+            geometry: retro_game_geometry {
+                base_width: DISPLAY_WIDTH as c_uint,
+                base_height: DISPLAY_HEIGHT as c_uint,
+                max_width: DISPLAY_WIDTH as c_uint,
+                max_height: DISPLAY_HEIGHT as c_uint,
+                aspect_ratio: DISPLAY_WIDTH as f32 / DISPLAY_HEIGHT as f32,
+            },
+            timing: retro_system_timing {
+                fps: chirp8::REFRESH_RATE_HZ as f64,
+                sample_rate: SAMPLE_RATE_HZ,
+            },
+        };
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    if let Some(core) = core().as_mut() {
+        // The ROM was loaded with the same options, it still fits.
+        core.restart();
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_run() {
+    let (environment, video_refresh, audio_sample_batch, input_poll, input_state) = {
+        let frontend = frontend();
+        (
+            frontend.environment,
+            frontend.video_refresh,
+            frontend.audio_sample_batch,
+            frontend.input_poll,
+            frontend.input_state,
+        )
+    };
+    let mut core = core();
+    let Some(core) = core.as_mut() else {
+        return;
+    };
+
+    if let Some(environment) = environment {
+        let mut updated = false;
+        if environment(
+            RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE,
+            &mut updated as *mut _ as *mut c_void,
+        ) && updated
+            && !core.configure(read_options(environment))
+        {
+            // The new options are refused, let the player know why.
+            let message = retro_message {
+                msg: c"The game does not fit in the memory of this mode".as_ptr(),
+                // Three seconds.
+                frames: 3 * chirp8::REFRESH_RATE_HZ as c_uint,
+            };
+            environment(
+                RETRO_ENVIRONMENT_SET_MESSAGE,
+                &message as *const _ as *mut c_void,
+            );
+        }
+    }
+
+    if let Some(input_poll) = input_poll {
+        input_poll();
+    }
+    core.run(|button| {
+        input_state.is_some_and(|input_state| input_state(0, RETRO_DEVICE_JOYPAD, 0, button) != 0)
+    });
+
+    if let Some(video_refresh) = video_refresh {
+        video_refresh(
+            core.frame.as_ptr() as *const c_void,
+            DISPLAY_WIDTH as c_uint,
+            DISPLAY_HEIGHT as c_uint,
+            DISPLAY_WIDTH * std::mem::size_of::<u32>(),
+        );
+    }
+    if let Some(audio_sample_batch) = audio_sample_batch {
+        audio_sample_batch(core.audio.as_ptr(), SAMPLES_PER_FRAME);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    core().as_ref().map_or(0, |core| core.emulator.state_size())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    match core().as_mut() {
+        Some(core) if !data.is_null() => core
+            .emulator
+            .save_state(std::slice::from_raw_parts_mut(data as *mut u8, size)),
+        _ => false,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    match core().as_mut() {
+        Some(core) if !data.is_null() => core
+            .emulator
+            .load_state(std::slice::from_raw_parts(data as *const u8, size)),
+        _ => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const retro_game_info) -> bool {
+    let Some(environment) = frontend().environment else {
+        return false;
+    };
+    let Some(game) = game.as_ref() else {
+        return false;
+    };
+    if game.data.is_null() {
+        return false;
+    }
+
+    let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+    if !environment(
+        RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+        &mut format as *mut _ as *mut c_void,
+    ) {
+        return false;
+    }
+
+    let mut descriptors: Vec<retro_input_descriptor> = KEY_MAP
+        .iter()
+        .map(|(button, _, description)| retro_input_descriptor {
+            port: 0,
+            device: RETRO_DEVICE_JOYPAD,
+            index: 0,
+            id: *button,
+            description: description.as_ptr(),
+        })
+        .collect();
+    descriptors.push(retro_input_descriptor {
+        port: 0,
+        device: 0,
+        index: 0,
+        id: 0,
+        description: std::ptr::null(),
+    });
+    environment(
+        RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+        descriptors.as_mut_ptr() as *mut c_void,
+    );
+
+    let rom = std::slice::from_raw_parts(game.data as *const u8, game.size);
+    let loaded = Core::new(rom, read_options(environment));
+    let success = loaded.is_some();
+    *core() = loaded;
+    success
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const retro_game_info,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    *core() = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    match core().as_mut() {
+        Some(core) if id == RETRO_MEMORY_SYSTEM_RAM => {
+            core.emulator.bus_mut().as_mut_ptr() as *mut c_void
+        }
+        _ => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    match core().as_ref() {
+        Some(core) if id == RETRO_MEMORY_SYSTEM_RAM => core.emulator.ram_size(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[rustfmt::skip]
+    const ROM: [u8; 10] = [
+        0x60, 0x05, // v0 = 5
+        0xF0, 0x29, // I = font sprite of v0
+        0xD1, 0x15, // Draw v1 v1 5
+        0xF0, 0x18, // Sound timer = v0
+        0x12, 0x08, // Infinite loop
+    ];
+
+    #[test]
+    fn options_from_variables() {
+        let options = Options::read(|key| match key {
+            OPTION_MODE => Some("XO-Chip".to_string()),
+            OPTION_SPEED => Some("200".to_string()),
+            OPTION_PALETTE => Some("Amber".to_string()),
+            "chirp8_quirk_flag_reset" => Some("enabled".to_string()),
+            "chirp8_quirk_use_several_planes" => Some("disabled".to_string()),
+            _ => None,
+        });
+        assert_eq!(options.mode, Chirp8Mode::XOChip);
+        assert_eq!(options.speed, Some(200));
+        assert_eq!(options.palette, PALETTES[3].1);
+        let mut quirks = QuirkFlags::from_mode(Chirp8Mode::XOChip);
+        quirks.insert(QuirkFlags::FLAG_RESET);
+        quirks.remove(QuirkFlags::USE_SEVERAL_PLANES);
+        assert_eq!(options.quirks, quirks);
+
+        let defaults = Options::read(|_| Some("unknown".to_string()));
+        assert_eq!(defaults.mode, MODES[0].1);
+        assert_eq!(defaults.quirks, QuirkFlags::from_mode(MODES[0].1));
+        assert_eq!(defaults.speed, None);
+
+        let keys: Vec<_> = variables()
+            .iter()
+            .map(|(key, _)| key.to_str().unwrap())
+            .collect();
+        assert!(keys.contains(&"chirp8_quirk_flag_reset"));
+        assert_eq!(keys.len(), 3 + QuirkFlags::all().iter().count());
+    }
+
+    #[test]
+    fn core_video_and_audio() {
+        let options = Options::read(|_| None);
+        let mut core = Core::new(&ROM, options).unwrap();
+        assert!(Core::new(&vec![0; chirp8::RAM_SIZE], options).is_none());
+
+        core.run(|button| button == RETRO_DEVICE_ID_JOYPAD_A);
+        assert!(core.emulator.is_sounding());
+        // Top-left pixels of the "5" digit, drawn in low resolution.
+        assert_eq!(core.frame[0], 0xFFFFFF);
+        assert_eq!(core.frame[DISPLAY_WIDTH * 2 + 2], 0x000000);
+        assert!(core.audio.contains(&VOLUME));
+        assert!(core.audio.contains(&-VOLUME));
+
+        // Changing the speed keeps the game running, changing the mode restarts it.
+        let mut faster = options;
+        faster.speed = Some(500);
+        core.configure(faster);
+        assert_eq!(core.emulator.steps_per_frame(), 500);
+        assert_ne!(core.emulator.steps(), 0);
+        faster.speed = None;
+        core.configure(faster);
+        assert_eq!(
+            core.emulator.steps_per_frame(),
+            Chirp8::new(options.mode).steps_per_frame()
+        );
+        faster.mode = Chirp8Mode::CosmacChip8;
+        assert!(core.configure(faster));
+        assert_eq!(core.emulator.steps(), 0);
+
+        for _ in 0..10 {
+            core.run(|_| false);
+        }
+        assert!(!core.emulator.is_sounding());
+        assert!(core.audio.iter().all(|sample| *sample == 0));
+    }
+
+    #[test]
+    fn core_refuses_mode_too_small() {
+        let mut options = Options::read(|_| None);
+        options.mode = Chirp8Mode::XOChip;
+        options.quirks = QuirkFlags::from_mode(options.mode);
+        let mut rom = vec![0; 0x2000];
+        rom[..ROM.len()].copy_from_slice(&ROM);
+        let mut core = Core::new(&rom, options).unwrap();
+        core.run(|_| false);
+        let steps = core.emulator.steps();
+
+        // The ROM does not fit in 4KB, the game keeps running with the previous options.
+        let mut smaller = options;
+        smaller.mode = Chirp8Mode::CosmacChip8;
+        smaller.quirks = QuirkFlags::from_mode(smaller.mode);
+        assert!(!core.configure(smaller));
+        assert_eq!(core.options.mode, Chirp8Mode::XOChip);
+        assert_eq!(core.emulator.steps(), steps);
+        assert!(core.emulator.is_sounding());
+    }
+}
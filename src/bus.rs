@@ -0,0 +1,119 @@
+/// Memory bus through which the emulator accesses its whole address space.
+///
+/// Every read and write performed by the emulator, whether fetching instructions, drawing sprites
+/// or storing registers, goes through this trait. The default implementation is [crate::Ram], a
+/// plain byte array, but custom implementations can map peripherals, switch banks or serve
+/// read-only regions directly from flash.
+///
+/// A bus ignoring writes to the font and interpreter area can be created as follow :
+/// ```
+/// struct ProtectedRam(Vec<u8>);
+///
+/// impl chirp8::Bus for ProtectedRam {
+///     fn read(&mut self, address: u16) -> u8 {
+///         self.0[address as usize]
+///     }
+///
+///     fn write(&mut self, address: u16, value: u8) {
+///         if address >= 0x200 {
+///             self.0[address as usize] = value;
+///         }
+///     }
+/// }
+///
+/// let bus = ProtectedRam(vec![0; 0x10000]);
+/// let mode = chirp8::Chirp8Mode::XOChip;
+/// let emulator = chirp8::Chirp8::with_bus(mode, chirp8::QuirkFlags::from_mode(mode), bus);
+/// ```
+pub trait Bus {
+    /// Whether the emulator may keep decoded instructions in a cache, with the `decode_cache`
+    /// feature. The cache is invalidated on every write made by the emulator, so this must be
+    /// `false` when the content of the bus can change otherwise, with bank switching for
+    /// instance, or when every instruction fetch must go through the bus.
+    const CACHEABLE: bool = true;
+
+    /// Number of bytes mapped from address 0, which limits the memory size of the emulator,
+    /// see [crate::Chirp8::ram_size]. Defaults to [crate::RAM_SIZE].
+    #[inline]
+    fn size(&self) -> usize {
+        crate::RAM_SIZE
+    }
+
+    /// Reads the byte at given `address`.
+    fn read(&mut self, address: u16) -> u8;
+
+    /// Writes `value` at given `address`.
+    fn write(&mut self, address: u16, value: u8);
+
+    /// Reads the byte at given `address` as part of an instruction about to be executed.
+    /// Defaults to [Bus::read], override it to tell instruction fetches from data reads.
+    #[inline]
+    fn fetch(&mut self, address: u16) -> u8 {
+        self.read(address)
+    }
+
+    /// Reads `buffer.len()` consecutive bytes starting at given `address`.
+    fn read_block(&mut self, address: u16, buffer: &mut [u8]) {
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read(address.wrapping_add(offset as u16));
+        }
+    }
+
+    /// Writes all bytes of `data` consecutively, starting at given `address`.
+    fn write_block(&mut self, address: u16, data: &[u8]) {
+        for (offset, byte) in data.iter().enumerate() {
+            self.write(address.wrapping_add(offset as u16), *byte);
+        }
+    }
+
+    /// Reads `buffer.len()` consecutive bytes starting at given `address` without any side
+    /// effect, to save the emulator's state (see [crate::Chirp8::save_state]).
+    /// Returns false when the bus cannot be read this way, which is the default.
+    fn peek_block(&self, _address: u16, _buffer: &mut [u8]) -> bool {
+        false
+    }
+}
+
+/// Implements [Bus] for a contiguous buffer of bytes, mapping its whole length from address 0.
+macro_rules! impl_slice_bus {
+    ([$($generics:tt)*] $buffer:ty) => {
+        impl<$($generics)*> Bus for $buffer {
+            #[inline]
+            fn size(&self) -> usize {
+                self.len()
+            }
+
+            #[inline]
+            fn read(&mut self, address: u16) -> u8 {
+                self[address as usize]
+            }
+
+            #[inline]
+            fn write(&mut self, address: u16, value: u8) {
+                self[address as usize] = value;
+            }
+
+            fn read_block(&mut self, address: u16, buffer: &mut [u8]) {
+                let start = address as usize;
+                buffer.copy_from_slice(&self[start..start + buffer.len()]);
+            }
+
+            fn write_block(&mut self, address: u16, data: &[u8]) {
+                let start = address as usize;
+                self[start..start + data.len()].copy_from_slice(data);
+            }
+
+            fn peek_block(&self, address: u16, buffer: &mut [u8]) -> bool {
+                let start = address as usize;
+                buffer.copy_from_slice(&self[start..start + buffer.len()]);
+                true
+            }
+        }
+    };
+}
+
+impl_slice_bus!([const N: usize] [u8; N]);
+// Memory borrowed from the caller, a static buffer for instance.
+impl_slice_bus!([] &mut [u8]);
+#[cfg(feature = "alloc")]
+impl_slice_bus!([] alloc::vec::Vec<u8>);
@@ -1,31 +1,48 @@
-use core::cmp::min;
-use rand::rngs::SmallRng;
-use rand::{RngCore, SeedableRng};
-
-use crate::QuirkFlags;
+use crate::diagnostics::{self, UnknownInstruction};
+#[cfg(not(feature = "packed_display"))]
+use crate::FlatDisplay;
+#[cfg(feature = "packed_display")]
+use crate::PackedDisplay;
+use crate::{
+    Bus, Colorizer, DirtyRegion, DrawCall, DrawLog, QuirkFlags, UnknownInstructionAction,
+    WriteMonitor,
+};
 
 use super::stack::Stack;
+use crate::display::{self, DisplayStorage};
+#[cfg(feature = "gdbstub")]
+use crate::gdb::Chirp8Registers;
+#[cfg(feature = "decode_cache")]
+use crate::instruction::DecodeCache;
+use crate::instruction::Operation;
+use crate::random::Random;
+use crate::sprites;
+use crate::state::{StateReader, StateWriter};
 
 /// Number of elements storable in the emulator's stack (originally 12, 16 from super chip and above).
 const STACK_SIZE: usize = 16;
-/// The whole emulator's memory is RAM : 12-bits addresses.
-#[cfg(not(feature = "mem_extend"))]
-const RAM_SIZE: usize = 0x1000;
-/// The whole emulator's memory is RAM : 16-bits addresses.
-#[cfg(feature = "mem_extend")]
-const RAM_SIZE: usize = 0x10000;
-/// A mask to use on addresses.
-const RAM_MASK: u16 = (RAM_SIZE - 1) as u16;
+/// Size of the largest default [Ram], allocated with the [Chirp8Mode::ram_size] of each mode :
+/// 64KB for XO-Chip programs.
+#[cfg(feature = "alloc")]
+pub const RAM_SIZE: usize = MAX_RAM_SIZE;
+/// Size of the default [Ram], a 4KB array whatever the mode : XO-Chip programs get their 64KB
+/// with [Chirp8::with_buffers].
+#[cfg(not(feature = "alloc"))]
+pub const RAM_SIZE: usize = MIN_RAM_SIZE;
+/// The smallest memory of the emulator, holding the fonts and a program.
+pub const MIN_RAM_SIZE: usize = 0x1000;
+/// The largest memory of the emulator, with 16-bits addresses.
+pub const MAX_RAM_SIZE: usize = 0x10000;
 /// Every Program should start at this address.
-const PROGRAM_START: usize = 0x200;
-/// The maximum size a program can use.
+pub const PROGRAM_START: usize = 0x200;
+/// The maximum size a program can use, with a memory of [RAM_SIZE] bytes.
 pub const PROGRAM_SIZE: usize = RAM_SIZE - PROGRAM_START;
 /// Number of registers used by the emulator.
-const REGISTERS_COUNT: usize = 16;
+pub(crate) const REGISTERS_COUNT: usize = 16;
 /// The index of the flag used as a flag register.
-const FLAG_REGISTER_INDEX: usize = 0xF;
+pub(crate) const FLAG_REGISTER_INDEX: usize = 0xF;
 /// Numbers of keys used by the system.
-const KEYS_COUNT: u8 = 16;
+pub(crate) const KEYS_COUNT: u8 = 16;
 /// The location in memory of the font sprite '0'.
 const FONT_SPRITES_ADDRESS: usize = 0;
 /// The address step between two consecutive font sprites.
@@ -85,7 +102,7 @@ pub const REFRESH_RATE_HZ: usize = 60;
 /// Number of RPL flags registers. 8 on the HP48, 16 on XO-Chip.
 const RPL_REGISTERS_COUNT: usize = 16;
 /// Number of memory bytes read by CPU at each cycle.
-const PROGRAM_COUNTER_STEP: u16 = 2;
+pub(crate) const PROGRAM_COUNTER_STEP: u16 = 2;
 
 /// Display width in pixels, in original Chip-8 mode or in low-resolution mode, every pixel is a 2 by 2 square.
 pub const DISPLAY_WIDTH: usize = 128;
@@ -95,33 +112,71 @@ pub const DISPLAY_HEIGHT: usize = 64;
 pub const PIXEL_OFF: u8 = 0x00;
 /// The value of pixel set, when not in XO-Chip where gray nuances are available.
 pub const PIXEL_ON: u8 = 0xFF;
-/// The value to add to a pixel to get the next color, on XO-Chip.
-/// For 2 display planes, this yields 85 : 0, 85 170, 255.
+/// The value to add to a pixel to get the next color, on XO-Chip with the default 2 planes.
+/// For 2 display planes, this yields 85 : 0, 85 170, 255. See [Chirp8::pixel_step].
 pub const PIXEL_STEP: u8 = repeat_bits(1, DISPLAY_PLANES);
-/// Number of display planes used by XO-Chip.
-const DISPLAY_PLANES: usize = 2;
-/// Mask of relevant bits in plane selection
-const PLANES_MASK: u8 = (1 << DISPLAY_PLANES as u8) - 1;
+/// Default number of display planes, used by XO-Chip.
+pub(crate) const DISPLAY_PLANES: usize = 2;
+/// Largest number of display planes, for 16 colors.
+pub const MAX_DISPLAY_PLANES: usize = 4;
+/// Bits of all planes in pixel values.
+pub(crate) const ALL_PLANES: u8 = u8::MAX;
 /// Number of bytes for the audio pattern buffer on XO-Chip.
 const AUDIO_BUFFER_SIZE: usize = 16;
+/// Identifies save states, followed by the version of their format.
+const STATE_MAGIC: [u8; 4] = *b"C8ST";
+/// Version of the save states format.
+const STATE_VERSION: u8 = 4;
+/// Number of bytes of a save state before the memory, see [Chirp8::save_state].
+const STATE_HEADER_SIZE: usize = STATE_MAGIC.len() + 1 // Magic and version
+    + 1 + 2 + 1 // Mode, quirks and display planes
+    + 4 // Memory size
+    + REGISTERS_COUNT + 2 + 2 // Registers, program counter and index
+    + 1 + STACK_SIZE * 2 // Stack
+    + 1 + 1 // Timers
+    + RPL_REGISTERS_COUNT + AUDIO_BUFFER_SIZE + 1 // RPL registers, audio buffer and pitch
+    + 2 + 2 // Keys and previous keys
+    + 1 + 1 // High-resolution and plane selection
+    + 4 + 8 + 4 // Steps since frame, steps and steps per frame
+    + 1 + 4 * 8 // Halted and random numbers generator
+    + DISPLAY_WIDTH * DISPLAY_HEIGHT;
+/// Number of bytes of the largest save state, with a memory of [MAX_RAM_SIZE] bytes.
+/// See [Chirp8::state_size].
+pub const MAX_STATE_SIZE: usize = STATE_HEADER_SIZE + MAX_RAM_SIZE;
 
 // Create type aliases depending on if the heap is available or not.
 // cfg_if is not used here in order to provide type hints in IDEs.
 
-#[cfg(feature = "alloc")]
-pub type DisplayBuffer = alloc::vec::Vec<alloc::vec::Vec<u8>>;
-#[cfg(not(feature = "alloc"))]
-pub type DisplayBuffer = [[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+#[cfg(feature = "packed_display")]
+pub type DisplayBuffer = PackedDisplay;
+#[cfg(not(feature = "packed_display"))]
+pub type DisplayBuffer = FlatDisplay;
 
+/// Default memory of the emulator, sized by [Chirp8Mode::ram_size].
 #[cfg(feature = "alloc")]
-type Ram = alloc::vec::Vec<u8>;
+pub type Ram = alloc::vec::Vec<u8>;
+/// Default memory of the emulator, limited to [RAM_SIZE] bytes without the heap. XO-Chip
+/// programs need a 64KB memory, given with [Chirp8::with_bus] or [Chirp8::with_buffers].
 #[cfg(not(feature = "alloc"))]
-type Ram = [u8; RAM_SIZE];
+pub type Ram = [u8; RAM_SIZE];
+
+/// Returns the mask of the addresses of a memory of `size` bytes, rounded down to a power of two
+/// between [MIN_RAM_SIZE] and 64KB.
+const fn ram_mask(size: usize) -> u16 {
+    let size = if size < MIN_RAM_SIZE {
+        MIN_RAM_SIZE
+    } else if size > MAX_RAM_SIZE {
+        MAX_RAM_SIZE
+    } else {
+        size
+    };
+    ((1usize << (usize::BITS - 1 - size.leading_zeros())) - 1) as u16
+}
 
 /// Repeats the `count` least-significant bits of `value` on following bits.
 /// See [test::test_repeat_bits].
 #[inline]
-const fn repeat_bits(value: u8, count: usize) -> u8 {
+pub(crate) const fn repeat_bits(value: u8, count: usize) -> u8 {
     let step = u8::MAX as u8 / ((1 << count) - 1);
     let mask = (1 << count) - 1;
     (value & mask).wrapping_mul(step)
@@ -134,7 +189,8 @@ const fn repeat_bits(value: u8, count: usize) -> u8 {
 /// ```
 /// let emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::SuperChip1_1);
 /// ```
-#[derive(Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Chirp8Mode {
     /// Original Cosmac VIP chip-8 mode from 1977, uses 64x32 display.
     CosmacChip8,
@@ -153,6 +209,17 @@ pub enum Chirp8Mode {
     // SuperChip1_0
 }
 
+impl Chirp8Mode {
+    /// Returns the size of the memory in this mode : 4KB, or 64KB on XO-Chip.
+    /// The index register and program counter wrap around at the end of this memory.
+    pub const fn ram_size(self) -> usize {
+        match self {
+            Chirp8Mode::XOChip => 0x10000,
+            _ => MIN_RAM_SIZE,
+        }
+    }
+}
+
 /// Chip-8 Emulator able to execute Chip-8 programs.
 /// Can be configured and used as follow :
 /// ```
@@ -166,11 +233,17 @@ pub enum Chirp8Mode {
 /// emulator.key_release(0xA);
 /// let screen = emulator.get_display_buffer();
 /// ```
-pub struct Chirp8 {
-    /// Memory of interpreter.
-    ram: Ram,
+///
+/// All memory accesses go through a [Bus], which is the emulator's [Ram] unless created with
+/// [Chirp8::with_bus], and pixels are drawn on a [DisplayBuffer] unless created with
+/// [Chirp8::with_bus_and_display] or [Chirp8::with_buffers].
+pub struct Chirp8<B: Bus = Ram, D: DisplayStorage = DisplayBuffer> {
+    /// Memory of interpreter, accessed through its bus.
+    bus: B,
+    /// Mask to use on addresses, the size of the memory minus one.
+    ram_mask: u16,
     /// Display buffer, true when pixel is on, false otherwise.
-    display_buffer: DisplayBuffer,
+    display_buffer: D,
     /// V0 to VF.
     registers: [u8; REGISTERS_COUNT],
     /// Program counter.
@@ -203,6 +276,8 @@ pub struct Chirp8 {
     /// - P=4 : [0b0000_0000, 0b0001_0001, 0b0010_0010, 0b0011_0011, 0b0100_0100, ..., 0b1110_1110, 0b1111_1111]
     /// This allows for 2^P values equally distant and filling all 0..255 range.
     plane_selection: u8,
+    /// Number of display planes P, 1, 2 or 4.
+    display_planes: usize,
 
     /// The current running mode of the emulator.
     mode: Chirp8Mode,
@@ -212,13 +287,38 @@ pub struct Chirp8 {
     steps_since_frame: usize,
     /// Meta flag to indicate that the display changed.
     display_changed: bool,
+    /// Pixels of the display buffer changed since the last call to `take_dirty_region`.
+    dirty_region: DirtyRegion,
     /// Random numbers generator.
-    randomizer: SmallRng,
+    randomizer: Random,
     /// Number of taken steps. This is not incremented if the interpreter is idle.
     steps: usize,
     /// Number of CPU steps executed between two consecutive frames.
     /// Also dictates the number of steps between two timer decreases.
     steps_per_frame: usize,
+    /// Optional monitor of writes to executed code and reserved memory.
+    write_monitor: Option<WriteMonitor>,
+    /// Optional record of the sprites drawn, boxed so that it costs a pointer when detached.
+    #[cfg(feature = "alloc")]
+    draw_log: Option<alloc::boxed::Box<DrawLog>>,
+    /// Optional colors of the pixels according to the sprites which drew them, boxed like the
+    /// draw log.
+    #[cfg(feature = "alloc")]
+    colorizer: Option<alloc::boxed::Box<Colorizer>>,
+    /// True when the emulator stopped executing instructions, after a monitored write error or
+    /// an unknown instruction.
+    halted: bool,
+    /// What to do on unknown instructions.
+    unknown_instruction_action: UnknownInstructionAction,
+    /// Instructions decoded so far.
+    #[cfg(feature = "decode_cache")]
+    decode_cache: DecodeCache,
+    /// Smallest and largest addresses written since the last call to `take_written_range`.
+    #[cfg(feature = "jit")]
+    written_range: Option<(u16, u16)>,
+    /// Identifies the emulator, so that code compiled for another emulator is never run.
+    #[cfg(feature = "jit")]
+    jit_instance: u64,
 }
 
 impl Default for Chirp8 {
@@ -229,6 +329,11 @@ impl Default for Chirp8 {
 
 impl Chirp8 {
     /// Creates a new emulator, which will behave according to given `mode`.
+    ///
+    /// # Panics
+    ///
+    /// Without the `alloc` feature, when the `mode` needs more memory than the default [Ram],
+    /// on XO-Chip.
     pub fn new(mode: Chirp8Mode) -> Self {
         Chirp8::with_custom_quirks(mode, QuirkFlags::from_mode(mode))
     }
@@ -240,25 +345,125 @@ impl Chirp8 {
     /// let quirks = chirp8::QuirkFlags::INC_INDEX | chirp8::QuirkFlags::USE_SEVERAL_PLANES | chirp8::QuirkFlags::JUMP_XNN;
     /// let emulator = chirp8::Chirp8::with_custom_quirks(chirp8::Chirp8Mode::XOChip, quirks);
     /// ```
+    ///
+    /// # Panics
+    ///
+    /// Without the `alloc` feature, when the `mode` needs more memory than the default [Ram],
+    /// on XO-Chip.
     pub fn with_custom_quirks(mode: Chirp8Mode, quirks: QuirkFlags) -> Self {
-        // Create RAM and display buffer
+        // Create RAM
         cfg_if::cfg_if! {
             if #[cfg(feature = "alloc")]{
-                let mut ram = alloc::vec![0u8; RAM_SIZE];
-                let display_buffer = alloc::vec![alloc::vec![PIXEL_OFF; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+                let ram = alloc::vec![0u8; mode.ram_size()];
             }else{
-                let mut ram = [0u8; RAM_SIZE];
-                let display_buffer = [[PIXEL_OFF; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+                assert!(
+                    mode.ram_size() <= RAM_SIZE,
+                    "{:?} needs {} bytes of memory",
+                    mode,
+                    mode.ram_size()
+                );
+                let ram = [0u8; RAM_SIZE];
             }
         }
 
+        Chirp8::with_bus(mode, quirks, ram)
+    }
+}
+
+impl<B: Bus> Chirp8<B> {
+    /// Creates a new emulator, which will behave according to given `mode` and with custom quirks
+    /// behavior, accessing its memory through given `bus`.
+    /// The bus must map the [Bus::size] bytes of the emulator's memory, see
+    /// [Chirp8::with_bus_and_display].
+    /// The font sprites are written to the bus at creation.
+    ///
+    /// # Panics
+    ///
+    /// When the `bus` panics on accesses to its first [MIN_RAM_SIZE] bytes, see
+    /// [Chirp8::with_bus_and_display].
+    pub fn with_bus(mode: Chirp8Mode, quirks: QuirkFlags, bus: B) -> Self {
+        // Create display buffer
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "packed_display")]{
+                let display_buffer = PackedDisplay::new();
+            }else{
+                let display_buffer = FlatDisplay::new();
+            }
+        }
+
+        Chirp8::with_bus_and_display(mode, quirks, bus, display_buffer)
+    }
+}
+
+impl<'a> Chirp8<&'a mut [u8], &'a mut [u8]> {
+    /// Creates a new emulator, which will behave according to given `mode` and with custom quirks
+    /// behavior, using memory borrowed from the caller : `ram` as its whole memory and `display`
+    /// as a display buffer of a byte per pixel, row after row.
+    ///
+    /// This allows placing the buffers anywhere, in static memory for instance, rather than in
+    /// the emulator. The size of the memory is the length of `ram`, rounded down to a power of
+    /// two and up to the [Chirp8Mode::ram_size] of the `mode`.
+    /// ```
+    /// let mut ram = [0; chirp8::MIN_RAM_SIZE];
+    /// let mut display = [0; chirp8::DISPLAY_WIDTH * chirp8::DISPLAY_HEIGHT];
+    /// let mode = chirp8::Chirp8Mode::CosmacChip8;
+    /// let quirks = chirp8::QuirkFlags::from_mode(mode);
+    /// let emulator = chirp8::Chirp8::with_buffers(mode, quirks, &mut ram, &mut display);
+    /// assert_eq!(emulator.ram_size(), chirp8::MIN_RAM_SIZE);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// When `ram` is shorter than [MIN_RAM_SIZE], or `display` shorter than
+    /// [DISPLAY_WIDTH] * [DISPLAY_HEIGHT].
+    pub fn with_buffers(
+        mode: Chirp8Mode,
+        quirks: QuirkFlags,
+        ram: &'a mut [u8],
+        display: &'a mut [u8],
+    ) -> Self {
+        assert!(
+            ram.len() >= MIN_RAM_SIZE,
+            "RAM smaller than {} bytes",
+            MIN_RAM_SIZE
+        );
+        assert!(
+            display.len() >= DISPLAY_WIDTH * DISPLAY_HEIGHT,
+            "Display smaller than {} bytes",
+            DISPLAY_WIDTH * DISPLAY_HEIGHT
+        );
+        Chirp8::with_bus_and_display(mode, quirks, ram, display)
+    }
+}
+
+impl<B: Bus, D: DisplayStorage> Chirp8<B, D> {
+    /// Creates a new emulator, which will behave according to given `mode` and with custom quirks
+    /// behavior, accessing its memory through given `bus` and drawing on given `display`.
+    ///
+    /// The size of the memory is the [Bus::size] of the `bus`, rounded down to a power of two
+    /// and between [MIN_RAM_SIZE] and the [Chirp8Mode::ram_size] of the `mode`. The font sprites
+    /// are written to the bus and the display is cleared at creation.
+    ///
+    /// Unlike [Chirp8::new], this never panics because of the `mode`: a bus smaller than the
+    /// [Chirp8Mode::ram_size] of the mode, such as the default [Ram] on XO-Chip without `alloc`,
+    /// gives a smaller memory.
+    ///
+    /// # Panics
+    ///
+    /// When the `bus` panics on accesses to its first [MIN_RAM_SIZE] bytes, which are always
+    /// mapped : the font sprites are written to it, as well as random bytes from [PROGRAM_START]
+    /// on with the [QuirkFlags::RAM_RANDOM] quirk.
+    pub fn with_bus_and_display(
+        mode: Chirp8Mode,
+        quirks: QuirkFlags,
+        mut bus: B,
+        mut display_buffer: D,
+    ) -> Self {
+        let ram_mask = ram_mask(bus.size()) & ram_mask(mode.ram_size());
+
         // Load font to RAM
-        const FONT_SPRITES_SIZE: usize = FONT_SPRITES_COUNT * FONT_SPRITES_STEP;
-        ram[FONT_SPRITES_ADDRESS..FONT_SPRITES_ADDRESS + FONT_SPRITES_SIZE]
-            .copy_from_slice(&FONT_SPRITES);
-        const FONT_SPRITES_HIGH_SIZE: usize = FONT_SPRITES_COUNT * FONT_SPRITES_HIGH_STEP;
-        ram[FONT_SPRITES_HIGH_ADDRESS..FONT_SPRITES_HIGH_ADDRESS + FONT_SPRITES_HIGH_SIZE]
-            .copy_from_slice(&FONT_SPRITES_HIGH);
+        bus.write_block(FONT_SPRITES_ADDRESS as u16, &FONT_SPRITES);
+        bus.write_block(FONT_SPRITES_HIGH_ADDRESS as u16, &FONT_SPRITES_HIGH);
 
         let steps_per_frame = match mode {
             Chirp8Mode::CosmacChip8 => 10,
@@ -267,13 +472,18 @@ impl Chirp8 {
             Chirp8Mode::XOChip => 30,
         };
 
-        let plane_selection = if quirks.contains(QuirkFlags::USE_SEVERAL_PLANES) {
+        // Monochrome programs draw on all planes at once, a single plane is enough.
+        let (display_planes, plane_selection) = if quirks.contains(QuirkFlags::USE_SEVERAL_PLANES) {
             // First plane selected, repeated 4 times.
-            repeat_bits(0b01, DISPLAY_PLANES)
+            (DISPLAY_PLANES, repeat_bits(0b01, DISPLAY_PLANES))
         } else {
             // Draw on all planes.
-            !0
+            (1, !0)
         };
+        // The display buffer may store fewer planes.
+        let display_planes = display_planes.min(display_buffer.max_planes_count());
+        display_buffer.set_planes_count(display_planes);
+        display_buffer.clear_planes(ALL_PLANES);
 
         // Fill audio buffer with 128-samples long square wave. (8x16)
         // Played at a rate of 4000 Hz, this yields a frequency of 31.25 Hz
@@ -283,17 +493,22 @@ impl Chirp8 {
             .1
             .fill(0xFF);
 
-        let mut randomizer = SmallRng::seed_from_u64(0xDEADCAFEDEADCAFE);
+        let mut randomizer = Random::new(0xDEADCAFEDEADCAFE);
 
         if quirks.contains(QuirkFlags::RAM_RANDOM) {
-            ram[PROGRAM_START..(PROGRAM_START + PROGRAM_SIZE)]
-                .fill_with(|| randomizer.next_u32() as u8);
+            // Memory sizes are powers of two, the program area is made of whole blocks.
+            let mut block = [0u8; 64];
+            for address in (PROGRAM_START..=(ram_mask as usize)).step_by(block.len()) {
+                block.fill_with(|| randomizer.next_u8());
+                bus.write_block(address as u16, &block);
+            }
         }
 
         // Create emulator
         Self {
-            ram: ram,
-            display_buffer: display_buffer,
+            bus,
+            ram_mask,
+            display_buffer,
             registers: [0; REGISTERS_COUNT],
             pc: PROGRAM_START as u16,
             index: 0,
@@ -301,19 +516,34 @@ impl Chirp8 {
             sound_timer: 0,
             delay_timer: 0,
             rpl_registers: [0; RPL_REGISTERS_COUNT],
-            audio_buffer: audio_buffer,
+            audio_buffer,
             pitch: 0,
             keys: [false; KEYS_COUNT as usize],
             keys_previous: [false; KEYS_COUNT as usize],
             high_resolution: false,
-            plane_selection: plane_selection,
-            mode: mode,
-            quirks: quirks,
+            plane_selection,
+            display_planes,
+            mode,
+            quirks,
             steps_since_frame: 0,
             display_changed: true,
-            randomizer: randomizer,
+            dirty_region: DirtyRegion::full(),
+            randomizer,
             steps: 0,
-            steps_per_frame: steps_per_frame,
+            steps_per_frame,
+            write_monitor: Option::None,
+            #[cfg(feature = "alloc")]
+            draw_log: Option::None,
+            #[cfg(feature = "alloc")]
+            colorizer: Option::None,
+            halted: false,
+            unknown_instruction_action: UnknownInstructionAction::Log,
+            #[cfg(feature = "decode_cache")]
+            decode_cache: DecodeCache::new(),
+            #[cfg(feature = "jit")]
+            written_range: Option::Some((0, ram_mask)),
+            #[cfg(feature = "jit")]
+            jit_instance: crate::jit::next_instance(),
         }
     }
 
@@ -344,27 +574,224 @@ impl Chirp8 {
         // Do-while
         loop {
             self.step();
-            if self.steps_since_frame == 0 {
+            if self.steps_since_frame == 0 || self.halted {
                 break;
             }
         }
     }
 
     /// Get the next instruction to execute from memory.
-    fn next_instruction(&self) -> u16 {
+    fn next_instruction(&mut self) -> u16 {
         const BITS_IN_BYTE: u16 = 8;
-        ((self.ram[self.pc as usize] as u16) << BITS_IN_BYTE)
-            + (self.ram[self.pc as usize + 1] as u16)
+        if let Option::Some(monitor) = &mut self.write_monitor {
+            monitor.record_execution(self.pc);
+            monitor.record_execution(self.pc.wrapping_add(1) & self.ram_mask);
+        }
+        ((self.bus.fetch(self.pc) as u16) << BITS_IN_BYTE)
+            + (self.bus.fetch(self.pc.wrapping_add(1) & self.ram_mask) as u16)
+    }
+
+    /// Get the next instruction from memory without it being fetched for execution.
+    fn peek_instruction(&mut self) -> u16 {
+        const BITS_IN_BYTE: u16 = 8;
+        ((Self::peek_byte(&mut self.bus, self.pc) as u16) << BITS_IN_BYTE)
+            + (Self::peek_byte(&mut self.bus, self.pc.wrapping_add(1) & self.ram_mask) as u16)
+    }
+
+    /// Reads the byte at given `address` for the emulator's own needs, rather than the program's.
+    /// The bus is peeked when possible, or else read as a block, so that the access is not seen
+    /// by buses recording them (see [crate::Coverage]).
+    fn peek_byte(bus: &mut B, address: u16) -> u8 {
+        let mut byte = [0];
+        if !bus.peek_block(address, &mut byte) {
+            bus.read_block(address, &mut byte);
+        }
+        byte[0]
     }
 
     /// Resets interpreter to beginning of program.
     pub fn reset(&mut self) {
         self.pc = PROGRAM_START as u16;
         self.registers.fill(0);
-        self.display_changed = true;
         self.clear_display();
         self.high_resolution = false;
-        self.plane_selection = repeat_bits(0b01, DISPLAY_PLANES);
+        self.plane_selection = if self.quirks.contains(QuirkFlags::USE_SEVERAL_PLANES) {
+            repeat_bits(0b01, self.display_planes)
+        } else {
+            ALL_PLANES
+        };
+        self.halted = false;
+    }
+
+    /// Reseeds the random numbers generator used by the `CXNN` instruction, so that runs of a
+    /// program with the same inputs and `seed` are identical.
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.randomizer = Random::new(seed);
+    }
+
+    /// Returns the number of bytes of a save state of this emulator : a fixed size header
+    /// followed by the [Chirp8::ram_size] bytes of the memory. See [Chirp8::save_state].
+    pub fn state_size(&self) -> usize {
+        STATE_HEADER_SIZE + self.ram_size()
+    }
+
+    /// Saves the whole emulator state to `state`, which must be at least [Chirp8::state_size]
+    /// bytes long. Returns true on success, false if `state` is too short or if the bus cannot
+    /// be read without side effects (see [Bus::peek_block]).
+    ///
+    /// The state is made of plain bytes and can be stored as is, see [Chirp8::load_state].
+    /// It does not depend on the platform nor on the crate features.
+    /// ```
+    /// let mut emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+    /// let mut state = vec![0; emulator.state_size()];
+    /// assert!(emulator.save_state(&mut state));
+    /// emulator.run_frame();
+    /// assert!(emulator.load_state(&state));
+    /// assert_eq!(emulator.steps(), 0);
+    /// ```
+    pub fn save_state(&self, state: &mut [u8]) -> bool {
+        if state.len() < self.state_size() {
+            return false;
+        }
+
+        let mut writer = StateWriter::new(state);
+        writer.bytes(&STATE_MAGIC);
+        writer.u8(STATE_VERSION);
+        writer.u8(self.mode as u8);
+        writer.u16(self.quirks.bits());
+        writer.u8(self.display_planes as u8);
+        writer.u32(self.ram_size() as u32);
+        writer.bytes(&self.registers);
+        writer.u16(self.pc);
+        writer.u16(self.index);
+        writer.u8(self.stack.len() as u8);
+        for i in 0..STACK_SIZE {
+            writer.u16(self.stack.as_slice().get(i).copied().unwrap_or(0));
+        }
+        writer.u8(self.sound_timer);
+        writer.u8(self.delay_timer);
+        writer.bytes(&self.rpl_registers);
+        writer.bytes(&self.audio_buffer);
+        writer.u8(self.pitch);
+        writer.bits(&self.keys);
+        writer.bits(&self.keys_previous);
+        writer.bool(self.high_resolution);
+        writer.u8(self.plane_selection);
+        writer.u32(self.steps_since_frame as u32);
+        writer.u64(self.steps as u64);
+        writer.u32(self.steps_per_frame as u32);
+        writer.bool(self.halted);
+        for word in self.randomizer.state() {
+            writer.u64(word);
+        }
+        for y in 0..DISPLAY_HEIGHT {
+            for (x, pixel) in writer.slice(DISPLAY_WIDTH).iter_mut().enumerate() {
+                *pixel = self.display_buffer.pixel(x, y);
+            }
+        }
+        debug_assert_eq!(writer.position(), STATE_HEADER_SIZE);
+        self.bus.peek_block(0, writer.slice(self.ram_size()))
+    }
+
+    /// Restores a state saved with [Chirp8::save_state], including the mode and quirks.
+    /// Returns true on success, false if `state` is not a valid save state or if its memory size
+    /// is not the one of this emulator in the saved mode, in which case the emulator is left
+    /// untouched.
+    pub fn load_state(&mut self, state: &[u8]) -> bool {
+        if state.len() < STATE_HEADER_SIZE
+            || state[..STATE_MAGIC.len()] != STATE_MAGIC
+            || state[STATE_MAGIC.len()] != STATE_VERSION
+        {
+            return false;
+        }
+
+        let mut reader = StateReader::new(&state[STATE_MAGIC.len() + 1..]);
+        let mode = match reader.u8() {
+            0 => Chirp8Mode::CosmacChip8,
+            1 => Chirp8Mode::SuperChip1_1,
+            2 => Chirp8Mode::SuperChipModern,
+            3 => Chirp8Mode::XOChip,
+            _ => return false,
+        };
+        let Option::Some(quirks) = QuirkFlags::from_bits(reader.u16()) else {
+            return false;
+        };
+        let display_planes = reader.u8() as usize;
+        if !matches!(display_planes, 1 | 2 | 4)
+            || display_planes > self.display_buffer.max_planes_count()
+        {
+            return false;
+        }
+        let ram_mask = ram_mask(self.bus.size()) & ram_mask(mode.ram_size());
+        let ram_size = reader.u32() as usize;
+        if ram_size != ram_mask as usize + 1 || state.len() < STATE_HEADER_SIZE + ram_size {
+            return false;
+        }
+        // The random numbers generator, right before the display, would only yield zeros.
+        let random_state = STATE_HEADER_SIZE - DISPLAY_WIDTH * DISPLAY_HEIGHT - 4 * 8;
+        if state[random_state..random_state + 4 * 8].iter().all(|byte| *byte == 0) {
+            return false;
+        }
+        let registers = reader.bytes(REGISTERS_COUNT);
+        let pc = reader.u16();
+        let index = reader.u16();
+        let stack_length = reader.u8() as usize;
+        if stack_length > STACK_SIZE {
+            return false;
+        }
+
+        self.mode = mode;
+        self.ram_mask = ram_mask;
+        self.quirks = quirks;
+        self.display_planes = display_planes;
+        self.display_buffer.set_planes_count(display_planes);
+        self.registers.copy_from_slice(registers);
+        self.pc = pc & self.ram_mask;
+        self.index = index & self.ram_mask;
+        self.stack.clear();
+        for i in 0..STACK_SIZE {
+            let address = reader.u16();
+            if i < stack_length {
+                let _ = self.stack.push(address);
+            }
+        }
+        self.sound_timer = reader.u8();
+        self.delay_timer = reader.u8();
+        self.rpl_registers
+            .copy_from_slice(reader.bytes(RPL_REGISTERS_COUNT));
+        self.audio_buffer
+            .copy_from_slice(reader.bytes(AUDIO_BUFFER_SIZE));
+        self.pitch = reader.u8();
+        reader.bits(&mut self.keys);
+        reader.bits(&mut self.keys_previous);
+        self.high_resolution = reader.bool();
+        self.plane_selection = reader.u8();
+        self.steps_since_frame = reader.u32() as usize;
+        self.steps = reader.u64() as usize;
+        self.steps_per_frame = (reader.u32() as usize).max(1);
+        self.steps_since_frame %= self.steps_per_frame;
+        self.halted = reader.bool();
+        let mut random_state = [0; 4];
+        random_state.fill_with(|| reader.u64());
+        self.randomizer = Random::from_state(random_state);
+        for y in 0..DISPLAY_HEIGHT {
+            for (x, pixel) in reader.bytes(DISPLAY_WIDTH).iter().enumerate() {
+                self.display_buffer.set_pixel(x, y, *pixel);
+            }
+        }
+        if let Option::Some(colorizer) = self.colorizer_mut() {
+            colorizer.clear();
+        }
+        self.bus.write_block(0, reader.bytes(ram_size));
+
+        self.mark_display_changed();
+        #[cfg(feature = "decode_cache")]
+        self.decode_cache.clear();
+        #[cfg(feature = "jit")]
+        {
+            self.written_range = Option::Some((0, self.ram_mask));
+        }
+        true
     }
 
     /// Forces the interpreter to take given number of `steps`.
@@ -372,7 +799,7 @@ impl Chirp8 {
     /// In most cases, do not use this method, prefer `run_frame` or just `step`.
     pub fn take_steps(&mut self, steps: usize) {
         let target_steps = self.steps.wrapping_add(steps);
-        while self.steps != target_steps {
+        while self.steps != target_steps && !self.halted {
             self.step();
         }
     }
@@ -380,275 +807,189 @@ impl Chirp8 {
     /// Execute one machine instruction, decrement timers if necessary.
     /// If the interpreter is idle, if waiting for an interrupt for instance, the step is not taken,
     /// which is to say the `steps` counter is not incremented.
+    /// Does nothing when the emulator is halted, see [Chirp8::is_halted].
     pub fn step(&mut self) {
-        // Big endian instruction
-        let instruction = self.next_instruction();
-        self.pc = self.pc.wrapping_add(PROGRAM_COUNTER_STEP) & RAM_MASK;
+        if self.halted {
+            return;
+        }
+
+        let operation = self.next_operation();
+        self.pc = self.pc.wrapping_add(PROGRAM_COUNTER_STEP) & self.ram_mask;
         self.steps = self.steps.wrapping_add(1);
+        if self.steps_since_frame == 0 {
+            if let Option::Some(log) = self.draw_log_mut() {
+                log.clear();
+            }
+        }
 
-        // See https://tobiasvl.github.io/blog/write-a-chip-8-emulator/
-        let opcode = 0xF & (instruction >> 12) as u8;
-        // The second nibble. Used to look up one of the 16 registers (VX) from V0 through VF.
-        let x = (0x0F & (instruction >> 8) as u8) as usize;
-        // The third nibble. Also used to look up one of the 16 registers (VY) from V0 through VF.
-        let y = (0x0F & (instruction >> 4) as u8) as usize;
-        // The fourth nibble. A 4-bit number.
-        let n = 0x0F & instruction as u8;
-        // The second byte (third and fourth nibbles). An 8-bit immediate number.
-        let nn = 0xFF & instruction as u8;
-        // The second, third and fourth nibbles. A 12-bit immediate memory address.
-        let nnn = 0x0FFF & instruction;
-
-        match opcode {
-            0x0 => match nn {
-                // Clear screen
-                0xE0 => {
-                    if self.mode != Chirp8Mode::XOChip {
-                        self.clear_display();
-                    } else {
-                        self.clear_planes();
-                    }
-                }
-                // Return from subroutine
-                0xEE => self.pc = self.stack.pop().ok().unwrap(),
-                // Exit from interpreter (Super-Chip)
-                0xFD => {
-                    if self.mode >= Chirp8Mode::SuperChip1_1 {
-                        self.reset()
-                    } else {
-                        self.print_unknown_instruction(instruction)
-                    }
-                }
-                // Disable High-res (Super-Chip and above)
-                0xFE => {
-                    if self.mode >= Chirp8Mode::SuperChip1_1 {
-                        self.high_resolution = false;
-                        if self.quirks.contains(QuirkFlags::CLEAR_ON_RES) {
-                            self.clear_display();
-                        }
-                    } else {
-                        self.print_unknown_instruction(instruction)
-                    }
-                }
-                // Enable High-res (Super-chip and above)
-                0xFF => {
-                    if self.mode >= Chirp8Mode::SuperChip1_1 {
-                        self.high_resolution = true;
-                        if self.quirks.contains(QuirkFlags::CLEAR_ON_RES) {
-                            self.clear_display();
-                        }
-                    } else {
-                        self.print_unknown_instruction(instruction)
-                    }
-                }
-                // Scroll up N pixels (XO-Chip)
-                0xD0..=0xDF => {
-                    if self.mode == Chirp8Mode::XOChip {
-                        self.scroll_up(n)
-                    } else {
-                        self.print_unknown_instruction(instruction)
-                    }
-                }
-                // Scroll up N pixels (Unofficial Super Chip)
-                0xB0..=0xBF => {
-                    if self.mode == Chirp8Mode::SuperChipModern {
-                        self.scroll_up(n)
-                    } else {
-                        self.print_unknown_instruction(instruction)
-                    }
-                }
-                // Scroll down N pixels (Super Chip and above)
-                0xC0..=0xCF => {
-                    if self.mode >= Chirp8Mode::SuperChip1_1 {
-                        self.scroll_down(n)
-                    } else {
-                        self.print_unknown_instruction(instruction)
-                    }
-                }
-                // Scroll right 4 pixels (Super Chip and above)
-                0xFB => {
-                    if self.mode >= Chirp8Mode::SuperChip1_1 {
-                        self.scroll_right(4)
-                    } else {
-                        self.print_unknown_instruction(instruction)
-                    }
+        self.execute(operation);
+
+        // Handle timers
+        self.step_timers();
+        // Handle keys
+        self.keys_previous.copy_from_slice(&self.keys);
+    }
+
+    /// Get the next instruction to execute from memory, decoded.
+    /// When the `decode_cache` feature is enabled, instructions are only decoded the first time they
+    /// are executed, as long as the bus allows it (see [Bus::CACHEABLE]).
+    #[inline]
+    fn next_operation(&mut self) -> Operation {
+        #[cfg(feature = "decode_cache")]
+        if B::CACHEABLE {
+            if let Option::Some(monitor) = &mut self.write_monitor {
+                monitor.record_execution(self.pc);
+                monitor.record_execution(self.pc.wrapping_add(1) & self.ram_mask);
+            }
+            let cached = self.decode_cache.get(self.pc);
+            if cached != Operation::Undecoded {
+                return cached;
+            }
+            let operation = Operation::decode(self.next_instruction(), self.mode, self.quirks);
+            self.decode_cache.set(self.pc, operation);
+            return operation;
+        }
+
+        Operation::decode(self.next_instruction(), self.mode, self.quirks)
+    }
+
+    /// Executes given decoded `operation`, the program counter pointing to the next instruction.
+    fn execute(&mut self, operation: Operation) {
+        match operation {
+            Operation::Undecoded => unreachable!(),
+            Operation::Unknown(instruction) => self.unknown_instruction(instruction),
+            Operation::ClearDisplay => self.clear_display(),
+            Operation::ClearPlanes => self.clear_planes(),
+            Operation::Return => self.pc = self.stack.pop().ok().unwrap(),
+            Operation::Exit => self.reset(),
+            Operation::LowResolution => {
+                self.high_resolution = false;
+                self.mark_display_changed();
+                if self.quirks.contains(QuirkFlags::CLEAR_ON_RES) {
+                    self.clear_display();
                 }
-                // Scroll left 4 pixels (Super Chip and above)
-                0xFC => {
-                    if self.mode >= Chirp8Mode::SuperChip1_1 {
-                        self.scroll_left(4)
-                    } else {
-                        self.print_unknown_instruction(instruction)
-                    }
+            }
+            Operation::HighResolution => {
+                self.high_resolution = true;
+                self.mark_display_changed();
+                if self.quirks.contains(QuirkFlags::CLEAR_ON_RES) {
+                    self.clear_display();
                 }
-                _ => self.print_unknown_instruction(instruction),
-            },
-            // Jump
-            0x1 => self.pc = nnn,
-            // Call subroutine
-            0x2 => {
+            }
+            Operation::ScrollUp(n) => self.scroll_up(n),
+            Operation::ScrollDown(n) => self.scroll_down(n),
+            Operation::ScrollRight => self.scroll_right(4),
+            Operation::ScrollLeft => self.scroll_left(4),
+            Operation::Jump(nnn) => self.pc = nnn,
+            Operation::Call(nnn) => {
                 self.stack.push(self.pc).ok().unwrap();
                 self.pc = nnn;
             }
-            // Skip
-            0x3 => {
-                if self.registers[x] == nn {
+            Operation::SkipEqual { x, nn } => {
+                if self.registers[x as usize] == nn {
                     self.skip_next_instruction();
                 }
             }
-            // Skip
-            0x4 => {
-                if self.registers[x] != nn {
+            Operation::SkipNotEqual { x, nn } => {
+                if self.registers[x as usize] != nn {
                     self.skip_next_instruction();
                 }
             }
-            0x5 => {
-                match n {
-                    // 0x5XY0 : Skip if vx == vy
-                    0 => {
-                        if self.registers[x] == self.registers[y] {
-                            self.skip_next_instruction();
-                        }
-                    }
-                    // 0x5XY2 : Save vx - vy (XO-chip)
-                    2 => {
-                        if self.mode == Chirp8Mode::XOChip {
-                            if x < y {
-                                let end = self.index as usize + y - x;
-                                self.ram[self.index as usize..=end]
-                                    .copy_from_slice(&self.registers[x..=y]);
-                            } else {
-                                let end = self.index as usize + x - y;
-                                self.ram[self.index as usize..=end]
-                                    .copy_from_slice(&self.registers[y..=x]);
-                                self.ram[self.index as usize..=end].reverse();
-                            }
-                        } else {
-                            self.print_unknown_instruction(instruction)
-                        }
-                    }
-                    // 0x5XY3 : Load vx - vy (XO-chip)
-                    3 => {
-                        if self.mode == Chirp8Mode::XOChip {
-                            if x < y {
-                                let end = self.index as usize + y - x;
-                                self.registers[x..=y]
-                                    .copy_from_slice(&self.ram[self.index as usize..=end]);
-                            } else {
-                                let end = self.index as usize + x - y;
-                                self.registers[y..=x]
-                                    .copy_from_slice(&self.ram[self.index as usize..=end]);
-                                self.registers[y..=x].reverse();
-                            }
-                        } else {
-                            self.print_unknown_instruction(instruction)
-                        }
-                    }
-                    _ => self.print_unknown_instruction(instruction),
-                }
-            }
-            // Skip
-            0x9 => {
-                // n should be equal to 0 (0x9XY0), not checked for performance.
-                if self.registers[x] != self.registers[y] {
+            Operation::SkipEqualRegisters { x, y } => {
+                if self.registers[x as usize] == self.registers[y as usize] {
                     self.skip_next_instruction();
                 }
             }
-            // Set register
-            0x6 => self.registers[x] = nn,
-            // Add to register
-            0x7 => self.registers[x] = self.registers[x].wrapping_add(nn),
-            // Logic and arithmetics
-            0x8 => match n {
-                // Set
-                0x0 => self.registers[x] = self.registers[y],
-                // OR
-                0x1 => {
-                    self.registers[x] |= self.registers[y];
-                    if self.quirks.contains(QuirkFlags::FLAG_RESET) {
-                        self.reset_flag();
-                    }
+            Operation::SaveRange { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let count = x.abs_diff(y) + 1;
+                for offset in 0..count {
+                    let register = if x < y { x + offset } else { x - offset };
+                    let address = self.index.wrapping_add(offset as u16) & self.ram_mask;
+                    self.write_ram(address, self.registers[register]);
                 }
-                // AND
-                0x2 => {
-                    self.registers[x] &= self.registers[y];
-                    if self.quirks.contains(QuirkFlags::FLAG_RESET) {
-                        self.reset_flag();
-                    }
+            }
+            Operation::LoadRange { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let count = x.abs_diff(y) + 1;
+                for offset in 0..count {
+                    let register = if x < y { x + offset } else { x - offset };
+                    let address = self.index.wrapping_add(offset as u16) & self.ram_mask;
+                    self.registers[register] = self.bus.read(address);
                 }
-                // XOR
-                0x3 => {
-                    self.registers[x] ^= self.registers[y];
-                    if self.quirks.contains(QuirkFlags::FLAG_RESET) {
-                        self.reset_flag();
-                    }
+            }
+            Operation::SkipNotEqualRegisters { x, y } => {
+                if self.registers[x as usize] != self.registers[y as usize] {
+                    self.skip_next_instruction();
                 }
-                // ADD
-                0x4 => {
-                    let flag = if self.registers[x].checked_add(self.registers[y]) == Option::None {
-                        1
-                    } else {
-                        0
-                    };
-                    self.registers[x] = self.registers[x].wrapping_add(self.registers[y]);
-                    self.registers[FLAG_REGISTER_INDEX] = flag;
+            }
+            Operation::Set { x, nn } => self.registers[x as usize] = nn,
+            Operation::Add { x, nn } => {
+                self.registers[x as usize] = self.registers[x as usize].wrapping_add(nn)
+            }
+            Operation::Copy { x, y } => self.registers[x as usize] = self.registers[y as usize],
+            Operation::Or { x, y } => {
+                self.registers[x as usize] |= self.registers[y as usize];
+                if self.quirks.contains(QuirkFlags::FLAG_RESET) {
+                    self.reset_flag();
                 }
-                // SUB VX - VY
-                0x5 => {
-                    let flag = if self.registers[x] >= self.registers[y] {
-                        1
-                    } else {
-                        0
-                    };
-                    self.registers[x] = self.registers[x].wrapping_sub(self.registers[y]);
-                    self.registers[FLAG_REGISTER_INDEX] = flag;
+            }
+            Operation::And { x, y } => {
+                self.registers[x as usize] &= self.registers[y as usize];
+                if self.quirks.contains(QuirkFlags::FLAG_RESET) {
+                    self.reset_flag();
                 }
-                // Shift VX right
-                0x6 => {
-                    if !self.quirks.contains(QuirkFlags::SHIFT_X_ONLY) {
-                        self.registers[x] = self.registers[y];
-                    }
-                    let flag = self.registers[x] & 0x1;
-                    self.registers[x] >>= 1;
-                    self.registers[FLAG_REGISTER_INDEX] = flag;
+            }
+            Operation::Xor { x, y } => {
+                self.registers[x as usize] ^= self.registers[y as usize];
+                if self.quirks.contains(QuirkFlags::FLAG_RESET) {
+                    self.reset_flag();
                 }
-                // SUB VY - VX
-                0x7 => {
-                    let flag = if self.registers[y] >= self.registers[x] {
-                        1
-                    } else {
-                        0
-                    };
-                    self.registers[x] = self.registers[y].wrapping_sub(self.registers[x]);
-                    self.registers[FLAG_REGISTER_INDEX] = flag;
+            }
+            Operation::AddRegisters { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let (result, overflow) = self.registers[x].overflowing_add(self.registers[y]);
+                self.registers[x] = result;
+                self.registers[FLAG_REGISTER_INDEX] = overflow as u8;
+            }
+            Operation::Sub { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let flag = (self.registers[x] >= self.registers[y]) as u8;
+                self.registers[x] = self.registers[x].wrapping_sub(self.registers[y]);
+                self.registers[FLAG_REGISTER_INDEX] = flag;
+            }
+            Operation::ShiftRight { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                if !self.quirks.contains(QuirkFlags::SHIFT_X_ONLY) {
+                    self.registers[x] = self.registers[y];
                 }
-                // Shift VX left
-                0xE => {
-                    if !self.quirks.contains(QuirkFlags::SHIFT_X_ONLY) {
-                        self.registers[x] = self.registers[y];
-                    }
-                    let flag = (self.registers[x] >> 7) & 0x1;
-                    self.registers[x] <<= 1;
-                    self.registers[FLAG_REGISTER_INDEX] = flag;
+                let flag = self.registers[x] & 0x1;
+                self.registers[x] >>= 1;
+                self.registers[FLAG_REGISTER_INDEX] = flag;
+            }
+            Operation::SubReverse { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let flag = (self.registers[y] >= self.registers[x]) as u8;
+                self.registers[x] = self.registers[y].wrapping_sub(self.registers[x]);
+                self.registers[FLAG_REGISTER_INDEX] = flag;
+            }
+            Operation::ShiftLeft { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                if !self.quirks.contains(QuirkFlags::SHIFT_X_ONLY) {
+                    self.registers[x] = self.registers[y];
                 }
-                _ => self.print_unknown_instruction(instruction),
-            },
-            // Set index
-            0xA => self.index = nnn,
-            // Jump with offset
-            0xB => {
-                self.pc = (nnn
-                    + self.registers[if self.quirks.contains(QuirkFlags::JUMP_XNN) {
-                        x
-                    } else {
-                        0
-                    }] as u16)
-                    & RAM_MASK;
+                let flag = (self.registers[x] >> 7) & 0x1;
+                self.registers[x] <<= 1;
+                self.registers[FLAG_REGISTER_INDEX] = flag;
+            }
+            Operation::SetIndex(nnn) => self.index = nnn,
+            Operation::JumpOffset { nnn, x } => {
+                self.pc = (nnn + self.registers[x as usize] as u16) & self.ram_mask;
+            }
+            Operation::Random { x, nn } => {
+                self.registers[x as usize] = self.randomizer.next_u8() & nn
             }
-            // Random
-            0xC => self.registers[x] = (self.randomizer.next_u32() as u8) & nn,
-            // Display
-            0xD => {
+            Operation::Display { x, y, n } => {
                 // Handle the "display wait" quirk. If enabled, the CPU waits for the next v-blank interrupt,
                 // so the step is not taken and the program counter is not incremented.
                 // This quirk is only enabled on original Chip 8 and low-resolution (low-speed) super-chip.
@@ -659,179 +1000,105 @@ impl Chirp8 {
                     self.quirks.contains(QuirkFlags::DISPLAY_WAIT_LORES)
                 };
 
-                if wait_enabled {
-                    if self.steps_since_frame != 0 {
-                        self.pc = self.pc.wrapping_sub(PROGRAM_COUNTER_STEP) & RAM_MASK;
-                        self.steps = self.steps.wrapping_sub(1);
-                    } else {
-                        self.handle_display_instruction((self.registers[x], self.registers[y]), n);
-                    }
+                let x_y_coordinates = (self.registers[x as usize], self.registers[y as usize]);
+                if wait_enabled && self.steps_since_frame != 0 {
+                    self.pc = self.pc.wrapping_sub(PROGRAM_COUNTER_STEP) & self.ram_mask;
+                    self.steps = self.steps.wrapping_sub(1);
                 } else {
-                    self.handle_display_instruction((self.registers[x], self.registers[y]), n);
+                    self.handle_display_instruction(x_y_coordinates, n);
                 }
             }
-            // Skip if key
-            0xE => match nn {
-                // Skip if VX pressed
-                0x9E => {
-                    let key = (0xF & self.registers[x]) as usize;
-                    if self.keys[key] {
-                        self.skip_next_instruction();
-                    }
+            Operation::SkipKeyPressed(x) => {
+                let key = (0xF & self.registers[x as usize]) as usize;
+                if self.keys[key] {
+                    self.skip_next_instruction();
                 }
-                // Skip if VX not pressed
-                0xA1 => {
-                    let key = (0xF & self.registers[x]) as usize;
-                    if !self.keys[key] {
-                        self.skip_next_instruction();
-                    }
+            }
+            Operation::SkipKeyNotPressed(x) => {
+                let key = (0xF & self.registers[x as usize]) as usize;
+                if !self.keys[key] {
+                    self.skip_next_instruction();
                 }
-                _ => self.print_unknown_instruction(instruction),
-            },
-            0xF => {
-                match nn {
-                    // F000 : Load 16-bits address in index (XO-Chip)
-                    0x00 => {
-                        if self.mode == Chirp8Mode::XOChip {
-                            if self.mode == Chirp8Mode::XOChip && x == 0 {
-                                // The next "instruction" is actually a 16-bits address
-                                self.index = self.next_instruction();
-                                self.pc = self.pc.wrapping_add(PROGRAM_COUNTER_STEP);
-                            } else {
-                                self.print_unknown_instruction(instruction);
-                            }
-                        } else {
-                            self.print_unknown_instruction(instruction)
-                        }
-                    }
-                    // FX01 Plane, select plane(s) X (XO-Chip)
-                    0x01 => {
-                        if self.mode == Chirp8Mode::XOChip {
-                            self.plane_selection = repeat_bits(x as u8, DISPLAY_PLANES)
-                        } else {
-                            self.print_unknown_instruction(instruction)
-                        }
-                    }
-
-                    // Timers set VX
-                    0x07 => self.registers[x] = self.delay_timer,
-                    0x15 => self.delay_timer = self.registers[x],
-                    0x18 => self.sound_timer = self.registers[x],
-                    // Add to index
-                    0x1E => {
-                        if cfg!(feature = "mem_extend") {
-                            // Check 16-bits overflow
-                            if let Some(result) = self.index.checked_add(self.registers[x] as u16) {
-                                self.index = result;
-                            } else {
-                                self.index = self.index.wrapping_add(self.registers[x] as u16);
-                                self.set_flag();
-                            }
-                        } else {
-                            self.index = self.index + self.registers[x] as u16;
-                            // Check 12-bits overflow
-                            if self.index & !RAM_MASK != 0 {
-                                self.set_flag();
-                                self.index &= RAM_MASK;
-                            }
-                        }
-                    }
-                    // Get Key
-                    0x0A => {
-                        if let Option::Some(key) = self.get_first_key_released() {
-                            self.registers[x] = key;
-                        } else {
-                            self.pc = self.pc.wrapping_sub(PROGRAM_COUNTER_STEP);
-                        }
-                    }
-                    // FX29: Font character
-                    0x29 => {
-                        // Not implemented : SuperChip1.0 : Point I to 5-byte font sprite as in CHIP-8,
-                        // but if the high nibble in VX is 1 (ie. for values between 10 and 19 in hex) it will
-                        // point I to a 10-byte font sprite for the digit in the lower nibble of VX (only digits 0-9).
-                        // The following is the SuperChip1.1 behavior.
-                        self.index = FONT_SPRITES_ADDRESS as u16
-                            + FONT_SPRITES_STEP as u16 * self.registers[x] as u16;
-                    }
-                    // FX30: Large font character (Super-Chip 1.1 and above)
-                    0x30 => {
-                        if self.mode >= Chirp8Mode::SuperChip1_1 {
-                            self.index = FONT_SPRITES_HIGH_ADDRESS as u16
-                                + FONT_SPRITES_HIGH_STEP as u16 * self.registers[x] as u16;
-                        } else {
-                            self.print_unknown_instruction(instruction)
-                        }
-                    }
-
-                    // FX33: Binary-coded decimal conversion
-                    0x33 => {
-                        let mut value = self.registers[x];
-                        self.ram[self.index as usize] = value / 100;
-                        value %= 100;
-                        self.ram[self.index as usize + 1] = value / 10;
-                        value %= 10;
-                        self.ram[self.index as usize + 2] = value;
-                    }
-                    // FX55 : Store
-                    0x55 => {
-                        let end_index = (x + 1) as u16;
-                        for i in 0..end_index {
-                            self.ram[((self.index.wrapping_add(i)) & RAM_MASK) as usize] =
-                                self.registers[i as usize];
-                        }
-                        // if mode == SuperChip1.0 self.index = (self.index + (end_index as u16) - 1) & RAM_MASK;
-                        if self.quirks.contains(QuirkFlags::INC_INDEX) {
-                            self.index = (self.index.wrapping_add(end_index as u16)) & RAM_MASK;
-                        }
-                    }
-                    // FX65: Load
-                    0x65 => {
-                        let end_index = (x + 1) as u16;
-                        for i in 0..end_index {
-                            self.registers[i as usize] =
-                                self.ram[((self.index.wrapping_add(i)) & RAM_MASK) as usize];
-                        }
-                        // if mode == SuperChip1.0 self.index = (self.index + (end_index as u16) - 1) & RAM_MASK;
-                        if self.quirks.contains(QuirkFlags::INC_INDEX) {
-                            self.index = (self.index.wrapping_add(end_index as u16)) & RAM_MASK;
-                        }
-                    }
-                    // FX75 : Save to flags registers (Super-Chip 1.0 and above)
-                    0x75 => {
-                        if self.mode >= Chirp8Mode::SuperChip1_1 {
-                            let count = if self.mode == Chirp8Mode::XOChip {
-                                x
-                            } else {
-                                x & 0x7
-                            };
-                            self.rpl_registers[0..count].copy_from_slice(&self.registers[0..count]);
-                        } else {
-                            self.print_unknown_instruction(instruction)
-                        }
-                    }
-                    // FX85 : Load from flags registers (Super-Chip 1.0 and above)
-                    0x85 => {
-                        if self.mode >= Chirp8Mode::SuperChip1_1 {
-                            let count = if self.mode == Chirp8Mode::XOChip {
-                                x
-                            } else {
-                                x & 0x7
-                            };
-                            self.registers[0..count].copy_from_slice(&self.rpl_registers[0..count]);
-                        } else {
-                            self.print_unknown_instruction(instruction)
-                        }
-                    }
-                    _ => self.print_unknown_instruction(instruction),
+            }
+            Operation::LoadLongIndex => {
+                // The next "instruction" is actually a 16-bits address
+                self.index = self.next_instruction() & self.ram_mask;
+                self.pc = self.pc.wrapping_add(PROGRAM_COUNTER_STEP) & self.ram_mask;
+            }
+            Operation::SelectPlanes(x) => {
+                self.plane_selection = repeat_bits(x, self.display_planes)
+            }
+            Operation::GetDelayTimer(x) => self.registers[x as usize] = self.delay_timer,
+            Operation::SetDelayTimer(x) => self.delay_timer = self.registers[x as usize],
+            Operation::SetSoundTimer(x) => self.sound_timer = self.registers[x as usize],
+            Operation::AddIndex(x) => {
+                let sum = self.index as u32 + self.registers[x as usize] as u32;
+                // Check overflow of the addressable memory
+                if sum & !(self.ram_mask as u32) != 0 {
+                    self.set_flag();
                 }
+                self.index = sum as u16 & self.ram_mask;
+            }
+            Operation::WaitKey(x) => {
+                if let Option::Some(key) = self.get_first_key_released() {
+                    self.registers[x as usize] = key;
+                } else {
+                    self.pc = self.pc.wrapping_sub(PROGRAM_COUNTER_STEP) & self.ram_mask;
+                }
+            }
+            Operation::Font(x) => {
+                // Not implemented : SuperChip1.0 : Point I to 5-byte font sprite as in CHIP-8,
+                // but if the high nibble in VX is 1 (ie. for values between 10 and 19 in hex) it will
+                // point I to a 10-byte font sprite for the digit in the lower nibble of VX (only digits 0-9).
+                // The following is the SuperChip1.1 behavior.
+                self.index = FONT_SPRITES_ADDRESS as u16
+                    + FONT_SPRITES_STEP as u16 * self.registers[x as usize] as u16;
+            }
+            Operation::LargeFont(x) => {
+                self.index = FONT_SPRITES_HIGH_ADDRESS as u16
+                    + FONT_SPRITES_HIGH_STEP as u16 * self.registers[x as usize] as u16;
+            }
+            Operation::BinaryCodedDecimal(x) => {
+                let mut value = self.registers[x as usize];
+                self.write_ram(self.index & self.ram_mask, value / 100);
+                value %= 100;
+                self.write_ram(self.index.wrapping_add(1) & self.ram_mask, value / 10);
+                value %= 10;
+                self.write_ram(self.index.wrapping_add(2) & self.ram_mask, value);
+            }
+            Operation::Store(x) => {
+                let end_index = (x + 1) as u16;
+                for i in 0..end_index {
+                    self.write_ram(
+                        (self.index.wrapping_add(i)) & self.ram_mask,
+                        self.registers[i as usize],
+                    );
+                }
+                // if mode == SuperChip1.0 self.index = (self.index + (end_index as u16) - 1) & self.ram_mask;
+                if self.quirks.contains(QuirkFlags::INC_INDEX) {
+                    self.index = (self.index.wrapping_add(end_index)) & self.ram_mask;
+                }
+            }
+            Operation::Load(x) => {
+                let end_index = (x + 1) as u16;
+                for i in 0..end_index {
+                    self.registers[i as usize] =
+                        self.bus.read((self.index.wrapping_add(i)) & self.ram_mask);
+                }
+                // if mode == SuperChip1.0 self.index = (self.index + (end_index as u16) - 1) & self.ram_mask;
+                if self.quirks.contains(QuirkFlags::INC_INDEX) {
+                    self.index = (self.index.wrapping_add(end_index)) & self.ram_mask;
+                }
+            }
+            Operation::SaveFlags(count) => {
+                let count = count as usize;
+                self.rpl_registers[0..count].copy_from_slice(&self.registers[0..count]);
+            }
+            Operation::LoadFlags(count) => {
+                let count = count as usize;
+                self.registers[0..count].copy_from_slice(&self.rpl_registers[0..count]);
             }
-
-            _ => self.print_unknown_instruction(instruction),
         }
-        // Handle timers
-        self.step_timers();
-        // Handle keys
-        self.keys_previous.copy_from_slice(&self.keys);
     }
 
     /// Tick timers by one machine cycle, and update them accordingly.
@@ -844,56 +1111,247 @@ impl Chirp8 {
         }
     }
 
+    /// Returns the registers and index register, to be modified by compiled code.
+    #[cfg(feature = "jit")]
+    pub(crate) fn jit_registers(&mut self) -> (&mut [u8; REGISTERS_COUNT], &mut u16) {
+        (&mut self.registers, &mut self.index)
+    }
+
+    /// Returns the number of steps left before the end of current frame, or 0 when compiled code
+    /// must not run, because of a write monitor, a draw log or of a bus that does not allow caching.
+    #[cfg(feature = "jit")]
+    pub(crate) fn jit_steps_left(&self) -> usize {
+        if !B::CACHEABLE || self.write_monitor.is_some() || self.draw_log().is_some() || self.halted
+        {
+            0
+        } else {
+            self.steps_per_frame.saturating_sub(self.steps_since_frame)
+        }
+    }
+
+    /// Indicates whether the next step starts a new frame.
+    #[cfg(feature = "jit")]
+    pub(crate) fn jit_frame_start(&self) -> bool {
+        self.steps_since_frame == 0
+    }
+
+    /// Reads memory at given `address`, to be compiled.
+    #[cfg(feature = "jit")]
+    pub(crate) fn jit_read(&mut self, address: u16) -> u8 {
+        self.bus.read(address & self.ram_mask)
+    }
+
+    /// Updates the emulator state after compiled code executed `steps` instructions, the next
+    /// instruction being at `pc`. The steps must not go beyond the end of current frame.
+    #[cfg(feature = "jit")]
+    pub(crate) fn jit_complete(&mut self, pc: u16, steps: usize) {
+        self.pc = pc;
+        self.steps = self.steps.wrapping_add(steps);
+        self.steps_since_frame += steps - 1;
+        self.step_timers();
+        self.keys_previous.copy_from_slice(&self.keys);
+    }
+
+    /// Returns the identifier of the emulator, unique among all emulators created.
+    #[cfg(feature = "jit")]
+    pub(crate) fn jit_instance(&self) -> u64 {
+        self.jit_instance
+    }
+
+    /// Returns and forgets the smallest and largest addresses written since the last call.
+    #[cfg(feature = "jit")]
+    pub(crate) fn take_written_range(&mut self) -> Option<(u16, u16)> {
+        self.written_range.take()
+    }
+
+    /// Copies the registers visible to a debugger into `registers`.
+    #[cfg(feature = "gdbstub")]
+    pub(crate) fn gdb_read_registers(&self, registers: &mut Chirp8Registers) {
+        registers.v = self.registers;
+        registers.i = self.index;
+        registers.pc = self.pc;
+        registers.sp = self.stack.len() as u8;
+        registers.delay_timer = self.delay_timer;
+        registers.sound_timer = self.sound_timer;
+    }
+
+    /// Modifies the registers from a debugger. Changing the stack pointer pops return addresses
+    /// or pushes null ones.
+    #[cfg(feature = "gdbstub")]
+    pub(crate) fn gdb_write_registers(&mut self, registers: &Chirp8Registers) {
+        self.registers = registers.v;
+        self.index = registers.i & self.ram_mask;
+        self.pc = registers.pc & self.ram_mask;
+        while self.stack.len() > registers.sp as usize {
+            let _ = self.stack.pop();
+        }
+        while self.stack.len() < (registers.sp as usize).min(STACK_SIZE) {
+            let _ = self.stack.push(0);
+        }
+        self.delay_timer = registers.delay_timer;
+        self.sound_timer = registers.sound_timer;
+    }
+
+    /// Reads memory at given `address` for a debugger.
+    #[cfg(feature = "gdbstub")]
+    pub(crate) fn gdb_read(&mut self, address: u16) -> u8 {
+        Self::peek_byte(&mut self.bus, address & self.ram_mask)
+    }
+
     /// Increments program counter so that the next instruction is skipped.
     fn skip_next_instruction(&mut self) {
         const LOAD_LARGE_INDEX_OPCODE: u16 = 0xF000;
         let offset = if self.mode == Chirp8Mode::XOChip
-            && self.next_instruction() == LOAD_LARGE_INDEX_OPCODE
+            && self.peek_instruction() == LOAD_LARGE_INDEX_OPCODE
         {
             // Jump over 4 bytes instructions
             PROGRAM_COUNTER_STEP * 2
         } else {
             PROGRAM_COUNTER_STEP
         };
-        self.pc = self.pc.wrapping_add(offset) & RAM_MASK;
+        self.pc = self.pc.wrapping_add(offset) & self.ram_mask;
     }
 
     /// Modifies the number of CPU steps executed between each frame.
     pub fn set_steps_per_frame(&mut self, steps: usize) {
-        while self.steps_since_frame != 0 {
+        while self.steps_since_frame != 0 && !self.halted {
             self.step()
         }
         self.steps_per_frame = steps;
     }
 
-    #[allow(unused_variables)]
-    fn print_unknown_instruction(&self, instruction: u16) {
-        #[cfg(feature = "std")]
+    /// Handles the unknown `instruction` that has just been fetched, according to the
+    /// [UnknownInstructionAction] of the emulator.
+    fn unknown_instruction(&mut self, instruction: u16) {
+        let unknown = UnknownInstruction {
+            opcode: instruction,
+            mode: self.mode,
+            pc: self.pc.wrapping_sub(PROGRAM_COUNTER_STEP) & self.ram_mask,
+        };
+        match self.unknown_instruction_action {
+            UnknownInstructionAction::Ignore => {}
+            UnknownInstructionAction::Log => diagnostics::warn_unknown_instruction(&unknown),
+            UnknownInstructionAction::Callback(callback) => callback(&unknown),
+            UnknownInstructionAction::Halt => self.halted = true,
+        }
+    }
+
+    /// Writes `value` to RAM at given `address`, as the result of an instruction.
+    /// The write is checked by the write monitor if any.
+    #[inline]
+    fn write_ram(&mut self, address: u16, value: u8) {
+        if let Option::Some(monitor) = &mut self.write_monitor {
+            let pc = self.pc.wrapping_sub(PROGRAM_COUNTER_STEP) & self.ram_mask;
+            if self.halted || !monitor.check_write(pc, address, value) {
+                self.halted = true;
+                return;
+            }
+        }
+        self.bus.write(address, value);
+        #[cfg(feature = "decode_cache")]
+        self.decode_cache.invalidate(address, self.ram_mask);
+        #[cfg(feature = "jit")]
         {
-            let message = alloc::format!(
-                "Unknown instruction 0x{:04X} in mode '{}', at program counter 0x{:04X} {}.",
-                instruction,
-                match self.mode {
-                    Chirp8Mode::CosmacChip8 => "Chip-8",
-                    Chirp8Mode::SuperChip1_1 => "Super Chip 1.1",
-                    Chirp8Mode::SuperChipModern => "Super Chip Modern",
-                    Chirp8Mode::XOChip => "XO-Chip",
-                },
-                self.pc.wrapping_sub(PROGRAM_COUNTER_STEP),
-                if let Option::Some(address) = self
-                    .pc
-                    .wrapping_sub(PROGRAM_COUNTER_STEP)
-                    .checked_sub(PROGRAM_START as u16)
-                {
-                    alloc::format!("(At program address 0x{:04X})", address)
-                } else {
-                    alloc::string::String::from("(Lost in reserved memory < 0x0200)")
-                }
-            );
-            std::println!("{}", message);
+            self.written_range = Option::Some(match self.written_range {
+                Option::Some((first, last)) => (first.min(address), last.max(address)),
+                Option::None => (address, address),
+            });
+        }
+    }
+
+    /// Attaches given write `monitor` to the emulator, or detaches it when `None`.
+    /// See [WriteMonitor].
+    pub fn set_write_monitor(&mut self, monitor: Option<WriteMonitor>) {
+        self.write_monitor = monitor;
+    }
+
+    /// Sets what the emulator does when it encounters an unknown instruction, logging a
+    /// warning by default. See [UnknownInstructionAction].
+    pub fn set_unknown_instruction_action(&mut self, action: UnknownInstructionAction) {
+        self.unknown_instruction_action = action;
+    }
+
+    /// Returns what the emulator does when it encounters an unknown instruction.
+    pub fn unknown_instruction_action(&self) -> UnknownInstructionAction {
+        self.unknown_instruction_action
+    }
+
+    /// Returns the write monitor attached to the emulator, if any.
+    pub fn write_monitor(&self) -> Option<&WriteMonitor> {
+        self.write_monitor.as_ref()
+    }
+
+    /// Returns the write monitor attached to the emulator mutably, if any.
+    pub fn write_monitor_mut(&mut self) -> Option<&mut WriteMonitor> {
+        self.write_monitor.as_mut()
+    }
+
+    /// Attaches given draw `log` to the emulator, or detaches it when `None`.
+    /// The log is stored on the heap, which requires the `alloc` feature. See [DrawLog].
+    #[cfg(feature = "alloc")]
+    pub fn set_draw_log(&mut self, log: Option<DrawLog>) {
+        self.draw_log = log.map(alloc::boxed::Box::new);
+    }
+
+    /// Returns the draw log attached to the emulator, if any.
+    pub fn draw_log(&self) -> Option<&DrawLog> {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "alloc")] {
+                self.draw_log.as_deref()
+            } else {
+                Option::None
+            }
+        }
+    }
+
+    /// Returns the draw log attached to the emulator mutably, if any.
+    pub fn draw_log_mut(&mut self) -> Option<&mut DrawLog> {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "alloc")] {
+                self.draw_log.as_deref_mut()
+            } else {
+                Option::None
+            }
         }
     }
 
+    /// Attaches given `colorizer` to the emulator, or detaches it when `None`.
+    /// The colorizer is stored on the heap, which requires the `alloc` feature. See [Colorizer].
+    #[cfg(feature = "alloc")]
+    pub fn set_colorizer(&mut self, colorizer: Option<Colorizer>) {
+        self.colorizer = colorizer.map(alloc::boxed::Box::new);
+    }
+
+    /// Returns the colorizer attached to the emulator, if any.
+    pub fn colorizer(&self) -> Option<&Colorizer> {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "alloc")] {
+                self.colorizer.as_deref()
+            } else {
+                Option::None
+            }
+        }
+    }
+
+    /// Returns the colorizer attached to the emulator mutably, if any.
+    pub fn colorizer_mut(&mut self) -> Option<&mut Colorizer> {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "alloc")] {
+                self.colorizer.as_deref_mut()
+            } else {
+                Option::None
+            }
+        }
+    }
+
+    /// Indicates whether the emulator is halted and does not execute instructions anymore,
+    /// which happens when a [WriteMonitor] configured with [crate::WriteAction::Error] flags a write,
+    /// or on an unknown instruction with [UnknownInstructionAction::Halt].
+    /// The emulator leaves this state when [Chirp8::reset].
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
     #[inline]
     fn set_flag(&mut self) {
         self.registers[FLAG_REGISTER_INDEX] = 1;
@@ -918,26 +1376,53 @@ impl Chirp8 {
 
     /// Clears the screen.
     fn clear_display(&mut self) {
-        for row in &mut self.display_buffer {
-            row.fill(PIXEL_OFF);
+        self.display_buffer.clear_planes(ALL_PLANES);
+        if let Option::Some(colorizer) = self.colorizer_mut() {
+            colorizer.clear();
         }
+        self.mark_display_changed();
     }
 
     /// Clears the selected screen planes.
     fn clear_planes(&mut self) {
-        if self.plane_selection & PLANES_MASK == PLANES_MASK {
-            self.clear_display();
+        self.display_buffer.clear_planes(self.plane_selection);
+        self.mark_display_changed();
+    }
+
+    /// Marks the whole display as changed.
+    fn mark_display_changed(&mut self) {
+        self.display_changed = true;
+        self.dirty_region.mark_all();
+        if let Option::Some(log) = self.draw_log_mut() {
+            log.clear_visible();
+        }
+    }
+
+    /// Marks the pixels of row `y` set in `bits` as drawn by the current sprite.
+    #[inline]
+    fn mark_sprite_row(&mut self, y: usize, bits: u128) {
+        self.dirty_region.mark_row(y, bits);
+        if let Option::Some(colorizer) = self.colorizer_mut() {
+            colorizer.paint_row(y, bits);
+        }
+    }
+
+    /// Returns the planes of the display buffer flipped by sprites drawn on given `plane`, as
+    /// their bits in pixel values. Without several planes, sprites are drawn on all of them at once.
+    fn sprite_planes(&self, plane: usize) -> u8 {
+        if self.quirks.contains(QuirkFlags::USE_SEVERAL_PLANES) {
+            repeat_bits(1 << plane, self.display_planes)
         } else {
-            for plane in 0..DISPLAY_PLANES {
-                let plane_mask = repeat_bits(1 << plane, DISPLAY_PLANES);
-                if plane_mask & self.plane_selection != 0 {
-                    for row in &mut self.display_buffer {
-                        for pixel in row {
-                            *pixel &= !plane_mask;
-                        }
-                    }
-                }
-            }
+            ALL_PLANES
+        }
+    }
+
+    /// Returns the planes moved by scroll instructions, as their bits in pixel values.
+    fn scrolled_planes(&self) -> u8 {
+        if self.quirks.contains(QuirkFlags::USE_SEVERAL_PLANES) {
+            self.plane_selection
+        } else {
+            ALL_PLANES
         }
     }
 
@@ -962,7 +1447,7 @@ impl Chirp8 {
 
         // Number of color planes
         let planes_count = if self.quirks.contains(QuirkFlags::USE_SEVERAL_PLANES) {
-            DISPLAY_PLANES
+            self.display_planes
         } else {
             1
         };
@@ -973,22 +1458,34 @@ impl Chirp8 {
             QuirkFlags::CLIP_SPRITES_LORES
         });
 
+        // Columns of the display buffer covered by the sprite, and the bit of each sprite bit.
+        let mut columns = 0u128;
+        let mut column_bits = [0u128; u8::BITS as usize];
+        for (bit, column_bit) in column_bits.iter_mut().enumerate() {
+            let col = (x_y_coordinates.0 as usize + bit) * coordinates_scaler;
+
+            // Handle width clipping / wrapping
+            if col >= DISPLAY_WIDTH && !wrapping {
+                break;
+            }
+            *column_bit = display::column_bit(col % DISPLAY_WIDTH);
+            columns |= *column_bit;
+        }
+
         // The number of planes drawn so far.
         let mut drawn_planes = 0;
         for plane in 0..planes_count {
-            // if 2 planes, masks of plane 0 and 1 are 0b01_01_01_01 and 0b10_10_10_10
-            // if 1 planes, mask of only plane is 0xFF
-            let pixel_bits_mask = repeat_bits(1 << plane, planes_count);
-            if self.plane_selection & pixel_bits_mask == 0 {
+            let planes = self.sprite_planes(plane);
+            if self.plane_selection & planes == 0 {
                 continue;
             }
             for line in 0..(height as usize) {
-                let sprite_address = (self
+                let sprite_address = self
                     .index
                     .wrapping_add((height as u16) * (drawn_planes as u16))//Plane offset
                     .wrapping_add(line as u16) // Line offset
-                    & RAM_MASK) as usize;
-                let sprite = self.ram[sprite_address];
+                    & self.ram_mask;
+                let sprite = self.bus.read(sprite_address);
                 let row = ((x_y_coordinates.1 as usize) + line) * coordinates_scaler;
 
                 // Handle line clipping / wrapping
@@ -1001,36 +1498,21 @@ impl Chirp8 {
                 }
                 let row = row % DISPLAY_HEIGHT;
 
-                let mut colliding_line = false;
-                for bit in 0..(u8::BITS as usize) {
-                    let col = (x_y_coordinates.0 as usize + bit) * coordinates_scaler;
-
-                    // Handle width clipping / wrapping
-                    if col >= DISPLAY_WIDTH && !wrapping {
-                        break;
-                    }
-                    let col = col % DISPLAY_WIDTH;
-
-                    // Should the pixel be flipped or not.
-                    let pixel_bits_xor = if ((sprite >> ((u8::BITS as usize) - 1 - bit)) & 1) == 0 {
-                        0x00
-                    } else {
-                        pixel_bits_mask
-                    };
-
-                    let pixel_before = self.display_buffer[row][col];
-                    let mut pixel = pixel_before;
-                    pixel ^= pixel_bits_xor;
-                    self.display_buffer[row][col] = pixel;
-                    if !self.high_resolution {
-                        // Draw 2x2 "pixels" when on low resolution
-                        self.display_buffer[row][col + 1] = pixel;
-                        self.display_buffer[row + 1][col] = pixel;
-                        self.display_buffer[row + 1][col + 1] = pixel;
-                    }
-                    // Set flag when turned off
-                    colliding_line |=
-                        pixel_before & pixel_bits_mask != 0 && pixel & pixel_bits_mask == 0;
+                // Pixels to be flipped.
+                let bits = column_bits
+                    .iter()
+                    .enumerate()
+                    .filter(|(bit, _)| (sprite >> ((u8::BITS as usize) - 1 - bit)) & 1 != 0)
+                    .fold(0, |bits, (_, column_bit)| bits | column_bit);
+
+                let colliding_line = self.display_buffer.xor_row(row, planes, bits);
+                if !self.high_resolution {
+                    // Draw 2x2 "pixels" when on low resolution
+                    self.display_buffer.copy_lores(row, columns);
+                    self.mark_sprite_row(row, bits | (bits >> 1));
+                    self.mark_sprite_row(row + 1, bits | (bits >> 1));
+                } else {
+                    self.mark_sprite_row(row, bits);
                 }
                 if colliding_line {
                     self.registers[FLAG_REGISTER_INDEX] += 1;
@@ -1049,7 +1531,7 @@ impl Chirp8 {
 
         // Number of color planes
         let planes_count = if self.quirks.contains(QuirkFlags::USE_SEVERAL_PLANES) {
-            DISPLAY_PLANES
+            self.display_planes
         } else {
             1
         };
@@ -1060,16 +1542,26 @@ impl Chirp8 {
             QuirkFlags::CLIP_SPRITES_LORES
         });
 
+        // The bit in the display buffer of each sprite bit, 0 when clipped.
+        let mut column_bits = [0u128; LARGE_SPRITE_SIZE];
+        for (bit, column_bit) in column_bits.iter_mut().enumerate() {
+            let col = x_y_coordinates.0 as usize % DISPLAY_WIDTH + bit;
+
+            // Handle width clipping / wrapping
+            if col >= DISPLAY_WIDTH && !wrapping {
+                break;
+            }
+            *column_bit = display::column_bit(col % DISPLAY_WIDTH);
+        }
+
         // In SChip mode, VF is set to the number of colliding rows, not just 0 or 1.
         // Although disabled on XO-chip, this quirk is handled as VF being the number of colliding rows on all planes.
 
         // The number of planes drawn so far.
         let mut drawn_planes = 0;
         for plane in 0..planes_count {
-            // if 2 planes, masks of plane 0 and 1 are 0b01_01_01_01 and 0b10_10_10_10
-            // if 1 planes, mask of only plane is 0xFF
-            let pixel_bits_mask = repeat_bits(1 << plane, planes_count);
-            if self.plane_selection & pixel_bits_mask == 0 {
+            let planes = self.sprite_planes(plane);
+            if self.plane_selection & planes == 0 {
                 continue;
             }
             for line in 0..LARGE_SPRITE_SIZE {
@@ -1085,49 +1577,27 @@ impl Chirp8 {
                 }
                 let row = row % DISPLAY_HEIGHT;
 
-                let mut colliding_line = false;
-                for half in 0..BYTES_PER_LINE {
-                    let sprite_address = (self
-                        .index
-                        .wrapping_add(BYTES_PER_LINE * (LARGE_SPRITE_SIZE as u16) * drawn_planes)
-                        .wrapping_add(BYTES_PER_LINE * (line as u16))
-                        .wrapping_add(half)
-                        & RAM_MASK) as usize;
-                    let sprite = self.ram[sprite_address];
-
-                    for bit in 0..(min(
-                        u8::BITS as usize,
-                        LARGE_SPRITE_SIZE - (half as usize) * (u8::BITS as usize),
-                    )) {
-                        let col = x_y_coordinates.0 as usize % DISPLAY_WIDTH
-                            + (half as usize) * (u8::BITS as usize)
-                            + bit;
-
-                        // Handle width clipping / wrapping
-                        if col >= DISPLAY_WIDTH && !wrapping {
-                            break;
-                        }
-                        let col = col % DISPLAY_WIDTH;
-
-                        // Should the pixel be flipped or not
-                        let pixel_bits_xor =
-                            if ((sprite >> ((u8::BITS as usize) - 1 - bit)) & 1) == 0 {
-                                0x00
-                            } else {
-                                pixel_bits_mask
-                            };
-
-                        let pixel = &mut self.display_buffer[row][col];
-                        let pixel_before = *pixel;
-                        *pixel ^= pixel_bits_xor;
-                        // Set flag when turned off
-                        colliding_line |= (pixel_before & pixel_bits_mask) != 0
-                            && (*pixel & pixel_bits_mask) == 0;
-                    }
-                }
-                if colliding_line {
+                let sprite_address = self
+                    .index
+                    .wrapping_add(BYTES_PER_LINE * (LARGE_SPRITE_SIZE as u16) * drawn_planes)
+                    .wrapping_add(BYTES_PER_LINE * (line as u16));
+                let sprite = u16::from_be_bytes([
+                    self.bus.read(sprite_address & self.ram_mask),
+                    self.bus
+                        .read(sprite_address.wrapping_add(1) & self.ram_mask),
+                ]);
+
+                // Pixels to be flipped.
+                let bits = column_bits
+                    .iter()
+                    .enumerate()
+                    .filter(|(bit, _)| (sprite >> (LARGE_SPRITE_SIZE - 1 - bit)) & 1 != 0)
+                    .fold(0, |bits, (_, column_bit)| bits | column_bit);
+
+                if self.display_buffer.xor_row(row, planes, bits) {
                     self.registers[FLAG_REGISTER_INDEX] += 1;
                 }
+                self.mark_sprite_row(row, bits);
             }
             drawn_planes += 1;
         }
@@ -1154,9 +1624,20 @@ impl Chirp8 {
         let colliding_rows_quirk = self.quirks.contains(if self.high_resolution {
             QuirkFlags::COLLISION_COUNT_HIRES
         } else {
-            QuirkFlags::COLLISION_COUNT_HIRES
+            QuirkFlags::COLLISION_COUNT_LORES
         });
 
+        // The sprite drawn, for the draw log and the colorizer.
+        let call = if self.draw_log().is_some() || self.colorizer().is_some() {
+            let (width, height) = if large_sprite { (16, 16) } else { (8, height) };
+            Option::Some(self.draw_call(x_y_coordinates, width, height))
+        } else {
+            Option::None
+        };
+        if let (Option::Some(colorizer), Option::Some(call)) = (self.colorizer_mut(), &call) {
+            colorizer.select(call);
+        }
+
         if large_sprite {
             // Handle instruction DXY0 : display 16x16 sprite (height is 16, not 0)
             self.display_large_sprite(x_y_coordinates, colliding_rows_quirk);
@@ -1169,6 +1650,48 @@ impl Chirp8 {
         if !colliding_rows_quirk && self.registers[FLAG_REGISTER_INDEX] != 0 {
             self.registers[FLAG_REGISTER_INDEX] = 1;
         }
+
+        let collision = self.registers[FLAG_REGISTER_INDEX];
+        if let (Option::Some(log), Option::Some(mut call)) = (self.draw_log_mut(), call) {
+            call.collision = collision;
+            log.record(call);
+        }
+    }
+
+    /// Returns the draw call of the sprite of given `width` and `height` about to be drawn at
+    /// `x_y_coordinates`, without its collision.
+    fn draw_call(&mut self, x_y_coordinates: (u8, u8), width: u8, height: u8) -> DrawCall {
+        let (resolution_width, resolution_height) = self.resolution();
+        let all_planes = (1u8 << self.display_planes) - 1;
+        let (planes, planes_count) = if self.quirks.contains(QuirkFlags::USE_SEVERAL_PLANES) {
+            let planes = (0..self.display_planes)
+                .filter(|plane| self.plane_selection & self.sprite_planes(*plane) != 0)
+                .fold(0u8, |planes, plane| planes | (1 << plane));
+            (planes, planes.count_ones() as u16)
+        } else {
+            (all_planes, 1)
+        };
+        let length = (width as u16 / u8::BITS as u16) * height as u16 * planes_count;
+
+        let (bus, index, ram_mask) = (&mut self.bus, self.index, self.ram_mask);
+        let hash = sprites::hash_sprite_bytes(
+            width,
+            height,
+            (0..length).map(|offset| Self::peek_byte(bus, index.wrapping_add(offset) & ram_mask)),
+        );
+
+        DrawCall {
+            address: self.index,
+            x: (x_y_coordinates.0 as usize % resolution_width) as u8,
+            y: (x_y_coordinates.1 as usize % resolution_height) as u8,
+            width,
+            height,
+            planes,
+            length,
+            high_resolution: self.high_resolution,
+            collision: 0,
+            hash,
+        }
     }
 
     /// Indicates if the display changed since the last time this method was called.
@@ -1178,166 +1701,65 @@ impl Chirp8 {
         result
     }
 
-    /// Scrolls up display by `scroll` pixels.
-    fn scroll_up(&mut self, scroll: u8) {
-        let scroll =
-            if !self.quirks.contains(QuirkFlags::SCROLL_HALF_PIXEL) && !self.high_resolution {
-                scroll * 2
-            } else {
-                scroll
-            } as usize;
+    /// Returns the pixels of the display buffer which may have changed since the last call to
+    /// [Chirp8::take_dirty_region], all of them at first.
+    pub fn dirty_region(&self) -> &DirtyRegion {
+        &self.dirty_region
+    }
 
-        if !self.quirks.contains(QuirkFlags::USE_SEVERAL_PLANES)
-            || self.plane_selection & PLANES_MASK == PLANES_MASK
-        {
-            // Scroll all planes
-            self.display_buffer.rotate_left(scroll);
-            // Bottom of screen is black.
-            for black_row in &mut self.display_buffer[(DISPLAY_HEIGHT - scroll)..DISPLAY_HEIGHT] {
-                black_row.fill(PIXEL_OFF);
-            }
+    /// Returns the pixels of the display buffer which may have changed since the last call,
+    /// all of them at first, then forgets them. See [DirtyRegion].
+    pub fn take_dirty_region(&mut self) -> DirtyRegion {
+        core::mem::take(&mut self.dirty_region)
+    }
+
+    /// Returns the number of pixels of the display buffer moved by scrolling `scroll` pixels.
+    fn scroll_pixels(&self, scroll: u8) -> usize {
+        if !self.quirks.contains(QuirkFlags::SCROLL_HALF_PIXEL) && !self.high_resolution {
+            scroll as usize * 2
         } else {
-            // Scroll plane by plane
-            for plane in 0..DISPLAY_PLANES {
-                let plane_mask = repeat_bits(1 << plane, DISPLAY_PLANES);
-                let not_plane_mask = !plane_mask;
-                if plane_mask & self.plane_selection == 0 {
-                    continue;
-                }
-                for row in 0..(DISPLAY_HEIGHT - scroll) {
-                    let source_row = row + scroll;
-                    for col in 0..DISPLAY_WIDTH {
-                        let source = self.display_buffer[source_row][col];
-                        let pixel = &mut self.display_buffer[row][col];
-                        *pixel = (*pixel & not_plane_mask) | (source & plane_mask);
-                    }
-                }
-                // Bottom of screen
-                for row in (DISPLAY_HEIGHT - scroll)..DISPLAY_HEIGHT {
-                    self.display_buffer[row]
-                        .iter_mut()
-                        .for_each(|pixel| *pixel &= not_plane_mask);
-                }
-            }
+            scroll as usize
+        }
+    }
+
+    /// Scrolls up display by `scroll` pixels.
+    fn scroll_up(&mut self, scroll: u8) {
+        let (planes, scroll) = (self.scrolled_planes(), self.scroll_pixels(scroll));
+        self.display_buffer.scroll_up(planes, scroll);
+        if let Option::Some(colorizer) = self.colorizer_mut() {
+            colorizer.scroll_up(scroll);
         }
+        self.mark_display_changed();
     }
 
     /// Scrolls down display by `scroll` pixels.
     fn scroll_down(&mut self, scroll: u8) {
-        let scroll =
-            if !self.quirks.contains(QuirkFlags::SCROLL_HALF_PIXEL) && !self.high_resolution {
-                scroll * 2
-            } else {
-                scroll
-            } as usize;
-        if !self.quirks.contains(QuirkFlags::USE_SEVERAL_PLANES)
-            || self.plane_selection & PLANES_MASK == PLANES_MASK
-        {
-            self.display_buffer.rotate_right(scroll);
-            // Top of screen is black.
-            for black_row in &mut self.display_buffer[0..scroll] {
-                black_row.fill(PIXEL_OFF);
-            }
-        } else {
-            // Scroll plane by plane
-            for plane in 0..DISPLAY_PLANES {
-                let plane_mask = repeat_bits(1 << plane, DISPLAY_PLANES);
-                let not_plane_mask = !plane_mask;
-                if plane_mask & self.plane_selection == 0 {
-                    continue;
-                }
-                for row in (scroll..DISPLAY_HEIGHT).rev() {
-                    let source_row = row - scroll;
-                    for col in 0..DISPLAY_WIDTH {
-                        let source = self.display_buffer[source_row][col];
-                        let pixel = &mut self.display_buffer[row][col];
-                        *pixel = (*pixel & not_plane_mask) | (source & plane_mask);
-                    }
-                }
-                // Top of screen
-                for row in 0..scroll {
-                    self.display_buffer[row]
-                        .iter_mut()
-                        .for_each(|pixel| *pixel &= not_plane_mask);
-                }
-            }
+        let (planes, scroll) = (self.scrolled_planes(), self.scroll_pixels(scroll));
+        self.display_buffer.scroll_down(planes, scroll);
+        if let Option::Some(colorizer) = self.colorizer_mut() {
+            colorizer.scroll_down(scroll);
         }
+        self.mark_display_changed();
     }
 
     /// Scrolls left display by `scroll` pixels.
     fn scroll_left(&mut self, scroll: u8) {
-        let scroll =
-            if !self.quirks.contains(QuirkFlags::SCROLL_HALF_PIXEL) && !self.high_resolution {
-                scroll * 2
-            } else {
-                scroll
-            } as usize;
-        if !self.quirks.contains(QuirkFlags::USE_SEVERAL_PLANES)
-            || self.plane_selection & PLANES_MASK == PLANES_MASK
-        {
-            for row in &mut self.display_buffer {
-                row.rotate_left(scroll);
-                row[(DISPLAY_WIDTH - scroll)..DISPLAY_WIDTH].fill(PIXEL_OFF);
-            }
-        } else {
-            // Scroll plane by plane
-            for plane in 0..DISPLAY_PLANES {
-                let plane_mask = repeat_bits(1 << plane, DISPLAY_PLANES);
-                let not_plane_mask = !plane_mask;
-                if plane_mask & self.plane_selection == 0 {
-                    continue;
-                }
-                for row in &mut self.display_buffer {
-                    for col in 0..(DISPLAY_WIDTH - scroll) {
-                        let source = row[col + scroll];
-                        let pixel = &mut row[col];
-                        *pixel = (*pixel & not_plane_mask) | (source & plane_mask);
-                        // Right of screen
-                        row[(DISPLAY_WIDTH - scroll)..DISPLAY_WIDTH]
-                            .iter_mut()
-                            .for_each(|pixel| *pixel &= not_plane_mask);
-                    }
-                }
-            }
+        let (planes, scroll) = (self.scrolled_planes(), self.scroll_pixels(scroll));
+        self.display_buffer.scroll_left(planes, scroll);
+        if let Option::Some(colorizer) = self.colorizer_mut() {
+            colorizer.scroll_left(scroll);
         }
+        self.mark_display_changed();
     }
 
     /// Scrolls right display by `scroll` pixels.
     fn scroll_right(&mut self, scroll: u8) {
-        let scroll =
-            if !self.quirks.contains(QuirkFlags::SCROLL_HALF_PIXEL) && !self.high_resolution {
-                scroll * 2
-            } else {
-                scroll
-            } as usize;
-        if !self.quirks.contains(QuirkFlags::USE_SEVERAL_PLANES)
-            || self.plane_selection & PLANES_MASK == PLANES_MASK
-        {
-            for row in &mut self.display_buffer {
-                row.rotate_right(scroll);
-                row[0..scroll].fill(PIXEL_OFF);
-            }
-        } else {
-            // Scroll plane by plane
-            for plane in 0..DISPLAY_PLANES {
-                let plane_mask = repeat_bits(1 << plane, DISPLAY_PLANES);
-                let not_plane_mask = !plane_mask;
-                if plane_mask & self.plane_selection == 0 {
-                    continue;
-                }
-                for row in &mut self.display_buffer {
-                    for col in (scroll..DISPLAY_WIDTH).rev() {
-                        let source = row[col - scroll];
-                        let pixel = &mut row[col];
-                        *pixel = (*pixel & not_plane_mask) | (source & plane_mask);
-                        // Right of screen
-                        row[0..scroll]
-                            .iter_mut()
-                            .for_each(|pixel| *pixel &= not_plane_mask);
-                    }
-                }
-            }
+        let (planes, scroll) = (self.scrolled_planes(), self.scroll_pixels(scroll));
+        self.display_buffer.scroll_right(planes, scroll);
+        if let Option::Some(colorizer) = self.colorizer_mut() {
+            colorizer.scroll_right(scroll);
         }
+        self.mark_display_changed();
     }
 
     /// Indicates whether the sound buzzer is currently on or not.
@@ -1353,11 +1775,18 @@ impl Chirp8 {
         self.mode == Chirp8Mode::XOChip
     }
 
-    /// Load a ROM into memory. The ROM must be smaller than [PROGRAM_SIZE].
+    /// Load a ROM into memory. The ROM must be smaller than the memory following
+    /// [PROGRAM_START], that is [PROGRAM_SIZE] bytes with the default [Ram].
     /// Returns true if the ROM has been loaded to RAM, false otherwise.
     pub fn load_rom(&mut self, rom: &[u8]) -> bool {
-        if rom.len() < PROGRAM_SIZE {
-            self.ram[PROGRAM_START..(PROGRAM_START + rom.len())].copy_from_slice(rom);
+        if rom.len() < self.ram_size() - PROGRAM_START {
+            self.bus.write_block(PROGRAM_START as u16, rom);
+            #[cfg(feature = "decode_cache")]
+            self.decode_cache.clear();
+            #[cfg(feature = "jit")]
+            {
+                self.written_range = Option::Some((0, self.ram_mask));
+            }
             true
         } else {
             false
@@ -1374,13 +1803,175 @@ impl Chirp8 {
         &self.rpl_registers
     }
 
+    /// Returns a reference to the memory bus of the emulator.
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    /// Returns the size of the memory of the emulator in bytes, see [Chirp8::with_bus_and_display].
+    pub fn ram_size(&self) -> usize {
+        self.ram_mask as usize + 1
+    }
+
+    /// Returns a mutable reference to the memory bus of the emulator.
+    /// With the `decode_cache` feature, this clears the decoded instructions cache.
+    pub fn bus_mut(&mut self) -> &mut B {
+        #[cfg(feature = "decode_cache")]
+        self.decode_cache.clear();
+        #[cfg(feature = "jit")]
+        {
+            self.written_range = Option::Some((0, self.ram_mask));
+        }
+        &mut self.bus
+    }
+
+    /// Returns true when the high resolution (128x64) is enabled, false when each pixel of the
+    /// low resolution (64x32) is drawn as 2x2 pixels of the display buffer.
+    pub fn is_high_resolution(&self) -> bool {
+        self.high_resolution
+    }
+
+    /// Returns the running mode of the emulator.
+    pub fn mode(&self) -> Chirp8Mode {
+        self.mode
+    }
+
+    /// Returns the enabled quirks of the emulator.
+    pub fn quirks(&self) -> QuirkFlags {
+        self.quirks
+    }
+
+    /// Returns the registers V0 to VF.
+    pub fn registers(&self) -> &[u8; REGISTERS_COUNT] {
+        &self.registers
+    }
+
+    /// Returns the program counter, the address of the next instruction to execute.
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// Returns the index register, "I".
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Returns the number of steps taken so far, see [Chirp8::step].
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Returns the number of steps per frame, see [Chirp8::set_steps_per_frame].
+    pub fn steps_per_frame(&self) -> usize {
+        self.steps_per_frame
+    }
+
     /// Returns a reference to the internal display buffer.
     /// Notice that when running on Cosmac mode, each "pixel" is displayed as a 2 by 2 square,
     /// in order to match the resolution of the Super-Chip / XO-Chip.
-    pub fn get_display_buffer(&self) -> &DisplayBuffer {
+    pub fn get_display_buffer(&self) -> &D {
         &self.display_buffer
     }
 
+    /// Returns the number of display planes, 2 by default with the
+    /// [QuirkFlags::USE_SEVERAL_PLANES] quirk and 1 without it. See [Chirp8::set_display_planes].
+    pub fn display_planes(&self) -> usize {
+        self.display_planes
+    }
+
+    /// Sets the number of display planes to 1, 2 or 4, for up to 2, 4 or 16 colors, then clears
+    /// the display and selects the first plane. Returns false for other counts, or for more
+    /// planes than the display buffer stores, see [DisplayStorage::max_planes_count].
+    ///
+    /// Planes are drawn on separately with the [QuirkFlags::USE_SEVERAL_PLANES] quirk, the
+    /// instruction `FN01` selecting them with the `N` bit-mask. Sprites then hold the data of
+    /// each selected plane one after the other, from the first plane to the last one.
+    ///
+    /// Pixel values repeat the bits of the planes they are lit on over the whole byte, so that
+    /// they range from [PIXEL_OFF] to [PIXEL_ON] by steps of [Chirp8::pixel_step] :
+    /// ```
+    /// let mut emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::XOChip);
+    /// assert!(emulator.set_display_planes(4));
+    /// emulator.load_rom(&[0xF4, 0x01, 0xD0, 0x01, 0x80]); // Draw on the third plane.
+    /// emulator.take_steps(2);
+    /// assert_eq!(emulator.get_pixel(0, 0), 0b0100_0100);
+    /// assert_eq!(emulator.pixel_step(), 0x11);
+    /// assert!(!emulator.set_display_planes(3));
+    /// ```
+    pub fn set_display_planes(&mut self, count: usize) -> bool {
+        if !matches!(count, 1 | 2 | 4) || count > self.display_buffer.max_planes_count() {
+            return false;
+        }
+        self.display_planes = count;
+        self.display_buffer.set_planes_count(count);
+        self.clear_display();
+        if self.quirks.contains(QuirkFlags::USE_SEVERAL_PLANES) {
+            self.plane_selection = repeat_bits(0b01, count);
+        }
+        true
+    }
+
+    /// Returns the difference between the values of two successive colors of the pixels :
+    /// 0xFF with 1 plane, 0x55 with 2 planes and 0x11 with 4 planes.
+    pub fn pixel_step(&self) -> u8 {
+        repeat_bits(1, self.display_planes)
+    }
+
+    /// Returns the width and height in pixels of the current resolution : 64x32 in low
+    /// resolution, 128x64 in high resolution.
+    pub fn resolution(&self) -> (usize, usize) {
+        let pixel_size = self.pixel_size();
+        (DISPLAY_WIDTH / pixel_size, DISPLAY_HEIGHT / pixel_size)
+    }
+
+    /// Returns the size of the pixels of the current resolution in the display buffer.
+    fn pixel_size(&self) -> usize {
+        if self.high_resolution {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Returns the value of the pixel at column `x` and row `y` of the current resolution,
+    /// see [Chirp8::resolution] and [crate::DisplayPixels::pixel].
+    pub fn get_pixel(&self, x: usize, y: usize) -> u8 {
+        let pixel_size = self.pixel_size();
+        self.display_buffer.pixel(x * pixel_size, y * pixel_size)
+    }
+
+    /// Indicates if the pixel at column `x` and row `y` of the current resolution is lit on
+    /// given `plane`.
+    pub fn get_plane_pixel(&self, plane: usize, x: usize, y: usize) -> bool {
+        // The plane bits are the lowest bits of pixel values.
+        self.get_pixel(x, y) & (1 << plane) != 0
+    }
+
+    /// Returns the row `y` of given `plane` in the current resolution, the pixel at column `x`
+    /// being the bit `width - 1 - x`, so that the leftmost pixel is the most significant bit
+    /// of the lowest `width` bits.
+    pub fn get_plane_row(&self, plane: usize, y: usize) -> u128 {
+        let (width, _) = self.resolution();
+        (0..width).fold(0, |bits, x| {
+            (bits << 1) | self.get_plane_pixel(plane, x, y) as u128
+        })
+    }
+
+    /// Returns the rows of pixel values of the current resolution, from top to bottom.
+    /// ```
+    /// let emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+    /// for (y, row) in emulator.logical_rows().enumerate() {
+    ///     for (x, pixel) in row.enumerate() {
+    ///         assert_eq!(pixel, emulator.get_pixel(x, y));
+    ///     }
+    /// }
+    /// assert_eq!(emulator.logical_rows().count(), 32);
+    /// ```
+    pub fn logical_rows(&self) -> impl Iterator<Item = impl Iterator<Item = u8> + '_> + '_ {
+        let (width, height) = self.resolution();
+        (0..height).map(move |y| (0..width).map(move |x| self.get_pixel(x, y)))
+    }
+
     /// Access the 128 1-bit samples in the audio buffer.
     pub fn get_audio_buffer(&self) -> &[u8; AUDIO_BUFFER_SIZE] {
         &self.audio_buffer
@@ -1412,8 +2003,26 @@ impl Chirp8 {
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
     use super::*;
+    use crate::DisplayPixels;
+
+    // Memory of the emulators of the tests, large enough for XO-Chip even without `alloc`.
+    #[cfg(feature = "alloc")]
+    pub(crate) type TestRam = Ram;
+    #[cfg(not(feature = "alloc"))]
+    pub(crate) type TestRam = [u8; MAX_RAM_SIZE];
+
+    /// Creates an emulator in given `mode` with its default quirks, see [Chirp8::new].
+    pub(crate) fn new_emulator(mode: Chirp8Mode) -> Chirp8<TestRam> {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "alloc")]{
+                Chirp8::new(mode)
+            }else{
+                Chirp8::with_bus(mode, mode.into(), [0u8; MAX_RAM_SIZE])
+            }
+        }
+    }
 
     #[test]
     fn test_repeat_bits() {
@@ -1430,7 +2039,7 @@ mod test {
     #[test]
     fn opcode_set_vx_nn() {
         let mut emulator = Chirp8::default();
-        emulator.ram[PROGRAM_START..PROGRAM_START + 2].copy_from_slice(&[0x63, 0xAB]);
+        emulator.bus[PROGRAM_START..PROGRAM_START + 2].copy_from_slice(&[0x63, 0xAB]);
         emulator.step();
 
         assert_eq!(emulator.registers[3], 0xAB);
@@ -1439,7 +2048,7 @@ mod test {
     #[test]
     fn opcode_skip_if_key_pressed() {
         let mut emulator = Chirp8::default();
-        emulator.ram[PROGRAM_START..PROGRAM_START + 2].copy_from_slice(&[0xE2, 0x9E]);
+        emulator.bus[PROGRAM_START..PROGRAM_START + 2].copy_from_slice(&[0xE2, 0x9E]);
         emulator.registers[2] = 11;
 
         emulator.key_release(11);
@@ -1458,7 +2067,7 @@ mod test {
     #[test]
     fn opcode_skip_if_key_not_pressed() {
         let mut emulator = Chirp8::default();
-        emulator.ram[PROGRAM_START..PROGRAM_START + 2].copy_from_slice(&[0xE2, 0xA1]);
+        emulator.bus[PROGRAM_START..PROGRAM_START + 2].copy_from_slice(&[0xE2, 0xA1]);
         emulator.registers[2] = 11;
 
         emulator.key_release(11);
@@ -1477,7 +2086,7 @@ mod test {
     #[test]
     fn opcode_draw_high_res() {
         let mut emulator = Chirp8::new(Chirp8Mode::SuperChipModern);
-        emulator.ram[PROGRAM_START..PROGRAM_START + 5].copy_from_slice(&[
+        emulator.bus[PROGRAM_START..PROGRAM_START + 5].copy_from_slice(&[
             0x00, 0xFF, // Enable High-res
             0xD0, 0x11, // Draw v0 v1 1
             0x80, // Sprite with one pixel to the left
@@ -1489,16 +2098,44 @@ mod test {
         emulator.step();
         emulator.step();
 
-        assert_eq!(emulator.get_display_buffer()[45][67], PIXEL_ON);
+        assert_eq!(emulator.get_display_buffer().pixel(67, 45), PIXEL_ON);
         assert_eq!(emulator.registers[FLAG_REGISTER_INDEX], 0);
 
         emulator.pc -= 2;
         emulator.step();
 
-        assert_eq!(emulator.get_display_buffer()[45][67], PIXEL_OFF);
+        assert_eq!(emulator.get_display_buffer().pixel(67, 45), PIXEL_OFF);
         assert_eq!(emulator.registers[FLAG_REGISTER_INDEX], 1);
     }
 
+    #[test]
+    fn opcode_draw_collision_count_low_res() {
+        #[rustfmt::skip]
+        let rom = [
+            0xD0, 0x13, // Draw v0 v1 3
+            0xD0, 0x13, // Draw v0 v1 3
+            0xFF, 0xFF, 0xFF, // Sprite of 3 full rows
+        ];
+        let mode = Chirp8Mode::SuperChipModern;
+        let all_quirks = QuirkFlags::from_mode(mode);
+        for (quirks, flag) in [
+            (all_quirks, 3),
+            (all_quirks - QuirkFlags::COLLISION_COUNT_LORES, 1),
+            (all_quirks - QuirkFlags::COLLISION_COUNT_HIRES, 3),
+        ] {
+            let mut emulator = Chirp8::with_custom_quirks(mode, quirks);
+            emulator.load_rom(&rom);
+            emulator.registers[0] = 10;
+            emulator.registers[1] = 5;
+            emulator.index = PROGRAM_START as u16 + 4;
+
+            emulator.step();
+            assert_eq!(emulator.registers[FLAG_REGISTER_INDEX], 0);
+            emulator.step();
+            assert_eq!(emulator.registers[FLAG_REGISTER_INDEX], flag);
+        }
+    }
+
     #[test]
     fn opcode_scroll_vertical_all() {
         let rom = [
@@ -1507,32 +2144,32 @@ mod test {
         ];
 
         let mut emulator = Chirp8::new(Chirp8Mode::SuperChipModern);
-        emulator.ram[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
-        emulator.display_buffer[37][67] = PIXEL_ON;
+        emulator.bus[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
+        emulator.display_buffer.set_pixel(67, 37, PIXEL_ON);
         emulator.high_resolution = true;
 
         emulator.step();
 
-        assert_eq!(emulator.display_buffer[37][67], PIXEL_OFF);
-        assert_eq!(emulator.display_buffer[32][67], PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(67, 37), PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(67, 32), PIXEL_ON);
 
         emulator.step();
 
-        assert_eq!(emulator.display_buffer[32][67], PIXEL_OFF);
-        assert_eq!(emulator.display_buffer[39][67], PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(67, 32), PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(67, 39), PIXEL_ON);
 
         emulator.pc = PROGRAM_START as u16;
         emulator.high_resolution = false;
 
         emulator.step();
 
-        assert_eq!(emulator.display_buffer[39][67], PIXEL_OFF);
-        assert_eq!(emulator.display_buffer[29][67], PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(67, 39), PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(67, 29), PIXEL_ON);
 
         emulator.step();
 
-        assert_eq!(emulator.display_buffer[29][67], PIXEL_OFF);
-        assert_eq!(emulator.display_buffer[43][67], PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(67, 29), PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(67, 43), PIXEL_ON);
     }
 
     #[test]
@@ -1543,32 +2180,32 @@ mod test {
         ];
 
         let mut emulator = Chirp8::new(Chirp8Mode::SuperChipModern);
-        emulator.ram[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
-        emulator.display_buffer[37][67] = PIXEL_ON;
+        emulator.bus[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
+        emulator.display_buffer.set_pixel(67, 37, PIXEL_ON);
         emulator.high_resolution = true;
 
         emulator.step();
 
-        assert_eq!(emulator.display_buffer[37][67], PIXEL_OFF);
-        assert_eq!(emulator.display_buffer[37][71], PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(67, 37), PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(71, 37), PIXEL_ON);
 
         emulator.step();
 
-        assert_eq!(emulator.display_buffer[37][71], PIXEL_OFF);
-        assert_eq!(emulator.display_buffer[37][67], PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(71, 37), PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(67, 37), PIXEL_ON);
 
         emulator.pc = PROGRAM_START as u16;
         emulator.high_resolution = false;
 
         emulator.step();
 
-        assert_eq!(emulator.display_buffer[37][67], PIXEL_OFF);
-        assert_eq!(emulator.display_buffer[37][75], PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(67, 37), PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(75, 37), PIXEL_ON);
 
         emulator.step();
 
-        assert_eq!(emulator.display_buffer[37][75], PIXEL_OFF);
-        assert_eq!(emulator.display_buffer[37][67], PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(75, 37), PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(67, 37), PIXEL_ON);
     }
 
     #[test]
@@ -1578,35 +2215,37 @@ mod test {
             0x00, 0xC7, // Scroll down by 7
         ];
 
-        let mut emulator = Chirp8::new(Chirp8Mode::XOChip);
-        emulator.ram[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
-        emulator.display_buffer[37][67] = repeat_bits(0b11, DISPLAY_PLANES);
+        let mut emulator = new_emulator(Chirp8Mode::XOChip);
+        emulator.bus[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
+        emulator
+            .display_buffer
+            .set_pixel(67, 37, repeat_bits(0b11, DISPLAY_PLANES));
         emulator.high_resolution = true;
         emulator.plane_selection = repeat_bits(0b10, DISPLAY_PLANES);
 
         emulator.step();
 
         assert_eq!(
-            emulator.display_buffer[37][67],
+            emulator.display_buffer.pixel(67, 37),
             repeat_bits(0b01, DISPLAY_PLANES)
         );
         assert_eq!(
-            emulator.display_buffer[32][67],
+            emulator.display_buffer.pixel(67, 32),
             repeat_bits(0b10, DISPLAY_PLANES)
         );
 
         emulator.step();
 
         assert_eq!(
-            emulator.display_buffer[37][67],
+            emulator.display_buffer.pixel(67, 37),
             repeat_bits(0b01, DISPLAY_PLANES)
         );
         assert_eq!(
-            emulator.display_buffer[32][67],
+            emulator.display_buffer.pixel(67, 32),
             repeat_bits(0b00, DISPLAY_PLANES)
         );
         assert_eq!(
-            emulator.display_buffer[39][67],
+            emulator.display_buffer.pixel(67, 39),
             repeat_bits(0b10, DISPLAY_PLANES)
         );
 
@@ -1616,30 +2255,30 @@ mod test {
         emulator.step();
 
         assert_eq!(
-            emulator.display_buffer[37][67],
+            emulator.display_buffer.pixel(67, 37),
             repeat_bits(0b01, DISPLAY_PLANES)
         );
         assert_eq!(
-            emulator.display_buffer[39][67],
+            emulator.display_buffer.pixel(67, 39),
             repeat_bits(0b00, DISPLAY_PLANES)
         );
         assert_eq!(
-            emulator.display_buffer[29][67],
+            emulator.display_buffer.pixel(67, 29),
             repeat_bits(0b10, DISPLAY_PLANES)
         );
 
         emulator.step();
 
         assert_eq!(
-            emulator.display_buffer[37][67],
+            emulator.display_buffer.pixel(67, 37),
             repeat_bits(0b01, DISPLAY_PLANES)
         );
         assert_eq!(
-            emulator.display_buffer[29][67],
+            emulator.display_buffer.pixel(67, 29),
             repeat_bits(0b00, DISPLAY_PLANES)
         );
         assert_eq!(
-            emulator.display_buffer[43][67],
+            emulator.display_buffer.pixel(67, 43),
             repeat_bits(0b10, DISPLAY_PLANES)
         );
     }
@@ -1651,31 +2290,33 @@ mod test {
             0x00, 0xFC, // Scroll left
         ];
 
-        let mut emulator = Chirp8::new(Chirp8Mode::XOChip);
-        emulator.ram[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
-        emulator.display_buffer[37][67] = repeat_bits(0b11, DISPLAY_PLANES);
+        let mut emulator = new_emulator(Chirp8Mode::XOChip);
+        emulator.bus[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
+        emulator
+            .display_buffer
+            .set_pixel(67, 37, repeat_bits(0b11, DISPLAY_PLANES));
         emulator.high_resolution = true;
         emulator.plane_selection = repeat_bits(0b10, DISPLAY_PLANES);
 
         emulator.step();
 
         assert_eq!(
-            emulator.display_buffer[37][67],
+            emulator.display_buffer.pixel(67, 37),
             repeat_bits(0b01, DISPLAY_PLANES)
         );
         assert_eq!(
-            emulator.display_buffer[37][71],
+            emulator.display_buffer.pixel(71, 37),
             repeat_bits(0b10, DISPLAY_PLANES)
         );
 
         emulator.step();
 
         assert_eq!(
-            emulator.display_buffer[37][71],
+            emulator.display_buffer.pixel(71, 37),
             repeat_bits(0b00, DISPLAY_PLANES)
         );
         assert_eq!(
-            emulator.display_buffer[37][67],
+            emulator.display_buffer.pixel(67, 37),
             repeat_bits(0b11, DISPLAY_PLANES)
         );
 
@@ -1685,22 +2326,22 @@ mod test {
         emulator.step();
 
         assert_eq!(
-            emulator.display_buffer[37][67],
+            emulator.display_buffer.pixel(67, 37),
             repeat_bits(0b01, DISPLAY_PLANES)
         );
         assert_eq!(
-            emulator.display_buffer[37][75],
+            emulator.display_buffer.pixel(75, 37),
             repeat_bits(0b10, DISPLAY_PLANES)
         );
 
         emulator.step();
 
         assert_eq!(
-            emulator.display_buffer[37][75],
+            emulator.display_buffer.pixel(75, 37),
             repeat_bits(0b00, DISPLAY_PLANES)
         );
         assert_eq!(
-            emulator.display_buffer[37][67],
+            emulator.display_buffer.pixel(67, 37),
             repeat_bits(0b11, DISPLAY_PLANES)
         );
     }
@@ -1718,7 +2359,7 @@ mod test {
         ];
 
         let mut emulator = Chirp8::new(Chirp8Mode::SuperChipModern);
-        emulator.ram[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
+        emulator.bus[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
 
         emulator.index = PROGRAM_START as u16 + 2;
         emulator.high_resolution = true;
@@ -1727,9 +2368,9 @@ mod test {
         emulator.registers[0] = 17;
         emulator.registers[1] = 61;
         emulator.step();
-        assert_eq!(emulator.display_buffer[61][17], PIXEL_ON);
-        assert_eq!(emulator.display_buffer[62][17], PIXEL_ON);
-        assert_eq!(emulator.display_buffer[63][17], PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(17, 61), PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(17, 62), PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(17, 63), PIXEL_ON);
         assert_eq!(emulator.registers[FLAG_REGISTER_INDEX], 2);
 
         // 3 colliding rows (61 to 63 included)
@@ -1738,11 +2379,11 @@ mod test {
         emulator.registers[1] = 59;
         emulator.step();
 
-        assert_eq!(emulator.display_buffer[59][17], PIXEL_ON);
-        assert_eq!(emulator.display_buffer[60][17], PIXEL_ON);
-        assert_eq!(emulator.display_buffer[61][17], PIXEL_OFF);
-        assert_eq!(emulator.display_buffer[62][17], PIXEL_OFF);
-        assert_eq!(emulator.display_buffer[63][17], PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(17, 59), PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(17, 60), PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(17, 61), PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(17, 62), PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(17, 63), PIXEL_OFF);
         assert_eq!(emulator.registers[FLAG_REGISTER_INDEX], 3);
     }
 
@@ -1770,7 +2411,7 @@ mod test {
         ];
 
         let mut emulator = Chirp8::new(Chirp8Mode::SuperChipModern);
-        emulator.ram[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
+        emulator.bus[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
 
         emulator.index = PROGRAM_START as u16 + 2;
         emulator.high_resolution = true;
@@ -1779,9 +2420,9 @@ mod test {
         emulator.registers[0] = 17;
         emulator.registers[1] = 61;
         emulator.step();
-        assert_eq!(emulator.display_buffer[61][17], PIXEL_ON);
-        assert_eq!(emulator.display_buffer[62][17], PIXEL_ON);
-        assert_eq!(emulator.display_buffer[63][17], PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(17, 61), PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(17, 62), PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(17, 63), PIXEL_ON);
         assert_eq!(emulator.registers[FLAG_REGISTER_INDEX], 13);
 
         // 3 colliding rows (61 to 63 included)
@@ -1790,9 +2431,9 @@ mod test {
         emulator.registers[1] = 48;
         emulator.step();
 
-        assert_eq!(emulator.display_buffer[61][17], PIXEL_OFF);
-        assert_eq!(emulator.display_buffer[62][17], PIXEL_OFF);
-        assert_eq!(emulator.display_buffer[63][17], PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(17, 61), PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(17, 62), PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(17, 63), PIXEL_OFF);
         assert_eq!(emulator.registers[FLAG_REGISTER_INDEX], 3);
     }
 
@@ -1804,24 +2445,24 @@ mod test {
             0x59, 0x62, // Save v9 v6
         ];
 
-        let mut emulator = Chirp8::new(Chirp8Mode::XOChip);
-        emulator.ram[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
+        let mut emulator = new_emulator(Chirp8Mode::XOChip);
+        emulator.bus[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
 
         emulator.registers[6..=9].copy_from_slice(&[3, 7, 13, 59]);
         emulator.index = 0x0ABC;
 
         emulator.step();
-        assert_eq!(emulator.ram[0xABC], 3);
-        assert_eq!(emulator.ram[0xABC + 1], 7);
-        assert_eq!(emulator.ram[0xABC + 2], 13);
-        assert_eq!(emulator.ram[0xABC + 3], 59);
+        assert_eq!(emulator.bus[0xABC], 3);
+        assert_eq!(emulator.bus[0xABC + 1], 7);
+        assert_eq!(emulator.bus[0xABC + 2], 13);
+        assert_eq!(emulator.bus[0xABC + 3], 59);
         assert_eq!(emulator.index, 0xABC);
 
         emulator.step();
-        assert_eq!(emulator.ram[0xABC], 59);
-        assert_eq!(emulator.ram[0xABC + 1], 13);
-        assert_eq!(emulator.ram[0xABC + 2], 7);
-        assert_eq!(emulator.ram[0xABC + 3], 3);
+        assert_eq!(emulator.bus[0xABC], 59);
+        assert_eq!(emulator.bus[0xABC + 1], 13);
+        assert_eq!(emulator.bus[0xABC + 2], 7);
+        assert_eq!(emulator.bus[0xABC + 3], 3);
         assert_eq!(emulator.index, 0xABC);
     }
 
@@ -1834,8 +2475,8 @@ mod test {
             0x07, 0x54, 0x23, 0xDA, // 4 bytes of data
         ];
 
-        let mut emulator = Chirp8::new(Chirp8Mode::XOChip);
-        emulator.ram[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
+        let mut emulator = new_emulator(Chirp8Mode::XOChip);
+        emulator.bus[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
 
         emulator.index = PROGRAM_START as u16 + 4;
 
@@ -1872,8 +2513,8 @@ mod test {
             0b00000000,
         ];
 
-        let mut emulator = Chirp8::new(Chirp8Mode::XOChip);
-        emulator.ram[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
+        let mut emulator = new_emulator(Chirp8Mode::XOChip);
+        emulator.bus[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
 
         emulator.index = PROGRAM_START as u16 + 8;
         emulator.high_resolution = true;
@@ -1891,9 +2532,9 @@ mod test {
         // 100
         // 100
         // 100
-        assert_eq!(emulator.display_buffer[23][17], repeat_bits(0b10, 2));
-        assert_eq!(emulator.display_buffer[24][17], repeat_bits(0b10, 2));
-        assert_eq!(emulator.display_buffer[25][17], repeat_bits(0b10, 2));
+        assert_eq!(emulator.display_buffer.pixel(17, 23), repeat_bits(0b10, 2));
+        assert_eq!(emulator.display_buffer.pixel(17, 24), repeat_bits(0b10, 2));
+        assert_eq!(emulator.display_buffer.pixel(17, 25), repeat_bits(0b10, 2));
         assert_eq!(emulator.registers[FLAG_REGISTER_INDEX], 0);
 
         emulator.step();
@@ -1908,14 +2549,76 @@ mod test {
         // 011 -> first pixel is XOR'ed with previous step.
         // 100
         // 100
-        assert_eq!(emulator.display_buffer[23][17], repeat_bits(0b01, 2));
-        assert_eq!(emulator.display_buffer[23][18], repeat_bits(0b10, 2));
-        assert_eq!(emulator.display_buffer[23][19], repeat_bits(0b10, 2));
-        assert_eq!(emulator.display_buffer[24][17], repeat_bits(0b11, 2));
-        assert_eq!(emulator.display_buffer[25][17], repeat_bits(0b11, 2));
+        assert_eq!(emulator.display_buffer.pixel(17, 23), repeat_bits(0b01, 2));
+        assert_eq!(emulator.display_buffer.pixel(18, 23), repeat_bits(0b10, 2));
+        assert_eq!(emulator.display_buffer.pixel(19, 23), repeat_bits(0b10, 2));
+        assert_eq!(emulator.display_buffer.pixel(17, 24), repeat_bits(0b11, 2));
+        assert_eq!(emulator.display_buffer.pixel(17, 25), repeat_bits(0b11, 2));
         assert_eq!(emulator.registers[FLAG_REGISTER_INDEX], 1);
     }
 
+    #[test]
+    fn opcode_display_four_planes_xo_chip() {
+        #[rustfmt::skip]
+        let rom = [
+            0xFA, 0x01, // Select planes 1 and 3
+            0xD0, 0x11, // Display v0 v1 1
+            0xF2, 0x01, // Select plane 1
+            0x00, 0xFB, // Scroll right
+            0xF8, 0x01, // Select plane 3
+            0x00, 0xE0, // Clear plane 3
+
+            0b1000_0000, // Plane 1 sprite
+            0b1100_0000, // Plane 3 sprite
+        ];
+
+        let mut emulator = new_emulator(Chirp8Mode::XOChip);
+        assert!(emulator.set_display_planes(4));
+        assert_eq!(emulator.display_planes(), 4);
+        assert_eq!(emulator.plane_selection, repeat_bits(0b0001, 4));
+        emulator.bus[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
+
+        emulator.index = PROGRAM_START as u16 + 12;
+        emulator.high_resolution = true;
+        emulator.registers[0] = 17;
+        emulator.registers[1] = 23;
+
+        emulator.step();
+        assert_eq!(emulator.plane_selection, 0b1010_1010);
+
+        emulator.step();
+        assert_eq!(emulator.display_buffer.pixel(17, 23), 0b1010_1010);
+        assert_eq!(emulator.display_buffer.pixel(18, 23), 0b1000_1000);
+        assert_eq!(emulator.get_plane_row(1, 23), display::column_bit(17));
+        let bits = display::column_bit(17) | display::column_bit(18);
+        assert_eq!(emulator.get_plane_row(3, 23), bits);
+        assert_eq!(emulator.get_plane_row(0, 23), 0);
+
+        // Only plane 1 is scrolled.
+        emulator.take_steps(2);
+        assert_eq!(emulator.display_buffer.pixel(17, 23), 0b1000_1000);
+        assert_eq!(emulator.display_buffer.pixel(21, 23), 0b0010_0010);
+
+        // Only plane 3 is cleared.
+        emulator.take_steps(2);
+        assert_eq!(emulator.display_buffer.pixel(17, 23), PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(18, 23), PIXEL_OFF);
+        assert_eq!(emulator.display_buffer.pixel(21, 23), 0b0010_0010);
+
+        let mut state = [0u8; MAX_STATE_SIZE];
+        assert!(emulator.save_state(&mut state));
+        let mut loaded = new_emulator(Chirp8Mode::XOChip);
+        assert!(loaded.load_state(&state));
+        assert_eq!(loaded.display_planes(), 4);
+        assert_eq!(loaded.get_display_buffer().pixel(21, 23), 0b0010_0010);
+
+        // Changing the number of planes clears the display.
+        assert!(!emulator.set_display_planes(0));
+        assert!(emulator.set_display_planes(1));
+        assert_eq!(emulator.pixel_step(), PIXEL_ON);
+        assert_eq!(emulator.display_buffer.pixel(21, 23), PIXEL_OFF);
+    }
+
     #[test]
     fn opcode_display_plane_16x16_xo_chip() {
         #[rustfmt::skip]
@@ -1936,8 +2639,8 @@ mod test {
             0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
         ];
 
-        let mut emulator = Chirp8::new(Chirp8Mode::XOChip);
-        emulator.ram[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
+        let mut emulator = new_emulator(Chirp8Mode::XOChip);
+        emulator.bus[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(&rom);
 
         emulator.index = PROGRAM_START as u16 + 8;
         emulator.high_resolution = true;
@@ -1955,9 +2658,9 @@ mod test {
         // 100
         // 100
         // 100
-        assert_eq!(emulator.display_buffer[23][17], repeat_bits(0b10, 2));
-        assert_eq!(emulator.display_buffer[24][17], repeat_bits(0b10, 2));
-        assert_eq!(emulator.display_buffer[25][17], repeat_bits(0b10, 2));
+        assert_eq!(emulator.display_buffer.pixel(17, 23), repeat_bits(0b10, 2));
+        assert_eq!(emulator.display_buffer.pixel(17, 24), repeat_bits(0b10, 2));
+        assert_eq!(emulator.display_buffer.pixel(17, 25), repeat_bits(0b10, 2));
         assert_eq!(emulator.registers[FLAG_REGISTER_INDEX], 0);
 
         emulator.step();
@@ -1972,17 +2675,372 @@ mod test {
         // 011 -> first pixel is XOR'ed with previous step.
         // 100
         // 100
-        assert_eq!(emulator.display_buffer[23][17], repeat_bits(0b01, 2));
-        assert_eq!(emulator.display_buffer[23][18], repeat_bits(0b10, 2));
-        assert_eq!(emulator.display_buffer[23][19], repeat_bits(0b10, 2));
-        assert_eq!(emulator.display_buffer[24][17], repeat_bits(0b11, 2));
-        assert_eq!(emulator.display_buffer[25][17], repeat_bits(0b11, 2));
+        assert_eq!(emulator.display_buffer.pixel(17, 23), repeat_bits(0b01, 2));
+        assert_eq!(emulator.display_buffer.pixel(18, 23), repeat_bits(0b10, 2));
+        assert_eq!(emulator.display_buffer.pixel(19, 23), repeat_bits(0b10, 2));
+        assert_eq!(emulator.display_buffer.pixel(17, 24), repeat_bits(0b11, 2));
+        assert_eq!(emulator.display_buffer.pixel(17, 25), repeat_bits(0b11, 2));
+        assert_eq!(emulator.registers[FLAG_REGISTER_INDEX], 1);
+    }
+
+    #[test]
+    fn custom_bus() {
+        /// Bus on which the program area is read-only.
+        struct RomBus([u8; RAM_SIZE]);
+
+        impl Bus for RomBus {
+            fn read(&mut self, address: u16) -> u8 {
+                self.0[address as usize]
+            }
+
+            fn write(&mut self, address: u16, value: u8) {
+                if (address as usize) < PROGRAM_START {
+                    self.0[address as usize] = value;
+                }
+            }
+        }
+
+        let mut bus = RomBus([0; RAM_SIZE]);
+        bus.0[PROGRAM_START..PROGRAM_START + 4].copy_from_slice(&[
+            0xF1, 0x55, // Store v0 v1
+            0xF1, 0x55, // Store v0 v1
+        ]);
+        let mode = Chirp8Mode::SuperChipModern;
+        let mut emulator = Chirp8::with_bus(mode, QuirkFlags::from_mode(mode), bus);
+        assert_eq!(emulator.bus().0[FONT_SPRITES_ADDRESS], FONT_SPRITES[0]);
+
+        emulator.registers[0..2].copy_from_slice(&[0xAB, 0xCD]);
+        emulator.index = 0x100;
+        emulator.step();
+        assert_eq!(emulator.bus().0[0x100..0x102], [0xAB, 0xCD]);
+
+        emulator.index = PROGRAM_START as u16;
+        emulator.step();
+        assert_eq!(
+            emulator.bus().0[PROGRAM_START..PROGRAM_START + 2],
+            [0xF1, 0x55]
+        );
+    }
+
+    #[test]
+    fn write_monitor_self_modifying_code() {
+        let rom = [
+            0x60, 0x12, // Set v0 to 0x12
+            0xA2, 0x00, // Set index to program start
+            0xF0, 0x55, // Store v0 at program start
+        ];
+
+        let mut emulator = Chirp8::new(Chirp8Mode::SuperChipModern);
+        emulator.load_rom(&rom);
+        emulator.set_write_monitor(Option::Some(WriteMonitor::new(crate::WriteAction::Warn)));
+        emulator.take_steps(3);
+
+        let violation = emulator.write_monitor().unwrap().last_violation().unwrap();
+        assert_eq!(violation.pc, PROGRAM_START as u16 + 4);
+        assert_eq!(violation.address, PROGRAM_START as u16);
+        assert_eq!(violation.value, 0x12);
+        assert_eq!(violation.kind, crate::WriteViolationKind::ExecutedCode);
+        assert_eq!(emulator.bus[PROGRAM_START], 0x12);
+        assert!(!emulator.is_halted());
+
+        emulator.reset();
+        emulator.bus[PROGRAM_START] = 0x60;
+        emulator.set_write_monitor(Option::Some(WriteMonitor::new(crate::WriteAction::Error)));
+        emulator.take_steps(3);
+
+        assert_eq!(emulator.write_monitor().unwrap().violations(), 1);
+        assert_eq!(emulator.bus[PROGRAM_START], 0x60);
+        assert!(emulator.is_halted());
+        let pc = emulator.pc;
+        emulator.run_frame();
+        assert_eq!(emulator.pc, pc);
+    }
+
+    #[test]
+    fn unknown_instruction_actions() {
+        use core::sync::atomic::{AtomicU16, Ordering};
+        static LAST_PC: AtomicU16 = AtomicU16::new(0);
+
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x01, // v0 = 1
+            0xFF, 0xFF, // Unknown
+            0x70, 0x01, // v0 += 1
+        ];
+        let mut emulator = Chirp8::new(Chirp8Mode::CosmacChip8);
+        emulator.load_rom(&rom);
+        emulator.set_unknown_instruction_action(UnknownInstructionAction::Ignore);
+        emulator.take_steps(3);
+        assert!(!emulator.is_halted());
+        assert_eq!(emulator.registers[0], 2);
+
+        emulator.reset();
+        emulator.set_unknown_instruction_action(UnknownInstructionAction::Callback(|unknown| {
+            assert_eq!(unknown.opcode, 0xFFFF);
+            assert_eq!(unknown.mode, Chirp8Mode::CosmacChip8);
+            LAST_PC.store(unknown.pc, Ordering::Relaxed);
+        }));
+        emulator.take_steps(3);
+        assert_eq!(LAST_PC.load(Ordering::Relaxed), 0x202);
+        assert_eq!(emulator.registers[0], 2);
+
+        emulator.reset();
+        emulator.set_unknown_instruction_action(UnknownInstructionAction::Halt);
+        emulator.take_steps(3);
+        assert!(emulator.is_halted());
+        assert_eq!(emulator.registers[0], 1);
+    }
+
+    #[test]
+    fn save_state_restores_everything() {
+        #[rustfmt::skip]
+        let rom = [
+            0xC0, 0xFF, // v0 = random
+            0xF0, 0x29, // I = font sprite of v0
+            0xD1, 0x25, // Draw v1 v2 5
+            0x71, 0x03, // v1 += 3
+            0x22, 0x0C, // Call 20C
+            0x12, 0x00, // Jump 200
+            0xF1, 0x18, // Sound timer = v1
+            0x00, 0xEE, // Return
+        ];
+        let mut emulator = new_emulator(Chirp8Mode::XOChip);
+        emulator.load_rom(&rom);
+        emulator.key_press(3);
+        emulator.take_steps(25);
+
+        let mut state = [0u8; MAX_STATE_SIZE];
+        let state_size = emulator.state_size();
+        assert!(!emulator.save_state(&mut state[..state_size - 1]));
+        assert!(emulator.save_state(&mut state[..state_size]));
+        assert!(!emulator.load_state(&state[..state_size - 1]));
+        // A random numbers generator state of zeros is invalid.
+        let mut zero_random = state;
+        let random_state = STATE_HEADER_SIZE - DISPLAY_WIDTH * DISPLAY_HEIGHT - 4 * 8;
+        zero_random[random_state..random_state + 4 * 8].fill(0);
+        assert!(!emulator.load_state(&zero_random[..state_size]));
+        zero_random[random_state] = 1;
+        assert!(emulator.load_state(&zero_random[..state_size]));
+        assert!(emulator.load_state(&state[..state_size]));
+        // The memory must be as large as the one of the state, whatever the mode.
+        let mut restored = Chirp8::new(Chirp8Mode::CosmacChip8);
+        assert_eq!(
+            restored.load_state(&state),
+            restored.ram_size() == emulator.ram_size()
+        );
+        let mut ram = emulator.bus.clone();
+        ram.fill(0);
+        let mut restored = Chirp8::with_bus(Chirp8Mode::CosmacChip8, QuirkFlags::empty(), ram);
+        assert!(restored.load_state(&state));
+        assert!(restored.mode == Chirp8Mode::XOChip);
+        assert_eq!(restored.stack.as_slice(), emulator.stack.as_slice());
+
+        for _ in 0..10 {
+            emulator.run_frame();
+            restored.run_frame();
+            assert_eq!(restored.registers, emulator.registers);
+            assert_eq!(restored.pc, emulator.pc);
+            assert_eq!(restored.sound_timer, emulator.sound_timer);
+            assert_eq!(restored.steps, emulator.steps);
+            assert_eq!(restored.keys, emulator.keys);
+            assert!(restored.display_buffer == emulator.display_buffer);
+            assert!(restored.bus == emulator.bus);
+        }
+
+        state[0] = b'X';
+        assert!(!restored.load_state(&state));
+    }
+
+    #[test]
+    fn save_state_has_no_side_effect() {
+        #[rustfmt::skip]
+        let rom = [
+            0xC0, 0xFF, // v0 = random
+            0x81, 0x04, // v1 += v0
+            0x12, 0x00, // Jump 200
+        ];
+        let mut saved = Chirp8::new(Chirp8Mode::SuperChipModern);
+        let mut unsaved = Chirp8::new(Chirp8Mode::SuperChipModern);
+        saved.load_rom(&rom);
+        unsaved.load_rom(&rom);
+        let mut state = [0u8; MAX_STATE_SIZE];
+        for _ in 0..10 {
+            assert!(saved.save_state(&mut state));
+            saved.run_frame();
+            unsaved.run_frame();
+            assert_eq!(saved.registers, unsaved.registers);
+        }
+
+        // A memory of another size cannot be restored.
+        let mut ram = [0u8; 0x2000];
+        let mut display = [PIXEL_OFF; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        let mode = Chirp8Mode::XOChip;
+        let small = Chirp8::with_buffers(mode, mode.into(), &mut ram, &mut display);
+        assert!(small.save_state(&mut state));
+        assert!(!saved.load_state(&state));
+    }
+
+    #[test]
+    fn test_with_buffers() {
+        #[rustfmt::skip]
+        let rom = [
+            0x00, 0xFF, // High resolution
+            0xF0, 0x30, // I = large font sprite of v0
+            0xD1, 0x20, // Draw v1 v2 large sprite
+            0x71, 0x05, // v1 += 5
+            0x00, 0xC3, // Scroll down 3
+            0x00, 0xFB, // Scroll right 4
+            0x12, 0x02, // Jump 202
+        ];
+        let mode = Chirp8Mode::SuperChipModern;
+        let quirks = QuirkFlags::from_mode(mode);
+        let mut ram = [0xFF; 0x1800];
+        let mut display = [PIXEL_ON; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        let mut borrowing = Chirp8::with_buffers(mode, quirks, &mut ram, &mut display);
+        assert_eq!(borrowing.ram_size(), MIN_RAM_SIZE);
+        let mut owning = Chirp8::new(mode);
+        assert!(borrowing.load_rom(&rom));
+        owning.load_rom(&rom);
+        for _ in 0..5 {
+            borrowing.run_frame();
+            owning.run_frame();
+            assert_eq!(borrowing.registers, owning.registers);
+            for y in 0..DISPLAY_HEIGHT {
+                for x in 0..DISPLAY_WIDTH {
+                    let pixel = owning.display_buffer.pixel(x, y);
+                    assert_eq!(borrowing.display_buffer.pixel(x, y), pixel);
+                }
+            }
+        }
+        let lit = display.iter().filter(|pixel| **pixel != PIXEL_OFF).count();
+        assert!(lit > 0);
+        assert!(ram[PROGRAM_START..(PROGRAM_START + rom.len())] == rom);
+    }
+
+    #[test]
+    fn test_ram_size() {
+        assert_eq!(ram_mask(0), 0xFFF);
+        assert_eq!(ram_mask(0x1FFF), 0xFFF);
+        assert_eq!(ram_mask(0x2000), 0x1FFF);
+        assert_eq!(ram_mask(0x20000), 0xFFFF);
+
+        #[rustfmt::skip]
+        let rom = [
+            0xAF, 0xFF, // I = FFF
+            0x60, 0x02, // v0 = 2
+            0xF0, 0x1E, // I += v0
+        ];
+        let mode = Chirp8Mode::XOChip;
+        let quirks = QuirkFlags::from_mode(mode);
+        let mut ram = [0; 0x10000];
+        let mut display = [PIXEL_OFF; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        let mut emulator = Chirp8::with_buffers(mode, quirks, &mut ram, &mut display);
+        assert_eq!(emulator.ram_size(), 0x10000);
+        emulator.load_rom(&rom);
+        emulator.take_steps(3);
+        assert_eq!(emulator.index, 0x1001);
+        assert_eq!(emulator.registers[FLAG_REGISTER_INDEX], 0);
+
+        // XO-Chip programs are limited by a smaller memory.
+        let mut small = [0; MIN_RAM_SIZE];
+        let mut emulator = Chirp8::with_buffers(mode, quirks, &mut small, &mut display);
+        emulator.load_rom(&rom);
+        emulator.take_steps(3);
+        assert_eq!(emulator.index, 0x001);
         assert_eq!(emulator.registers[FLAG_REGISTER_INDEX], 1);
+        assert!(!emulator.load_rom(&[0; MIN_RAM_SIZE - PROGRAM_START]));
+    }
+
+    #[test]
+    fn test_ram_size_by_mode() {
+        #[rustfmt::skip]
+        let rom = [
+            0xAF, 0xFF, // I = FFF
+            0x60, 0x02, // v0 = 2
+            0xF0, 0x1E, // I += v0
+        ];
+        assert_eq!(Chirp8Mode::SuperChip1_1.ram_size(), 0x1000);
+        assert_eq!(Chirp8Mode::XOChip.ram_size(), 0x10000);
+        for (mode, ram_size) in [
+            (Chirp8Mode::CosmacChip8, 0x1000),
+            (Chirp8Mode::SuperChipModern, 0x1000),
+            (Chirp8Mode::XOChip, 0x10000),
+        ] {
+            // Without `alloc`, the default memory cannot hold XO-Chip programs.
+            if ram_size > RAM_SIZE {
+                continue;
+            }
+            let mut emulator = Chirp8::new(mode);
+            assert_eq!(emulator.ram_size(), ram_size);
+            // The default memory is sized by the mode.
+            assert_eq!(emulator.bus.len(), ram_size);
+            emulator.load_rom(&rom);
+            emulator.take_steps(3);
+            assert_eq!(emulator.index as usize, 0x1001 & (ram_size - 1));
+            let flag = (ram_size == 0x1000) as u8;
+            assert_eq!(emulator.registers[FLAG_REGISTER_INDEX], flag);
+
+            // The program counter wraps around at the end of the memory.
+            emulator.pc = 0xFFE;
+            emulator.bus_mut().write_block(0xFFE, &[0x60, 0x05]); // v0 = 5
+            emulator.step();
+            assert_eq!(emulator.pc as usize, 0x1000 % ram_size);
+        }
+
+        // Restoring a state of another mode changes the size of the memory, within the bus.
+        let mut emulator = new_emulator(Chirp8Mode::XOChip);
+        emulator.index = 0x1234 & (emulator.ram_size() - 1) as u16;
+        let mut state = [0u8; MAX_STATE_SIZE];
+        assert!(emulator.save_state(&mut state));
+        let quirks = QuirkFlags::from_mode(Chirp8Mode::CosmacChip8);
+        let ram = [0u8; MAX_RAM_SIZE];
+        let mut restored = Chirp8::with_bus(Chirp8Mode::CosmacChip8, quirks, ram);
+        assert_eq!(restored.ram_size(), MIN_RAM_SIZE);
+        assert!(restored.load_state(&state));
+        assert_eq!(restored.ram_size(), emulator.ram_size());
+        assert_eq!(restored.index, emulator.index);
+    }
+
+    #[test]
+    fn test_ram_wrapping() {
+        #[rustfmt::skip]
+        let rom = [
+            0xF0, 0x00, 0xF0, 0x00, // I = F000
+            0xF0, 0x33, // Store BCD of v0
+        ];
+        let mode = Chirp8Mode::XOChip;
+        let quirks = QuirkFlags::from_mode(mode);
+        let mut ram = [0; MIN_RAM_SIZE];
+        let mut display = [PIXEL_OFF; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+        let mut emulator = Chirp8::with_buffers(mode, quirks, &mut ram, &mut display);
+        emulator.load_rom(&rom);
+        emulator.registers[0] = 123;
+        emulator.take_steps(2);
+        assert_eq!(emulator.index, 0x000);
+        assert_eq!(emulator.pc as usize, PROGRAM_START + 6);
+        assert_eq!(emulator.bus[0..3], [1, 2, 3]);
+
+        // The long index load wraps the program counter around at the end of the memory.
+        emulator.pc = 0xFFE;
+        emulator.bus.write_block(0xFFE, &[0xF0, 0x00]);
+        emulator.bus.write_block(0x000, &[0x12, 0x34]);
+        emulator.step();
+        assert_eq!(emulator.index, 0x234);
+        assert_eq!(emulator.pc, 0x002);
+
+        // Waiting for a key at the end of the memory does not leave the memory.
+        let mut emulator = Chirp8::new(Chirp8Mode::CosmacChip8);
+        emulator.pc = 0xFFE;
+        emulator.bus.write_block(0xFFE, &[0xF0, 0x0A]);
+        for _ in 0..3 {
+            emulator.step();
+            assert_eq!(emulator.pc, 0xFFE);
+        }
     }
 
     #[test]
     fn test_pitch() {
-        let mut emulator = Chirp8::new(Chirp8Mode::XOChip);
+        let mut emulator = new_emulator(Chirp8Mode::XOChip);
         // Values given in https://johnearnest.github.io/Octo/docs/XO-ChipSpecification.html
 
         emulator.pitch = 247;
@@ -1991,4 +3049,43 @@ mod test {
 
         assert_eq!(rate_log2, LOG2_56200_06);
     }
+
+    #[test]
+    fn test_logical_pixels() {
+        #[rustfmt::skip]
+        let rom = [
+            0xF3, 0x01, // Select both planes
+            0x60, 0x3F, // v0 = 63
+            0xA2, 0x0C, // I = 20C
+            0xD0, 0x01, // Draw v0 v0 1 on both planes, wrapping around
+            0x00, 0xFF, // High resolution
+            0x12, 0x0A, // Loop
+            0xC0, 0x80, // Plane 1 sprite, then plane 2 sprite
+        ];
+        let mut emulator = new_emulator(Chirp8Mode::XOChip);
+        emulator.load_rom(&rom);
+        emulator.take_steps(4);
+
+        assert_eq!(emulator.resolution(), (64, 32));
+        assert_eq!(emulator.get_pixel(63, 31), PIXEL_ON);
+        assert_eq!(emulator.get_pixel(0, 31), PIXEL_STEP);
+        assert_eq!(emulator.get_pixel(1, 31), PIXEL_OFF);
+        assert!(emulator.get_plane_pixel(0, 0, 31));
+        assert!(!emulator.get_plane_pixel(1, 0, 31));
+        assert_eq!(emulator.get_plane_row(0, 31), (1 << 63) | 1);
+        assert_eq!(emulator.get_plane_row(1, 31), 1);
+        let rows = emulator.logical_rows();
+        assert!(rows
+            .take(31)
+            .all(|mut row| row.all(|pixel| pixel == PIXEL_OFF)));
+        let last_row = emulator.logical_rows().nth(31).unwrap();
+        assert!(last_row.eq((0..64).map(|x| emulator.get_pixel(x, 31))));
+
+        emulator.take_steps(1);
+        assert_eq!(emulator.resolution(), (128, 64));
+        assert_eq!(emulator.get_pixel(127, 63), PIXEL_ON);
+        assert_eq!(emulator.get_pixel(126, 62), PIXEL_ON);
+        assert_eq!(emulator.logical_rows().count(), 64);
+        assert_eq!(emulator.get_plane_row(1, 63), 0b11);
+    }
 }
@@ -0,0 +1,418 @@
+use core::fmt;
+
+use crate::display::column_bit;
+use crate::render::render;
+use crate::stack::Stack;
+use crate::{
+    Bus, Chirp8, DisplayPixels, DisplayStorage, DrawCall, FlatDisplay, Palette, DISPLAY_WIDTH,
+    PIXEL_OFF,
+};
+
+/// Largest number of rules of a [Colorizer].
+pub const MAX_COLOR_RULES: usize = 64;
+
+/// Attribute of the pixels drawn by sprites without a matching rule.
+const NO_RULE: u8 = 0;
+
+/// Sprites a [ColorRule] applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpriteMatch {
+    /// Sprites whose data starts between the `first` and `last` addresses included.
+    Addresses {
+        /// Smallest address.
+        first: u16,
+        /// Largest address.
+        last: u16,
+    },
+    /// Sprites of given [crate::sprite_hash].
+    Hash(u64),
+}
+
+impl SpriteMatch {
+    /// Indicates if the sprite drawn by given `call` matches.
+    pub fn matches(&self, call: &DrawCall) -> bool {
+        match *self {
+            SpriteMatch::Addresses { first, last } => (first..=last).contains(&call.address),
+            SpriteMatch::Hash(hash) => call.hash == hash,
+        }
+    }
+}
+
+/// Color given to the pixels lit by some sprites.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorRule {
+    /// Sprites the rule applies to.
+    pub sprites: SpriteMatch,
+    /// Color of their pixels, as `0xRRGGBB`.
+    pub color: u32,
+}
+
+impl Default for ColorRule {
+    fn default() -> Self {
+        Self {
+            sprites: SpriteMatch::Addresses { first: 0, last: 0 },
+            color: 0,
+        }
+    }
+}
+
+/// Error while parsing the rules of a [Colorizer].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RuleError {
+    /// Number of the line of the error, from 1.
+    pub line: usize,
+    /// What went wrong.
+    pub message: &'static str,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at line {}", self.message, self.line)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RuleError {}
+
+/// Opt-in coloring of monochrome games, giving to the pixels the color of the sprite which last
+/// drew them.
+///
+/// Each pixel of the display buffer has an attribute, set to the first [ColorRule] matching the
+/// sprite when it is drawn. Lit pixels are given the color of their rule, other pixels keep the
+/// colors of the palette. The attributes follow the scrolled pixels and are reset when the
+/// display is cleared.
+///
+/// Rules are usually parsed from a file written for each ROM, a rule per line made of the
+/// sprites and their color in hexadecimal, `#` starting comments :
+/// ```text
+/// # Paddles and ball.
+/// 2EA-2F3 FF0000
+/// 2F4     00FF00
+/// # Sprite identified by its hash.
+/// hash 8A3F00C2D1E4B567 0000FF
+/// ```
+/// Sprites are a single address, a range of addresses, or `hash` followed by a
+/// [crate::sprite_hash], which a [crate::DrawLog] gives for each sprite drawn.
+/// ```
+/// let mut emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+/// let colorizer = chirp8::Colorizer::parse("000-04F FF0000 # The font").unwrap();
+/// emulator.set_colorizer(Some(colorizer));
+/// emulator.load_rom(&[0xF0, 0x29, 0xD0, 0x05]); // Draw the font sprite of 0.
+/// emulator.take_steps(2);
+/// let colorizer = emulator.colorizer().unwrap();
+/// assert_eq!(colorizer.color(&emulator, &chirp8::Palette::OCTO, 0, 0), 0xFF0000);
+/// ```
+pub struct Colorizer {
+    /// Rules from the first to the last, the first matching one applying.
+    rules: Stack<ColorRule, MAX_COLOR_RULES>,
+    /// Attribute of each pixel of the display buffer, the index of its rule plus one.
+    attributes: FlatDisplay,
+    /// Attribute of the sprite being drawn.
+    current: u8,
+}
+
+impl Colorizer {
+    /// Creates a colorizer without any rule.
+    pub fn new() -> Self {
+        Self {
+            rules: Stack::new(),
+            attributes: FlatDisplay::new(),
+            current: NO_RULE,
+        }
+    }
+
+    /// Creates a colorizer from the rules of given `text`, see [Colorizer] for the syntax.
+    pub fn parse(text: &str) -> Result<Self, RuleError> {
+        let mut colorizer = Self::new();
+        for (index, line) in text.lines().enumerate() {
+            let error = |message| RuleError {
+                line: index + 1,
+                message,
+            };
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            let Option::Some(first) = words.next() else {
+                continue;
+            };
+            let sprites = if first.eq_ignore_ascii_case("hash") {
+                let hash = words.next().ok_or(error("Expected a hash"))?;
+                SpriteMatch::Hash(
+                    u64::from_str_radix(hex_digits(hash), 16).map_err(|_| error("Invalid hash"))?,
+                )
+            } else {
+                let (first, last) = first.split_once('-').unwrap_or((first, first));
+                let address = |address| {
+                    u16::from_str_radix(hex_digits(address), 16)
+                        .map_err(|_| error("Invalid address"))
+                };
+                let (first, last) = (address(first)?, address(last)?);
+                if first > last {
+                    return Err(error("Invalid range of addresses"));
+                }
+                SpriteMatch::Addresses { first, last }
+            };
+            let digits = hex_digits(words.next().ok_or(error("Expected a color"))?);
+            let color = match u32::from_str_radix(digits, 16) {
+                Ok(color) if digits.len() == 6 => color,
+                _ => return Err(error("Invalid color")),
+            };
+            if words.next().is_some() {
+                return Err(error("Unexpected text after the color"));
+            }
+            if !colorizer.add_rule(ColorRule { sprites, color }) {
+                return Err(error("Too many rules"));
+            }
+        }
+        Ok(colorizer)
+    }
+
+    /// Adds given `rule` after the others. Returns false if there are already
+    /// [MAX_COLOR_RULES] rules.
+    pub fn add_rule(&mut self, rule: ColorRule) -> bool {
+        self.rules.push(rule).is_ok()
+    }
+
+    /// Returns the rules, from the first to the last.
+    pub fn rules(&self) -> &[ColorRule] {
+        self.rules.as_slice()
+    }
+
+    /// Returns the first rule matching the sprite drawn by given `call`, if any.
+    pub fn rule(&self, call: &DrawCall) -> Option<&ColorRule> {
+        self.rules().iter().find(|rule| rule.sprites.matches(call))
+    }
+
+    /// Returns the rule of the pixel at column `x` and row `y` of the display buffer, the one of
+    /// the sprite which last drew it, if any.
+    pub fn pixel_rule(&self, x: usize, y: usize) -> Option<&ColorRule> {
+        match self.attributes.pixel(x, y) {
+            NO_RULE => Option::None,
+            attribute => self.rules().get(attribute as usize - 1),
+        }
+    }
+
+    /// Returns the color as `0xRRGGBB` of the pixel at column `x` and row `y` of the `emulator`'s
+    /// display, in its current resolution like [Chirp8::get_pixel]. Pixels which are not lit or
+    /// without a rule have the color of the `palette`.
+    pub fn color<B: Bus, D: DisplayStorage>(
+        &self,
+        emulator: &Chirp8<B, D>,
+        palette: &Palette,
+        x: usize,
+        y: usize,
+    ) -> u32 {
+        let palette = palette.with_planes(emulator.display_planes());
+        self.planes_color(emulator, &palette, x, y)
+    }
+
+    /// Returns the color like [Colorizer::color], with a `palette` of the pixel values of the
+    /// `emulator`'s display planes, see [Palette::with_planes].
+    fn planes_color<B: Bus, D: DisplayStorage>(
+        &self,
+        emulator: &Chirp8<B, D>,
+        palette: &Palette,
+        x: usize,
+        y: usize,
+    ) -> u32 {
+        let pixel = emulator.get_pixel(x, y);
+        let pixel_size = DISPLAY_WIDTH / emulator.resolution().0;
+        match self.pixel_rule(x * pixel_size, y * pixel_size) {
+            Option::Some(rule) if pixel != PIXEL_OFF => rule.color,
+            _ => palette.color(pixel),
+        }
+    }
+
+    /// Writes the image of the `emulator`'s display to `output` as red, green, blue and alpha
+    /// bytes like [crate::render_rgba8], with the colors of the rules and of the `palette`.
+    /// Returns false if `output` is shorter than 4 bytes per pixel of the [crate::render_size].
+    pub fn render_rgba8<B: Bus, D: DisplayStorage>(
+        &self,
+        emulator: &Chirp8<B, D>,
+        palette: &Palette,
+        scale: usize,
+        output: &mut [u8],
+    ) -> bool {
+        let palette = palette.with_planes(emulator.display_planes());
+        render(emulator, scale, output.as_chunks_mut().0, |x, y| {
+            let [_, red, green, blue] = self.planes_color(emulator, &palette, x, y).to_be_bytes();
+            [red, green, blue, u8::MAX]
+        })
+    }
+
+    /// Selects the rule of the sprite about to be drawn by given `call`.
+    pub(crate) fn select(&mut self, call: &DrawCall) {
+        self.current = self
+            .rules()
+            .iter()
+            .position(|rule| rule.sprites.matches(call))
+            .map_or(NO_RULE, |index| index as u8 + 1);
+    }
+
+    /// Gives the rule of the sprite being drawn to the pixels of row `y` set in `bits`.
+    pub(crate) fn paint_row(&mut self, y: usize, mut bits: u128) {
+        let row = self.attributes.row_mut(y);
+        while bits != 0 {
+            let x = bits.leading_zeros() as usize;
+            row[x] = self.current;
+            bits &= !column_bit(x);
+        }
+    }
+
+    /// Resets the attributes of all pixels.
+    pub(crate) fn clear(&mut self) {
+        self.attributes.as_bytes_mut().fill(NO_RULE);
+    }
+
+    /// Scrolls the attributes up by `scroll` pixels of the display buffer.
+    pub(crate) fn scroll_up(&mut self, scroll: usize) {
+        self.attributes.scroll_up(u8::MAX, scroll);
+    }
+
+    /// Scrolls the attributes down by `scroll` pixels of the display buffer.
+    pub(crate) fn scroll_down(&mut self, scroll: usize) {
+        self.attributes.scroll_down(u8::MAX, scroll);
+    }
+
+    /// Scrolls the attributes left by `scroll` pixels of the display buffer.
+    pub(crate) fn scroll_left(&mut self, scroll: usize) {
+        self.attributes.scroll_left(u8::MAX, scroll);
+    }
+
+    /// Scrolls the attributes right by `scroll` pixels of the display buffer.
+    pub(crate) fn scroll_right(&mut self, scroll: usize) {
+        self.attributes.scroll_right(u8::MAX, scroll);
+    }
+}
+
+impl Default for Colorizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns given hexadecimal number without its `0x` prefix.
+fn hex_digits(number: &str) -> &str {
+    number
+        .strip_prefix("0x")
+        .or_else(|| number.strip_prefix("0X"))
+        .unwrap_or(number)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(feature = "alloc")]
+    use crate::{sprite_hash, Chirp8Mode};
+
+    #[test]
+    fn colorizer_parse_rules() {
+        let text = "# Comment\n\n 0x200-2FF FF0000 # Sprites\n300 0x00ff00\nHASH 0x1F 0000FF\n";
+        let colorizer = Colorizer::parse(text).unwrap();
+        assert_eq!(
+            colorizer.rules(),
+            [
+                ColorRule {
+                    sprites: SpriteMatch::Addresses {
+                        first: 0x200,
+                        last: 0x2FF
+                    },
+                    color: 0xFF0000
+                },
+                ColorRule {
+                    sprites: SpriteMatch::Addresses {
+                        first: 0x300,
+                        last: 0x300
+                    },
+                    color: 0x00FF00
+                },
+                ColorRule {
+                    sprites: SpriteMatch::Hash(0x1F),
+                    color: 0x0000FF
+                },
+            ]
+        );
+
+        let error = |line, message| Err(RuleError { line, message });
+        let parse = |text| Colorizer::parse(text).map(|colorizer| colorizer.rules().len());
+        assert_eq!(parse("200\n"), error(1, "Expected a color"));
+        assert_eq!(parse("\n2G0 FF0000"), error(2, "Invalid address"));
+        assert_eq!(
+            parse("300-200 FF0000"),
+            error(1, "Invalid range of addresses")
+        );
+        assert_eq!(parse("hash"), error(1, "Expected a hash"));
+        assert_eq!(parse("hash X 000000"), error(1, "Invalid hash"));
+        assert_eq!(parse("200 FFF"), error(1, "Invalid color"));
+        assert_eq!(
+            parse("200 FF0000 00FF00"),
+            error(1, "Unexpected text after the color")
+        );
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn colorizer_sprites() {
+        #[rustfmt::skip]
+        let rom = [
+            0xA2, 0x0E, // I = 20E
+            0xD0, 0x01, // Draw v0 v0 1
+            0xA2, 0x0F, // I = 20F
+            0x61, 0x02, // v1 = 2
+            0xD1, 0x01, // Draw v1 v0 1
+            0x00, 0xFB, // Scroll right
+            0x00, 0xE0, // Clear
+            0xF0, 0xFF, // Sprites
+        ];
+        let mut colorizer = Colorizer::new();
+        let hash = sprite_hash(8, 1, &[0xFF]);
+        assert!(colorizer.add_rule(ColorRule {
+            sprites: SpriteMatch::Hash(hash),
+            color: 0x00FF00,
+        }));
+        assert!(colorizer.add_rule(ColorRule {
+            sprites: SpriteMatch::Addresses {
+                first: 0x20E,
+                last: 0x20F
+            },
+            color: 0xFF0000,
+        }));
+        let mut emulator = Chirp8::new(Chirp8Mode::SuperChipModern);
+        emulator.set_colorizer(Option::Some(colorizer));
+        emulator.load_rom(&rom);
+        emulator.take_steps(5);
+
+        // The second sprite is drawn over the right half of the first one.
+        let palette = Palette::monochrome(0x000000, 0xFFFFFF);
+        let colorizer = emulator.colorizer().unwrap();
+        let colors = core::array::from_fn(|x| colorizer.color(&emulator, &palette, x, 0));
+        #[rustfmt::skip]
+        assert_eq!(colors, [
+            0xFF0000, 0xFF0000, 0x000000, 0x000000, 0x00FF00, 0x00FF00,
+            0x00FF00, 0x00FF00, 0x00FF00, 0x00FF00, 0x000000,
+        ]);
+        assert_eq!(colorizer.pixel_rule(0, 1), colorizer.rules().get(1));
+        assert_eq!(colorizer.pixel_rule(4, 0), colorizer.rules().first());
+        assert_eq!(colorizer.pixel_rule(20, 0), Option::None);
+
+        let mut output = [0; 64 * 32 * 4];
+        assert!(colorizer.render_rgba8(&emulator, &palette, 1, &mut output));
+        assert_eq!(
+            output[..8],
+            [0xFF, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00, 0xFF]
+        );
+        assert_eq!(
+            output[(9 * 4)..(11 * 4)],
+            [0x00, 0xFF, 0x00, 0xFF, 0x00, 0x00, 0x00, 0xFF]
+        );
+
+        // The colors follow the scrolled pixels, and are forgotten when clearing.
+        emulator.take_steps(1);
+        let colorizer = emulator.colorizer().unwrap();
+        assert_eq!(colorizer.color(&emulator, &palette, 3, 0), 0x000000);
+        assert_eq!(colorizer.color(&emulator, &palette, 4, 0), 0xFF0000);
+        assert_eq!(colorizer.color(&emulator, &palette, 13, 0), 0x00FF00);
+        emulator.take_steps(1);
+        let colorizer = emulator.colorizer().unwrap();
+        assert_eq!(colorizer.pixel_rule(8, 0), Option::None);
+    }
+}
@@ -0,0 +1,368 @@
+use bitflags::bitflags;
+
+use crate::{Bus, PROGRAM_START, RAM_SIZE};
+
+bitflags! {
+    /// The ways an address has been accessed by the emulator.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct Access: u8 {
+        /// The address has been fetched as part of an instruction.
+        const EXECUTE = 1 << 0;
+        /// The address has been read as data, by sprite drawing or register loads for instance.
+        const READ = 1 << 1;
+        /// The address has been written, by register stores or binary-coded decimal conversion.
+        const WRITE = 1 << 2;
+    }
+}
+
+// Create type alias depending on if the heap is available or not.
+#[cfg(feature = "alloc")]
+type CoverageData = alloc::vec::Vec<u8>;
+#[cfg(not(feature = "alloc"))]
+type CoverageData = [u8; RAM_SIZE];
+
+/// Per-address record of the accesses made by the emulator.
+///
+/// Maps recorded on different runs of the same program can be merged together :
+/// ```
+/// let mut total = chirp8::CoverageMap::new();
+/// let mut run = chirp8::CoverageMap::new();
+/// run.record(0x200, chirp8::Access::EXECUTE);
+/// total.merge(&run);
+/// assert!(total.get(0x200).contains(chirp8::Access::EXECUTE));
+/// ```
+pub struct CoverageMap {
+    /// The [Access] bits of every address.
+    accesses: CoverageData,
+}
+
+impl Default for CoverageMap {
+    fn default() -> Self {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "alloc")]{
+                let accesses = alloc::vec![0u8; RAM_SIZE];
+            }else{
+                let accesses = [0u8; RAM_SIZE];
+            }
+        }
+
+        Self { accesses }
+    }
+}
+
+impl CoverageMap {
+    /// Creates an empty coverage map, where no address has been accessed.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the accesses recorded at given `address`.
+    pub fn get(&self, address: u16) -> Access {
+        Access::from_bits_truncate(self.accesses[address as usize % RAM_SIZE])
+    }
+
+    /// Records that given `address` has been accessed as described by `access`.
+    #[inline]
+    pub fn record(&mut self, address: u16, access: Access) {
+        self.accesses[address as usize % RAM_SIZE] |= access.bits();
+    }
+
+    /// Adds all accesses recorded in `other` to this map.
+    pub fn merge(&mut self, other: &CoverageMap) {
+        self.merge_bytes(&other.accesses);
+    }
+
+    /// Adds all accesses of a map exported with [CoverageMap::as_bytes] to this map.
+    /// Only the first [RAM_SIZE] bytes of `bytes` are used.
+    pub fn merge_bytes(&mut self, bytes: &[u8]) {
+        for (accesses, other) in self.accesses.iter_mut().zip(bytes) {
+            *accesses |= other & Access::all().bits();
+        }
+    }
+
+    /// Forgets every recorded access.
+    pub fn clear(&mut self) {
+        self.accesses.fill(0);
+    }
+
+    /// Returns the raw map, one byte of [Access] bits per address.
+    /// Can be stored and merged later on with [CoverageMap::merge_bytes].
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.accesses
+    }
+
+    /// Returns the number of addresses for which all of the given `access` bits have been recorded.
+    pub fn count(&self, access: Access) -> usize {
+        self.accesses
+            .iter()
+            .filter(|accesses| Access::from_bits_truncate(**accesses).contains(access))
+            .count()
+    }
+
+    /// Writes a human-readable report of the coverage of the program `rom`, as loaded by
+    /// [crate::Chirp8::load_rom].
+    /// Every byte of the program is listed along with its address and recorded accesses, followed
+    /// by a summary. Accesses are shown as `X` (executed), `R` (read) and `W` (written).
+    /// ```
+    /// let map = chirp8::CoverageMap::new();
+    /// let mut report = String::new();
+    /// map.write_report(&[0x00, 0xE0], &mut report).unwrap();
+    /// assert!(report.starts_with("0x0200  0x00  ---"));
+    /// ```
+    pub fn write_report<W: core::fmt::Write>(&self, rom: &[u8], out: &mut W) -> core::fmt::Result {
+        let mut executed = 0;
+        let mut read = 0;
+        let mut written = 0;
+        for (offset, byte) in rom.iter().enumerate() {
+            let address = PROGRAM_START + offset;
+            let access = self.get(address as u16);
+            let mark =
+                |flag: Access, symbol: char| if access.contains(flag) { symbol } else { '-' };
+            writeln!(
+                out,
+                "0x{:04X}  0x{:02X}  {}{}{}",
+                address,
+                byte,
+                mark(Access::EXECUTE, 'X'),
+                mark(Access::READ, 'R'),
+                mark(Access::WRITE, 'W'),
+            )?;
+            executed += access.contains(Access::EXECUTE) as usize;
+            read += access.contains(Access::READ) as usize;
+            written += access.contains(Access::WRITE) as usize;
+        }
+        writeln!(
+            out,
+            "{} bytes : {} executed, {} read, {} written, {} never accessed.",
+            rom.len(),
+            executed,
+            read,
+            written,
+            (PROGRAM_START..PROGRAM_START + rom.len())
+                .filter(|address| self.get(*address as u16).is_empty())
+                .count()
+        )
+    }
+}
+
+/// Memory bus recording every access made by the emulator in a [CoverageMap], while forwarding
+/// them to the wrapped bus.
+///
+/// Only the accesses made by the program are recorded. Block reads and writes, used by the
+/// emulator to load its font and ROMs, to randomize memory or to serve a debugger, are forwarded
+/// without being recorded, as are peeks.
+/// ```
+/// let mode = chirp8::Chirp8Mode::CosmacChip8;
+/// let bus = chirp8::Coverage::new(vec![0u8; chirp8::RAM_SIZE]);
+/// let mut emulator = chirp8::Chirp8::with_bus(mode, chirp8::QuirkFlags::from_mode(mode), bus);
+/// emulator.load_rom(&[0x12, 0x00]); // Infinite loop
+/// emulator.run_frame();
+/// assert!(emulator.bus().map().get(0x200).contains(chirp8::Access::EXECUTE));
+/// ```
+pub struct Coverage<B: Bus> {
+    /// The actual memory bus.
+    bus: B,
+    /// The accesses recorded so far.
+    map: CoverageMap,
+}
+
+impl<B: Bus> Coverage<B> {
+    /// Starts recording accesses made to given `bus`.
+    pub fn new(bus: B) -> Self {
+        Self {
+            bus,
+            map: CoverageMap::new(),
+        }
+    }
+
+    /// Returns the accesses recorded so far.
+    pub fn map(&self) -> &CoverageMap {
+        &self.map
+    }
+
+    /// Returns the accesses recorded so far, to be cleared or merged for instance.
+    pub fn map_mut(&mut self) -> &mut CoverageMap {
+        &mut self.map
+    }
+
+    /// Returns the wrapped bus.
+    pub fn inner(&self) -> &B {
+        &self.bus
+    }
+
+    /// Returns the wrapped bus mutably, accesses made this way are not recorded.
+    pub fn inner_mut(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    /// Stops recording, returns the wrapped bus and the recorded accesses.
+    pub fn into_parts(self) -> (B, CoverageMap) {
+        (self.bus, self.map)
+    }
+}
+
+impl<B: Bus> Bus for Coverage<B> {
+    // Every fetch must be recorded.
+    const CACHEABLE: bool = false;
+
+    #[inline]
+    fn size(&self) -> usize {
+        self.bus.size()
+    }
+
+    #[inline]
+    fn read(&mut self, address: u16) -> u8 {
+        self.map.record(address, Access::READ);
+        self.bus.read(address)
+    }
+
+    #[inline]
+    fn write(&mut self, address: u16, value: u8) {
+        self.map.record(address, Access::WRITE);
+        self.bus.write(address, value);
+    }
+
+    #[inline]
+    fn fetch(&mut self, address: u16) -> u8 {
+        self.map.record(address, Access::EXECUTE);
+        self.bus.fetch(address)
+    }
+
+    // Not recorded, block accesses are made by the emulator itself.
+    fn read_block(&mut self, address: u16, buffer: &mut [u8]) {
+        self.bus.read_block(address, buffer);
+    }
+
+    // Not recorded, see read_block.
+    fn write_block(&mut self, address: u16, data: &[u8]) {
+        self.bus.write_block(address, data);
+    }
+
+    fn peek_block(&self, address: u16, buffer: &mut [u8]) -> bool {
+        self.bus.peek_block(address, buffer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Chirp8, Chirp8Mode, QuirkFlags};
+
+    #[test]
+    fn coverage_accesses() {
+        #[rustfmt::skip]
+        let rom = [
+            0xA2, 0x08, // Set index to sprite
+            0xD0, 0x11, // Display v0 v1 1
+            0xF0, 0x33, // BCD of v0 at index
+            0x12, 0x06, // Infinite loop
+            0x80,       // Sprite
+        ];
+
+        let mode = Chirp8Mode::CosmacChip8;
+        let bus = Coverage::new([0u8; RAM_SIZE]);
+        let mut emulator = Chirp8::with_bus(mode, QuirkFlags::from_mode(mode), bus);
+        emulator.load_rom(&rom);
+        assert!(emulator.bus().map().get(PROGRAM_START as u16).is_empty());
+
+        emulator.run_frame();
+        emulator.run_frame();
+
+        let map = emulator.bus().map();
+        for address in PROGRAM_START..PROGRAM_START + 8 {
+            assert_eq!(map.get(address as u16), Access::EXECUTE);
+        }
+        assert_eq!(
+            map.get(PROGRAM_START as u16 + 8),
+            Access::READ | Access::WRITE
+        );
+        assert_eq!(map.get(PROGRAM_START as u16 + 9), Access::WRITE);
+        assert_eq!(map.get(PROGRAM_START as u16 + 10), Access::WRITE);
+        assert_eq!(map.get(PROGRAM_START as u16 + 11), Access::empty());
+        assert_eq!(map.count(Access::EXECUTE), 8);
+
+        let mut merged = CoverageMap::new();
+        merged.record(0x300, Access::READ);
+        merged.merge(map);
+        assert_eq!(merged.count(Access::empty()), RAM_SIZE);
+        assert_eq!(merged.count(Access::READ), 2);
+    }
+
+    #[test]
+    fn coverage_superchip_random_memory() {
+        let rom = [0x12, 0x00]; // Infinite loop
+
+        // The memory randomized on start is not recorded as written by the program.
+        let mode = Chirp8Mode::SuperChip1_1;
+        let bus = Coverage::new([0u8; RAM_SIZE]);
+        let mut emulator = Chirp8::with_bus(mode, QuirkFlags::from_mode(mode), bus);
+        assert!(emulator.bus().inner()[PROGRAM_START..]
+            .iter()
+            .any(|byte| *byte != 0));
+        assert_eq!(emulator.bus().map().count(Access::empty()), RAM_SIZE);
+        assert_eq!(emulator.bus().map().count(Access::WRITE), 0);
+
+        emulator.load_rom(&rom);
+        emulator.run_frame();
+        let map = emulator.bus().map();
+        assert_eq!(map.get(PROGRAM_START as u16), Access::EXECUTE);
+        assert_eq!(map.get(PROGRAM_START as u16 + 1), Access::EXECUTE);
+        assert_eq!(map.count(Access::EXECUTE), 2);
+        assert_eq!(map.count(Access::READ), 0);
+        assert_eq!(map.count(Access::WRITE), 0);
+    }
+
+    #[test]
+    fn coverage_bytes_round_trip() {
+        let mut map = CoverageMap::new();
+        map.record(0x200, Access::EXECUTE);
+        map.record(0x300, Access::READ | Access::WRITE);
+        assert_eq!(map.as_bytes().len(), RAM_SIZE);
+
+        let mut copy = CoverageMap::new();
+        copy.merge_bytes(map.as_bytes());
+        assert_eq!(copy.as_bytes(), map.as_bytes());
+        assert_eq!(copy.get(0x200), Access::EXECUTE);
+        assert_eq!(copy.get(0x300), Access::READ | Access::WRITE);
+
+        // Unknown bits are dropped and extra bytes ignored.
+        let mut bytes = [0u8; RAM_SIZE + 1];
+        bytes[0x201] = 0xFF;
+        bytes[RAM_SIZE] = 0xFF;
+        copy.merge_bytes(&bytes);
+        assert_eq!(copy.get(0x201), Access::all());
+        assert_eq!(copy.as_bytes()[0x201], Access::all().bits());
+        assert_eq!(copy.count(Access::empty()), RAM_SIZE);
+        assert_eq!(copy.count(Access::EXECUTE), 2);
+
+        copy.clear();
+        assert!(copy.as_bytes().iter().all(|byte| *byte == 0));
+        assert!(copy.get(0x200).is_empty());
+        assert_eq!(copy.count(Access::READ), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn coverage_report() {
+        let rom = [0x00, 0xE0, 0x12, 0x02, 0x42];
+        let mut map = CoverageMap::new();
+        map.record(0x200, Access::EXECUTE);
+        map.record(0x201, Access::EXECUTE);
+        map.record(0x202, Access::EXECUTE | Access::READ);
+        map.record(0x203, Access::EXECUTE | Access::WRITE);
+
+        let mut report = alloc::string::String::new();
+        map.write_report(&rom, &mut report).unwrap();
+        let mut lines = report.lines();
+        assert_eq!(lines.next(), Option::Some("0x0200  0x00  X--"));
+        assert_eq!(lines.next(), Option::Some("0x0201  0xE0  X--"));
+        assert_eq!(lines.next(), Option::Some("0x0202  0x12  XR-"));
+        assert_eq!(lines.next(), Option::Some("0x0203  0x02  X-W"));
+        assert_eq!(lines.next(), Option::Some("0x0204  0x42  ---"));
+        assert_eq!(
+            lines.next(),
+            Option::Some("5 bytes : 4 executed, 1 read, 1 written, 1 never accessed.")
+        );
+        assert_eq!(lines.next(), Option::None);
+    }
+}
@@ -0,0 +1,453 @@
+//! Minimal zlib streams, as used by PNG images.
+
+use std::io;
+use std::vec::Vec;
+
+/// Largest length of a match.
+const MAX_MATCH: usize = 258;
+/// Smallest length of a match.
+const MIN_MATCH: usize = 3;
+/// Largest distance of a match.
+const WINDOW_SIZE: usize = 32768;
+/// Number of entries of the table of the last positions of 3 bytes sequences.
+const HASH_SIZE: usize = 1 << 15;
+
+/// Smallest length of each length code, from 257.
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+/// Number of extra bits of each length code, from 257.
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Smallest distance of each distance code.
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+/// Number of extra bits of each distance code.
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order in which the lengths of the code lengths code are given in dynamic blocks.
+const CODE_LENGTHS_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// Returns an error about invalid compressed data.
+fn invalid_data<T>(message: &'static str) -> io::Result<T> {
+    Err(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+/// Returns the Adler-32 checksum of `data`.
+fn adler32(data: &[u8]) -> u32 {
+    const MODULO: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // Sums stay below 2^32 for chunks of 5552 bytes.
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= MODULO;
+        b %= MODULO;
+    }
+    (b << 16) | a
+}
+
+/// Writes bits to bytes, from the least significant bit of each byte.
+struct BitWriter {
+    output: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    /// Writes the `count` lowest bits of `bits`, from the least significant.
+    fn write(&mut self, bits: u32, count: u32) {
+        self.bits |= (bits as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.output.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes the `length` bits of a Huffman `code`, from the most significant.
+    fn write_code(&mut self, code: u32, length: u32) {
+        self.write(code.reverse_bits() >> (u32::BITS - length), length);
+    }
+
+    /// Writes the remaining bits, padded with zeros, and returns the bytes.
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.output.push(self.bits as u8);
+        }
+        self.output
+    }
+}
+
+/// Writes given literal or length `symbol` with the fixed Huffman codes.
+fn write_fixed_symbol(writer: &mut BitWriter, symbol: u16) {
+    let symbol = symbol as u32;
+    match symbol {
+        0..=143 => writer.write_code(0b0011_0000 + symbol, 8),
+        144..=255 => writer.write_code(0b1_1001_0000 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0b1100_0000 + symbol - 280, 8),
+    }
+}
+
+/// Returns the index of the base of `value` in `bases`, the largest one not above it.
+fn base_index(bases: &[u16], value: usize) -> usize {
+    bases.partition_point(|base| *base as usize <= value) - 1
+}
+
+/// Compresses `data` to a zlib stream, with fixed Huffman codes. Matches are searched at the
+/// last occurrence of the next bytes and at given `distances`, like the previous pixel or the
+/// row above.
+pub(crate) fn compress(data: &[u8], distances: &[usize]) -> Vec<u8> {
+    let mut writer = BitWriter {
+        output: Vec::with_capacity(data.len() / 4 + 16),
+        bits: 0,
+        count: 0,
+    };
+    // Deflate with a 32K window, without preset dictionary, fastest compression.
+    writer.write(0x78, 8);
+    writer.write(0x01, 8);
+    // Single final block with fixed codes.
+    writer.write(1, 1);
+    writer.write(1, 2);
+
+    let hash = |position: usize| {
+        let bytes = &data[position..(position + MIN_MATCH)];
+        ((bytes[0] as usize) << 10 ^ (bytes[1] as usize) << 5 ^ bytes[2] as usize) % HASH_SIZE
+    };
+    let mut last_positions = std::vec![usize::MAX; HASH_SIZE];
+    let mut position = 0;
+    while position < data.len() {
+        let mut best = (0, 0);
+        if position + MIN_MATCH <= data.len() {
+            let last = last_positions[hash(position)];
+            let candidates = distances
+                .iter()
+                .copied()
+                .chain((last != usize::MAX).then(|| position - last));
+            for distance in candidates {
+                if distance == 0 || distance > position || distance > WINDOW_SIZE {
+                    continue;
+                }
+                let length = data[position..]
+                    .iter()
+                    .zip(&data[(position - distance)..])
+                    .take(MAX_MATCH)
+                    .take_while(|(byte, previous)| byte == previous)
+                    .count();
+                if length > best.0 {
+                    best = (length, distance);
+                }
+            }
+        }
+
+        let (length, distance) = best;
+        let advance = if length >= MIN_MATCH {
+            let code = base_index(&LENGTH_BASES, length);
+            write_fixed_symbol(&mut writer, 257 + code as u16);
+            writer.write(
+                (length - LENGTH_BASES[code] as usize) as u32,
+                LENGTH_EXTRA_BITS[code] as u32,
+            );
+            let code = base_index(&DISTANCE_BASES, distance);
+            writer.write_code(code as u32, 5);
+            writer.write(
+                (distance - DISTANCE_BASES[code] as usize) as u32,
+                DISTANCE_EXTRA_BITS[code] as u32,
+            );
+            length
+        } else {
+            write_fixed_symbol(&mut writer, data[position] as u16);
+            1
+        };
+        for skipped in position..(position + advance) {
+            if skipped + MIN_MATCH <= data.len() {
+                last_positions[hash(skipped)] = skipped;
+            }
+        }
+        position += advance;
+    }
+    write_fixed_symbol(&mut writer, 256);
+
+    let mut output = writer.finish();
+    output.extend_from_slice(&adler32(data).to_be_bytes());
+    output
+}
+
+/// Reads bits from bytes, from the least significant bit of each byte.
+struct BitReader<'a> {
+    input: &'a [u8],
+    position: usize,
+    bit: u32,
+}
+
+impl BitReader<'_> {
+    /// Reads `count` bits, the first one being the least significant.
+    fn bits(&mut self, count: u32) -> io::Result<u32> {
+        let mut value = 0;
+        for index in 0..count {
+            let Option::Some(byte) = self.input.get(self.position) else {
+                return invalid_data("Unexpected end of compressed data");
+            };
+            value |= ((*byte as u32 >> self.bit) & 1) << index;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.position += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    /// Skips the remaining bits of the current byte.
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.position += 1;
+        }
+    }
+}
+
+/// Canonical Huffman code, decoded bit by bit.
+struct Huffman {
+    /// Number of codes of each length.
+    counts: [u16; 16],
+    /// Symbols ordered by code.
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Creates the code of symbols of given code `lengths`, 0 for unused symbols.
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols = Vec::with_capacity(lengths.len());
+        for length in 1..counts.len() {
+            for (symbol, _) in lengths
+                .iter()
+                .enumerate()
+                .filter(|(_, l)| **l as usize == length)
+            {
+                symbols.push(symbol as u16);
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    /// Reads the next symbol from `reader`.
+    fn decode(&self, reader: &mut BitReader) -> io::Result<u16> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for count in &self.counts[1..] {
+            code |= reader.bits(1)? as i32;
+            let count = *count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        invalid_data("Invalid Huffman code")
+    }
+}
+
+/// Reads the literal/length and distance codes of a dynamic block.
+fn read_dynamic_codes(reader: &mut BitReader) -> io::Result<(Huffman, Huffman)> {
+    let literals = reader.bits(5)? as usize + 257;
+    let distances = reader.bits(5)? as usize + 1;
+    let code_lengths = reader.bits(4)? as usize + 4;
+    let mut lengths = [0u8; 19];
+    for index in CODE_LENGTHS_ORDER.iter().take(code_lengths) {
+        lengths[*index] = reader.bits(3)? as u8;
+    }
+    let lengths_code = Huffman::new(&lengths);
+
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let symbol = lengths_code.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => match lengths.last() {
+                Option::Some(previous) => (*previous, 3 + reader.bits(2)?),
+                Option::None => return invalid_data("Repeated code length without previous one"),
+            },
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() > literals + distances {
+        return invalid_data("Too many code lengths");
+    }
+    Ok((
+        Huffman::new(&lengths[..literals]),
+        Huffman::new(&lengths[literals..]),
+    ))
+}
+
+/// Error of data decompressing to more bytes than expected.
+fn too_large<T>() -> io::Result<T> {
+    invalid_data("Decompressed data too large")
+}
+
+/// Decompresses the zlib stream `input`, failing as soon as it would yield more than `max_size`
+/// bytes, so that small streams cannot expand to any amount of memory.
+pub(crate) fn decompress(input: &[u8], max_size: usize) -> io::Result<Vec<u8>> {
+    if input.len() < 6
+        || input[0] & 0x0F != 8
+        || !u16::from_be_bytes([input[0], input[1]]).is_multiple_of(31)
+        || input[1] & 0x20 != 0
+    {
+        return invalid_data("Invalid zlib header");
+    }
+    let mut reader = BitReader {
+        input: &input[2..],
+        position: 0,
+        bit: 0,
+    };
+    let mut output = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let start = reader.position;
+                let Option::Some(header) = reader.input.get(start..(start + 4)) else {
+                    return invalid_data("Unexpected end of compressed data");
+                };
+                let length = u16::from_le_bytes([header[0], header[1]]);
+                if length != !u16::from_le_bytes([header[2], header[3]]) {
+                    return invalid_data("Invalid stored block length");
+                }
+                let start = start + 4;
+                let Option::Some(data) = reader.input.get(start..(start + length as usize)) else {
+                    return invalid_data("Unexpected end of compressed data");
+                };
+                if output.len() + data.len() > max_size {
+                    return too_large();
+                }
+                output.extend_from_slice(data);
+                reader.position = start + length as usize;
+            }
+            block_type @ (1 | 2) => {
+                let (literals, distances) = if block_type == 1 {
+                    let mut lengths = [8u8; 288];
+                    lengths[144..256].fill(9);
+                    lengths[256..280].fill(7);
+                    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+                } else {
+                    read_dynamic_codes(&mut reader)?
+                };
+                loop {
+                    let symbol = literals.decode(&mut reader)? as usize;
+                    if symbol < 256 {
+                        if output.len() == max_size {
+                            return too_large();
+                        }
+                        output.push(symbol as u8);
+                        continue;
+                    } else if symbol == 256 {
+                        break;
+                    }
+                    let code = symbol - 257;
+                    if code >= LENGTH_BASES.len() {
+                        return invalid_data("Invalid length code");
+                    }
+                    let length = LENGTH_BASES[code] as usize
+                        + reader.bits(LENGTH_EXTRA_BITS[code] as u32)? as usize;
+                    let code = distances.decode(&mut reader)? as usize;
+                    if code >= DISTANCE_BASES.len() {
+                        return invalid_data("Invalid distance code");
+                    }
+                    let distance = DISTANCE_BASES[code] as usize
+                        + reader.bits(DISTANCE_EXTRA_BITS[code] as u32)? as usize;
+                    if distance > output.len() {
+                        return invalid_data("Distance beyond the decompressed data");
+                    }
+                    if output.len() + length > max_size {
+                        return too_large();
+                    }
+                    let start = output.len() - distance;
+                    for index in start..(start + length) {
+                        output.push(output[index]);
+                    }
+                }
+            }
+            _ => return invalid_data("Invalid block type"),
+        }
+        if last {
+            break;
+        }
+    }
+
+    reader.align();
+    let checksum = reader.input.get(reader.position..(reader.position + 4));
+    if checksum != Option::Some(&adler32(&output).to_be_bytes()[..]) {
+        return invalid_data("Invalid zlib checksum");
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deflate_round_trip() {
+        let data: Vec<u8> = (0..5000u32)
+            .map(|index| {
+                if index % 7 < 3 {
+                    0xFF
+                } else {
+                    (index / 13) as u8
+                }
+            })
+            .collect();
+        for distances in [&[][..], &[4, 100]] {
+            let compressed = compress(&data, distances);
+            assert!(compressed.len() < data.len() / 2);
+            assert_eq!(decompress(&compressed, data.len()).unwrap(), data);
+        }
+        assert_eq!(decompress(&compress(&[], &[]), 0).unwrap(), []);
+
+        // Stored block, from another encoder.
+        let stored = [
+            0x78, 0x01, 0x01, 0x03, 0x00, 0xFC, 0xFF, 1, 2, 3, 0x00, 0x0D, 0x00, 0x07,
+        ];
+        assert_eq!(decompress(&stored, 3).unwrap(), [1, 2, 3]);
+        // Fixed and dynamic blocks compressed by zlib.
+        let fixed = [
+            0x78, 0xDA, 0x4B, 0x4C, 0x4A, 0x4E, 0x04, 0x23, 0x2E, 0x00, 0x14, 0xBA, 0x03, 0x7D,
+        ];
+        assert_eq!(decompress(&fixed, 10).unwrap(), b"abcabcabc\n");
+        let dynamic = [
+            0x78, 0xDA, 0x1D, 0x8A, 0x81, 0x0D, 0x00, 0x00, 0x0C, 0xC1, 0x6E, 0x2D, 0xFE, 0xBF,
+            0x61, 0x4C, 0xA4, 0x44, 0x10, 0x40, 0x55, 0xD9, 0x4C, 0xC9, 0x03, 0x6F, 0x18, 0xB5,
+            0xF6, 0x36, 0x3B, 0xFB, 0x33, 0xF2, 0x01, 0xBA, 0xD8, 0x16, 0xEA,
+        ];
+        let text = b"adaaabbbbaababbdabaadabaaacababacabbcabaabaabcaaaabcbcaaadbc";
+        assert_eq!(decompress(&dynamic, text.len()).unwrap(), text);
+        assert!(decompress(&stored[..12], 3).is_err());
+
+        // The output is limited, whatever the kind of block.
+        assert!(decompress(&stored, 2).is_err());
+        assert!(decompress(&fixed, 9).is_err());
+        assert!(decompress(&fixed, 3).is_err());
+        let compressed = compress(&data, &[4, 100]);
+        assert!(decompress(&compressed, data.len() - 1).is_err());
+    }
+}
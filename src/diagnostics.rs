@@ -0,0 +1,131 @@
+use core::fmt;
+
+use crate::{Chirp8Mode, WriteViolation, PROGRAM_START};
+
+/// An instruction the emulator does not know in its mode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UnknownInstruction {
+    /// The opcode of the instruction.
+    pub opcode: u16,
+    /// The mode of the emulator.
+    pub mode: Chirp8Mode,
+    /// Address of the instruction.
+    pub pc: u16,
+}
+
+impl fmt::Display for UnknownInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            Chirp8Mode::CosmacChip8 => "Chip-8",
+            Chirp8Mode::SuperChip1_1 => "Super Chip 1.1",
+            Chirp8Mode::SuperChipModern => "Super Chip Modern",
+            Chirp8Mode::XOChip => "XO-Chip",
+        };
+        write!(
+            f,
+            "Unknown instruction 0x{:04X} in mode '{}', at program counter 0x{:04X} ",
+            self.opcode, mode, self.pc
+        )?;
+        match self.pc.checked_sub(PROGRAM_START as u16) {
+            Option::Some(address) => write!(f, "(At program address 0x{:04X}).", address),
+            Option::None => write!(f, "(Lost in reserved memory < 0x0200)."),
+        }
+    }
+}
+
+/// What the emulator does when it encounters an unknown instruction.
+///
+/// To stop at the first unknown instruction :
+/// ```
+/// let mut emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+/// emulator.set_unknown_instruction_action(chirp8::UnknownInstructionAction::Halt);
+/// emulator.load_rom(&[0xFF, 0xFF]);
+/// emulator.step();
+/// assert!(emulator.is_halted());
+/// ```
+#[derive(Clone, Copy)]
+pub enum UnknownInstructionAction {
+    /// The instruction is skipped silently.
+    Ignore,
+    /// The instruction is skipped and a warning is logged with the `log` or `defmt` feature,
+    /// or printed when only the `std` feature is enabled.
+    Log,
+    /// The instruction is skipped, then given function is called.
+    Callback(fn(&UnknownInstruction)),
+    /// The emulator halts, see [crate::Chirp8::is_halted].
+    Halt,
+}
+
+/// Reports an unknown instruction through the enabled logging backend.
+#[allow(unused_variables)]
+pub(crate) fn warn_unknown_instruction(instruction: &UnknownInstruction) {
+    #[cfg(feature = "log")]
+    log::warn!(
+        opcode = instruction.opcode,
+        mode:? = instruction.mode,
+        pc = instruction.pc;
+        "{}",
+        instruction
+    );
+    #[cfg(feature = "defmt")]
+    defmt::warn!(
+        "Unknown instruction {=u16:#06X} in mode {}, at program counter {=u16:#06X}.",
+        instruction.opcode,
+        instruction.mode,
+        instruction.pc
+    );
+    #[cfg(all(feature = "std", not(any(feature = "log", feature = "defmt"))))]
+    std::println!("{}", instruction);
+}
+
+/// Reports a write flagged by a [crate::WriteMonitor] through the enabled logging backend.
+#[allow(unused_variables)]
+pub(crate) fn warn_write(violation: &WriteViolation) {
+    #[cfg(feature = "log")]
+    log::warn!(
+        pc = violation.pc,
+        address = violation.address,
+        value = violation.value,
+        kind:? = violation.kind;
+        "{}",
+        violation
+    );
+    #[cfg(feature = "defmt")]
+    defmt::warn!(
+        "Write of {=u8:#04X} to {} {=u16:#06X}, at program counter {=u16:#06X}.",
+        violation.value,
+        violation.kind,
+        violation.address,
+        violation.pc
+    );
+    #[cfg(all(feature = "std", not(any(feature = "log", feature = "defmt"))))]
+    std::println!("{}", violation);
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use std::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn unknown_instruction_message() {
+        let instruction = UnknownInstruction {
+            opcode: 0xFFFF,
+            mode: Chirp8Mode::XOChip,
+            pc: 0x0204,
+        };
+        assert_eq!(
+            instruction.to_string(),
+            "Unknown instruction 0xFFFF in mode 'XO-Chip', at program counter 0x0204 \
+             (At program address 0x0004)."
+        );
+        let instruction = UnknownInstruction {
+            pc: 0x0010,
+            ..instruction
+        };
+        assert!(instruction
+            .to_string()
+            .ends_with("(Lost in reserved memory < 0x0200)."));
+    }
+}
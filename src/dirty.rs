@@ -0,0 +1,202 @@
+use core::ops::Range;
+
+use crate::display::column_bit;
+use crate::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+/// Rectangle of pixels of the display buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DirtyRect {
+    /// Leftmost column.
+    pub x: usize,
+    /// Top row.
+    pub y: usize,
+    /// Number of columns.
+    pub width: usize,
+    /// Number of rows.
+    pub height: usize,
+}
+
+/// Pixels of the display buffer which may have changed, for frontends to only redraw them.
+///
+/// Pixels are addressed in the 128x64 display buffer like [crate::DisplayPixels], each pixel of
+/// the low resolution being a 2x2 square. Pixels may be marked even though their value is the
+/// same, after scrolling or clearing the display for instance.
+/// ```
+/// let mut emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+/// emulator.load_rom(&[0xF0, 0x29, 0xD0, 0x05]); // Draw the font sprite of 0.
+/// emulator.take_dirty_region(); // All pixels are dirty at first.
+/// emulator.take_steps(2);
+/// let dirty = emulator.take_dirty_region();
+/// assert_eq!(
+///     dirty.bounding_rect(),
+///     Some(chirp8::DirtyRect { x: 0, y: 0, width: 8, height: 10 })
+/// );
+/// for (y, columns) in dirty.rows() {
+///     // Redraw the pixels of row y at given columns.
+/// }
+/// assert!(emulator.take_dirty_region().is_empty());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DirtyRegion {
+    /// The dirty columns of each row, in the order of [crate::DisplayPixels::plane_row].
+    rows: [u128; DISPLAY_HEIGHT],
+}
+
+impl DirtyRegion {
+    /// Creates a region without any dirty pixel.
+    pub const fn new() -> Self {
+        Self {
+            rows: [0; DISPLAY_HEIGHT],
+        }
+    }
+
+    /// Creates a region where all pixels are dirty.
+    pub const fn full() -> Self {
+        Self {
+            rows: [u128::MAX; DISPLAY_HEIGHT],
+        }
+    }
+
+    /// Indicates if no pixel is dirty.
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|row| *row == 0)
+    }
+
+    /// Indicates if the pixel at column `x` and row `y` is dirty.
+    pub fn is_dirty(&self, x: usize, y: usize) -> bool {
+        self.rows[y] & column_bit(x) != 0
+    }
+
+    /// Returns the dirty pixels of row `y` as bits, in the order of
+    /// [crate::DisplayPixels::plane_row].
+    pub fn row_bits(&self, y: usize) -> u128 {
+        self.rows[y]
+    }
+
+    /// Returns the columns from the leftmost to the rightmost dirty pixel of row `y`, if any.
+    pub fn row_span(&self, y: usize) -> Option<Range<usize>> {
+        let row = self.rows[y];
+        if row == 0 {
+            return Option::None;
+        }
+        Option::Some(row.leading_zeros() as usize..(DISPLAY_WIDTH - row.trailing_zeros() as usize))
+    }
+
+    /// Returns the rows containing dirty pixels, from top to bottom, along with the columns
+    /// from their leftmost to their rightmost dirty pixel.
+    pub fn rows(&self) -> impl Iterator<Item = (usize, Range<usize>)> + '_ {
+        (0..DISPLAY_HEIGHT).filter_map(|y| self.row_span(y).map(|columns| (y, columns)))
+    }
+
+    /// Returns the smallest rectangle containing all dirty pixels, if any.
+    pub fn bounding_rect(&self) -> Option<DirtyRect> {
+        let top = self.rows.iter().position(|row| *row != 0)?;
+        let bottom = self.rows.iter().rposition(|row| *row != 0)?;
+        let columns = self.rows.iter().fold(0, |columns, row| columns | row);
+        let left = columns.leading_zeros() as usize;
+        Option::Some(DirtyRect {
+            x: left,
+            y: top,
+            width: DISPLAY_WIDTH - columns.trailing_zeros() as usize - left,
+            height: bottom + 1 - top,
+        })
+    }
+
+    /// Marks the pixels of row `y` set in `bits` as dirty.
+    #[inline]
+    pub(crate) fn mark_row(&mut self, y: usize, bits: u128) {
+        self.rows[y] |= bits;
+    }
+
+    /// Marks all pixels as dirty.
+    pub(crate) fn mark_all(&mut self) {
+        self.rows.fill(u128::MAX);
+    }
+}
+
+impl Default for DirtyRegion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Chirp8, Chirp8Mode};
+
+    #[test]
+    fn dirty_region_sprites() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x03, // v0 = 3
+            0xA2, 0x0E, // I = 20E
+            0xD0, 0x02, // Draw v0 v0 2
+            0x00, 0xFF, // High resolution
+            0x61, 0x78, // v1 = 120
+            0xD1, 0x00, // Draw large sprite v1 v0
+            0x12, 0x0C, // Loop
+            0x81, 0x00, // Sprite
+        ];
+        let mut emulator = Chirp8::new(Chirp8Mode::SuperChipModern);
+        emulator.load_rom(&rom);
+        assert_eq!(emulator.dirty_region(), &DirtyRegion::full());
+        assert!(!emulator.take_dirty_region().is_empty());
+        assert!(emulator.dirty_region().is_empty());
+
+        emulator.take_steps(3);
+        let dirty = emulator.take_dirty_region();
+        let lores_row = column_bit(6) | column_bit(7) | column_bit(20) | column_bit(21);
+        let rows = [(6, 6..22), (7, 6..22)];
+        assert!(dirty.rows().eq(rows));
+        assert_eq!(dirty.row_bits(6), lores_row);
+        assert!(dirty.is_dirty(21, 7) && !dirty.is_dirty(8, 7));
+        assert_eq!(
+            dirty.bounding_rect(),
+            Option::Some(DirtyRect {
+                x: 6,
+                y: 6,
+                width: 16,
+                height: 2
+            })
+        );
+
+        // Changing the resolution redraws everything.
+        emulator.take_steps(1);
+        assert_eq!(emulator.take_dirty_region(), DirtyRegion::full());
+
+        // Large sprite at the right edge.
+        emulator.take_steps(2);
+        let dirty = emulator.take_dirty_region();
+        assert_eq!(
+            dirty.bounding_rect(),
+            Option::Some(DirtyRect {
+                x: 120,
+                y: 3,
+                width: 8,
+                height: 1
+            })
+        );
+        assert_eq!(dirty.row_span(3), Option::Some(120..128));
+        assert_eq!(dirty.row_span(4), Option::None);
+    }
+
+    #[test]
+    fn dirty_region_clear_and_scroll() {
+        #[rustfmt::skip]
+        let rom = [
+            0x00, 0xE0, // Clear
+            0x00, 0xC1, // Scroll down 1
+            0x00, 0xFB, // Scroll right
+        ];
+        let mut emulator = Chirp8::new(Chirp8Mode::SuperChipModern);
+        emulator.load_rom(&rom);
+        for _ in 0..3 {
+            emulator.take_dirty_region();
+            emulator.display_changed();
+            emulator.take_steps(1);
+            assert_eq!(emulator.take_dirty_region(), DirtyRegion::full());
+            assert!(emulator.display_changed());
+        }
+    }
+}
@@ -0,0 +1,784 @@
+use core::ops::{Index, IndexMut};
+
+use crate::chirp8::{repeat_bits, ALL_PLANES, DISPLAY_PLANES};
+use crate::{DISPLAY_HEIGHT, DISPLAY_WIDTH, MAX_DISPLAY_PLANES, PIXEL_OFF};
+
+/// Read access to the pixels of a display buffer, whatever its internal representation.
+///
+/// Pixels are addressed in the 128x64 display buffer, where each pixel of the low resolution
+/// is a 2x2 square.
+/// ```
+/// use chirp8::DisplayPixels;
+///
+/// let emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+/// let screen = emulator.get_display_buffer();
+/// assert_eq!(screen.pixel(12, 34), chirp8::PIXEL_OFF);
+/// ```
+pub trait DisplayPixels {
+    /// Returns the value of the pixel at column `x` and row `y`, from [PIXEL_OFF] to
+    /// [crate::PIXEL_ON] by steps of [crate::Chirp8::pixel_step].
+    fn pixel(&self, x: usize, y: usize) -> u8;
+
+    /// Returns the row `y` of given `plane`, lower than the number of planes, the pixel at column `x` being the bit
+    /// `DISPLAY_WIDTH - 1 - x`, so that the leftmost pixel is the most significant bit.
+    fn plane_row(&self, plane: usize, y: usize) -> u128 {
+        // The plane bits are the lowest bits of pixel values.
+        (0..DISPLAY_WIDTH).fold(0, |bits, x| {
+            (bits << 1) | (self.pixel(x, y) & (1 << plane) != 0) as u128
+        })
+    }
+}
+
+/// Modifications of a display buffer, for the emulator to draw on any representation.
+///
+/// Implemented by [FlatDisplay], by [PackedDisplay], by rows of bytes such as
+/// `[[u8; 128]; 64]`, and by `&mut [u8]` storing a byte per pixel row after row, see
+/// [crate::Chirp8::with_buffers].
+///
+/// Pixel values repeat the bits of their planes over the whole byte, see
+/// [crate::Chirp8::set_display_planes], and planes are given as the bits they take in pixel
+/// values : with `P` planes, plane `p` takes the bits `p`, `p + P` and so on, so that the first
+/// of 2 planes is `0x55` and all planes are `0xFF`.
+/// Rows of pixels are given as bits, in the order of [DisplayPixels::plane_row].
+pub trait DisplayStorage: DisplayPixels {
+    /// Sets the number of planes of the pixel values, 1, 2 or 4, up to
+    /// [DisplayStorage::max_planes_count]. Storages keeping the pixel values as is do not need it.
+    fn set_planes_count(&mut self, _count: usize) {}
+
+    /// Returns the largest number of planes stored, [MAX_DISPLAY_PLANES] unless the storage
+    /// has room for fewer planes.
+    fn max_planes_count(&self) -> usize {
+        MAX_DISPLAY_PLANES
+    }
+
+    /// Sets the value of the pixel at column `x` and row `y`.
+    fn set_pixel(&mut self, x: usize, y: usize, value: u8);
+
+    /// Turns off all pixels of given `planes`.
+    fn clear_planes(&mut self, planes: u8);
+
+    /// Flips the pixels of `planes` set in `bits` on row `y`.
+    /// Returns true if a pixel lit on any of the `planes` is turned off on all of them.
+    fn xor_row(&mut self, y: usize, planes: u8, bits: u128) -> bool;
+
+    /// Copies the pixels of row `y` at the columns set in `columns` to their right neighbor,
+    /// then to the row below, for each of them to be a 2x2 square.
+    fn copy_lores(&mut self, y: usize, columns: u128);
+
+    /// Moves the pixels of `planes` up by `scroll` rows, the bottom rows being turned off.
+    fn scroll_up(&mut self, planes: u8, scroll: usize);
+
+    /// Moves the pixels of `planes` down by `scroll` rows, the top rows being turned off.
+    fn scroll_down(&mut self, planes: u8, scroll: usize);
+
+    /// Moves the pixels of `planes` left by `scroll` columns, the right columns being turned off.
+    fn scroll_left(&mut self, planes: u8, scroll: usize);
+
+    /// Moves the pixels of `planes` right by `scroll` columns, the left columns being turned off.
+    fn scroll_right(&mut self, planes: u8, scroll: usize);
+}
+
+/// Returns the bit of column `x` in a row of pixels.
+pub(crate) const fn column_bit(x: usize) -> u128 {
+    1 << (DISPLAY_WIDTH - 1 - x)
+}
+
+// The display buffer storing a byte per pixel, rows being vectors or arrays.
+impl<R: AsRef<[u8]>> DisplayPixels for [R] {
+    #[inline]
+    fn pixel(&self, x: usize, y: usize) -> u8 {
+        self[y].as_ref()[x]
+    }
+}
+
+impl<R: AsRef<[u8]> + AsMut<[u8]>> DisplayStorage for [R] {
+    #[inline]
+    fn set_pixel(&mut self, x: usize, y: usize, value: u8) {
+        self[y].as_mut()[x] = value;
+    }
+
+    fn clear_planes(&mut self, planes: u8) {
+        for row in self.iter_mut() {
+            for pixel in row.as_mut() {
+                *pixel &= !planes;
+            }
+        }
+    }
+
+    fn xor_row(&mut self, y: usize, planes: u8, mut bits: u128) -> bool {
+        let row = self[y].as_mut();
+        let mut colliding = false;
+        while bits != 0 {
+            let x = bits.leading_zeros() as usize;
+            bits &= !column_bit(x);
+            let pixel_before = row[x];
+            row[x] ^= planes;
+            colliding |= pixel_before & planes != 0 && row[x] & planes == 0;
+        }
+        colliding
+    }
+
+    fn copy_lores(&mut self, y: usize, mut columns: u128) {
+        while columns != 0 {
+            let x = columns.leading_zeros() as usize;
+            columns &= !column_bit(x);
+            let pixel = self[y].as_ref()[x];
+            self[y].as_mut()[x + 1] = pixel;
+            self[y + 1].as_mut()[x] = pixel;
+            self[y + 1].as_mut()[x + 1] = pixel;
+        }
+    }
+
+    fn scroll_up(&mut self, planes: u8, scroll: usize) {
+        if planes == ALL_PLANES {
+            self.rotate_left(scroll);
+            // Bottom of screen is black.
+            for black_row in &mut self[(DISPLAY_HEIGHT - scroll)..DISPLAY_HEIGHT] {
+                black_row.as_mut().fill(PIXEL_OFF);
+            }
+            return;
+        }
+        for row in 0..DISPLAY_HEIGHT {
+            for col in 0..DISPLAY_WIDTH {
+                let source = if row + scroll < DISPLAY_HEIGHT {
+                    self[row + scroll].as_ref()[col]
+                } else {
+                    PIXEL_OFF
+                };
+                let pixel = &mut self[row].as_mut()[col];
+                *pixel = (*pixel & !planes) | (source & planes);
+            }
+        }
+    }
+
+    fn scroll_down(&mut self, planes: u8, scroll: usize) {
+        if planes == ALL_PLANES {
+            self.rotate_right(scroll);
+            // Top of screen is black.
+            for black_row in &mut self[0..scroll] {
+                black_row.as_mut().fill(PIXEL_OFF);
+            }
+            return;
+        }
+        for row in (0..DISPLAY_HEIGHT).rev() {
+            for col in 0..DISPLAY_WIDTH {
+                let source = if row >= scroll {
+                    self[row - scroll].as_ref()[col]
+                } else {
+                    PIXEL_OFF
+                };
+                let pixel = &mut self[row].as_mut()[col];
+                *pixel = (*pixel & !planes) | (source & planes);
+            }
+        }
+    }
+
+    fn scroll_left(&mut self, planes: u8, scroll: usize) {
+        for row in self.iter_mut() {
+            let row = row.as_mut();
+            if planes == ALL_PLANES {
+                let scroll = scroll.min(DISPLAY_WIDTH);
+                row.copy_within(scroll.., 0);
+                // Right of screen is black.
+                row[(DISPLAY_WIDTH - scroll)..].fill(PIXEL_OFF);
+                continue;
+            }
+            for col in 0..DISPLAY_WIDTH {
+                let source = row.get(col + scroll).copied().unwrap_or(PIXEL_OFF);
+                row[col] = (row[col] & !planes) | (source & planes);
+            }
+        }
+    }
+
+    fn scroll_right(&mut self, planes: u8, scroll: usize) {
+        for row in self.iter_mut() {
+            let row = row.as_mut();
+            if planes == ALL_PLANES {
+                let scroll = scroll.min(DISPLAY_WIDTH);
+                row.copy_within(..(DISPLAY_WIDTH - scroll), scroll);
+                // Left of screen is black.
+                row[..scroll].fill(PIXEL_OFF);
+                continue;
+            }
+            for col in (0..DISPLAY_WIDTH).rev() {
+                let source = match col.checked_sub(scroll) {
+                    Option::Some(source) => row[source],
+                    Option::None => PIXEL_OFF,
+                };
+                row[col] = (row[col] & !planes) | (source & planes);
+            }
+        }
+    }
+}
+
+/// Implements the display traits for a sized display buffer of a byte per pixel, delegating to its
+/// slice of rows.
+macro_rules! impl_rows_storage {
+    ($buffer:ty) => {
+        impl DisplayPixels for $buffer {
+            #[inline]
+            fn pixel(&self, x: usize, y: usize) -> u8 {
+                self[..].pixel(x, y)
+            }
+        }
+
+        impl DisplayStorage for $buffer {
+            #[inline]
+            fn set_pixel(&mut self, x: usize, y: usize, value: u8) {
+                self[..].set_pixel(x, y, value);
+            }
+
+            fn clear_planes(&mut self, planes: u8) {
+                self[..].clear_planes(planes);
+            }
+
+            fn xor_row(&mut self, y: usize, planes: u8, bits: u128) -> bool {
+                self[..].xor_row(y, planes, bits)
+            }
+
+            fn copy_lores(&mut self, y: usize, columns: u128) {
+                self[..].copy_lores(y, columns);
+            }
+
+            fn scroll_up(&mut self, planes: u8, scroll: usize) {
+                self[..].scroll_up(planes, scroll);
+            }
+
+            fn scroll_down(&mut self, planes: u8, scroll: usize) {
+                self[..].scroll_down(planes, scroll);
+            }
+
+            fn scroll_left(&mut self, planes: u8, scroll: usize) {
+                self[..].scroll_left(planes, scroll);
+            }
+
+            fn scroll_right(&mut self, planes: u8, scroll: usize) {
+                self[..].scroll_right(planes, scroll);
+            }
+        }
+    };
+}
+
+impl_rows_storage!([[u8; DISPLAY_WIDTH]; DISPLAY_HEIGHT]);
+#[cfg(feature = "alloc")]
+impl_rows_storage!(alloc::vec::Vec<alloc::vec::Vec<u8>>);
+
+/// Returns the mutable rows of `pixels`, a byte per pixel stored row after row.
+fn rows_mut(pixels: &mut [u8]) -> &mut [[u8; DISPLAY_WIDTH]] {
+    pixels[..DISPLAY_WIDTH * DISPLAY_HEIGHT].as_chunks_mut().0
+}
+
+// The display buffer borrowed from the caller, storing a byte per pixel row after row.
+impl DisplayPixels for &mut [u8] {
+    #[inline]
+    fn pixel(&self, x: usize, y: usize) -> u8 {
+        self[y * DISPLAY_WIDTH + x]
+    }
+}
+
+impl DisplayStorage for &mut [u8] {
+    #[inline]
+    fn set_pixel(&mut self, x: usize, y: usize, value: u8) {
+        self[y * DISPLAY_WIDTH + x] = value;
+    }
+
+    fn clear_planes(&mut self, planes: u8) {
+        rows_mut(self).clear_planes(planes);
+    }
+
+    fn xor_row(&mut self, y: usize, planes: u8, bits: u128) -> bool {
+        rows_mut(self).xor_row(y, planes, bits)
+    }
+
+    fn copy_lores(&mut self, y: usize, columns: u128) {
+        rows_mut(self).copy_lores(y, columns);
+    }
+
+    fn scroll_up(&mut self, planes: u8, scroll: usize) {
+        rows_mut(self).scroll_up(planes, scroll);
+    }
+
+    fn scroll_down(&mut self, planes: u8, scroll: usize) {
+        rows_mut(self).scroll_down(planes, scroll);
+    }
+
+    fn scroll_left(&mut self, planes: u8, scroll: usize) {
+        rows_mut(self).scroll_left(planes, scroll);
+    }
+
+    fn scroll_right(&mut self, planes: u8, scroll: usize) {
+        rows_mut(self).scroll_right(planes, scroll);
+    }
+}
+
+// Create type alias depending on if the heap is available or not.
+#[cfg(feature = "alloc")]
+type FlatPixels = alloc::vec::Vec<u8>;
+#[cfg(not(feature = "alloc"))]
+type FlatPixels = [u8; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+
+/// Display buffer storing a byte per pixel in a single contiguous block, row after row, each row
+/// starting [FlatDisplay::stride] bytes after the previous one.
+///
+/// This is the [crate::DisplayBuffer] of the emulator unless the `packed_display` feature is
+/// enabled. Its bytes can be uploaded to a texture as is :
+/// ```
+/// let mode = chirp8::Chirp8Mode::CosmacChip8;
+/// let quirks = chirp8::QuirkFlags::from_mode(mode);
+/// let display = chirp8::FlatDisplay::new();
+/// let emulator = chirp8::Chirp8::with_bus_and_display(mode, quirks, [0u8; 0x1000], display);
+/// let display = emulator.get_display_buffer();
+/// let bytes: &[u8] = display.as_bytes();
+/// assert_eq!(bytes.len(), display.stride() * chirp8::DISPLAY_HEIGHT);
+/// assert_eq!(display[3][2], bytes[3 * display.stride() + 2]);
+/// for row in display.rows() {
+///     assert_eq!(row.len(), chirp8::DISPLAY_WIDTH);
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlatDisplay {
+    /// Pixels row after row.
+    pixels: FlatPixels,
+}
+
+impl FlatDisplay {
+    /// Creates a display with all pixels turned off.
+    pub fn new() -> Self {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "alloc")]{
+                let pixels = alloc::vec![PIXEL_OFF; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+            }else{
+                let pixels = [PIXEL_OFF; DISPLAY_WIDTH * DISPLAY_HEIGHT];
+            }
+        }
+
+        Self { pixels }
+    }
+
+    /// Returns the number of bytes from the start of a row to the start of the next one.
+    pub const fn stride(&self) -> usize {
+        DISPLAY_WIDTH
+    }
+
+    /// Returns all pixels, row after row.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.pixels[..]
+    }
+
+    /// Returns all pixels mutably, row after row.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.pixels[..]
+    }
+
+    /// Returns the pixels of row `y`.
+    pub fn row(&self, y: usize) -> &[u8] {
+        let start = y * self.stride();
+        &self.pixels[start..(start + DISPLAY_WIDTH)]
+    }
+
+    /// Returns the pixels of row `y` mutably.
+    pub fn row_mut(&mut self, y: usize) -> &mut [u8] {
+        let start = y * self.stride();
+        &mut self.pixels[start..(start + DISPLAY_WIDTH)]
+    }
+
+    /// Returns the rows of pixels, from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.pixels.chunks_exact(self.stride())
+    }
+
+    /// Returns the rows of pixels mutably, from top to bottom.
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u8]> {
+        let stride = self.stride();
+        self.pixels.chunks_exact_mut(stride)
+    }
+}
+
+impl Default for FlatDisplay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Index<usize> for FlatDisplay {
+    type Output = [u8];
+
+    /// Returns the pixels of row `y`, so that pixels are read with `display[y][x]`.
+    #[inline]
+    fn index(&self, y: usize) -> &[u8] {
+        self.row(y)
+    }
+}
+
+impl IndexMut<usize> for FlatDisplay {
+    #[inline]
+    fn index_mut(&mut self, y: usize) -> &mut [u8] {
+        self.row_mut(y)
+    }
+}
+
+impl DisplayPixels for FlatDisplay {
+    #[inline]
+    fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.pixels[y * self.stride() + x]
+    }
+}
+
+impl DisplayStorage for FlatDisplay {
+    #[inline]
+    fn set_pixel(&mut self, x: usize, y: usize, value: u8) {
+        let stride = self.stride();
+        self.pixels[y * stride + x] = value;
+    }
+
+    fn clear_planes(&mut self, planes: u8) {
+        rows_mut(&mut self.pixels).clear_planes(planes);
+    }
+
+    fn xor_row(&mut self, y: usize, planes: u8, bits: u128) -> bool {
+        rows_mut(&mut self.pixels).xor_row(y, planes, bits)
+    }
+
+    fn copy_lores(&mut self, y: usize, columns: u128) {
+        rows_mut(&mut self.pixels).copy_lores(y, columns);
+    }
+
+    fn scroll_up(&mut self, planes: u8, scroll: usize) {
+        rows_mut(&mut self.pixels).scroll_up(planes, scroll);
+    }
+
+    fn scroll_down(&mut self, planes: u8, scroll: usize) {
+        rows_mut(&mut self.pixels).scroll_down(planes, scroll);
+    }
+
+    fn scroll_left(&mut self, planes: u8, scroll: usize) {
+        rows_mut(&mut self.pixels).scroll_left(planes, scroll);
+    }
+
+    fn scroll_right(&mut self, planes: u8, scroll: usize) {
+        rows_mut(&mut self.pixels).scroll_right(planes, scroll);
+    }
+}
+
+/// Display buffer storing one bit per pixel and per plane, each row of a plane being a `u128`.
+///
+/// Each of the `PLANES` planes stored takes 1KB, from 1 to [MAX_DISPLAY_PLANES], instead of
+/// the 8KB of a byte per pixel. The default [crate::DisplayBuffer] of the `packed_display`
+/// feature stores 4 planes, 4KB, whatever the mode. Monochrome programs only need one, which
+/// must be spelled out and given to [crate::Chirp8::with_bus_and_display] :
+/// ```
+/// let display = chirp8::PackedDisplay::<1>::blank();
+/// let quirks = chirp8::QuirkFlags::from_mode(chirp8::Chirp8Mode::CosmacChip8);
+/// let emulator = chirp8::Chirp8::with_bus_and_display(
+///     chirp8::Chirp8Mode::CosmacChip8, quirks, [0u8; chirp8::MIN_RAM_SIZE], display);
+/// assert!(core::mem::size_of_val(emulator.get_display_buffer()) <= 1024 + 16);
+/// ```
+/// Pixels are read with [DisplayPixels].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackedDisplay<const PLANES: usize = MAX_DISPLAY_PLANES> {
+    /// Rows of each plane, see [DisplayPixels::plane_row]. Planes beyond the count stay off.
+    planes: [[u128; DISPLAY_HEIGHT]; PLANES],
+    /// Number of planes of the pixel values.
+    planes_count: usize,
+}
+
+impl PackedDisplay {
+    /// Creates a display storing 4 planes, using 2 of them, with all pixels turned off.
+    /// This takes 4KB even for monochrome programs, see [PackedDisplay::blank] to store fewer
+    /// planes.
+    pub const fn new() -> Self {
+        Self::blank()
+    }
+}
+
+impl<const PLANES: usize> PackedDisplay<PLANES> {
+    /// Creates a display storing `PLANES` planes, using 2 of them or fewer, with all pixels
+    /// turned off.
+    pub const fn blank() -> Self {
+        const {
+            assert!(PLANES >= 1 && PLANES <= MAX_DISPLAY_PLANES);
+        }
+        Self {
+            planes: [[0; DISPLAY_HEIGHT]; PLANES],
+            planes_count: if PLANES < DISPLAY_PLANES {
+                PLANES
+            } else {
+                DISPLAY_PLANES
+            },
+        }
+    }
+
+    /// Returns the rows of the planes whose bits are set in `planes`.
+    fn selected_planes(&mut self, planes: u8) -> impl Iterator<Item = &mut [u128; DISPLAY_HEIGHT]> {
+        self.planes[..self.planes_count]
+            .iter_mut()
+            .enumerate()
+            .filter(move |(plane, _)| planes & (1 << plane) != 0)
+            .map(|(_, rows)| rows)
+    }
+}
+
+impl<const PLANES: usize> Default for PackedDisplay<PLANES> {
+    fn default() -> Self {
+        Self::blank()
+    }
+}
+
+impl<const PLANES: usize> DisplayPixels for PackedDisplay<PLANES> {
+    #[inline]
+    fn pixel(&self, x: usize, y: usize) -> u8 {
+        let value = self.planes[..self.planes_count]
+            .iter()
+            .enumerate()
+            .fold(0, |value, (plane, rows)| {
+                value | (((rows[y] & column_bit(x) != 0) as u8) << plane)
+            });
+        repeat_bits(value, self.planes_count)
+    }
+
+    #[inline]
+    fn plane_row(&self, plane: usize, y: usize) -> u128 {
+        self.planes[plane][y]
+    }
+}
+
+impl<const PLANES: usize> DisplayStorage for PackedDisplay<PLANES> {
+    fn set_planes_count(&mut self, count: usize) {
+        self.planes_count = count.clamp(1, PLANES);
+        for rows in &mut self.planes[self.planes_count..] {
+            rows.fill(0);
+        }
+    }
+
+    fn max_planes_count(&self) -> usize {
+        PLANES
+    }
+
+    fn set_pixel(&mut self, x: usize, y: usize, value: u8) {
+        for (plane, rows) in self.planes[..self.planes_count].iter_mut().enumerate() {
+            if value & (1 << plane) != 0 {
+                rows[y] |= column_bit(x);
+            } else {
+                rows[y] &= !column_bit(x);
+            }
+        }
+    }
+
+    fn clear_planes(&mut self, planes: u8) {
+        for rows in self.selected_planes(planes) {
+            rows.fill(0);
+        }
+    }
+
+    fn xor_row(&mut self, y: usize, planes: u8, bits: u128) -> bool {
+        let mut lit_before = 0;
+        let mut lit_after = 0;
+        for rows in self.selected_planes(planes) {
+            lit_before |= rows[y];
+            rows[y] ^= bits;
+            lit_after |= rows[y];
+        }
+        lit_before & !lit_after & bits != 0
+    }
+
+    fn copy_lores(&mut self, y: usize, columns: u128) {
+        let square = columns | (columns >> 1);
+        for rows in &mut self.planes {
+            let copied = rows[y] & columns;
+            let copied = copied | (copied >> 1);
+            rows[y] = (rows[y] & !square) | copied;
+            rows[y + 1] = (rows[y + 1] & !square) | copied;
+        }
+    }
+
+    fn scroll_up(&mut self, planes: u8, scroll: usize) {
+        for rows in self.selected_planes(planes) {
+            rows.copy_within(scroll.., 0);
+            rows[(DISPLAY_HEIGHT - scroll)..].fill(0);
+        }
+    }
+
+    fn scroll_down(&mut self, planes: u8, scroll: usize) {
+        for rows in self.selected_planes(planes) {
+            rows.copy_within(..(DISPLAY_HEIGHT - scroll), scroll);
+            rows[..scroll].fill(0);
+        }
+    }
+
+    fn scroll_left(&mut self, planes: u8, scroll: usize) {
+        for rows in self.selected_planes(planes) {
+            for row in rows.iter_mut() {
+                *row = row.checked_shl(scroll as u32).unwrap_or(0);
+            }
+        }
+    }
+
+    fn scroll_right(&mut self, planes: u8, scroll: usize) {
+        for rows in self.selected_planes(planes) {
+            for row in rows.iter_mut() {
+                *row = row.checked_shr(scroll as u32).unwrap_or(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::random::Random;
+    use crate::PIXEL_ON;
+
+    /// Returns a pseudo-random number lower than `bound`.
+    fn below(random: &mut Random, bound: usize) -> usize {
+        (random.next_u64() % bound as u64) as usize
+    }
+
+    /// Returns 128 pseudo-random bits.
+    fn bits(random: &mut Random) -> u128 {
+        ((random.next_u64() as u128) << 64) | random.next_u64() as u128
+    }
+
+    /// Asserts both displays have the same pixels.
+    fn assert_same(bytes: &[[u8; DISPLAY_WIDTH]], packed: &PackedDisplay) {
+        for y in 0..DISPLAY_HEIGHT {
+            for plane in 0..packed.planes_count {
+                assert_eq!(bytes.plane_row(plane, y), packed.plane_row(plane, y));
+            }
+            for x in 0..DISPLAY_WIDTH {
+                assert_eq!(bytes.pixel(x, y), packed.pixel(x, y), "Pixel {} {}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn packed_and_flat_displays_match_bytes() {
+        let mut rng = Random::new(8);
+        for count in [1, 2, 4] {
+            let mut bytes = [[PIXEL_OFF; DISPLAY_WIDTH]; DISPLAY_HEIGHT];
+            let mut packed = PackedDisplay::new();
+            packed.set_planes_count(count);
+            let mut flat = FlatDisplay::new();
+            for _ in 0..1000 {
+                let planes = repeat_bits(1 + below(&mut rng, (1 << count) - 1) as u8, count);
+                let y = below(&mut rng, DISPLAY_HEIGHT - 1);
+                let scroll = below(&mut rng, 16);
+                match below(&mut rng, 8) {
+                    0..=2 => {
+                        let bits = bits(&mut rng) & bits(&mut rng);
+                        let colliding = bytes[..].xor_row(y, planes, bits);
+                        assert_eq!(colliding, packed.xor_row(y, planes, bits));
+                        assert_eq!(colliding, flat.xor_row(y, planes, bits));
+                    }
+                    3 => {
+                        // Even columns only.
+                        let columns = bits(&mut rng) & 0xAAAA_AAAA_AAAA_AAAA_AAAA_AAAA_AAAA_AAAA;
+                        bytes[..].copy_lores(y & !1, columns);
+                        packed.copy_lores(y & !1, columns);
+                        flat.copy_lores(y & !1, columns);
+                    }
+                    4 => {
+                        bytes[..].scroll_up(planes, scroll);
+                        packed.scroll_up(planes, scroll);
+                        flat.scroll_up(planes, scroll);
+                    }
+                    5 => {
+                        bytes[..].scroll_down(planes, scroll);
+                        packed.scroll_down(planes, scroll);
+                        flat.scroll_down(planes, scroll);
+                    }
+                    6 => {
+                        bytes[..].scroll_left(planes, scroll);
+                        packed.scroll_left(planes, scroll);
+                        flat.scroll_left(planes, scroll);
+                    }
+                    _ => {
+                        bytes[..].scroll_right(planes, scroll);
+                        packed.scroll_right(planes, scroll);
+                        flat.scroll_right(planes, scroll);
+                    }
+                }
+                assert_same(&bytes, &packed);
+                assert!(flat.rows().eq(bytes.iter().map(|row| &row[..])));
+                if below(&mut rng, 100) == 0 {
+                    bytes[..].clear_planes(planes);
+                    packed.clear_planes(planes);
+                    flat.clear_planes(planes);
+                    assert_same(&bytes, &packed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn packed_display_pixels() {
+        let mut packed = PackedDisplay::new();
+        packed.set_pixel(3, 5, repeat_bits(0b10, DISPLAY_PLANES));
+        assert_eq!(packed.pixel(3, 5), 0xAA);
+        assert_eq!(packed.plane_row(0, 5), 0);
+        assert_eq!(packed.plane_row(1, 5), column_bit(3));
+        // Pixel 3 stays lit on plane 0, so there is no collision.
+        assert!(!packed.xor_row(5, ALL_PLANES, column_bit(3) | column_bit(4)));
+        assert_eq!(packed.pixel(3, 5), 0x55);
+        assert_eq!(packed.pixel(4, 5), 0xFF);
+        assert!(packed.xor_row(5, 0b01, column_bit(3)));
+        assert_eq!(packed.pixel(3, 5), PIXEL_OFF);
+    }
+
+    #[test]
+    fn packed_display_fewer_planes() {
+        let mut packed = PackedDisplay::<2>::blank();
+        assert_eq!(packed.max_planes_count(), 2);
+        packed.set_planes_count(4);
+        packed.set_pixel(3, 5, 0xFF);
+        assert_eq!(packed.pixel(3, 5), 0xFF);
+        assert_eq!(packed.plane_row(1, 5), column_bit(3));
+
+        let mode = crate::Chirp8Mode::XOChip;
+        let quirks = crate::QuirkFlags::from_mode(mode);
+        let display = PackedDisplay::<1>::blank();
+        let mut emulator =
+            crate::Chirp8::with_bus_and_display(mode, quirks, [0u8; 0x1000], display);
+        assert_eq!(emulator.display_planes(), 1);
+        assert!(!emulator.set_display_planes(2));
+        assert!(emulator.set_display_planes(1));
+    }
+
+    #[test]
+    fn packed_display_size() {
+        let planes_size = |planes| planes * DISPLAY_HEIGHT * core::mem::size_of::<u128>();
+        assert_eq!(planes_size(1), 1024);
+        let size = core::mem::size_of::<PackedDisplay>();
+        let max_size = planes_size(MAX_DISPLAY_PLANES);
+        assert!(size >= max_size && size < max_size + 32);
+        let size = core::mem::size_of::<PackedDisplay<1>>();
+        assert!(size >= planes_size(1) && size < planes_size(1) + 32);
+
+        let mode = crate::Chirp8Mode::CosmacChip8;
+        let quirks = crate::QuirkFlags::from_mode(mode);
+        let display = PackedDisplay::<1>::blank();
+        let emulator = crate::Chirp8::with_bus_and_display(mode, quirks, [0u8; 0x1000], display);
+        assert!(core::mem::size_of_val(emulator.get_display_buffer()) < planes_size(1) + 32);
+    }
+
+    #[test]
+    fn flat_display_rows() {
+        let mut flat = FlatDisplay::new();
+        flat.set_pixel(3, 5, 0xAA);
+        flat.row_mut(6)[4] = 0x55;
+        flat[7][5] = PIXEL_ON;
+        let bytes = flat.as_bytes();
+        assert_eq!(bytes.len(), DISPLAY_WIDTH * DISPLAY_HEIGHT);
+        assert_eq!(bytes[5 * flat.stride() + 3], 0xAA);
+        assert_eq!(flat.pixel(4, 6), 0x55);
+        assert_eq!(flat.row(7)[5], PIXEL_ON);
+        assert_eq!(
+            flat.rows().filter(|row| row.contains(&PIXEL_OFF)).count(),
+            DISPLAY_HEIGHT
+        );
+        for row in flat.rows_mut() {
+            row.fill(PIXEL_ON);
+        }
+        flat.as_bytes_mut()[0] = PIXEL_OFF;
+        assert_eq!(flat.plane_row(1, 0), u128::MAX >> 1);
+    }
+}
@@ -0,0 +1,419 @@
+use core::ffi::c_void;
+use core::mem::{align_of, size_of};
+
+#[cfg(feature = "packed_display")]
+use crate::DisplayPixels;
+use crate::{Chirp8, Chirp8Mode, DISPLAY_HEIGHT, DISPLAY_WIDTH, RAM_SIZE};
+
+/// Original Cosmac VIP chip-8 mode, see [Chirp8Mode::CosmacChip8].
+pub const CHIRP8_MODE_COSMAC_CHIP8: u32 = 0;
+/// Super-Chip 1.1 mode, see [Chirp8Mode::SuperChip1_1].
+pub const CHIRP8_MODE_SUPER_CHIP_1_1: u32 = 1;
+/// Modern Super-Chip mode, see [Chirp8Mode::SuperChipModern].
+pub const CHIRP8_MODE_SUPER_CHIP_MODERN: u32 = 2;
+/// XO-Chip mode, see [Chirp8Mode::XOChip].
+pub const CHIRP8_MODE_XO_CHIP: u32 = 3;
+/// Number of pixels of each row of the display, see [chirp8_display_buffer].
+pub const CHIRP8_DISPLAY_WIDTH: u32 = DISPLAY_WIDTH as u32;
+/// Number of rows of the display, see [chirp8_display_buffer].
+pub const CHIRP8_DISPLAY_HEIGHT: u32 = DISPLAY_HEIGHT as u32;
+
+/// Opaque handle to an emulator, as seen from C.
+///
+/// With the `packed_display` feature, the display is copied to a contiguous buffer when
+/// requested with [chirp8_display_buffer].
+pub struct Chirp8Handle {
+    emulator: Chirp8,
+    #[cfg(feature = "packed_display")]
+    display: [u8; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+}
+
+/// Converts the C mode constants to [Chirp8Mode].
+fn mode_from_c(mode: u32) -> Option<Chirp8Mode> {
+    match mode {
+        CHIRP8_MODE_COSMAC_CHIP8 => Option::Some(Chirp8Mode::CosmacChip8),
+        CHIRP8_MODE_SUPER_CHIP_1_1 => Option::Some(Chirp8Mode::SuperChip1_1),
+        CHIRP8_MODE_SUPER_CHIP_MODERN => Option::Some(Chirp8Mode::SuperChipModern),
+        CHIRP8_MODE_XO_CHIP => Option::Some(Chirp8Mode::XOChip),
+        _ => Option::None,
+    }
+}
+
+impl Chirp8Handle {
+    #[cfg(feature = "alloc")]
+    fn new(mode: Chirp8Mode) -> Self {
+        Self {
+            emulator: Chirp8::new(mode),
+            #[cfg(feature = "packed_display")]
+            display: [0; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+        }
+    }
+}
+
+/// Creates an emulator running in given `mode`, one of the `CHIRP8_MODE_*` constants.
+/// Returns null if the mode is unknown. The emulator must be freed with [chirp8_free].
+#[cfg(feature = "alloc")]
+#[no_mangle]
+pub extern "C" fn chirp8_new(mode: u32) -> *mut Chirp8Handle {
+    match mode_from_c(mode) {
+        Option::Some(mode) => {
+            alloc::boxed::Box::into_raw(alloc::boxed::Box::new(Chirp8Handle::new(mode)))
+        }
+        Option::None => core::ptr::null_mut(),
+    }
+}
+
+/// Frees an emulator created with [chirp8_new]. Does nothing if `handle` is null.
+///
+/// # Safety
+/// `handle` must come from [chirp8_new] and must not be used afterwards.
+#[cfg(feature = "alloc")]
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_free(handle: *mut Chirp8Handle) {
+    if !handle.is_null() {
+        drop(alloc::boxed::Box::from_raw(handle));
+    }
+}
+
+/// Returns the number of bytes needed by [chirp8_init].
+#[no_mangle]
+pub extern "C" fn chirp8_handle_size() -> usize {
+    size_of::<Chirp8Handle>()
+}
+
+/// Returns the alignment needed by [chirp8_init].
+#[no_mangle]
+pub extern "C" fn chirp8_handle_align() -> usize {
+    align_of::<Chirp8Handle>()
+}
+
+/// Creates an emulator running in given `mode` in caller-provided `storage` of `size` bytes,
+/// without allocating. Returns null if the mode is unknown or needs more memory than the default
+/// [crate::Ram], on XO-Chip without `alloc`, or if the storage is too small or misaligned, see
+/// [chirp8_handle_size] and [chirp8_handle_align]. The emulator must be destroyed with
+/// [chirp8_deinit].
+///
+/// # Safety
+/// `storage` must be valid for writes of `size` bytes for as long as the emulator is used.
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_init(
+    storage: *mut c_void,
+    size: usize,
+    mode: u32,
+) -> *mut Chirp8Handle {
+    let handle = storage as *mut Chirp8Handle;
+    match mode_from_c(mode) {
+        Option::Some(mode)
+            if !handle.is_null()
+                && handle.is_aligned()
+                && size >= chirp8_handle_size()
+                && mode.ram_size() <= RAM_SIZE =>
+        {
+            // Fields are written one by one, the handle would not fit on small stacks.
+            core::ptr::addr_of_mut!((*handle).emulator).write(Chirp8::new(mode));
+            #[cfg(feature = "packed_display")]
+            core::ptr::addr_of_mut!((*handle).display).write_bytes(0, 1);
+            handle
+        }
+        _ => core::ptr::null_mut(),
+    }
+}
+
+/// Destroys an emulator created with [chirp8_init], its storage can be reused afterwards.
+/// Does nothing if `handle` is null.
+///
+/// # Safety
+/// `handle` must come from [chirp8_init] and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_deinit(handle: *mut Chirp8Handle) {
+    if !handle.is_null() {
+        core::ptr::drop_in_place(handle);
+    }
+}
+
+/// Loads the `length` bytes long `rom`, see [Chirp8::load_rom].
+/// Returns true if the ROM has been loaded.
+///
+/// # Safety
+/// `handle` must be a valid emulator or null, `rom` must be valid for reads of `length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_load_rom(
+    handle: *mut Chirp8Handle,
+    rom: *const u8,
+    length: usize,
+) -> bool {
+    match handle.as_mut() {
+        Option::Some(handle) if !rom.is_null() => handle
+            .emulator
+            .load_rom(core::slice::from_raw_parts(rom, length)),
+        _ => false,
+    }
+}
+
+/// Resets the emulator to the beginning of the program, see [Chirp8::reset].
+///
+/// # Safety
+/// `handle` must be a valid emulator or null.
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_reset(handle: *mut Chirp8Handle) {
+    if let Option::Some(handle) = handle.as_mut() {
+        handle.emulator.reset();
+    }
+}
+
+/// Presses or releases given `key`, between 0x0 and 0xF, see [Chirp8::key_set].
+///
+/// # Safety
+/// `handle` must be a valid emulator or null.
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_key_set(handle: *mut Chirp8Handle, key: u8, pressed: bool) {
+    if let Option::Some(handle) = handle.as_mut() {
+        handle.emulator.key_set(key, pressed);
+    }
+}
+
+/// Runs as many instructions as necessary to generate a frame, see [Chirp8::run_frame].
+///
+/// # Safety
+/// `handle` must be a valid emulator or null.
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_run_frame(handle: *mut Chirp8Handle) {
+    if let Option::Some(handle) = handle.as_mut() {
+        handle.emulator.run_frame();
+    }
+}
+
+/// Indicates if the display changed since the last call, see [Chirp8::display_changed].
+///
+/// # Safety
+/// `handle` must be a valid emulator or null.
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_display_changed(handle: *mut Chirp8Handle) -> bool {
+    handle
+        .as_mut()
+        .is_some_and(|handle| handle.emulator.display_changed())
+}
+
+/// Returns the display, `CHIRP8_DISPLAY_HEIGHT` rows of `CHIRP8_DISPLAY_WIDTH` pixels, one byte
+/// per pixel. Rows are `*stride` bytes apart. Returns null if `handle` is null.
+///
+/// The buffer is valid until the next call to any function with this handle. It is the display
+/// of the emulator itself, or a copy of it with the `packed_display` feature.
+///
+/// # Safety
+/// `handle` must be a valid emulator or null, `stride` must be valid for writes or null.
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_display_buffer(
+    handle: *mut Chirp8Handle,
+    stride: *mut usize,
+) -> *const u8 {
+    let Option::Some(handle) = handle.as_mut() else {
+        return core::ptr::null();
+    };
+    let display = handle.emulator.get_display_buffer();
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "packed_display")]{
+            for (y, output) in handle.display.chunks_exact_mut(DISPLAY_WIDTH).enumerate() {
+                for (x, pixel) in output.iter_mut().enumerate() {
+                    *pixel = display.pixel(x, y);
+                }
+            }
+            let (pixels, row_stride) = (&handle.display, DISPLAY_WIDTH);
+        }else{
+            let (pixels, row_stride) = (display.as_bytes(), display.stride());
+        }
+    }
+    if let Option::Some(stride) = stride.as_mut() {
+        *stride = row_stride;
+    }
+    pixels.as_ptr()
+}
+
+/// Indicates whether the sound is on, see [Chirp8::is_sounding].
+///
+/// # Safety
+/// `handle` must be a valid emulator or null.
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_is_sounding(handle: *const Chirp8Handle) -> bool {
+    handle
+        .as_ref()
+        .is_some_and(|handle| handle.emulator.is_sounding())
+}
+
+/// Indicates whether the sound is made of the audio buffer, rather than a simple buzzer.
+/// See [Chirp8::has_sound_wave].
+///
+/// # Safety
+/// `handle` must be a valid emulator or null.
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_has_sound_wave(handle: *const Chirp8Handle) -> bool {
+    handle
+        .as_ref()
+        .is_some_and(|handle| handle.emulator.has_sound_wave())
+}
+
+/// Returns the 16 bytes long XO-Chip audio buffer, each bit being played at the rate given by
+/// [chirp8_audio_bit_rate_log2_hz]. Returns null if `handle` is null.
+///
+/// # Safety
+/// `handle` must be a valid emulator or null.
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_audio_buffer(handle: *const Chirp8Handle) -> *const u8 {
+    handle.as_ref().map_or(core::ptr::null(), |handle| {
+        handle.emulator.get_audio_buffer().as_ptr()
+    })
+}
+
+/// Returns the base 2 logarithm of the XO-Chip audio bit rate in Hertz,
+/// see [Chirp8::get_audio_bit_rate_log2_hz].
+///
+/// # Safety
+/// `handle` must be a valid emulator or null.
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_audio_bit_rate_log2_hz(handle: *const Chirp8Handle) -> f32 {
+    handle
+        .as_ref()
+        .map_or(0.0, |handle| handle.emulator.get_audio_bit_rate_log2_hz())
+}
+
+/// Returns the number of bytes of a save state of the emulator, see [Chirp8::state_size].
+/// Returns 0 if `handle` is null.
+///
+/// # Safety
+/// `handle` must be a valid emulator or null.
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_state_size(handle: *const Chirp8Handle) -> usize {
+    handle
+        .as_ref()
+        .map_or(0, |handle| handle.emulator.state_size())
+}
+
+/// Saves the emulator state to `state`, `length` bytes long, see [Chirp8::save_state].
+/// Returns true on success, false if the state is too short.
+///
+/// # Safety
+/// `handle` must be a valid emulator or null, `state` must be valid for writes of `length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_save_state(
+    handle: *mut Chirp8Handle,
+    state: *mut u8,
+    length: usize,
+) -> bool {
+    match handle.as_ref() {
+        Option::Some(handle) if !state.is_null() => handle
+            .emulator
+            .save_state(core::slice::from_raw_parts_mut(state, length)),
+        _ => false,
+    }
+}
+
+/// Restores the emulator state from `state`, `length` bytes long, see [Chirp8::load_state].
+/// Returns true on success, false if the state is invalid.
+///
+/// # Safety
+/// `handle` must be a valid emulator or null, `state` must be valid for reads of `length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chirp8_load_state(
+    handle: *mut Chirp8Handle,
+    state: *const u8,
+    length: usize,
+) -> bool {
+    match handle.as_mut() {
+        Option::Some(handle) if !state.is_null() => handle
+            .emulator
+            .load_state(core::slice::from_raw_parts(state, length)),
+        _ => false,
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ffi_round_trip() {
+        let rom = [0x60, 0x05, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x06];
+        unsafe {
+            assert!(chirp8_new(42).is_null());
+            let handle = chirp8_new(CHIRP8_MODE_COSMAC_CHIP8);
+            assert!(chirp8_load_rom(handle, rom.as_ptr(), rom.len()));
+            let mut state = std::vec![0u8; chirp8_state_size(handle)];
+            assert!(chirp8_save_state(handle, state.as_mut_ptr(), state.len()));
+
+            chirp8_key_set(handle, 1, true);
+            chirp8_run_frame(handle);
+            chirp8_run_frame(handle);
+            assert!(chirp8_display_changed(handle));
+            let mut stride = 0;
+            let display = chirp8_display_buffer(handle, &mut stride);
+            assert_eq!(stride, DISPLAY_WIDTH);
+            #[cfg(not(feature = "packed_display"))]
+            {
+                // The display of the emulator is not copied.
+                let pixels = (*handle).emulator.get_display_buffer().as_bytes();
+                assert_eq!(display, pixels.as_ptr());
+                let shadow = DISPLAY_WIDTH * DISPLAY_HEIGHT;
+                assert!(chirp8_handle_size() < size_of::<Chirp8>() + shadow);
+            }
+            // Top-left pixel of the "5" digit.
+            assert_eq!(*display, 0xFF);
+            assert!(!chirp8_is_sounding(handle));
+            assert!(!chirp8_audio_buffer(handle).is_null());
+
+            // The storage of the second emulator is provided by the caller.
+            let mut storage =
+                std::vec![0u64; chirp8_handle_size().div_ceil(core::mem::size_of::<u64>())];
+            let size = storage.len() * core::mem::size_of::<u64>();
+            let storage = storage.as_mut_ptr() as *mut c_void;
+            assert!(chirp8_init(storage, size - 1, CHIRP8_MODE_XO_CHIP).is_null());
+            let other = chirp8_init(storage, size, CHIRP8_MODE_XO_CHIP);
+            assert!(chirp8_load_state(other, state.as_ptr(), state.len()));
+            assert!(!chirp8_load_state(other, state.as_ptr(), state.len() - 1));
+            chirp8_run_frame(other);
+            chirp8_run_frame(other);
+            let other_display = chirp8_display_buffer(other, core::ptr::null_mut());
+            assert_eq!(
+                core::slice::from_raw_parts(display, DISPLAY_WIDTH * DISPLAY_HEIGHT),
+                core::slice::from_raw_parts(other_display, DISPLAY_WIDTH * DISPLAY_HEIGHT)
+            );
+            chirp8_deinit(other);
+            chirp8_free(handle);
+
+            chirp8_run_frame(core::ptr::null_mut());
+            assert!(chirp8_display_buffer(core::ptr::null_mut(), &mut stride).is_null());
+        }
+    }
+
+    #[test]
+    fn ffi_header_declares_all_functions() {
+        let header = include_str!("../include/chirp8.h");
+        let source = include_str!("ffi.rs");
+        let mut count = 0;
+        for line in source.lines() {
+            if let Option::Some(start) = line.find("extern \"C\" fn chirp8_") {
+                let name = &line[start + "extern \"C\" fn ".len()..];
+                let name = &name[..name.find('(').unwrap()];
+                assert!(header.contains(&std::format!("{}(", name)), "{}", name);
+                count += 1;
+            }
+        }
+        assert_eq!(count, 19);
+        for (name, value) in [
+            ("CHIRP8_DISPLAY_WIDTH", DISPLAY_WIDTH as u32),
+            ("CHIRP8_DISPLAY_HEIGHT", DISPLAY_HEIGHT as u32),
+            ("CHIRP8_MODE_COSMAC_CHIP8", CHIRP8_MODE_COSMAC_CHIP8),
+            ("CHIRP8_MODE_SUPER_CHIP_1_1", CHIRP8_MODE_SUPER_CHIP_1_1),
+            (
+                "CHIRP8_MODE_SUPER_CHIP_MODERN",
+                CHIRP8_MODE_SUPER_CHIP_MODERN,
+            ),
+            ("CHIRP8_MODE_XO_CHIP", CHIRP8_MODE_XO_CHIP),
+        ] {
+            assert!(
+                header.contains(&std::format!("#define {} {}", name, value)),
+                "{}",
+                name
+            );
+        }
+    }
+}
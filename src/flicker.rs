@@ -0,0 +1,198 @@
+use crate::{DisplayPixels, DISPLAY_HEIGHT, DISPLAY_WIDTH, PIXEL_OFF};
+
+/// Number of pixels of the display buffer.
+const PIXELS_COUNT: usize = DISPLAY_WIDTH * DISPLAY_HEIGHT;
+/// Largest number of frames a [FlickerMode::Or] or [FlickerMode::Blend] combines.
+pub const MAX_FLICKER_FRAMES: u8 = 8;
+
+// Create type alias depending on if the heap is available or not.
+#[cfg(feature = "alloc")]
+type PixelsData = alloc::vec::Vec<u8>;
+#[cfg(not(feature = "alloc"))]
+type PixelsData = [u8; PIXELS_COUNT];
+
+/// How a [FlickerFilter] combines the successive frames of the display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlickerMode {
+    /// A pixel is fully bright when lit in any of the last `frames` frames, between 1 and
+    /// [MAX_FLICKER_FRAMES].
+    Or {
+        /// Number of frames combined.
+        frames: u8,
+    },
+    /// A pixel is as bright as the proportion of the last `frames` frames it was lit in, between 1
+    /// and [MAX_FLICKER_FRAMES].
+    Blend {
+        /// Number of frames combined.
+        frames: u8,
+    },
+    /// A lit pixel is fully bright, then its brightness is multiplied by `decay / 256` every
+    /// frame it stays off, like the phosphor of a CRT.
+    Phosphor {
+        /// Brightness kept every frame, out of 256.
+        decay: u8,
+    },
+}
+
+/// Opt-in post-processing of the display reducing the flicker of sprites erased and drawn again
+/// every frame, by combining the last frames of the display.
+///
+/// Fed with the display buffer after every frame, the filter gives the brightness of each pixel,
+/// from 0 when off to 255 when lit, pixels lit on any plane being lit.
+/// ```
+/// let mut emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+/// let mut filter = chirp8::FlickerFilter::new(chirp8::FlickerMode::Phosphor { decay: 128 });
+/// emulator.load_rom(&[0xF0, 0x29, 0xD0, 0x05, 0xD0, 0x05]); // Draw then erase the font sprite of 0.
+/// emulator.take_steps(2);
+/// filter.update(emulator.get_display_buffer());
+/// emulator.take_steps(1);
+/// filter.update(emulator.get_display_buffer());
+/// assert_eq!(filter.intensity(0, 0), 127);
+/// ```
+pub struct FlickerFilter {
+    /// How the frames are combined.
+    mode: FlickerMode,
+    /// For each pixel, bit `n` is set when the pixel was lit `n` frames ago.
+    history: PixelsData,
+    /// Brightness of each pixel, row after row.
+    intensities: PixelsData,
+}
+
+impl FlickerFilter {
+    /// Creates a filter combining frames according to given `mode`, all pixels being off.
+    pub fn new(mode: FlickerMode) -> Self {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "alloc")]{
+                let history = alloc::vec![0; PIXELS_COUNT];
+                let intensities = alloc::vec![0; PIXELS_COUNT];
+            }else{
+                let history = [0; PIXELS_COUNT];
+                let intensities = [0; PIXELS_COUNT];
+            }
+        }
+
+        Self {
+            mode,
+            history,
+            intensities,
+        }
+    }
+
+    /// Returns how the frames are combined.
+    pub fn mode(&self) -> FlickerMode {
+        self.mode
+    }
+
+    /// Changes how the frames are combined, keeping the previous frames.
+    pub fn set_mode(&mut self, mode: FlickerMode) {
+        self.mode = mode;
+    }
+
+    /// Turns all pixels off and forgets the previous frames.
+    pub fn clear(&mut self) {
+        self.history.fill(0);
+        self.intensities.fill(0);
+    }
+
+    /// Adds a frame of the `display`, usually [crate::Chirp8::get_display_buffer] after each
+    /// [crate::Chirp8::run_frame], and updates the brightness of all pixels.
+    pub fn update<P: DisplayPixels + ?Sized>(&mut self, display: &P) {
+        let pixels = self.history.iter_mut().zip(self.intensities.iter_mut());
+        for (index, (history, intensity)) in pixels.enumerate() {
+            let lit = display.pixel(index % DISPLAY_WIDTH, index / DISPLAY_WIDTH) != PIXEL_OFF;
+            *history = (*history << 1) | lit as u8;
+            *intensity = match self.mode {
+                FlickerMode::Or { frames } => {
+                    if *history & frames_mask(frames) != 0 {
+                        u8::MAX
+                    } else {
+                        0
+                    }
+                }
+                FlickerMode::Blend { frames } => {
+                    let frames = frames.clamp(1, MAX_FLICKER_FRAMES);
+                    let lit_frames = (*history & frames_mask(frames)).count_ones();
+                    (lit_frames * u8::MAX as u32 / frames as u32) as u8
+                }
+                FlickerMode::Phosphor { decay } => {
+                    if lit {
+                        u8::MAX
+                    } else {
+                        (*intensity as u32 * decay as u32 / 256) as u8
+                    }
+                }
+            };
+        }
+    }
+
+    /// Returns the brightness of the pixel at column `x` and row `y` of the display buffer.
+    pub fn intensity(&self, x: usize, y: usize) -> u8 {
+        self.intensities[y * DISPLAY_WIDTH + x]
+    }
+
+    /// Returns the brightness of all pixels of the display buffer, row after row.
+    pub fn intensities(&self) -> &[u8] {
+        &self.intensities[..]
+    }
+}
+
+/// Returns the mask of the history bits of the last `frames` frames.
+const fn frames_mask(frames: u8) -> u8 {
+    let frames = if frames > MAX_FLICKER_FRAMES {
+        MAX_FLICKER_FRAMES
+    } else {
+        frames
+    };
+    (u8::MAX as u16 >> (MAX_FLICKER_FRAMES - frames)) as u8 | 1
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PackedDisplay;
+    use crate::{DisplayStorage, PIXEL_ON};
+
+    /// Feeds the `filter` with frames where the pixel 0, 0 is lit according to `frames`,
+    /// returns the brightness of this pixel after each frame.
+    fn intensities<const N: usize>(filter: &mut FlickerFilter, frames: [bool; N]) -> [u8; N] {
+        let mut display = PackedDisplay::new();
+        frames.map(|lit| {
+            display.set_pixel(0, 0, if lit { PIXEL_ON } else { PIXEL_OFF });
+            filter.update(&display);
+            filter.intensity(0, 0)
+        })
+    }
+
+    #[test]
+    fn flicker_or_and_blend() {
+        let frames = [true, false, false, true, false, true, true, false];
+        let mut filter = FlickerFilter::new(FlickerMode::Or { frames: 2 });
+        assert_eq!(
+            intensities(&mut filter, frames),
+            [255, 255, 0, 255, 255, 255, 255, 255]
+        );
+
+        let mut filter = FlickerFilter::new(FlickerMode::Blend { frames: 4 });
+        assert_eq!(
+            intensities(&mut filter, frames),
+            [63, 63, 63, 127, 63, 127, 191, 127]
+        );
+        assert_eq!(filter.intensities().len(), DISPLAY_WIDTH * DISPLAY_HEIGHT);
+        assert_eq!(filter.intensity(1, 0), 0);
+
+        filter.clear();
+        filter.set_mode(FlickerMode::Or { frames: 0 });
+        assert_eq!(intensities(&mut filter, [true, false]), [255, 0]);
+    }
+
+    #[test]
+    fn flicker_phosphor() {
+        let mut filter = FlickerFilter::new(FlickerMode::Phosphor { decay: 128 });
+        assert_eq!(
+            intensities(&mut filter, [true, false, false, true, false]),
+            [255, 127, 63, 255, 127]
+        );
+        let mut filter = FlickerFilter::new(FlickerMode::Phosphor { decay: 0 });
+        assert_eq!(intensities(&mut filter, [true, false]), [255, 0]);
+    }
+}
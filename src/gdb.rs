@@ -0,0 +1,461 @@
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use std::io;
+use std::net::TcpStream;
+use std::vec::Vec;
+
+use gdbstub::arch::{Arch, Registers};
+use gdbstub::common::Signal;
+use gdbstub::conn::ConnectionExt;
+use gdbstub::stub::run_blocking::{BlockingEventLoop, Event, WaitForStopReasonError};
+use gdbstub::stub::{DisconnectReason, GdbStub, GdbStubError, SingleThreadStopReason};
+use gdbstub::target::ext::base::singlethread::{
+    SingleThreadBase, SingleThreadResume, SingleThreadResumeOps, SingleThreadSingleStep,
+    SingleThreadSingleStepOps,
+};
+use gdbstub::target::ext::base::BaseOps;
+use gdbstub::target::ext::breakpoints::{
+    Breakpoints, BreakpointsOps, SwBreakpoint, SwBreakpointOps,
+};
+use gdbstub::target::{Target, TargetResult};
+
+use crate::chirp8::REGISTERS_COUNT;
+use crate::{Bus, Chirp8, Ram};
+
+/// Number of steps taken between two checks for incoming data from GDB while running.
+const STEPS_BETWEEN_POLLS: usize = 1024;
+
+/// Registers description sent to GDB. 16 bits registers are little endian.
+const TARGET_DESCRIPTION: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chirp8.cpu">
+    <reg name="v0" bitsize="8" type="uint8" regnum="0"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="dt" bitsize="8" type="uint8"/>
+    <reg name="st" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+/// The CHIP-8 architecture as seen by GDB : 16 bits addresses, registers described by
+/// [Chirp8Registers].
+pub enum Chirp8Arch {}
+
+impl Arch for Chirp8Arch {
+    type Usize = u16;
+    type Registers = Chirp8Registers;
+    type BreakpointKind = usize;
+    type RegId = ();
+
+    fn target_description_xml() -> Option<&'static str> {
+        Some(TARGET_DESCRIPTION)
+    }
+}
+
+/// The registers of the emulator, in the order GDB sees them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Chirp8Registers {
+    /// V0 to VF.
+    pub v: [u8; REGISTERS_COUNT],
+    /// Index register, "I".
+    pub i: u16,
+    /// Program counter.
+    pub pc: u16,
+    /// Number of return addresses on the stack.
+    pub sp: u8,
+    /// Delay timer.
+    pub delay_timer: u8,
+    /// Sound timer.
+    pub sound_timer: u8,
+}
+
+/// Size of the registers once serialized.
+const REGISTERS_SIZE: usize = REGISTERS_COUNT + 2 + 2 + 1 + 1 + 1;
+
+impl Registers for Chirp8Registers {
+    type ProgramCounter = u16;
+
+    fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    fn gdb_serialize(&self, mut write_byte: impl FnMut(Option<u8>)) {
+        self.v
+            .iter()
+            .copied()
+            .chain(self.i.to_le_bytes())
+            .chain(self.pc.to_le_bytes())
+            .chain([self.sp, self.delay_timer, self.sound_timer])
+            .for_each(|byte| write_byte(Some(byte)));
+    }
+
+    fn gdb_deserialize(&mut self, bytes: &[u8]) -> Result<(), ()> {
+        if bytes.len() != REGISTERS_SIZE {
+            return Err(());
+        }
+        let (v, rest) = bytes.split_at(REGISTERS_COUNT);
+        self.v.copy_from_slice(v);
+        self.i = u16::from_le_bytes([rest[0], rest[1]]);
+        self.pc = u16::from_le_bytes([rest[2], rest[3]]);
+        self.sp = rest[4];
+        self.delay_timer = rest[5];
+        self.sound_timer = rest[6];
+        Ok(())
+    }
+}
+
+/// How execution resumes once GDB lets the emulator run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Resume {
+    Continue,
+    Step,
+}
+
+/// Serves the GDB remote serial protocol for an emulator, so that ROMs can be debugged with GDB
+/// or any debugger front-end speaking the protocol.
+///
+/// Registers V0 to VF, I, PC, SP (the stack depth) and both timers are exposed, as well as the
+/// whole RAM. Software breakpoints, single-stepping and continuing are supported, and GDB can
+/// interrupt a running emulator.
+/// ```no_run
+/// let mut emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+/// emulator.load_rom(&[0x12, 0x00]);
+///
+/// // Then run `target remote localhost:9000` from GDB.
+/// let listener = std::net::TcpListener::bind("localhost:9000").unwrap();
+/// let (stream, _) = listener.accept().unwrap();
+/// chirp8::GdbServer::new(&mut emulator).serve(stream).unwrap();
+/// ```
+pub struct GdbServer<'a, B: Bus = Ram> {
+    emulator: &'a mut Chirp8<B>,
+    /// Addresses of the software breakpoints.
+    breakpoints: Vec<u16>,
+    /// Set when GDB resumes execution.
+    resume: Option<Resume>,
+}
+
+/// Error raised when serving GDB, only because of the connection or the protocol.
+pub type GdbError = GdbStubError<Infallible, io::Error>;
+
+impl<'a, B: Bus> GdbServer<'a, B> {
+    /// Prepares to debug the `emulator`, which is left in its current state.
+    pub fn new(emulator: &'a mut Chirp8<B>) -> Self {
+        Self {
+            emulator,
+            breakpoints: Vec::new(),
+            resume: Option::None,
+        }
+    }
+
+    /// Returns the addresses of the breakpoints set by GDB.
+    pub fn breakpoints(&self) -> &[u16] {
+        &self.breakpoints
+    }
+
+    /// Serves GDB connected with `stream` until it detaches or kills the program.
+    pub fn serve(&mut self, stream: TcpStream) -> Result<DisconnectReason, GdbError> {
+        GdbStub::new(stream).run_blocking::<EventLoop<'a, B>>(self)
+    }
+
+    /// Runs the emulator as requested by GDB, until it stops or `interrupted` returns true.
+    fn run(
+        &mut self,
+        mut interrupted: impl FnMut() -> bool,
+    ) -> Option<SingleThreadStopReason<u16>> {
+        match self.resume.take()? {
+            Resume::Step => {
+                self.emulator.step();
+                Some(match self.emulator.is_halted() {
+                    true => SingleThreadStopReason::Signal(Signal::SIGSEGV),
+                    false => SingleThreadStopReason::DoneStep,
+                })
+            }
+            Resume::Continue => loop {
+                for _ in 0..STEPS_BETWEEN_POLLS {
+                    self.emulator.step();
+                    if let Some(reason) = self.stop_reason() {
+                        return Some(reason);
+                    }
+                }
+                if interrupted() {
+                    self.resume = Some(Resume::Continue);
+                    return None;
+                }
+            },
+        }
+    }
+
+    /// Returns why the emulator must stop after a step, if it must.
+    fn stop_reason(&self) -> Option<SingleThreadStopReason<u16>> {
+        if self.emulator.is_halted() {
+            // A write monitor caught an invalid write.
+            Some(SingleThreadStopReason::Signal(Signal::SIGSEGV))
+        } else if self.breakpoints.contains(&self.emulator.pc()) {
+            Some(SingleThreadStopReason::SwBreak(()))
+        } else {
+            None
+        }
+    }
+}
+
+impl<B: Bus> Target for GdbServer<'_, B> {
+    type Arch = Chirp8Arch;
+    type Error = Infallible;
+
+    fn base_ops(&mut self) -> BaseOps<'_, Chirp8Arch, Infallible> {
+        BaseOps::SingleThread(self)
+    }
+
+    fn support_breakpoints(&mut self) -> Option<BreakpointsOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl<B: Bus> SingleThreadBase for GdbServer<'_, B> {
+    fn read_registers(&mut self, registers: &mut Chirp8Registers) -> TargetResult<(), Self> {
+        self.emulator.gdb_read_registers(registers);
+        Ok(())
+    }
+
+    fn write_registers(&mut self, registers: &Chirp8Registers) -> TargetResult<(), Self> {
+        self.emulator.gdb_write_registers(registers);
+        Ok(())
+    }
+
+    fn read_addrs(&mut self, start_addr: u16, data: &mut [u8]) -> TargetResult<usize, Self> {
+        let length = data
+            .len()
+            .min(self.emulator.ram_size().saturating_sub(start_addr as usize));
+        for (address, byte) in (start_addr..).zip(&mut data[..length]) {
+            *byte = self.emulator.gdb_read(address);
+        }
+        Ok(length)
+    }
+
+    fn write_addrs(&mut self, start_addr: u16, data: &[u8]) -> TargetResult<(), Self> {
+        if start_addr as usize + data.len() > self.emulator.ram_size() {
+            return Err(gdbstub::target::TargetError::NonFatal);
+        }
+        self.emulator.bus_mut().write_block(start_addr, data);
+        Ok(())
+    }
+
+    fn support_resume(&mut self) -> Option<SingleThreadResumeOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl<B: Bus> SingleThreadResume for GdbServer<'_, B> {
+    fn resume(&mut self, _signal: Option<Signal>) -> Result<(), Infallible> {
+        self.resume = Some(Resume::Continue);
+        Ok(())
+    }
+
+    fn support_single_step(&mut self) -> Option<SingleThreadSingleStepOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl<B: Bus> SingleThreadSingleStep for GdbServer<'_, B> {
+    fn step(&mut self, _signal: Option<Signal>) -> Result<(), Infallible> {
+        self.resume = Some(Resume::Step);
+        Ok(())
+    }
+}
+
+impl<B: Bus> Breakpoints for GdbServer<'_, B> {
+    fn support_sw_breakpoint(&mut self) -> Option<SwBreakpointOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl<B: Bus> SwBreakpoint for GdbServer<'_, B> {
+    fn add_sw_breakpoint(&mut self, addr: u16, _kind: usize) -> TargetResult<bool, Self> {
+        if !self.breakpoints.contains(&addr) {
+            self.breakpoints.push(addr);
+        }
+        Ok(true)
+    }
+
+    fn remove_sw_breakpoint(&mut self, addr: u16, _kind: usize) -> TargetResult<bool, Self> {
+        let count = self.breakpoints.len();
+        self.breakpoints.retain(|breakpoint| *breakpoint != addr);
+        Ok(self.breakpoints.len() != count)
+    }
+}
+
+/// Runs the emulator in the thread serving GDB, polling the connection every few steps.
+struct EventLoop<'a, B: Bus>(PhantomData<&'a mut B>);
+
+impl<'a, B: Bus> BlockingEventLoop for EventLoop<'a, B> {
+    type Target = GdbServer<'a, B>;
+    type Connection = TcpStream;
+    type StopReason = SingleThreadStopReason<u16>;
+
+    fn wait_for_stop_reason(
+        target: &mut GdbServer<'a, B>,
+        connection: &mut TcpStream,
+    ) -> Result<Event<Self::StopReason>, WaitForStopReasonError<Infallible, io::Error>> {
+        let stop = target.run(|| !matches!(connection.peek(), Ok(Option::None)));
+        match stop {
+            Some(reason) => Ok(Event::TargetStopped(reason)),
+            None => {
+                let byte =
+                    ConnectionExt::read(connection).map_err(WaitForStopReasonError::Connection)?;
+                Ok(Event::IncomingData(byte))
+            }
+        }
+    }
+
+    fn on_interrupt(
+        _target: &mut GdbServer<'a, B>,
+    ) -> Result<Option<Self::StopReason>, Infallible> {
+        Ok(Some(SingleThreadStopReason::Signal(Signal::SIGINT)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::string::String;
+
+    use super::*;
+    use crate::Chirp8Mode;
+
+    /// Minimal GDB client, acknowledging every packet.
+    struct Client {
+        stream: TcpStream,
+    }
+
+    impl Client {
+        fn byte(&mut self) -> u8 {
+            let mut byte = [0];
+            self.stream.read_exact(&mut byte).unwrap();
+            byte[0]
+        }
+
+        /// Sends a `command` and returns the answer.
+        fn request(&mut self, command: &str) -> String {
+            let checksum = command
+                .bytes()
+                .fold(0u8, |sum, byte| sum.wrapping_add(byte));
+            let packet = std::format!("${}#{:02x}", command, checksum);
+            self.stream.write_all(packet.as_bytes()).unwrap();
+            assert_eq!(self.byte(), b'+');
+            self.answer()
+        }
+
+        /// Waits for the next packet from the stub and decodes it.
+        fn answer(&mut self) -> String {
+            while self.byte() != b'$' {}
+            let mut answer = String::new();
+            loop {
+                match self.byte() {
+                    b'#' => break,
+                    // Run-length encoding of the previous character.
+                    b'*' => {
+                        let previous = answer.chars().last().unwrap();
+                        let count = self.byte() - 29;
+                        answer.extend(core::iter::repeat_n(previous, count as usize));
+                    }
+                    byte => answer.push(byte as char),
+                }
+            }
+            self.byte();
+            self.byte();
+            self.stream.write_all(b"+").unwrap();
+            answer
+        }
+    }
+
+    #[test]
+    fn gdb_session() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x12, // 200 : v0 = 0x12
+            0xA3, 0x00, // 202 : I = 0x300
+            0x70, 0x01, // 204 : v0 += 1
+            0x22, 0x0A, // 206 : call 20A
+            0x12, 0x04, // 208 : jump 204
+            0x00, 0xEE, // 20A : return
+        ];
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut emulator = Chirp8::new(Chirp8Mode::CosmacChip8);
+            emulator.load_rom(&rom);
+            let (stream, _) = listener.accept().unwrap();
+            let reason = GdbServer::new(&mut emulator).serve(stream).unwrap();
+            assert!(matches!(reason, DisconnectReason::Kill));
+            emulator
+        });
+        let mut client = Client {
+            stream: TcpStream::connect(address).unwrap(),
+        };
+
+        assert!(client
+            .request("qSupported:swbreak+")
+            .contains("qXfer:features:read+"));
+        assert!(client
+            .request("qXfer:features:read:target.xml:0,fff")
+            .contains("org.chirp8.cpu"));
+
+        // Step twice, then read v0, I and PC.
+        assert!(client.request("s")[1..3] == *"05");
+        assert!(client.request("s")[1..3] == *"05");
+        let registers = client.request("g");
+        assert_eq!(&registers[..2], "12");
+        assert_eq!(&registers[32..40], "00030402");
+
+        // Continue to the subroutine, inside of which SP is 1.
+        assert_eq!(client.request("Z0,20a,2"), "OK");
+        assert!(client.request("c")[1..3] == *"05");
+        let registers = client.request("g");
+        assert_eq!(&registers[..2], "13");
+        assert_eq!(&registers[36..42], "0a0201");
+        assert!(client.request("c")[1..3] == *"05");
+        assert_eq!(&client.request("g")[..2], "14");
+        assert_eq!(client.request("z0,20a,2"), "OK");
+
+        // Modify registers and memory.
+        let mut registers = client.request("g");
+        registers.replace_range(2..4, "ab");
+        assert_eq!(client.request(&std::format!("G{}", registers)), "OK");
+        assert_eq!(client.request("M300,3:010203"), "OK");
+        assert_eq!(client.request("m2fe,6"), "000001020300");
+        assert_eq!(client.request("m200,2"), "6012");
+
+        // Interrupt a running program.
+        client.stream.write_all(b"$c#63").unwrap();
+        assert_eq!(client.byte(), b'+');
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        client.stream.write_all(&[0x03]).unwrap();
+        assert!(client.answer()[1..3] == *"02");
+
+        client.stream.write_all(b"$k#6b").unwrap();
+        let emulator = server.join().unwrap();
+        assert_eq!(emulator.registers()[1], 0xAB);
+        let mut memory = [0; 3];
+        emulator.bus().clone().read_block(0x300, &mut memory);
+        assert_eq!(memory, [1, 2, 3]);
+    }
+}
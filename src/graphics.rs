@@ -0,0 +1,254 @@
+use embedded_graphics_core::draw_target::DrawTarget;
+use embedded_graphics_core::geometry::{Dimensions, OriginDimensions, Point, Size};
+use embedded_graphics_core::image::{GetPixel, ImageDrawable};
+use embedded_graphics_core::pixelcolor::PixelColor;
+use embedded_graphics_core::primitives::Rectangle;
+
+use crate::{Bus, Chirp8, DisplayBuffer, DisplayPixels, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+/// The display of an emulator, to be drawn with `embedded-graphics` on any [DrawTarget].
+///
+/// In low resolution, every 2x2 pixels of the display buffer are collapsed into a single pixel,
+/// the image being 64x32 instead of 128x64. The image can be scaled up by an integer factor, and
+/// its colors are picked from a palette of 4 or 16 colors like [crate::Palette], the color `n`
+/// being used for the pixels lit on the planes set in `n`.
+/// ```
+/// use embedded_graphics::{image::Image, mock_display::MockDisplay, pixelcolor::BinaryColor, prelude::*};
+///
+/// let mut emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+/// emulator.load_rom(&[0xF0, 0x29, 0xD0, 0x05]); // Draw the font sprite of 0.
+/// emulator.take_steps(2);
+///
+/// let mut display = MockDisplay::new();
+/// let image = chirp8::DisplayImage::monochrome(&emulator, BinaryColor::Off, BinaryColor::On);
+/// Image::new(&image, Point::zero()).draw(&mut display).unwrap();
+/// assert_eq!(display.get_pixel(Point::new(0, 0)), Some(BinaryColor::On));
+/// assert_eq!(display.get_pixel(Point::new(1, 1)), Some(BinaryColor::Off));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayImage<'a, C: PixelColor> {
+    buffer: &'a DisplayBuffer,
+    /// Size of a pixel of the image in pixels of the display buffer, 1 or 2.
+    collapse: usize,
+    /// Colors of the pixel values of the display buffer, indexed by their lowest 4 bits.
+    palette: [C; 16],
+    scale: u32,
+}
+
+impl<'a, C: PixelColor> DisplayImage<'a, C> {
+    /// Creates an image of the current display of the `emulator`, with a `palette` giving the
+    /// color of pixels off, lit on the first plane, on the second plane and on both planes.
+    /// With 4 planes, the third and fourth planes have the colors of the first and second ones.
+    pub fn new<B: Bus>(emulator: &'a Chirp8<B>, palette: [C; 4]) -> Self {
+        let palette = core::array::from_fn(|planes| palette[(planes & 0b11) | (planes >> 2)]);
+        Self::new_16(emulator, palette)
+    }
+
+    /// Creates an image of the current display of the `emulator`, with a `palette` of 16 colors
+    /// for 4 planes, the color `n` being used for the pixels lit on the planes set in `n`.
+    pub fn new_16<B: Bus>(emulator: &'a Chirp8<B>, palette: [C; 16]) -> Self {
+        // Pixel values repeat their plane bits over the whole byte.
+        let mask = (1 << emulator.display_planes().min(4)) - 1;
+        Self {
+            buffer: emulator.get_display_buffer(),
+            collapse: if emulator.is_high_resolution() { 1 } else { 2 },
+            palette: core::array::from_fn(|value| palette[value & mask]),
+            scale: 1,
+        }
+    }
+
+    /// Creates an image of the current display of the `emulator` with only two colors,
+    /// pixels being `on` when lit on any plane.
+    pub fn monochrome<B: Bus>(emulator: &'a Chirp8<B>, off: C, on: C) -> Self {
+        Self::new(emulator, [off, on, on, on])
+    }
+
+    /// Returns the image scaled up by given integer factor, at least 1.
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        self.scale = scale.max(1);
+        self
+    }
+
+    /// Returns the color of the pixel of the image at `x`, `y`, which must be in bounds.
+    fn color(&self, x: u32, y: u32) -> C {
+        let column = (x / self.scale) as usize * self.collapse;
+        let row = (y / self.scale) as usize * self.collapse;
+        let pixel = self.buffer.pixel(column, row);
+        self.palette[(pixel & 0b1111) as usize]
+    }
+}
+
+impl<C: PixelColor> OriginDimensions for DisplayImage<'_, C> {
+    fn size(&self) -> Size {
+        Size::new(
+            (DISPLAY_WIDTH / self.collapse) as u32 * self.scale,
+            (DISPLAY_HEIGHT / self.collapse) as u32 * self.scale,
+        )
+    }
+}
+
+impl<C: PixelColor> ImageDrawable for DisplayImage<'_, C> {
+    type Color = C;
+
+    fn draw<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = C>,
+    {
+        self.draw_sub_image(target, &self.bounding_box())
+    }
+
+    fn draw_sub_image<D>(&self, target: &mut D, area: &Rectangle) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = C>,
+    {
+        let area = area.intersection(&self.bounding_box());
+        let (left, top) = (area.top_left.x as u32, area.top_left.y as u32);
+        let colors = (top..top + area.size.height)
+            .flat_map(|y| (left..left + area.size.width).map(move |x| self.color(x, y)));
+        target.fill_contiguous(&Rectangle::new(Point::zero(), area.size), colors)
+    }
+}
+
+impl<C: PixelColor> GetPixel for DisplayImage<'_, C> {
+    type Color = C;
+
+    fn pixel(&self, point: Point) -> Option<C> {
+        let size = self.size();
+        if point.x < 0
+            || point.y < 0
+            || point.x as u32 >= size.width
+            || point.y as u32 >= size.height
+        {
+            return Option::None;
+        }
+        Option::Some(self.color(point.x as u32, point.y as u32))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use embedded_graphics::image::Image;
+    use embedded_graphics::mock_display::MockDisplay;
+    use embedded_graphics::pixelcolor::{BinaryColor, Rgb565};
+    use embedded_graphics::prelude::*;
+
+    use super::*;
+    use crate::Chirp8Mode;
+
+    /// Returns an emulator after running given number of `steps` of the `rom`.
+    fn emulator(mode: Chirp8Mode, rom: &[u8], steps: usize) -> Chirp8 {
+        let mut emulator = Chirp8::new(mode);
+        emulator.load_rom(rom);
+        emulator.take_steps(steps);
+        emulator
+    }
+
+    #[test]
+    fn graphics_lores_collapse() {
+        #[rustfmt::skip]
+        let rom = [
+            0xA2, 0x06, // I = 206
+            0x60, 0x01, // v0 = 1
+            0xD0, 0x02, // Draw v0 v0 2
+            0xA0, 0x60, // Sprite
+        ];
+        let emulator = emulator(Chirp8Mode::CosmacChip8, &rom, 3);
+        let image = DisplayImage::monochrome(&emulator, BinaryColor::Off, BinaryColor::On);
+        assert_eq!(image.size(), Size::new(64, 32));
+
+        let mut display = MockDisplay::new();
+        Image::new(&image, Point::zero())
+            .draw(&mut display.clipped(&Rectangle::new(Point::zero(), Size::new(5, 4))))
+            .unwrap();
+        display.assert_pattern(&[
+            ".....", //
+            ".#.#.", //
+            "..##.", //
+            ".....", //
+        ]);
+    }
+
+    #[test]
+    fn graphics_scale_and_sub_image() {
+        #[rustfmt::skip]
+        let rom = [
+            0x00, 0xFF, // High resolution
+            0xA2, 0x0A, // I = 20A
+            0x60, 0x7E, // v0 = 126
+            0x61, 0x3F, // v1 = 63
+            0xD0, 0x11, // Draw v0 v1 1
+            0xC0, 0x00, // Sprite
+        ];
+        let emulator = emulator(Chirp8Mode::SuperChipModern, &rom, 5);
+        let image =
+            DisplayImage::monochrome(&emulator, BinaryColor::Off, BinaryColor::On).with_scale(2);
+        assert_eq!(image.size(), Size::new(256, 128));
+        assert_eq!(
+            image.pixel(Point::new(253, 126)),
+            Option::Some(BinaryColor::On)
+        );
+        assert_eq!(
+            image.pixel(Point::new(251, 127)),
+            Option::Some(BinaryColor::Off)
+        );
+        assert_eq!(image.pixel(Point::new(256, 0)), Option::None);
+
+        let mut display = MockDisplay::new();
+        let area = Rectangle::new(Point::new(250, 126), Size::new(6, 2));
+        image.draw_sub_image(&mut display, &area).unwrap();
+        display.assert_pattern(&[
+            "..####", //
+            "..####", //
+        ]);
+    }
+
+    #[test]
+    fn graphics_palette() {
+        #[rustfmt::skip]
+        let rom = [
+            0xF3, 0x01, // Select both planes
+            0xA2, 0x08, // I = 208
+            0xD0, 0x01, // Draw v0 v0 1 on both planes
+            0x12, 0x06, // Loop
+            0xF0, 0x30, // Plane 1 sprite, then plane 2 sprite
+        ];
+        let emulator = emulator(Chirp8Mode::XOChip, &rom, 3);
+        let palette = [Rgb565::BLACK, Rgb565::RED, Rgb565::GREEN, Rgb565::WHITE];
+        let mut display = MockDisplay::new();
+        let image = DisplayImage::new(&emulator, palette);
+        Image::new(&image, Point::zero())
+            .draw(&mut display.clipped(&Rectangle::new(Point::zero(), Size::new(6, 1))))
+            .unwrap();
+        display.assert_pattern(&["RRWWKK"]);
+    }
+
+    #[test]
+    fn graphics_sixteen_colors() {
+        #[rustfmt::skip]
+        let rom = [
+            0xF9, 0x01, // Select planes 0 and 3
+            0xA2, 0x06, // I = 206
+            0xD0, 0x01, // Draw v0 v0 1
+            0x80, 0xC0, // Plane 0 sprite, then plane 3 sprite
+        ];
+        let mut colored = Chirp8::new(Chirp8Mode::XOChip);
+        assert!(colored.set_display_planes(4));
+        colored.load_rom(&rom);
+        colored.take_steps(3);
+        let mut palette = [Rgb565::BLACK; 16];
+        palette[0b0001] = Rgb565::RED;
+        palette[0b1001] = Rgb565::GREEN;
+        palette[0b1000] = Rgb565::BLUE;
+        let image = DisplayImage::new_16(&colored, palette);
+        assert_eq!(image.pixel(Point::new(0, 0)), Option::Some(Rgb565::GREEN));
+        assert_eq!(image.pixel(Point::new(1, 0)), Option::Some(Rgb565::BLUE));
+        assert_eq!(image.pixel(Point::new(2, 0)), Option::Some(Rgb565::BLACK));
+
+        // Lit monochrome pixels have the color of the first plane.
+        // I = 204, draw v0 v0 1, sprite.
+        let rom = [0xA2, 0x04, 0xD0, 0x01, 0x80];
+        let emulator = emulator(Chirp8Mode::CosmacChip8, &rom, 2);
+        let image = DisplayImage::new_16(&emulator, palette);
+        assert_eq!(image.pixel(Point::new(0, 0)), Option::Some(Rgb565::RED));
+    }
+}
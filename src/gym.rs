@@ -0,0 +1,893 @@
+use std::boxed::Box;
+use std::thread;
+use std::vec::Vec;
+
+use crate::chirp8::{KEYS_COUNT, REGISTERS_COUNT};
+use crate::{Chirp8, Chirp8Mode, DisplayPixels, QuirkFlags, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+/// Number of bytes of an observation : one byte per pixel of the display, row after row.
+/// Low resolution displays are stretched to fill the whole observation.
+pub const OBSERVATION_SIZE: usize = DISPLAY_WIDTH * DISPLAY_HEIGHT;
+
+/// Error raised when parsing an [Expression].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExpressionError {
+    /// Position in bytes of the error in the parsed text.
+    pub position: usize,
+    /// What went wrong.
+    pub message: &'static str,
+}
+
+impl core::fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+/// Error raised when creating an [Env] or a [VecEnv].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnvError {
+    /// The program of [EnvConfig::rom] does not fit in the memory of the emulator, see
+    /// [Chirp8::load_rom].
+    RomTooLarge {
+        /// Size of the program in bytes.
+        size: usize,
+        /// Size of the memory of the emulator in bytes.
+        ram_size: usize,
+    },
+}
+
+impl core::fmt::Display for EnvError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            EnvError::RomTooLarge { size, ram_size } => write!(
+                f,
+                "ROM of {} bytes does not fit in a memory of {} bytes",
+                size, ram_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EnvError {}
+
+/// Binary operators of [Expression]s, comparisons and logical operators yield 0 or 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Add,
+    Sub,
+    Mul,
+    BitAnd,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+/// Integer expression over the emulator's memory and registers, defining rewards and termination
+/// of an [Env].
+///
+/// Expressions are usually parsed from text, with the usual operators `+ - * & == != < <= > >= &&
+/// || !`, parentheses and the following values :
+/// - Numbers in decimal or hexadecimal (`0x`) notation.
+/// - `ram[address]` and `ram16[address]`, the byte and big endian word at a constant address.
+///   Like the index register, addresses wrap around the memory of the emulator.
+/// - `v0` to `vf`, the registers.
+/// - `delta(expression)`, the variation of an expression since the previous frame.
+/// ```
+/// let reward = chirp8::Expression::parse("delta(ram[0x2F0]) - 10 * (vf == 1)").unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expression {
+    Constant(i64),
+    Ram(u16),
+    Ram16(u16),
+    Register(u8),
+    Delta(Box<Expression>),
+    Negate(Box<Expression>),
+    Not(Box<Expression>),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+}
+
+/// The values an [Expression] may depend on, captured at the end of a frame.
+#[derive(Clone, Default)]
+struct Sample {
+    /// V0 to VF.
+    registers: [u8; REGISTERS_COUNT],
+    /// Referenced addresses, sorted, with their values.
+    ram: Vec<(u16, u8)>,
+}
+
+impl Sample {
+    /// Captures the value of the registers and given sorted `addresses` of the `emulator`.
+    /// Addresses wrap around the memory of the emulator, see [Chirp8::ram_size].
+    fn capture(emulator: &Chirp8, addresses: &[u16]) -> Self {
+        let ram_mask = emulator.ram_size() - 1;
+        Self {
+            registers: *emulator.registers(),
+            ram: addresses
+                .iter()
+                .map(|address| (*address, emulator.bus()[*address as usize & ram_mask]))
+                .collect(),
+        }
+    }
+
+    fn ram(&self, address: u16) -> u8 {
+        self.ram
+            .binary_search_by_key(&address, |(address, _)| *address)
+            .map_or(0, |index| self.ram[index].1)
+    }
+}
+
+/// Recursive descent parser of [Expression]s.
+struct Parser<'a> {
+    text: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: &'static str) -> Result<T, ExpressionError> {
+        Err(ExpressionError {
+            position: self.position,
+            message,
+        })
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.position < self.text.len() && self.text[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+    }
+
+    /// Consumes `token` if it comes next.
+    fn accept(&mut self, token: &str) -> bool {
+        self.skip_spaces();
+        if self.text[self.position..].starts_with(token.as_bytes()) {
+            self.position += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(
+        &mut self,
+        token: &'static str,
+        message: &'static str,
+    ) -> Result<(), ExpressionError> {
+        if self.accept(token) {
+            Ok(())
+        } else {
+            self.error(message)
+        }
+    }
+
+    /// Consumes the next word made of alphanumeric characters.
+    fn word(&mut self) -> &'a [u8] {
+        self.skip_spaces();
+        let start = self.position;
+        while self.position < self.text.len() && self.text[self.position].is_ascii_alphanumeric() {
+            self.position += 1;
+        }
+        &self.text[start..self.position]
+    }
+
+    /// Parses binary operations of operands parsed by `operand`, with given `operators` of the
+    /// same precedence. Longer operators must come first.
+    fn binary(
+        &mut self,
+        operators: &[(&str, BinaryOperator)],
+        operand: fn(&mut Self) -> Result<Expression, ExpressionError>,
+    ) -> Result<Expression, ExpressionError> {
+        let mut left = operand(self)?;
+        'operations: loop {
+            for (token, operator) in operators {
+                // Do not mistake logical operators for bitwise ones.
+                let start = self.position;
+                if self.accept(token) {
+                    if *token == "&" && self.accept("&") {
+                        self.position = start;
+                        break 'operations;
+                    }
+                    let right = operand(self)?;
+                    left = Expression::Binary(*operator, Box::new(left), Box::new(right));
+                    continue 'operations;
+                }
+            }
+            break;
+        }
+        Ok(left)
+    }
+
+    fn or(&mut self) -> Result<Expression, ExpressionError> {
+        self.binary(&[("||", BinaryOperator::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Expression, ExpressionError> {
+        self.binary(&[("&&", BinaryOperator::And)], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Expression, ExpressionError> {
+        self.binary(
+            &[
+                ("==", BinaryOperator::Equal),
+                ("!=", BinaryOperator::NotEqual),
+                ("<=", BinaryOperator::LessEqual),
+                (">=", BinaryOperator::GreaterEqual),
+                ("<", BinaryOperator::Less),
+                (">", BinaryOperator::Greater),
+            ],
+            Self::sum,
+        )
+    }
+
+    fn sum(&mut self) -> Result<Expression, ExpressionError> {
+        self.binary(
+            &[("+", BinaryOperator::Add), ("-", BinaryOperator::Sub)],
+            Self::product,
+        )
+    }
+
+    fn product(&mut self) -> Result<Expression, ExpressionError> {
+        self.binary(
+            &[("*", BinaryOperator::Mul), ("&", BinaryOperator::BitAnd)],
+            Self::unary,
+        )
+    }
+
+    fn unary(&mut self) -> Result<Expression, ExpressionError> {
+        if self.accept("-") {
+            Ok(Expression::Negate(Box::new(self.unary()?)))
+        } else if self.accept("!") {
+            Ok(Expression::Not(Box::new(self.unary()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn number(&mut self) -> Result<i64, ExpressionError> {
+        let start = self.position;
+        let word = self.word();
+        let (digits, radix) = match word {
+            [b'0', b'x' | b'X', digits @ ..] => (digits, 16),
+            _ => (word, 10),
+        };
+        core::str::from_utf8(digits)
+            .ok()
+            .and_then(|digits| i64::from_str_radix(digits, radix).ok())
+            .ok_or(ExpressionError {
+                position: start,
+                message: "Invalid number",
+            })
+    }
+
+    fn address(&mut self) -> Result<u16, ExpressionError> {
+        self.expect("[", "Expected '['")?;
+        let start = self.position;
+        let address = self.number()?;
+        self.expect("]", "Expected ']'")?;
+        u16::try_from(address).map_err(|_| ExpressionError {
+            position: start,
+            message: "Address out of range",
+        })
+    }
+
+    fn primary(&mut self) -> Result<Expression, ExpressionError> {
+        if self.accept("(") {
+            let expression = self.or()?;
+            self.expect(")", "Expected ')'")?;
+            return Ok(expression);
+        }
+        self.skip_spaces();
+        let start = self.position;
+        match self.text.get(start) {
+            Option::Some(digit) if digit.is_ascii_digit() => {
+                return Ok(Expression::Constant(self.number()?))
+            }
+            Option::None => return self.error("Unexpected end of expression"),
+            _ => {}
+        }
+        match self.word() {
+            b"ram" => Ok(Expression::Ram(self.address()?)),
+            b"ram16" => Ok(Expression::Ram16(self.address()?)),
+            b"delta" => {
+                self.expect("(", "Expected '('")?;
+                let expression = self.or()?;
+                self.expect(")", "Expected ')'")?;
+                Ok(Expression::Delta(Box::new(expression)))
+            }
+            [b'v' | b'V', register] if register.is_ascii_hexdigit() => Ok(Expression::Register(
+                (*register as char).to_digit(16).unwrap() as u8,
+            )),
+            _ => {
+                self.position = start;
+                self.error("Unexpected token")
+            }
+        }
+    }
+}
+
+impl Expression {
+    /// Parses an expression from given `text`, see [Expression] for the syntax.
+    pub fn parse(text: &str) -> Result<Self, ExpressionError> {
+        let mut parser = Parser {
+            text: text.as_bytes(),
+            position: 0,
+        };
+        let expression = parser.or()?;
+        parser.skip_spaces();
+        if parser.position != parser.text.len() {
+            return parser.error("Unexpected token");
+        }
+        Ok(expression)
+    }
+
+    /// Adds the addresses this expression depends on to `addresses`.
+    fn addresses(&self, addresses: &mut Vec<u16>) {
+        match self {
+            Expression::Constant(_) | Expression::Register(_) => {}
+            Expression::Ram(address) => addresses.push(*address),
+            Expression::Ram16(address) => {
+                addresses.push(*address);
+                addresses.push(address.wrapping_add(1));
+            }
+            Expression::Delta(expression)
+            | Expression::Negate(expression)
+            | Expression::Not(expression) => expression.addresses(addresses),
+            Expression::Binary(_, left, right) => {
+                left.addresses(addresses);
+                right.addresses(addresses);
+            }
+        }
+    }
+
+    /// Evaluates the expression on the `current` frame, `previous` being used by `delta`.
+    fn evaluate(&self, current: &Sample, previous: &Sample) -> i64 {
+        match self {
+            Expression::Constant(value) => *value,
+            Expression::Ram(address) => current.ram(*address) as i64,
+            Expression::Ram16(address) => {
+                ((current.ram(*address) as i64) << 8) | current.ram(address.wrapping_add(1)) as i64
+            }
+            Expression::Register(x) => current.registers[*x as usize] as i64,
+            Expression::Delta(expression) => expression
+                .evaluate(current, previous)
+                .wrapping_sub(expression.evaluate(previous, previous)),
+            Expression::Negate(expression) => expression.evaluate(current, previous).wrapping_neg(),
+            Expression::Not(expression) => (expression.evaluate(current, previous) == 0) as i64,
+            Expression::Binary(operator, left, right) => {
+                let left = left.evaluate(current, previous);
+                let right = right.evaluate(current, previous);
+                match operator {
+                    BinaryOperator::Add => left.wrapping_add(right),
+                    BinaryOperator::Sub => left.wrapping_sub(right),
+                    BinaryOperator::Mul => left.wrapping_mul(right),
+                    BinaryOperator::BitAnd => left & right,
+                    BinaryOperator::Equal => (left == right) as i64,
+                    BinaryOperator::NotEqual => (left != right) as i64,
+                    BinaryOperator::Less => (left < right) as i64,
+                    BinaryOperator::LessEqual => (left <= right) as i64,
+                    BinaryOperator::Greater => (left > right) as i64,
+                    BinaryOperator::GreaterEqual => (left >= right) as i64,
+                    BinaryOperator::And => (left != 0 && right != 0) as i64,
+                    BinaryOperator::Or => (left != 0 || right != 0) as i64,
+                }
+            }
+        }
+    }
+}
+
+/// The actions available to an agent, each one being a set of keys held during a step.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActionSet {
+    /// One bit per key pressed, for every action.
+    actions: Vec<u16>,
+}
+
+impl ActionSet {
+    /// Creates an action set from the keys pressed by each action.
+    /// Keys out of the 0x0 to 0xF range are ignored.
+    /// ```
+    /// // Do nothing, left, right, left and fire, right and fire.
+    /// let actions = chirp8::ActionSet::new(&[&[], &[0x4], &[0x6], &[0x4, 0x5], &[0x6, 0x5]]);
+    /// assert_eq!(actions.len(), 5);
+    /// ```
+    pub fn new(actions: &[&[u8]]) -> Self {
+        Self {
+            actions: actions
+                .iter()
+                .map(|keys| {
+                    keys.iter()
+                        .filter(|key| **key < KEYS_COUNT)
+                        .fold(0, |mask, key| mask | (1 << key))
+                })
+                .collect(),
+        }
+    }
+
+    /// Creates the action set pressing no key (action 0) or a single key (action `1 + key`).
+    pub fn single_keys() -> Self {
+        Self {
+            actions: core::iter::once(0)
+                .chain((0..KEYS_COUNT).map(|key| 1 << key))
+                .collect(),
+        }
+    }
+
+    /// Returns the number of actions.
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    /// Indicates whether there is no action at all.
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Indicates whether the given `key` is pressed by the given `action`.
+    pub fn is_pressed(&self, action: usize, key: u8) -> bool {
+        key < KEYS_COUNT && self.actions[action] & (1 << key) != 0
+    }
+}
+
+/// Everything defining an [Env].
+#[derive(Clone, Debug)]
+pub struct EnvConfig {
+    /// The mode of the emulator.
+    pub mode: Chirp8Mode,
+    /// The quirks of the emulator.
+    pub quirks: QuirkFlags,
+    /// The program, loaded at every reset.
+    pub rom: Vec<u8>,
+    /// The actions available to the agent.
+    pub actions: ActionSet,
+    /// The reward of a frame.
+    pub reward: Expression,
+    /// The episode is done when this expression is not zero.
+    pub done: Expression,
+    /// Number of frames emulated by each step, during which the action is repeated.
+    pub frame_skip: usize,
+}
+
+impl EnvConfig {
+    /// Creates a configuration running the `rom` in given `mode`, with the default quirks of the
+    /// mode, [ActionSet::single_keys], no reward, never done and no frame skipped.
+    pub fn new(mode: Chirp8Mode, rom: &[u8]) -> Self {
+        Self {
+            mode,
+            quirks: QuirkFlags::from_mode(mode),
+            rom: rom.to_vec(),
+            actions: ActionSet::single_keys(),
+            reward: Expression::Constant(0),
+            done: Expression::Constant(0),
+            frame_skip: 1,
+        }
+    }
+}
+
+/// Reinforcement learning environment running a single emulator, in the manner of `gym`.
+/// ```
+/// let rom = [0x70, 0x01, 0x12, 0x00]; // Increment v0 forever
+/// let mut config = chirp8::EnvConfig::new(chirp8::Chirp8Mode::SuperChipModern, &rom);
+/// config.reward = chirp8::Expression::parse("delta(v0) > 0").unwrap();
+/// config.done = chirp8::Expression::parse("v0 >= 200").unwrap();
+/// config.frame_skip = 4;
+///
+/// let mut env = chirp8::Env::new(config).unwrap();
+/// env.reset(42);
+/// let (observation, reward, done) = env.step(0);
+/// assert_eq!(observation.len(), chirp8::OBSERVATION_SIZE);
+/// assert_eq!((reward, done), (4.0, false));
+/// ```
+pub struct Env {
+    config: EnvConfig,
+    emulator: Chirp8,
+    /// Seed of the current episode.
+    seed: u64,
+    /// Addresses the reward and termination depend on, sorted.
+    addresses: Vec<u16>,
+    /// Values the reward and termination depend on, at the end of the last frame.
+    sample: Sample,
+    /// The display at the end of the last step.
+    observation: Vec<u8>,
+}
+
+impl Env {
+    /// Creates an environment, which must be [Env::reset] before stepping.
+    /// Returns an error if the program does not fit in the memory of the emulator.
+    pub fn new(config: EnvConfig) -> Result<Self, EnvError> {
+        let mut addresses = Vec::new();
+        config.reward.addresses(&mut addresses);
+        config.done.addresses(&mut addresses);
+        addresses.sort_unstable();
+        addresses.dedup();
+
+        let mut emulator = Chirp8::with_custom_quirks(config.mode, config.quirks);
+        if !emulator.load_rom(&config.rom) {
+            return Err(EnvError::RomTooLarge {
+                size: config.rom.len(),
+                ram_size: emulator.ram_size(),
+            });
+        }
+        Ok(Self {
+            config,
+            emulator,
+            seed: 0,
+            addresses,
+            sample: Sample::default(),
+            observation: std::vec![0; OBSERVATION_SIZE],
+        })
+    }
+
+    /// Returns the configuration of the environment.
+    pub fn config(&self) -> &EnvConfig {
+        &self.config
+    }
+
+    /// Returns the emulator, to inspect its state.
+    pub fn emulator(&self) -> &Chirp8 {
+        &self.emulator
+    }
+
+    /// Returns the seed of the current episode.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Returns the number of actions, see [EnvConfig::actions].
+    pub fn action_count(&self) -> usize {
+        self.config.actions.len()
+    }
+
+    /// Restarts the program from scratch, the random numbers generator being seeded with `seed`.
+    /// Returns the first observation.
+    pub fn reset(&mut self, seed: u64) -> &[u8] {
+        self.reset_emulator(seed);
+        write_observation(&self.emulator, &mut self.observation);
+        &self.observation
+    }
+
+    /// Runs [EnvConfig::frame_skip] frames with the keys of given `action` held, stopping early
+    /// when the episode is done. Returns the observation, the reward summed over the frames and
+    /// whether the episode is done.
+    ///
+    /// Panics if `action` is not lower than [Env::action_count].
+    pub fn step(&mut self, action: usize) -> (&[u8], f32, bool) {
+        let (reward, done) = self.step_frames(action);
+        write_observation(&self.emulator, &mut self.observation);
+        (&self.observation, reward, done)
+    }
+
+    fn reset_emulator(&mut self, seed: u64) {
+        self.emulator = Chirp8::with_custom_quirks(self.config.mode, self.config.quirks);
+        self.emulator.set_random_seed(seed);
+        self.seed = seed;
+        // The program has been checked to fit at creation.
+        let loaded = self.emulator.load_rom(&self.config.rom);
+        debug_assert!(loaded);
+        self.sample = Sample::capture(&self.emulator, &self.addresses);
+    }
+
+    fn step_frames(&mut self, action: usize) -> (f32, bool) {
+        for key in 0..KEYS_COUNT {
+            self.emulator
+                .key_set(key, self.config.actions.is_pressed(action, key));
+        }
+        let mut reward = 0;
+        let mut done = false;
+        for _ in 0..self.config.frame_skip.max(1) {
+            self.emulator.run_frame();
+            let sample = Sample::capture(&self.emulator, &self.addresses);
+            reward += self.config.reward.evaluate(&sample, &self.sample);
+            done = self.config.done.evaluate(&sample, &self.sample) != 0;
+            self.sample = sample;
+            if done {
+                break;
+            }
+        }
+        (reward as f32, done)
+    }
+}
+
+/// Copies the display of the `emulator` to `observation`.
+fn write_observation(emulator: &Chirp8, observation: &mut [u8]) {
+    let display = emulator.get_display_buffer();
+    for (y, output) in observation.chunks_exact_mut(DISPLAY_WIDTH).enumerate() {
+        for (x, pixel) in output.iter_mut().enumerate() {
+            *pixel = display.pixel(x, y);
+        }
+    }
+}
+
+/// Several environments sharing the same configuration, stepped in parallel threads.
+///
+/// Observations are packed in a single buffer of [OBSERVATION_SIZE] bytes per environment.
+/// Environments whose episode is done are reset automatically, the observation returned for them
+/// being the first of the new episode.
+/// ```
+/// let rom = [0x70, 0x01, 0x12, 0x00]; // Increment v0 forever
+/// let config = chirp8::EnvConfig::new(chirp8::Chirp8Mode::SuperChipModern, &rom);
+/// let mut envs = chirp8::VecEnv::new(config, 8).unwrap();
+/// envs.reset(0);
+/// let (observations, rewards, dones) = envs.step(&[0; 8]);
+/// assert_eq!(observations.len(), 8 * chirp8::OBSERVATION_SIZE);
+/// ```
+pub struct VecEnv {
+    envs: Vec<Env>,
+    observations: Vec<u8>,
+    rewards: Vec<f32>,
+    dones: Vec<bool>,
+    /// Number of threads the environments are spread on.
+    threads: usize,
+}
+
+impl VecEnv {
+    /// Creates `count` environments, run on as many threads as available cores.
+    /// Returns an error if the program does not fit in the memory of the emulator.
+    pub fn new(config: EnvConfig, count: usize) -> Result<Self, EnvError> {
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        Ok(Self {
+            envs: (0..count)
+                .map(|_| Env::new(config.clone()))
+                .collect::<Result<_, _>>()?,
+            observations: std::vec![0; count * OBSERVATION_SIZE],
+            rewards: std::vec![0.0; count],
+            dones: std::vec![false; count],
+            threads,
+        })
+    }
+
+    /// Sets the number of threads the environments are spread on, at least 1.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Returns the number of environments.
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    /// Indicates whether there is no environment at all.
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    /// Returns the environments.
+    pub fn envs(&self) -> &[Env] {
+        &self.envs
+    }
+
+    /// Resets every environment, the `i`th one with seed `seed + i`.
+    /// The next episodes of the `i`th environment use seeds `seed + i + n * len()`.
+    /// Returns the first observations.
+    pub fn reset(&mut self, seed: u64) -> &[u8] {
+        self.run(|env, index, observation, _, _| {
+            env.reset_emulator(seed.wrapping_add(index as u64));
+            write_observation(&env.emulator, observation);
+        });
+        &self.observations
+    }
+
+    /// Steps every environment with its action in `actions`.
+    /// Returns the observations, rewards and done flags of all environments.
+    ///
+    /// Panics if there is not exactly one valid action per environment.
+    pub fn step(&mut self, actions: &[usize]) -> (&[u8], &[f32], &[bool]) {
+        assert_eq!(actions.len(), self.envs.len(), "One action per environment");
+        let count = self.envs.len() as u64;
+        self.run(|env, index, observation, reward, done| {
+            (*reward, *done) = env.step_frames(actions[index]);
+            if *done {
+                env.reset_emulator(env.seed.wrapping_add(count));
+            }
+            write_observation(&env.emulator, observation);
+        });
+        (&self.observations, &self.rewards, &self.dones)
+    }
+
+    /// Runs `function` on every environment along with its index and its slots of the outputs,
+    /// spreading environments on threads.
+    fn run<F>(&mut self, function: F)
+    where
+        F: Fn(&mut Env, usize, &mut [u8], &mut f32, &mut bool) + Sync,
+    {
+        if self.envs.is_empty() {
+            return;
+        }
+        let per_thread = self.envs.len().div_ceil(self.threads);
+        let function = &function;
+        thread::scope(|scope| {
+            for (chunk, ((envs, observations), (rewards, dones))) in self
+                .envs
+                .chunks_mut(per_thread)
+                .zip(self.observations.chunks_mut(per_thread * OBSERVATION_SIZE))
+                .zip(
+                    self.rewards
+                        .chunks_mut(per_thread)
+                        .zip(self.dones.chunks_mut(per_thread)),
+                )
+                .enumerate()
+            {
+                scope.spawn(move || {
+                    for (i, (env, (observation, (reward, done)))) in envs
+                        .iter_mut()
+                        .zip(
+                            observations
+                                .chunks_exact_mut(OBSERVATION_SIZE)
+                                .zip(rewards.iter_mut().zip(dones.iter_mut())),
+                        )
+                        .enumerate()
+                    {
+                        function(env, chunk * per_thread + i, observation, reward, done);
+                    }
+                });
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[rustfmt::skip]
+    const ROM: [u8; 20] = [
+        0x62, 0x05, // 200 : v2 = 5
+        0xE2, 0xA1, // 202 : skip if key v2 not pressed
+        0x71, 0x01, // 204 : v1 += 1, the score
+        0xC0, 0xFF, // 206 : v0 = random
+        0xF0, 0x29, // 208 : I = font sprite of v0
+        0xD3, 0x45, // 20A : draw v3 v4 5
+        0xA3, 0x00, // 20C : I = 0x300
+        0xF1, 0x55, // 20E : store v0 v1 at 0x300
+        0x12, 0x02, // 210 : jump 202
+        0x00, 0x00,
+    ];
+
+    fn config() -> EnvConfig {
+        let mut config = EnvConfig::new(Chirp8Mode::SuperChipModern, &ROM);
+        config.reward = Expression::parse("delta(ram[0x301])").unwrap();
+        config.done = Expression::parse("ram[0x301] >= 100").unwrap();
+        config.frame_skip = 2;
+        config
+    }
+
+    #[test]
+    fn gym_expression() {
+        let expression = Expression::parse("delta(ram16[0x300]) * 2 + (v1 == 3) - -1").unwrap();
+        let mut addresses = Vec::new();
+        expression.addresses(&mut addresses);
+        assert_eq!(addresses, [0x300, 0x301]);
+
+        let previous = Sample {
+            registers: [0; 16],
+            ram: std::vec![(0x300, 0x01), (0x301, 0x00)],
+        };
+        let mut current = previous.clone();
+        current.registers[1] = 3;
+        current.ram[1].1 = 0x10;
+        assert_eq!(expression.evaluate(&current, &previous), 0x10 * 2 + 1 + 1);
+
+        let expression = Expression::parse("!(ram[0x10] & 0x80) && vF || 0").unwrap();
+        assert_eq!(expression.evaluate(&current, &previous), 0);
+
+        let error = Expression::parse("ram[0x300] + foo").unwrap_err();
+        assert_eq!(error.position, 13);
+        assert!(Expression::parse("(v0").is_err());
+        assert!(Expression::parse("ram[0x10000]").is_err());
+    }
+
+    #[test]
+    fn gym_env() {
+        let mut env = Env::new(config()).unwrap();
+        let first = env.reset(7).to_vec();
+
+        // Same seed, same episode.
+        let mut episode = Vec::new();
+        for _ in 0..10 {
+            let (observation, reward, done) = env.step(0);
+            assert_eq!((reward, done), (0.0, false));
+            episode.push(observation.to_vec());
+        }
+        assert_eq!(env.reset(7), &first[..]);
+        for observation in &episode {
+            assert_eq!(env.step(0).0, &observation[..]);
+        }
+        env.reset(8);
+        assert!((0..10).any(|i| env.step(0).0 != &episode[i][..]));
+
+        // Pressing key 5 scores until done.
+        env.reset(7);
+        let mut total = 0.0;
+        loop {
+            let (_, reward, done) = env.step(1 + 5);
+            assert!(reward > 0.0);
+            total += reward;
+            if done {
+                break;
+            }
+        }
+        assert_eq!(total, env.emulator().registers()[1] as f32);
+        assert!(total >= 100.0);
+    }
+
+    #[test]
+    fn gym_addresses_wrap_around() {
+        let mut config = EnvConfig::new(Chirp8Mode::CosmacChip8, &ROM);
+        config.reward = Expression::parse("ram[0xF301] - ram[0x301]").unwrap();
+        config.done = Expression::parse("ram16[0xFFFF] != 0x00F0").unwrap();
+        let mut env = Env::new(config).unwrap();
+        env.reset(7);
+        for _ in 0..10 {
+            let (_, reward, done) = env.step(1 + 5);
+            assert_eq!((reward, done), (0.0, false));
+        }
+        assert!(env.emulator().registers()[1] > 0);
+    }
+
+    #[test]
+    fn gym_rom_too_large() {
+        let rom = std::vec![0; 0x1000];
+        let config = EnvConfig::new(Chirp8Mode::CosmacChip8, &rom);
+        let error = EnvError::RomTooLarge {
+            size: 0x1000,
+            ram_size: 0x1000,
+        };
+        assert_eq!(Env::new(config.clone()).err(), Option::Some(error));
+        assert_eq!(VecEnv::new(config, 2).err(), Option::Some(error));
+
+        // XO-Chip programs have a larger memory.
+        let config = EnvConfig::new(Chirp8Mode::XOChip, &rom);
+        assert!(Env::new(config).is_ok());
+    }
+
+    #[test]
+    fn gym_vec_env() {
+        let count = 5;
+        let mut envs = VecEnv::new(config(), count).unwrap();
+        envs.set_threads(2);
+        let mut singles: Vec<Env> = (0..count).map(|_| Env::new(config()).unwrap()).collect();
+
+        let observations = envs.reset(100).to_vec();
+        for (i, env) in singles.iter_mut().enumerate() {
+            let observation = env.reset(100 + i as u64);
+            assert_eq!(
+                observation,
+                &observations[i * OBSERVATION_SIZE..][..OBSERVATION_SIZE]
+            );
+        }
+
+        let mut resets = 0;
+        for step in 0..40 {
+            let actions: Vec<usize> = (0..count)
+                .map(|i| if i % 2 == 0 { 6 } else { step % 3 })
+                .collect();
+            let (observations, rewards, dones) = envs.step(&actions);
+            for (i, env) in singles.iter_mut().enumerate() {
+                let (observation, reward, done) = env.step(actions[i]);
+                assert_eq!(reward, rewards[i]);
+                assert_eq!(done, dones[i]);
+                if done {
+                    resets += 1;
+                    env.reset(env.seed() + count as u64);
+                } else {
+                    assert_eq!(
+                        observation,
+                        &observations[i * OBSERVATION_SIZE..][..OBSERVATION_SIZE]
+                    );
+                }
+            }
+        }
+        assert!(resets > 0);
+        for (env, single) in envs.envs().iter().zip(&singles) {
+            assert_eq!(env.seed(), single.seed());
+            assert_eq!(env.emulator().registers(), single.emulator().registers());
+        }
+    }
+}
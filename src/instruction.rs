@@ -0,0 +1,374 @@
+use crate::{Chirp8Mode, QuirkFlags};
+
+/// Number of entries of the decode cache, each entry holding the operation decoded at one of the
+/// addresses sharing it. With `alloc`, this covers every instruction of a 4KB memory.
+#[cfg(all(feature = "decode_cache", feature = "alloc"))]
+pub(crate) const DECODE_CACHE_ENTRIES: usize = 2048;
+#[cfg(all(feature = "decode_cache", not(feature = "alloc")))]
+pub(crate) const DECODE_CACHE_ENTRIES: usize = 256;
+
+// Create type alias depending on if the heap is available or not.
+// Entries are tagged with their address, so that the cache does not grow with the memory.
+#[cfg(all(feature = "decode_cache", feature = "alloc"))]
+type DecodeCacheData = alloc::vec::Vec<(u16, Operation)>;
+#[cfg(all(feature = "decode_cache", not(feature = "alloc")))]
+type DecodeCacheData = [(u16, Operation); DECODE_CACHE_ENTRIES];
+
+/// A decoded instruction, ready to be executed.
+///
+/// Operands are extracted from the opcode once and for all, and instructions that are not
+/// available in the emulator's mode are resolved to [Operation::Unknown].
+/// `x` and `y` are register indices, `n`, `nn` and `nnn` are immediate values.
+/// See https://tobiasvl.github.io/blog/write-a-chip-8-emulator/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Operation {
+    /// Not decoded yet, only used to mark empty decode cache entries.
+    #[cfg_attr(not(feature = "decode_cache"), allow(dead_code))]
+    Undecoded,
+    /// An instruction not supported in the emulator's mode.
+    Unknown(u16),
+    /// 00E0 : Clear screen.
+    ClearDisplay,
+    /// 00E0 : Clear selected planes, the XO-Chip form of [Operation::ClearDisplay].
+    ClearPlanes,
+    /// 00EE : Return from subroutine.
+    Return,
+    /// 00FD : Exit from interpreter (Super-Chip).
+    Exit,
+    /// 00FE : Disable High-res (Super-Chip and above).
+    LowResolution,
+    /// 00FF : Enable High-res (Super-chip and above).
+    HighResolution,
+    /// 00DN : Scroll up N pixels (XO-Chip), 00BN on unofficial Super Chip.
+    ScrollUp(u8),
+    /// 00CN : Scroll down N pixels (Super Chip and above).
+    ScrollDown(u8),
+    /// 00FB : Scroll right 4 pixels (Super Chip and above).
+    ScrollRight,
+    /// 00FC : Scroll left 4 pixels (Super Chip and above).
+    ScrollLeft,
+    /// 1NNN : Jump.
+    Jump(u16),
+    /// 2NNN : Call subroutine.
+    Call(u16),
+    /// 3XNN : Skip if vx == nn.
+    SkipEqual { x: u8, nn: u8 },
+    /// 4XNN : Skip if vx != nn.
+    SkipNotEqual { x: u8, nn: u8 },
+    /// 5XY0 : Skip if vx == vy.
+    SkipEqualRegisters { x: u8, y: u8 },
+    /// 5XY2 : Save vx - vy (XO-chip).
+    SaveRange { x: u8, y: u8 },
+    /// 5XY3 : Load vx - vy (XO-chip).
+    LoadRange { x: u8, y: u8 },
+    /// 6XNN : Set register.
+    Set { x: u8, nn: u8 },
+    /// 7XNN : Add to register.
+    Add { x: u8, nn: u8 },
+    /// 8XY0 : Set vx to vy.
+    Copy { x: u8, y: u8 },
+    /// 8XY1 : OR.
+    Or { x: u8, y: u8 },
+    /// 8XY2 : AND.
+    And { x: u8, y: u8 },
+    /// 8XY3 : XOR.
+    Xor { x: u8, y: u8 },
+    /// 8XY4 : ADD.
+    AddRegisters { x: u8, y: u8 },
+    /// 8XY5 : SUB VX - VY.
+    Sub { x: u8, y: u8 },
+    /// 8XY6 : Shift VX right.
+    ShiftRight { x: u8, y: u8 },
+    /// 8XY7 : SUB VY - VX.
+    SubReverse { x: u8, y: u8 },
+    /// 8XYE : Shift VX left.
+    ShiftLeft { x: u8, y: u8 },
+    /// 9XY0 : Skip if vx != vy.
+    SkipNotEqualRegisters { x: u8, y: u8 },
+    /// ANNN : Set index.
+    SetIndex(u16),
+    /// BNNN : Jump with offset, to nnn + v`x`, where `x` depends on the "jump" quirk.
+    JumpOffset { nnn: u16, x: u8 },
+    /// CXNN : Random.
+    Random { x: u8, nn: u8 },
+    /// DXYN : Display.
+    Display { x: u8, y: u8, n: u8 },
+    /// EX9E : Skip if VX pressed.
+    SkipKeyPressed(u8),
+    /// EXA1 : Skip if VX not pressed.
+    SkipKeyNotPressed(u8),
+    /// F000 : Load 16-bits address in index (XO-Chip), the address is read at execution.
+    LoadLongIndex,
+    /// FX01 : Select plane(s) X (XO-Chip).
+    SelectPlanes(u8),
+    /// FX07 : Set VX to delay timer.
+    GetDelayTimer(u8),
+    /// FX0A : Get Key.
+    WaitKey(u8),
+    /// FX15 : Set delay timer to VX.
+    SetDelayTimer(u8),
+    /// FX18 : Set sound timer to VX.
+    SetSoundTimer(u8),
+    /// FX1E : Add to index.
+    AddIndex(u8),
+    /// FX29 : Font character.
+    Font(u8),
+    /// FX30 : Large font character (Super-Chip 1.1 and above).
+    LargeFont(u8),
+    /// FX33 : Binary-coded decimal conversion.
+    BinaryCodedDecimal(u8),
+    /// FX55 : Store v0 to vx.
+    Store(u8),
+    /// FX65 : Load v0 to vx.
+    Load(u8),
+    /// FX75 : Save `count` registers to flags registers (Super-Chip 1.0 and above).
+    SaveFlags(u8),
+    /// FX85 : Load `count` registers from flags registers (Super-Chip 1.0 and above).
+    LoadFlags(u8),
+}
+
+impl Operation {
+    /// Decodes given big endian `instruction`, as understood by an emulator running in given
+    /// `mode` with given `quirks`.
+    pub(crate) fn decode(instruction: u16, mode: Chirp8Mode, quirks: QuirkFlags) -> Self {
+        let opcode = 0xF & (instruction >> 12) as u8;
+        // The second nibble. Used to look up one of the 16 registers (VX) from V0 through VF.
+        let x = 0x0F & (instruction >> 8) as u8;
+        // The third nibble. Also used to look up one of the 16 registers (VY) from V0 through VF.
+        let y = 0x0F & (instruction >> 4) as u8;
+        // The fourth nibble. A 4-bit number.
+        let n = 0x0F & instruction as u8;
+        // The second byte (third and fourth nibbles). An 8-bit immediate number.
+        let nn = instruction as u8;
+        // The second, third and fourth nibbles. A 12-bit immediate memory address.
+        let nnn = 0x0FFF & instruction;
+
+        let super_chip = mode >= Chirp8Mode::SuperChip1_1;
+        let xo_chip = mode == Chirp8Mode::XOChip;
+        // Keeps `operation` if `available` in current mode.
+        let available = |available: bool, operation: Operation| {
+            if available {
+                operation
+            } else {
+                Operation::Unknown(instruction)
+            }
+        };
+
+        match opcode {
+            0x0 => match nn {
+                0xE0 if xo_chip => Operation::ClearPlanes,
+                0xE0 => Operation::ClearDisplay,
+                0xEE => Operation::Return,
+                0xFD => available(super_chip, Operation::Exit),
+                0xFE => available(super_chip, Operation::LowResolution),
+                0xFF => available(super_chip, Operation::HighResolution),
+                0xD0..=0xDF => available(xo_chip, Operation::ScrollUp(n)),
+                0xB0..=0xBF => {
+                    available(mode == Chirp8Mode::SuperChipModern, Operation::ScrollUp(n))
+                }
+                0xC0..=0xCF => available(super_chip, Operation::ScrollDown(n)),
+                0xFB => available(super_chip, Operation::ScrollRight),
+                0xFC => available(super_chip, Operation::ScrollLeft),
+                _ => Operation::Unknown(instruction),
+            },
+            0x1 => Operation::Jump(nnn),
+            0x2 => Operation::Call(nnn),
+            0x3 => Operation::SkipEqual { x, nn },
+            0x4 => Operation::SkipNotEqual { x, nn },
+            0x5 => match n {
+                0 => Operation::SkipEqualRegisters { x, y },
+                2 => available(xo_chip, Operation::SaveRange { x, y }),
+                3 => available(xo_chip, Operation::LoadRange { x, y }),
+                _ => Operation::Unknown(instruction),
+            },
+            0x6 => Operation::Set { x, nn },
+            0x7 => Operation::Add { x, nn },
+            0x8 => match n {
+                0x0 => Operation::Copy { x, y },
+                0x1 => Operation::Or { x, y },
+                0x2 => Operation::And { x, y },
+                0x3 => Operation::Xor { x, y },
+                0x4 => Operation::AddRegisters { x, y },
+                0x5 => Operation::Sub { x, y },
+                0x6 => Operation::ShiftRight { x, y },
+                0x7 => Operation::SubReverse { x, y },
+                0xE => Operation::ShiftLeft { x, y },
+                _ => Operation::Unknown(instruction),
+            },
+            // n should be equal to 0 (0x9XY0), not checked for performance.
+            0x9 => Operation::SkipNotEqualRegisters { x, y },
+            0xA => Operation::SetIndex(nnn),
+            0xB => Operation::JumpOffset {
+                nnn,
+                x: if quirks.contains(QuirkFlags::JUMP_XNN) {
+                    x
+                } else {
+                    0
+                },
+            },
+            0xC => Operation::Random { x, nn },
+            0xD => Operation::Display { x, y, n },
+            0xE => match nn {
+                0x9E => Operation::SkipKeyPressed(x),
+                0xA1 => Operation::SkipKeyNotPressed(x),
+                _ => Operation::Unknown(instruction),
+            },
+            0xF => match nn {
+                0x00 => available(xo_chip && x == 0, Operation::LoadLongIndex),
+                0x01 => available(xo_chip, Operation::SelectPlanes(x)),
+                0x07 => Operation::GetDelayTimer(x),
+                0x0A => Operation::WaitKey(x),
+                0x15 => Operation::SetDelayTimer(x),
+                0x18 => Operation::SetSoundTimer(x),
+                0x1E => Operation::AddIndex(x),
+                0x29 => Operation::Font(x),
+                0x30 => available(super_chip, Operation::LargeFont(x)),
+                0x33 => Operation::BinaryCodedDecimal(x),
+                0x55 => Operation::Store(x),
+                0x65 => Operation::Load(x),
+                // XO-Chip has 16 flags registers, the HP48 only had 8.
+                0x75 => available(
+                    super_chip,
+                    Operation::SaveFlags(if xo_chip { x } else { x & 0x7 }),
+                ),
+                0x85 => available(
+                    super_chip,
+                    Operation::LoadFlags(if xo_chip { x } else { x & 0x7 }),
+                ),
+                _ => Operation::Unknown(instruction),
+            },
+            _ => Operation::Unknown(instruction),
+        }
+    }
+}
+
+/// Decoded operations of the last executed addresses, filled as instructions are executed.
+///
+/// Only [DECODE_CACHE_ENTRIES] operations are kept, an address using the entry of index
+/// `address / 2` modulo the number of entries, whatever the size of the memory. This covers the
+/// tight loops of most programs while keeping the cache small, on XO-Chip as well.
+#[cfg(feature = "decode_cache")]
+pub(crate) struct DecodeCache {
+    /// The decoded operations, tagged with their address.
+    operations: DecodeCacheData,
+}
+
+#[cfg(feature = "decode_cache")]
+impl DecodeCache {
+    /// Creates an empty cache.
+    pub(crate) fn new() -> Self {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "alloc")]{
+                let operations = alloc::vec![(0, Operation::Undecoded); DECODE_CACHE_ENTRIES];
+            }else{
+                let operations = [(0, Operation::Undecoded); DECODE_CACHE_ENTRIES];
+            }
+        }
+
+        Self { operations }
+    }
+
+    /// Returns the operation decoded at given `address`, [Operation::Undecoded] if none.
+    #[inline]
+    pub(crate) fn get(&self, address: u16) -> Operation {
+        match self.operations[Self::entry(address)] {
+            (tag, operation) if tag == address => operation,
+            _ => Operation::Undecoded,
+        }
+    }
+
+    /// Stores the `operation` decoded at given `address`.
+    #[inline]
+    pub(crate) fn set(&mut self, address: u16, operation: Operation) {
+        self.operations[Self::entry(address)] = (address, operation);
+    }
+
+    /// Forgets the operations using the byte at given `address`, after it has been written.
+    #[inline]
+    pub(crate) fn invalidate(&mut self, address: u16, ram_mask: u16) {
+        let previous = address.wrapping_sub(1) & ram_mask;
+        if self.get(address) != Operation::Undecoded {
+            self.set(address, Operation::Undecoded);
+        }
+        if self.get(previous) != Operation::Undecoded {
+            self.set(previous, Operation::Undecoded);
+        }
+    }
+
+    /// Forgets all decoded operations.
+    pub(crate) fn clear(&mut self) {
+        self.operations.fill((0, Operation::Undecoded));
+    }
+
+    /// Returns the index of the entry used by given `address`.
+    #[inline]
+    fn entry(address: u16) -> usize {
+        (address as usize >> 1) % DECODE_CACHE_ENTRIES
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_depends_on_mode() {
+        let decode = |instruction, mode| Operation::decode(instruction, mode, mode.into());
+
+        assert_eq!(
+            decode(0xD12F, Chirp8Mode::CosmacChip8),
+            Operation::Display { x: 1, y: 2, n: 0xF }
+        );
+        assert_eq!(
+            decode(0x00FF, Chirp8Mode::CosmacChip8),
+            Operation::Unknown(0x00FF)
+        );
+        assert_eq!(
+            decode(0x00FF, Chirp8Mode::SuperChip1_1),
+            Operation::HighResolution
+        );
+        assert_eq!(decode(0x00E0, Chirp8Mode::XOChip), Operation::ClearPlanes);
+        assert_eq!(
+            decode(0xB345, Chirp8Mode::CosmacChip8),
+            Operation::JumpOffset { nnn: 0x345, x: 0 }
+        );
+        assert_eq!(
+            decode(0xB345, Chirp8Mode::SuperChipModern),
+            Operation::JumpOffset { nnn: 0x345, x: 3 }
+        );
+        assert_eq!(
+            decode(0xFF75, Chirp8Mode::SuperChip1_1),
+            Operation::SaveFlags(0x7)
+        );
+        assert_eq!(
+            decode(0xFF75, Chirp8Mode::XOChip),
+            Operation::SaveFlags(0xF)
+        );
+    }
+
+    #[cfg(feature = "decode_cache")]
+    #[test]
+    fn decode_cache_invalidation() {
+        let mut cache = DecodeCache::new();
+        cache.set(0x200, Operation::ClearDisplay);
+        cache.set(0x202, Operation::Return);
+        assert_eq!(cache.get(0x200), Operation::ClearDisplay);
+        assert_eq!(cache.get(0x202), Operation::Return);
+        assert_eq!(cache.get(0x204), Operation::Undecoded);
+
+        // Writing the second byte of an instruction invalidates it, not the previous one.
+        cache.invalidate(0x203, 0xFFF);
+        assert_eq!(cache.get(0x200), Operation::ClearDisplay);
+        assert_eq!(cache.get(0x202), Operation::Undecoded);
+
+        // Addresses sharing an entry replace each other.
+        let other = 0x200 + 2 * DECODE_CACHE_ENTRIES as u16;
+        cache.set(other, Operation::Return);
+        assert_eq!(cache.get(0x200), Operation::Undecoded);
+        assert_eq!(cache.get(other), Operation::Return);
+        assert!(core::mem::size_of_val(&cache.operations[..]) <= 16 * 1024);
+
+        cache.clear();
+        assert_eq!(cache.get(other), Operation::Undecoded);
+    }
+}
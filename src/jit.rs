@@ -0,0 +1,761 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+use std::collections::HashMap;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{types, AbiParam, InstBuilder, MemFlags, Value};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_codegen::Context;
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Module};
+
+use crate::chirp8::{FLAG_REGISTER_INDEX, PROGRAM_COUNTER_STEP};
+use crate::instruction::Operation;
+use crate::{Bus, Chirp8, Chirp8Mode, DisplayStorage, QuirkFlags};
+
+/// Maximum number of instructions in a compiled block.
+const MAX_BLOCK_LENGTH: usize = 64;
+/// Number of compiled blocks after which all compiled code is freed, since the code of invalidated
+/// blocks cannot be freed individually.
+const MAX_COMPILED_BLOCKS: usize = 4096;
+/// Number of registers V0 to VF.
+const REGISTERS_COUNT: usize = 16;
+
+/// Identifier of the last emulator created, see [next_instance].
+static LAST_INSTANCE: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a new emulator identifier, never 0.
+pub(crate) fn next_instance() -> u64 {
+    LAST_INSTANCE.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// Signature of compiled blocks : takes pointers to registers V0 to VF and to the index register,
+/// returns the address of the next instruction to execute.
+type BlockFunction = unsafe extern "C" fn(*mut u8, *mut u16) -> u32;
+
+/// A basic block of instructions compiled to native code.
+struct CompiledBlock {
+    /// The native code.
+    function: BlockFunction,
+    /// Number of instructions in the block.
+    length: usize,
+    /// Last address read to compile the block, from the block's start address.
+    end: u16,
+}
+
+/// Error raised when the native code generator cannot be created.
+#[derive(Debug)]
+pub struct JitError(String);
+
+impl core::fmt::Display for JitError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "JIT error : {}", self.0)
+    }
+}
+
+impl std::error::Error for JitError {}
+
+/// Just-in-time compiler running an emulator's program as native code.
+///
+/// Basic blocks of arithmetic, register and jump instructions are translated to native code the
+/// first time they are executed, everything else (display, timers, keys, memory accesses) is
+/// handled by the emulator's interpreter. Blocks are recompiled when the program writes over them.
+/// The emulator ends up in the exact same state as if it was only run with [Chirp8::step].
+///
+/// Compiled code does not run when a [crate::WriteMonitor] is attached to the emulator, or if its
+/// bus does not allow caching (see [Bus::CACHEABLE]). A compiler can be used with several
+/// emulators, but switching from one emulator to another forgets all compiled blocks : prefer a
+/// compiler per emulator.
+/// ```
+/// let mut emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::SuperChipModern);
+/// emulator.load_rom(&[0x70, 0x01, 0x12, 0x00]); // Increment v0 forever
+/// let mut jit = chirp8::Jit::new().unwrap();
+/// jit.run_frame(&mut emulator);
+/// assert_eq!(emulator.registers()[0], 15);
+/// ```
+pub struct Jit {
+    /// The code generator, absent if it could not be recreated when flushed.
+    module: Option<JITModule>,
+    /// Reusable function compilation context.
+    context: Context,
+    /// Reusable function building context.
+    builder_context: FunctionBuilderContext,
+    /// Compiled blocks by starting address, `None` when the first instruction cannot be compiled.
+    blocks: HashMap<u16, Option<CompiledBlock>>,
+    /// Number of blocks compiled by current module.
+    compiled: usize,
+    /// The emulator configuration the blocks have been compiled for : mode, quirks, maximum
+    /// block length and address mask.
+    target: Option<(Chirp8Mode, QuirkFlags, usize, u16)>,
+    /// Identifier of the emulator the blocks have been compiled from, 0 if none.
+    instance: u64,
+}
+
+impl Drop for Jit {
+    fn drop(&mut self) {
+        self.blocks.clear();
+        if let Option::Some(module) = self.module.take() {
+            // Safety : the functions of the module are not referenced anymore.
+            unsafe { module.free_memory() };
+        }
+    }
+}
+
+/// Creates a code generator for the host machine.
+fn create_module() -> Result<JITModule, JitError> {
+    let mut flags = settings::builder();
+    let error = |error: &dyn ToString| JitError(error.to_string());
+    flags
+        .set("use_colocated_libcalls", "false")
+        .map_err(|e| error(&e))?;
+    flags.set("is_pic", "false").map_err(|e| error(&e))?;
+    flags.set("opt_level", "speed").map_err(|e| error(&e))?;
+    let isa = cranelift_native::builder()
+        .map_err(|e| error(&e))?
+        .finish(settings::Flags::new(flags))
+        .map_err(|e| error(&e))?;
+    Ok(JITModule::new(JITBuilder::with_isa(
+        isa,
+        default_libcall_names(),
+    )))
+}
+
+/// Indicates whether the given `operation` can be compiled.
+fn is_compilable(operation: Operation) -> bool {
+    matches!(
+        operation,
+        Operation::Set { .. }
+            | Operation::Add { .. }
+            | Operation::Copy { .. }
+            | Operation::Or { .. }
+            | Operation::And { .. }
+            | Operation::Xor { .. }
+            | Operation::AddRegisters { .. }
+            | Operation::Sub { .. }
+            | Operation::ShiftRight { .. }
+            | Operation::SubReverse { .. }
+            | Operation::ShiftLeft { .. }
+            | Operation::SetIndex(_)
+            | Operation::AddIndex(_)
+    ) || is_terminator(operation)
+}
+
+/// Indicates whether the given compilable `operation` ends a block.
+fn is_terminator(operation: Operation) -> bool {
+    matches!(
+        operation,
+        Operation::Jump(_)
+            | Operation::JumpOffset { .. }
+            | Operation::SkipEqual { .. }
+            | Operation::SkipNotEqual { .. }
+            | Operation::SkipEqualRegisters { .. }
+            | Operation::SkipNotEqualRegisters { .. }
+    )
+}
+
+/// Reads the big endian instruction at given `address` of the `emulator`'s memory.
+fn read_instruction<B: Bus, D: DisplayStorage>(emulator: &mut Chirp8<B, D>, address: u16) -> u16 {
+    ((emulator.jit_read(address) as u16) << 8) | emulator.jit_read(address.wrapping_add(1)) as u16
+}
+
+/// Generates the native code of a block, keeping track of the registers loaded and modified.
+struct Translator<'a> {
+    builder: FunctionBuilder<'a>,
+    /// Pointer to V0.
+    registers_pointer: Value,
+    /// Pointer to the index register.
+    index_pointer: Value,
+    /// Current value of each register, if loaded or set.
+    registers: [Option<Value>; REGISTERS_COUNT],
+    /// Registers to be stored at the end of the block.
+    modified: [bool; REGISTERS_COUNT],
+    /// Current value of the index register, if loaded or set.
+    index: Option<Value>,
+    /// Whether the index register must be stored at the end of the block.
+    index_modified: bool,
+    /// Mask to use on addresses, see [Chirp8::ram_size].
+    ram_mask: u16,
+}
+
+impl<'a> Translator<'a> {
+    fn register(&mut self, x: u8) -> Value {
+        let x = x as usize;
+        if let Option::Some(value) = self.registers[x] {
+            return value;
+        }
+        let value = self.builder.ins().load(
+            types::I8,
+            MemFlags::trusted(),
+            self.registers_pointer,
+            x as i32,
+        );
+        self.registers[x] = Option::Some(value);
+        value
+    }
+
+    fn set_register(&mut self, x: u8, value: Value) {
+        self.registers[x as usize] = Option::Some(value);
+        self.modified[x as usize] = true;
+    }
+
+    fn set_flag(&mut self, value: Value) {
+        self.set_register(FLAG_REGISTER_INDEX as u8, value);
+    }
+
+    fn index(&mut self) -> Value {
+        if let Option::Some(value) = self.index {
+            return value;
+        }
+        let value = self
+            .builder
+            .ins()
+            .load(types::I16, MemFlags::trusted(), self.index_pointer, 0);
+        self.index = Option::Some(value);
+        value
+    }
+
+    fn set_index(&mut self, value: Value) {
+        self.index = Option::Some(value);
+        self.index_modified = true;
+    }
+
+    /// Translates a non-terminating `operation`, executed with given `quirks`.
+    fn translate(&mut self, operation: Operation, quirks: QuirkFlags) {
+        match operation {
+            Operation::Set { x, nn } => {
+                let value = self.builder.ins().iconst(types::I8, nn as i64);
+                self.set_register(x, value);
+            }
+            Operation::Add { x, nn } => {
+                let vx = self.register(x);
+                let value = self.builder.ins().iadd_imm(vx, nn as i64);
+                self.set_register(x, value);
+            }
+            Operation::Copy { x, y } => {
+                let vy = self.register(y);
+                self.set_register(x, vy);
+            }
+            Operation::Or { x, y } | Operation::And { x, y } | Operation::Xor { x, y } => {
+                let (vx, vy) = (self.register(x), self.register(y));
+                let value = match operation {
+                    Operation::Or { .. } => self.builder.ins().bor(vx, vy),
+                    Operation::And { .. } => self.builder.ins().band(vx, vy),
+                    _ => self.builder.ins().bxor(vx, vy),
+                };
+                self.set_register(x, value);
+                if quirks.contains(QuirkFlags::FLAG_RESET) {
+                    let zero = self.builder.ins().iconst(types::I8, 0);
+                    self.set_flag(zero);
+                }
+            }
+            Operation::AddRegisters { x, y } => {
+                let (vx, vy) = (self.register(x), self.register(y));
+                let wide_x = self.builder.ins().uextend(types::I16, vx);
+                let wide_y = self.builder.ins().uextend(types::I16, vy);
+                let sum = self.builder.ins().iadd(wide_x, wide_y);
+                let value = self.builder.ins().ireduce(types::I8, sum);
+                let carry = self.builder.ins().ushr_imm(sum, 8);
+                let flag = self.builder.ins().ireduce(types::I8, carry);
+                self.set_register(x, value);
+                self.set_flag(flag);
+            }
+            Operation::Sub { x, y } | Operation::SubReverse { x, y } => {
+                let (vx, vy) = (self.register(x), self.register(y));
+                let (left, right) = match operation {
+                    Operation::Sub { .. } => (vx, vy),
+                    _ => (vy, vx),
+                };
+                let flag = self
+                    .builder
+                    .ins()
+                    .icmp(IntCC::UnsignedGreaterThanOrEqual, left, right);
+                let value = self.builder.ins().isub(left, right);
+                self.set_register(x, value);
+                self.set_flag(flag);
+            }
+            Operation::ShiftRight { x, y } | Operation::ShiftLeft { x, y } => {
+                if !quirks.contains(QuirkFlags::SHIFT_X_ONLY) {
+                    let vy = self.register(y);
+                    self.set_register(x, vy);
+                }
+                let vx = self.register(x);
+                let (value, flag) = match operation {
+                    Operation::ShiftRight { .. } => (
+                        self.builder.ins().ushr_imm(vx, 1),
+                        self.builder.ins().band_imm(vx, 1),
+                    ),
+                    _ => (
+                        self.builder.ins().ishl_imm(vx, 1),
+                        self.builder.ins().ushr_imm(vx, 7),
+                    ),
+                };
+                self.set_register(x, value);
+                self.set_flag(flag);
+            }
+            Operation::SetIndex(nnn) => {
+                let value = self.builder.ins().iconst(types::I16, nnn as i64);
+                self.set_index(value);
+            }
+            Operation::AddIndex(x) => {
+                // The flag is set when the index goes beyond the addressable memory.
+                let vx = self.register(x);
+                let index = self.index();
+                let wide_x = self.builder.ins().uextend(types::I32, vx);
+                let wide_index = self.builder.ins().uextend(types::I32, index);
+                let sum = self.builder.ins().iadd(wide_index, wide_x);
+                let outside = self
+                    .builder
+                    .ins()
+                    .band_imm(sum, !(self.ram_mask as u32) as i64);
+                let overflow = self.builder.ins().icmp_imm(IntCC::NotEqual, outside, 0);
+                let masked = self.builder.ins().band_imm(sum, self.ram_mask as i64);
+                let value = self.builder.ins().ireduce(types::I16, masked);
+                self.set_index(value);
+                let one = self.builder.ins().iconst(types::I8, 1);
+                let flag = self.register(FLAG_REGISTER_INDEX as u8);
+                let flag = self.builder.ins().select(overflow, one, flag);
+                self.set_flag(flag);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Translates the terminating `operation`, returns the address of the next instruction.
+    /// `next` is the address following the operation and `skip` the size of the instruction at
+    /// this address.
+    fn translate_terminator(&mut self, operation: Operation, next: u16, skip: u16) -> Value {
+        let ram_mask = self.ram_mask;
+        let mut constant = |address: u16| {
+            self.builder
+                .ins()
+                .iconst(types::I32, (address & ram_mask) as i64)
+        };
+        let (not_taken, taken) = (constant(next), constant(next.wrapping_add(skip)));
+        let condition = match operation {
+            Operation::Jump(nnn) => return self.builder.ins().iconst(types::I32, nnn as i64),
+            Operation::JumpOffset { nnn, x } => {
+                let vx = self.register(x);
+                let wide_x = self.builder.ins().uextend(types::I32, vx);
+                let address = self.builder.ins().iadd_imm(wide_x, nnn as i64);
+                return self.builder.ins().band_imm(address, self.ram_mask as i64);
+            }
+            Operation::SkipEqual { x, nn } => {
+                let vx = self.register(x);
+                self.builder.ins().icmp_imm(IntCC::Equal, vx, nn as i64)
+            }
+            Operation::SkipNotEqual { x, nn } => {
+                let vx = self.register(x);
+                self.builder.ins().icmp_imm(IntCC::NotEqual, vx, nn as i64)
+            }
+            Operation::SkipEqualRegisters { x, y } => {
+                let (vx, vy) = (self.register(x), self.register(y));
+                self.builder.ins().icmp(IntCC::Equal, vx, vy)
+            }
+            Operation::SkipNotEqualRegisters { x, y } => {
+                let (vx, vy) = (self.register(x), self.register(y));
+                self.builder.ins().icmp(IntCC::NotEqual, vx, vy)
+            }
+            _ => unreachable!(),
+        };
+        self.builder.ins().select(condition, taken, not_taken)
+    }
+
+    /// Stores the modified registers and returns `next_pc`.
+    fn finish(mut self, next_pc: Value) {
+        for x in 0..REGISTERS_COUNT {
+            if let (true, Option::Some(value)) = (self.modified[x], self.registers[x]) {
+                self.builder.ins().store(
+                    MemFlags::trusted(),
+                    value,
+                    self.registers_pointer,
+                    x as i32,
+                );
+            }
+        }
+        if let (true, Option::Some(value)) = (self.index_modified, self.index) {
+            self.builder
+                .ins()
+                .store(MemFlags::trusted(), value, self.index_pointer, 0);
+        }
+        self.builder.ins().return_(&[next_pc]);
+        self.builder.finalize();
+    }
+}
+
+impl Jit {
+    /// Creates a compiler generating code for the host machine.
+    pub fn new() -> Result<Self, JitError> {
+        let module = create_module()?;
+        Ok(Self {
+            context: module.make_context(),
+            module: Option::Some(module),
+            builder_context: FunctionBuilderContext::new(),
+            blocks: HashMap::new(),
+            compiled: 0,
+            target: Option::None,
+            instance: 0,
+        })
+    }
+
+    /// Number of basic blocks currently compiled.
+    pub fn compiled_blocks(&self) -> usize {
+        self.blocks.values().filter(|block| block.is_some()).count()
+    }
+
+    /// Runs as many instructions of the `emulator` as necessary to generate a frame,
+    /// like [Chirp8::run_frame].
+    pub fn run_frame<B: Bus, D: DisplayStorage>(&mut self, emulator: &mut Chirp8<B, D>) {
+        // Do-while
+        loop {
+            self.step(emulator);
+            if emulator.jit_frame_start() || emulator.is_halted() {
+                break;
+            }
+        }
+    }
+
+    /// Executes the compiled block starting at the `emulator`'s program counter, or a single
+    /// instruction with the interpreter when there is no such block, or when it would go beyond
+    /// the end of the current frame.
+    pub fn step<B: Bus, D: DisplayStorage>(&mut self, emulator: &mut Chirp8<B, D>) {
+        self.update_target(emulator);
+        if let Option::Some((first, last)) = emulator.take_written_range() {
+            self.invalidate(first, last);
+        }
+
+        let steps_left = emulator.jit_steps_left();
+        if steps_left > 0 {
+            let pc = emulator.pc();
+            if !self.blocks.contains_key(&pc) {
+                let block = self.compile(emulator, pc);
+                self.blocks.insert(pc, block);
+            }
+            if let Option::Some(Option::Some(block)) = self.blocks.get(&pc) {
+                if block.length <= steps_left {
+                    let (registers, index) = emulator.jit_registers();
+                    // Safety : the block only accesses the given registers.
+                    let next_pc = unsafe { (block.function)(registers.as_mut_ptr(), index) };
+                    emulator.jit_complete(next_pc as u16, block.length);
+                    return;
+                }
+            }
+        }
+        emulator.step();
+    }
+
+    /// Frees all compiled code if the `emulator` configuration changed, and forgets the compiled
+    /// blocks if they were compiled from another emulator.
+    fn update_target<B: Bus, D: DisplayStorage>(&mut self, emulator: &Chirp8<B, D>) {
+        if self.instance != emulator.jit_instance() {
+            self.instance = emulator.jit_instance();
+            self.blocks.clear();
+        }
+        let target = Option::Some((
+            emulator.mode(),
+            emulator.quirks(),
+            MAX_BLOCK_LENGTH.min(emulator.steps_per_frame()),
+            (emulator.ram_size() - 1) as u16,
+        ));
+        if self.target != target {
+            self.target = target;
+            self.flush();
+        }
+    }
+
+    /// Forgets the blocks using addresses between `first` and `last` included.
+    fn invalidate(&mut self, first: u16, last: u16) {
+        if first == 0 && Option::Some(last) == self.target.map(|target| target.3) {
+            self.blocks.clear();
+            return;
+        }
+        self.blocks.retain(|start, block| {
+            let end = match block {
+                Option::Some(block) => block.end,
+                Option::None => start.wrapping_add(1),
+            };
+            end < first || *start > last
+        });
+    }
+
+    /// Frees all compiled code.
+    fn flush(&mut self) {
+        self.blocks.clear();
+        self.compiled = 0;
+        if let Option::Some(module) = self.module.take() {
+            // Safety : the functions of the module are not referenced anymore.
+            unsafe { module.free_memory() };
+        }
+        self.module = create_module().ok();
+    }
+
+    /// Compiles the basic block starting at given `start` address of the `emulator`'s memory.
+    /// Returns `None` if the first instruction cannot be compiled.
+    fn compile<B: Bus, D: DisplayStorage>(
+        &mut self,
+        emulator: &mut Chirp8<B, D>,
+        start: u16,
+    ) -> Option<CompiledBlock> {
+        let (mode, quirks, max_length, ram_mask) = self.target?;
+
+        // Gather the operations of the block.
+        let mut operations = Vec::new();
+        let mut address = start;
+        let mut end = start;
+        let mut skip = PROGRAM_COUNTER_STEP;
+        while operations.len() < max_length && address <= ram_mask - 3 {
+            let operation = Operation::decode(read_instruction(emulator, address), mode, quirks);
+            if !is_compilable(operation) {
+                break;
+            }
+            operations.push(operation);
+            address += PROGRAM_COUNTER_STEP;
+            end = address - 1;
+            if is_terminator(operation) {
+                // Skipping over the 4 bytes long instruction of XO-Chip.
+                const LOAD_LARGE_INDEX_OPCODE: u16 = 0xF000;
+                if mode == Chirp8Mode::XOChip
+                    && read_instruction(emulator, address) == LOAD_LARGE_INDEX_OPCODE
+                {
+                    skip = PROGRAM_COUNTER_STEP * 2;
+                }
+                end = address + 1;
+                break;
+            }
+        }
+        if operations.is_empty() {
+            return Option::None;
+        }
+
+        if self.compiled >= MAX_COMPILED_BLOCKS {
+            self.flush();
+        }
+        let module = self.module.as_mut()?;
+
+        // Generate code.
+        let pointer = module.target_config().pointer_type();
+        let signature = &mut self.context.func.signature;
+        signature.params.push(AbiParam::new(pointer));
+        signature.params.push(AbiParam::new(pointer));
+        signature.returns.push(AbiParam::new(types::I32));
+
+        let mut builder = FunctionBuilder::new(&mut self.context.func, &mut self.builder_context);
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        builder.seal_block(entry);
+        let parameters = builder.block_params(entry);
+        let (registers_pointer, index_pointer) = (parameters[0], parameters[1]);
+        let mut translator = Translator {
+            builder,
+            registers_pointer,
+            index_pointer,
+            registers: [Option::None; REGISTERS_COUNT],
+            modified: [false; REGISTERS_COUNT],
+            index: Option::None,
+            index_modified: false,
+            ram_mask,
+        };
+
+        let mut next_pc = Option::None;
+        for operation in &operations {
+            if is_terminator(*operation) {
+                next_pc = Option::Some(translator.translate_terminator(*operation, address, skip));
+            } else {
+                translator.translate(*operation, quirks);
+            }
+        }
+        let next_pc = next_pc.unwrap_or_else(|| {
+            translator
+                .builder
+                .ins()
+                .iconst(types::I32, (address & ram_mask) as i64)
+        });
+        translator.finish(next_pc);
+
+        let result = module
+            .declare_anonymous_function(&self.context.func.signature)
+            .ok()
+            .and_then(|id| {
+                module
+                    .define_function(id, &mut self.context)
+                    .ok()
+                    .map(|_| id)
+            });
+        module.clear_context(&mut self.context);
+        let id = result?;
+        module.finalize_definitions().ok()?;
+        self.compiled += 1;
+
+        // Safety : the function has been generated with the signature of BlockFunction.
+        let function = unsafe {
+            core::mem::transmute::<*const u8, BlockFunction>(module.get_finalized_function(id))
+        };
+        Option::Some(CompiledBlock {
+            function,
+            length: operations.len(),
+            end,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::DisplayPixels;
+
+    /// Runs the same `rom` for given number of `frames` with and without compiled code,
+    /// asserting that both emulators are in the same state after every frame.
+    fn assert_same_as_interpreter(mode: Chirp8Mode, rom: &[u8], frames: usize) -> Jit {
+        let mut interpreted = Chirp8::new(mode);
+        let mut compiled = Chirp8::new(mode);
+        interpreted.load_rom(rom);
+        compiled.load_rom(rom);
+        let mut jit = Jit::new().unwrap();
+
+        for frame in 0..frames {
+            interpreted.run_frame();
+            jit.run_frame(&mut compiled);
+
+            assert_eq!(
+                interpreted.registers(),
+                compiled.registers(),
+                "frame {}",
+                frame
+            );
+            assert_eq!(interpreted.pc(), compiled.pc(), "frame {}", frame);
+            assert_eq!(interpreted.index(), compiled.index(), "frame {}", frame);
+            assert_eq!(interpreted.steps(), compiled.steps(), "frame {}", frame);
+            assert_eq!(interpreted.bus(), compiled.bus(), "frame {}", frame);
+            assert!(interpreted.get_display_buffer() == compiled.get_display_buffer());
+        }
+        jit
+    }
+
+    #[test]
+    fn jit_arithmetic() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x05, // 200 : v0 = 5
+            0x61, 0xF3, // 202 : v1 = 0xF3
+            0x80, 0x14, // 204 : v0 += v1
+            0x81, 0x05, // 206 : v1 -= v0
+            0x82, 0x06, // 208 : v2 = v0 >> 1
+            0x83, 0x1E, // 20A : v3 = v1 << 1
+            0x82, 0x37, // 20C : v2 = v3 - v2
+            0x84, 0x21, // 20E : v4 |= v2
+            0x85, 0x42, // 210 : v5 &= v4
+            0x86, 0x53, // 212 : v6 ^= v5
+            0x87, 0x30, // 214 : v7 = v3
+            0xA3, 0x00, // 216 : I = 0x300
+            0xF7, 0x1E, // 218 : I += v7
+            0xC8, 0x0F, // 21A : v8 = random
+            0xD8, 0x15, // 21C : draw v8 v1 5
+            0xF2, 0x33, // 21E : BCD v2
+            0x79, 0x01, // 220 : v9 += 1
+            0x39, 0x40, // 222 : skip if v9 == 0x40
+            0x12, 0x04, // 224 : jump 204
+            0x9A, 0x90, // 226 : skip if vA != v9
+            0x6A, 0x40, // 228 : vA = 0x40
+            0x4A, 0x40, // 22A : skip if vA != 0x40
+            0x12, 0x00, // 22C : jump 200
+            0x12, 0x2E, // 22E : infinite loop
+        ];
+        for mode in [
+            Chirp8Mode::CosmacChip8,
+            Chirp8Mode::SuperChip1_1,
+            Chirp8Mode::SuperChipModern,
+            Chirp8Mode::XOChip,
+        ] {
+            let jit = assert_same_as_interpreter(mode, &rom, 200);
+            assert!(jit.compiled_blocks() > 0);
+        }
+    }
+
+    #[test]
+    fn jit_self_modifying_code() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x70, // 200 : v0 = 0x70
+            0x61, 0x01, // 202 : v1 = 0x01
+            0xA2, 0x0C, // 204 : I = 0x20C
+            0x7E, 0x01, // 206 : vE += 1
+            0x3E, 0x20, // 208 : skip if vE == 0x20
+            0x12, 0x0C, // 20A : jump 20C
+            0x60, 0x00, // 20C : patched to "v0 += 1" by the store
+            0x7D, 0x01, // 20E : vD += 1
+            0xF1, 0x55, // 210 : store v0 v1 at 20C
+            0x12, 0x06, // 212 : jump 206
+        ];
+        assert_same_as_interpreter(Chirp8Mode::SuperChipModern, &rom, 50);
+    }
+
+    #[test]
+    fn jit_skip_long_instruction() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x01, // 200 : v0 = 1
+            0x30, 0x01, // 202 : skip if v0 == 1
+            0xF0, 0x00, // 204 : I = 0x1234, skipped
+            0x12, 0x34,
+            0x61, 0x02, // 208 : v1 = 2
+            0x12, 0x0A, // 20A : infinite loop
+        ];
+        assert_same_as_interpreter(Chirp8Mode::XOChip, &rom, 5);
+    }
+
+    #[test]
+    fn jit_several_emulators() {
+        let mode = Chirp8Mode::SuperChipModern;
+        let create = |rom: &[u8]| {
+            let mut emulator = Chirp8::new(mode);
+            emulator.load_rom(rom);
+            emulator
+        };
+        // Increment v0, and v1 by 2, forever.
+        let roms: [&[u8]; 2] = [&[0x70, 0x01, 0x12, 0x00], &[0x71, 0x02, 0x12, 0x00]];
+        let mut interpreted = roms.map(create);
+        let mut compiled = roms.map(create);
+        let mut jit = Jit::new().unwrap();
+
+        for frame in 0..10 {
+            for (interpreted, compiled) in interpreted.iter_mut().zip(compiled.iter_mut()) {
+                interpreted.run_frame();
+                jit.run_frame(compiled);
+                assert_eq!(
+                    interpreted.registers(),
+                    compiled.registers(),
+                    "frame {}",
+                    frame
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn jit_borrowed_buffers() {
+        let mode = Chirp8Mode::CosmacChip8;
+        let rom = [0x70, 0x01, 0xD0, 0x15, 0x12, 0x00]; // Draw "0" moving right
+        let mut interpreted = Chirp8::new(mode);
+        interpreted.load_rom(&rom);
+        let mut ram = [0; crate::MIN_RAM_SIZE];
+        let mut display = [0; crate::DISPLAY_WIDTH * crate::DISPLAY_HEIGHT];
+        let mut compiled = Chirp8::with_buffers(mode, mode.into(), &mut ram, &mut display);
+        compiled.load_rom(&rom);
+        let mut jit = Jit::new().unwrap();
+
+        for _ in 0..20 {
+            interpreted.run_frame();
+            jit.run_frame(&mut compiled);
+        }
+        assert_eq!(interpreted.registers(), compiled.registers());
+        assert_eq!(interpreted.pc(), compiled.pc());
+        drop(compiled);
+        assert_eq!(&interpreted.bus()[..crate::MIN_RAM_SIZE], &ram[..]);
+        for (i, pixel) in display.iter().enumerate() {
+            let (x, y) = (i % crate::DISPLAY_WIDTH, i / crate::DISPLAY_WIDTH);
+            assert_eq!(interpreted.get_display_buffer().pixel(x, y), *pixel);
+        }
+    }
+}
@@ -8,9 +8,64 @@ extern crate std;
 #[cfg(feature = "alloc")]
 extern crate alloc;
 
+mod bus;
 mod chirp8;
+mod colorize;
+mod coverage;
+#[cfg(feature = "std")]
+mod deflate;
+mod diagnostics;
+mod dirty;
+mod display;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod flicker;
+#[cfg(feature = "gdbstub")]
+mod gdb;
+#[cfg(feature = "embedded-graphics")]
+mod graphics;
+#[cfg(feature = "gym")]
+mod gym;
+mod instruction;
+#[cfg(feature = "jit")]
+mod jit;
+mod monitor;
+#[cfg(feature = "netplay")]
+mod netplay;
+mod random;
+mod render;
+#[cfg(feature = "std")]
+mod snapshot;
+mod sprites;
 mod stack;
+mod state;
 mod quirks;
+mod upscale;
 
+pub use bus::*;
 pub use chirp8::*;
+pub use colorize::*;
+pub use coverage::*;
+pub use diagnostics::{UnknownInstruction, UnknownInstructionAction};
+pub use dirty::{DirtyRect, DirtyRegion};
+pub use display::{DisplayPixels, DisplayStorage, FlatDisplay, PackedDisplay};
+#[cfg(feature = "ffi")]
+pub use ffi::*;
+pub use flicker::*;
+#[cfg(feature = "gdbstub")]
+pub use gdb::*;
+#[cfg(feature = "embedded-graphics")]
+pub use graphics::*;
+#[cfg(feature = "gym")]
+pub use gym::*;
+#[cfg(feature = "jit")]
+pub use jit::*;
+pub use monitor::*;
+#[cfg(feature = "netplay")]
+pub use netplay::*;
 pub use quirks::*;
+pub use render::*;
+#[cfg(feature = "std")]
+pub use snapshot::*;
+pub use sprites::*;
+pub use upscale::*;
@@ -0,0 +1,267 @@
+use core::fmt;
+
+use crate::diagnostics::warn_write;
+use crate::{PROGRAM_START, RAM_SIZE};
+
+/// Number of addresses tracked by each word of the executed addresses set.
+const BITS_PER_WORD: usize = u32::BITS as usize;
+
+// Create type alias depending on if the heap is available or not.
+#[cfg(feature = "alloc")]
+type ExecutedData = alloc::vec::Vec<u32>;
+#[cfg(not(feature = "alloc"))]
+type ExecutedData = [u32; RAM_SIZE / BITS_PER_WORD];
+
+/// Why a write has been flagged by a [WriteMonitor].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WriteViolationKind {
+    /// The written address has previously been executed, the program modifies its own code.
+    ExecutedCode,
+    /// The written address lies below [PROGRAM_START], in the font or interpreter area.
+    ReservedMemory,
+}
+
+/// A write flagged by a [WriteMonitor].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WriteViolation {
+    /// Address of the instruction performing the write.
+    pub pc: u16,
+    /// The written address.
+    pub address: u16,
+    /// The written value.
+    pub value: u8,
+    /// Why the write has been flagged.
+    pub kind: WriteViolationKind,
+}
+
+impl fmt::Display for WriteViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Write of 0x{:02X} to {} 0x{:04X}, at program counter 0x{:04X}.",
+            self.value,
+            match self.kind {
+                WriteViolationKind::ExecutedCode => "executed code",
+                WriteViolationKind::ReservedMemory => "reserved memory",
+            },
+            self.address,
+            self.pc
+        )
+    }
+}
+
+/// What a [WriteMonitor] does when a flagged write occurs.
+#[derive(Clone, Copy)]
+pub enum WriteAction {
+    /// The write is performed and a warning is logged, like
+    /// [crate::UnknownInstructionAction::Log].
+    Warn,
+    /// The write is performed, then given function is called.
+    Callback(fn(&WriteViolation)),
+    /// The write is not performed and the emulator halts, see [crate::Chirp8::is_halted].
+    Error,
+}
+
+/// Opt-in monitor flagging writes to code that has already been executed, or to memory reserved
+/// for the font and interpreter. Executed addresses are tracked in the first [RAM_SIZE] bytes.
+///
+/// Attach it to an emulator as follow :
+/// ```
+/// let mut emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+/// emulator.set_write_monitor(Some(chirp8::WriteMonitor::new(chirp8::WriteAction::Error)));
+/// emulator.load_rom(&[0xF0, 0x55]); // Store v0 at index 0, the font area.
+/// emulator.step();
+/// assert!(emulator.is_halted());
+/// let violation = emulator.write_monitor().unwrap().last_violation().unwrap();
+/// assert_eq!(violation.pc, 0x200);
+/// assert_eq!(violation.kind, chirp8::WriteViolationKind::ReservedMemory);
+/// ```
+pub struct WriteMonitor {
+    /// One bit per address, set when the address has been fetched as part of an instruction.
+    executed: ExecutedData,
+    /// What to do when a flagged write occurs.
+    action: WriteAction,
+    /// The most recent flagged write.
+    last_violation: Option<WriteViolation>,
+    /// Number of flagged writes so far.
+    violations: usize,
+}
+
+impl WriteMonitor {
+    /// Creates a monitor which will react to flagged writes with given `action`.
+    pub fn new(action: WriteAction) -> Self {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "alloc")]{
+                let executed = alloc::vec![0u32; RAM_SIZE / BITS_PER_WORD];
+            }else{
+                let executed = [0u32; RAM_SIZE / BITS_PER_WORD];
+            }
+        }
+
+        Self {
+            executed,
+            action,
+            last_violation: Option::None,
+            violations: 0,
+        }
+    }
+
+    /// Returns the action taken on flagged writes.
+    pub fn action(&self) -> WriteAction {
+        self.action
+    }
+
+    /// Returns the most recent flagged write, if any.
+    pub fn last_violation(&self) -> Option<WriteViolation> {
+        self.last_violation
+    }
+
+    /// Returns the number of flagged writes so far.
+    pub fn violations(&self) -> usize {
+        self.violations
+    }
+
+    /// Indicates whether given `address` has been executed so far.
+    pub fn is_executed(&self, address: u16) -> bool {
+        let address = address as usize;
+        self.executed
+            .get(address / BITS_PER_WORD)
+            .is_some_and(|word| word & (1 << (address % BITS_PER_WORD)) != 0)
+    }
+
+    /// Forgets executed addresses and flagged writes, for instance after loading a new program.
+    pub fn clear(&mut self) {
+        self.executed.fill(0);
+        self.last_violation = Option::None;
+        self.violations = 0;
+    }
+
+    /// Marks given `address` as executed.
+    #[inline]
+    pub(crate) fn record_execution(&mut self, address: u16) {
+        let address = address as usize;
+        if let Option::Some(word) = self.executed.get_mut(address / BITS_PER_WORD) {
+            *word |= 1 << (address % BITS_PER_WORD);
+        }
+    }
+
+    /// Checks the write of `value` at `address` by the instruction at `pc`.
+    /// Returns false if the write must not be performed.
+    pub(crate) fn check_write(&mut self, pc: u16, address: u16, value: u8) -> bool {
+        let kind = if (address as usize) < PROGRAM_START {
+            WriteViolationKind::ReservedMemory
+        } else if self.is_executed(address) {
+            WriteViolationKind::ExecutedCode
+        } else {
+            return true;
+        };
+
+        let violation = WriteViolation {
+            pc,
+            address,
+            value,
+            kind,
+        };
+        self.last_violation = Option::Some(violation);
+        self.violations += 1;
+
+        match self.action {
+            WriteAction::Warn => {
+                warn_write(&violation);
+                true
+            }
+            WriteAction::Callback(callback) => {
+                callback(&violation);
+                true
+            }
+            WriteAction::Error => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn monitor_reserved_range() {
+        let mut monitor = WriteMonitor::new(WriteAction::Error);
+        assert!(!monitor.check_write(0x200, 0x000, 1));
+        assert!(!monitor.check_write(0x202, PROGRAM_START as u16 - 1, 2));
+        assert!(monitor.check_write(0x204, PROGRAM_START as u16, 3));
+        assert!(monitor.check_write(0x206, (RAM_SIZE - 1) as u16, 4));
+        assert_eq!(monitor.violations(), 2);
+        assert_eq!(
+            monitor.last_violation(),
+            Option::Some(WriteViolation {
+                pc: 0x202,
+                address: PROGRAM_START as u16 - 1,
+                value: 2,
+                kind: WriteViolationKind::ReservedMemory,
+            })
+        );
+    }
+
+    #[test]
+    fn monitor_executed_ranges() {
+        let mut monitor = WriteMonitor::new(WriteAction::Warn);
+        // Two separate pieces of code, the first one crossing a word of the executed set.
+        for address in 0x21E..0x222 {
+            monitor.record_execution(address);
+        }
+        monitor.record_execution(0x300);
+        monitor.record_execution(0x301);
+
+        for address in 0x21C..0x224 {
+            assert_eq!(
+                monitor.is_executed(address),
+                (0x21E..0x222).contains(&address)
+            );
+        }
+        assert!(!monitor.is_executed(0x2FF));
+        assert!(monitor.is_executed(0x300) && monitor.is_executed(0x301));
+        assert!(!monitor.is_executed(0x302));
+
+        // Warnings do not prevent writes.
+        assert!(monitor.check_write(0x300, 0x21D, 0));
+        assert!(monitor.check_write(0x300, 0x21E, 0));
+        assert!(monitor.check_write(0x300, 0x221, 0));
+        assert!(monitor.check_write(0x300, 0x222, 0));
+        assert!(monitor.check_write(0x21E, 0x301, 0));
+        assert_eq!(monitor.violations(), 3);
+        let violation = monitor.last_violation().unwrap();
+        assert_eq!((violation.pc, violation.address), (0x21E, 0x301));
+        assert_eq!(violation.kind, WriteViolationKind::ExecutedCode);
+    }
+
+    #[test]
+    fn monitor_clear() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let mut monitor = WriteMonitor::new(WriteAction::Callback(|violation| {
+            assert_eq!(violation.kind, WriteViolationKind::ExecutedCode);
+            CALLS.fetch_add(1, Ordering::Relaxed);
+        }));
+        monitor.record_execution(0x200);
+        monitor.record_execution(0x201);
+        assert!(monitor.check_write(0x202, 0x200, 0xFF));
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+
+        // Executed addresses are forgotten, the reserved memory is still flagged.
+        monitor.clear();
+        assert_eq!(monitor.violations(), 0);
+        assert_eq!(monitor.last_violation(), Option::None);
+        assert!(!monitor.is_executed(0x200) && !monitor.is_executed(0x201));
+        assert!(monitor.check_write(0x202, 0x200, 0xFF));
+        assert!(monitor.check_write(0x202, 0x201, 0xFF));
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+        assert_eq!(monitor.violations(), 0);
+
+        let mut monitor = WriteMonitor::new(WriteAction::Error);
+        monitor.clear();
+        assert!(!monitor.check_write(0x202, 0x050, 0));
+        assert_eq!(monitor.violations(), 1);
+    }
+}
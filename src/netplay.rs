@@ -0,0 +1,795 @@
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::vec::Vec;
+
+use crate::chirp8::KEYS_COUNT;
+use crate::{Chirp8, DisplayPixels, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+/// Maximum number of inputs carried by a single message.
+const MAX_INPUTS_PER_MESSAGE: usize = 64;
+/// Maximum input delay plus rollback, in frames, of a [NetplaySession].
+///
+/// A peer can run up to `delay + max_rollback` frames ahead of the keys it got from the other,
+/// which can itself be as far ahead, so up to twice as many keys may be unacknowledged and
+/// must fit in a message.
+pub const MAX_INPUT_WINDOW: u32 = MAX_INPUTS_PER_MESSAGE as u32 / 2;
+/// Size of the largest message, in bytes.
+const MAX_MESSAGE_SIZE: usize = 4 + 4 + 1 + MAX_INPUTS_PER_MESSAGE * 2 + 4 + 8;
+/// Frame number meaning "no frame".
+const NO_FRAME: u32 = u32::MAX;
+
+/// Returns the FNV-1a hash of the `emulator`'s memory and display, identical on all peers as long
+/// as they are in sync.
+pub fn state_hash(emulator: &Chirp8) -> u64 {
+    const OFFSET_BASIS: u64 = 0xCBF29CE484222325;
+    const PRIME: u64 = 0x100000001B3;
+    let display = emulator.get_display_buffer();
+    let pixels =
+        (0..DISPLAY_HEIGHT).flat_map(|y| (0..DISPLAY_WIDTH).map(move |x| display.pixel(x, y)));
+    emulator
+        .bus()
+        .iter()
+        .copied()
+        .chain(pixels)
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(PRIME)
+        })
+}
+
+/// Error raised by a [NetplaySession].
+#[derive(Debug)]
+pub enum NetplayError {
+    /// The transport failed, or the peer disconnected.
+    Io(io::Error),
+    /// The peers computed different states at given frame.
+    Desync { frame: u32 },
+}
+
+impl core::fmt::Display for NetplayError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            NetplayError::Io(error) => write!(f, "Netplay transport error : {}", error),
+            NetplayError::Desync { frame } => write!(f, "Peers desynchronized at frame {}", frame),
+        }
+    }
+}
+
+impl std::error::Error for NetplayError {}
+
+impl From<io::Error> for NetplayError {
+    fn from(error: io::Error) -> Self {
+        NetplayError::Io(error)
+    }
+}
+
+/// Unreliable or reliable channel carrying messages between two peers, without blocking.
+pub trait Transport {
+    /// Sends a whole message to the peer.
+    fn send(&mut self, message: &[u8]) -> io::Result<()>;
+
+    /// Receives the next whole message into `buffer`, returning its length,
+    /// or `None` if no message is available yet.
+    fn receive(&mut self, buffer: &mut [u8]) -> io::Result<Option<usize>>;
+}
+
+/// Transport over a connected UDP socket. Lost messages are made up for by the following ones.
+pub struct UdpTransport {
+    socket: UdpSocket,
+}
+
+impl UdpTransport {
+    /// Uses given `socket`, which must already be connected to the peer.
+    pub fn new(socket: UdpSocket) -> io::Result<Self> {
+        socket.set_nonblocking(true)?;
+        Ok(Self { socket })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        match self.socket.send(message) {
+            // The message is lost, as it could be on the way.
+            Err(error) if error.kind() == ErrorKind::WouldBlock => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+
+    fn receive(&mut self, buffer: &mut [u8]) -> io::Result<Option<usize>> {
+        match self.socket.recv(buffer) {
+            Ok(length) => Ok(Option::Some(length)),
+            Err(error) if error.kind() == ErrorKind::WouldBlock => Ok(Option::None),
+            // The peer is not listening yet.
+            Err(error) if error.kind() == ErrorKind::ConnectionRefused => Ok(Option::None),
+            Err(error) => Err(error),
+        }
+    }
+}
+
+/// Transport over a TCP stream, each message being prefixed with its length.
+pub struct TcpTransport {
+    stream: TcpStream,
+    /// Bytes received but not yet returned.
+    pending: Vec<u8>,
+    /// Bytes sent but not yet accepted by the stream.
+    unsent: Vec<u8>,
+}
+
+impl TcpTransport {
+    /// Uses given `stream`, connected to the peer.
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        Ok(Self {
+            stream,
+            pending: Vec::new(),
+            unsent: Vec::new(),
+        })
+    }
+
+    /// Writes as many unsent bytes as the stream accepts without blocking, the others being
+    /// written on the next call.
+    fn flush(&mut self) -> io::Result<()> {
+        let mut written = 0;
+        while written < self.unsent.len() {
+            match self.stream.write(&self.unsent[written..]) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(length) => written += length,
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+        }
+        self.unsent.drain(..written);
+        Ok(())
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        // Messages cannot be dropped from a stream, those not fully written are kept for later.
+        self.unsent
+            .extend_from_slice(&(message.len() as u16).to_be_bytes());
+        self.unsent.extend_from_slice(message);
+        self.flush()
+    }
+
+    fn receive(&mut self, buffer: &mut [u8]) -> io::Result<Option<usize>> {
+        self.flush()?;
+        let mut chunk = [0u8; 512];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(length) => self.pending.extend_from_slice(&chunk[..length]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => return Err(error),
+            }
+        }
+        if self.pending.len() < 2 {
+            return Ok(Option::None);
+        }
+        let length = u16::from_be_bytes([self.pending[0], self.pending[1]]) as usize;
+        if self.pending.len() < 2 + length {
+            return Ok(Option::None);
+        }
+        if length > buffer.len() {
+            return Err(ErrorKind::InvalidData.into());
+        }
+        buffer[..length].copy_from_slice(&self.pending[2..2 + length]);
+        self.pending.drain(..2 + length);
+        Ok(Option::Some(length))
+    }
+}
+
+/// A message from a peer.
+struct Message {
+    /// The next frame whose input the sender expects from the receiver.
+    next_expected: u32,
+    /// Frame of the first input.
+    first_frame: u32,
+    /// Keys pressed by the sender from `first_frame` onward, one bit per key.
+    inputs: Vec<u16>,
+    /// A frame whose inputs are all known by the sender, or [NO_FRAME].
+    hash_frame: u32,
+    /// The [state_hash] after `hash_frame`.
+    hash: u64,
+}
+
+impl Message {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.clear();
+        buffer.extend_from_slice(&self.next_expected.to_be_bytes());
+        buffer.extend_from_slice(&self.first_frame.to_be_bytes());
+        buffer.push(self.inputs.len() as u8);
+        for keys in &self.inputs {
+            buffer.extend_from_slice(&keys.to_be_bytes());
+        }
+        buffer.extend_from_slice(&self.hash_frame.to_be_bytes());
+        buffer.extend_from_slice(&self.hash.to_be_bytes());
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        let u32_at = |offset: usize| -> Option<u32> {
+            Option::Some(u32::from_be_bytes(
+                data.get(offset..offset + 4)?.try_into().ok()?,
+            ))
+        };
+        let count = *data.get(8)? as usize;
+        let hash_offset = 9 + count * 2;
+        Option::Some(Self {
+            next_expected: u32_at(0)?,
+            first_frame: u32_at(4)?,
+            inputs: data
+                .get(9..hash_offset)?
+                .chunks_exact(2)
+                .map(|keys| u16::from_be_bytes([keys[0], keys[1]]))
+                .collect(),
+            hash_frame: u32_at(hash_offset)?,
+            hash: u64::from_be_bytes(
+                data.get(hash_offset + 4..hash_offset + 12)?
+                    .try_into()
+                    .ok()?,
+            ),
+        })
+    }
+}
+
+/// One of the two peers of a networked game, both running the same program on their own
+/// emulator.
+///
+/// Every frame, the keys pressed locally are sent to the peer, and the emulator runs with the keys
+/// pressed on either side. Local keys take effect after an input delay, giving them time to reach
+/// the peer. When the keys of the peer are late, they are predicted to be unchanged and the frame
+/// runs anyway, up to a number of frames : the emulator is rolled back to a snapshot and the
+/// frames are run again if the prediction was wrong. The states of both emulators are hashed and
+/// compared to detect desynchronization.
+/// ```no_run
+/// let socket = std::net::UdpSocket::bind("0.0.0.0:8000").unwrap();
+/// socket.connect("192.168.0.2:8000").unwrap();
+/// let transport = chirp8::UdpTransport::new(socket).unwrap();
+///
+/// let mut emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+/// emulator.load_rom(&[0x12, 0x00]);
+/// let mut session = chirp8::NetplaySession::new(emulator, transport, 2, 8);
+/// loop {
+///     let keys = 0b10; // Key 1 pressed
+///     session.advance(keys).unwrap();
+///     let screen = session.emulator().get_display_buffer();
+///     // ...
+/// }
+/// ```
+pub struct NetplaySession<T: Transport> {
+    emulator: Chirp8,
+    transport: T,
+    /// Number of frames before local keys take effect.
+    delay: u32,
+    /// Maximum number of frames run with predicted keys of the peer.
+    max_rollback: u32,
+    /// The next frame to run.
+    frame: u32,
+    /// Keys pressed locally, by frame.
+    local_inputs: HashMap<u32, u16>,
+    /// Keys pressed by the peer, by frame.
+    remote_inputs: HashMap<u32, u16>,
+    /// The first frame whose keys from the peer are unknown.
+    remote_next_expected: u32,
+    /// The first frame whose keys the peer does not know yet.
+    local_acknowledged: u32,
+    /// The keys of the peer used to run frames they were unknown for.
+    predictions: HashMap<u32, u16>,
+    /// Save states taken before running frames, indexed by frame modulo their count.
+    snapshots: Vec<Vec<u8>>,
+    /// [state_hash] after every recently run frame.
+    local_hashes: HashMap<u32, u64>,
+    /// Hashes received from the peer, by frame.
+    remote_hashes: HashMap<u32, u64>,
+    /// Number of rollbacks so far.
+    rollbacks: usize,
+    /// Reusable message buffer.
+    buffer: Vec<u8>,
+}
+
+impl<T: Transport> NetplaySession<T> {
+    /// Starts a session with the `emulator` in the same state as the peer's one, sending and
+    /// receiving messages through `transport`. Local keys take effect after `delay` frames, and up
+    /// to `max_rollback` frames can be run before the keys of the peer are known, 0 making
+    /// both emulators run in strict lockstep.
+    ///
+    /// # Panics
+    ///
+    /// The keys not yet acknowledged by the peer are sent again in every message, which can only
+    /// carry so many of them. Panics if `delay` and `max_rollback` add up to more than
+    /// [MAX_INPUT_WINDOW] frames.
+    pub fn new(emulator: Chirp8, transport: T, delay: u32, max_rollback: u32) -> Self {
+        assert!(
+            delay + max_rollback <= MAX_INPUT_WINDOW,
+            "Input delay and rollback of {} frames exceed {} frames",
+            delay + max_rollback,
+            MAX_INPUT_WINDOW
+        );
+        let state_size = emulator.state_size();
+        let mut session = Self {
+            emulator,
+            transport,
+            delay,
+            max_rollback,
+            frame: 0,
+            local_inputs: HashMap::new(),
+            remote_inputs: HashMap::new(),
+            remote_next_expected: delay,
+            local_acknowledged: 0,
+            predictions: HashMap::new(),
+            snapshots: (0..=max_rollback)
+                .map(|_| std::vec![0; state_size])
+                .collect(),
+            local_hashes: HashMap::new(),
+            remote_hashes: HashMap::new(),
+            rollbacks: 0,
+            buffer: Vec::with_capacity(MAX_MESSAGE_SIZE),
+        };
+        // No key is pressed during the input delay.
+        for frame in 0..delay {
+            session.local_inputs.insert(frame, 0);
+            session.remote_inputs.insert(frame, 0);
+        }
+        session
+    }
+
+    /// Returns the emulator, to get its display or sound.
+    pub fn emulator(&self) -> &Chirp8 {
+        &self.emulator
+    }
+
+    /// Returns the next frame to run.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Returns the number of times the emulator has been rolled back so far.
+    pub fn rollbacks(&self) -> usize {
+        self.rollbacks
+    }
+
+    /// Exchanges messages with the peer and runs the next frame, the `keys` pressed locally
+    /// (one bit per key) taking effect after the input delay.
+    ///
+    /// Returns `Ok(false)` without running the frame when waiting for the keys of the peer,
+    /// in which case `keys` are ignored and the call must be repeated.
+    pub fn advance(&mut self, keys: u16) -> Result<bool, NetplayError> {
+        self.receive()?;
+
+        let can_run = self.frame < self.remote_next_expected + self.max_rollback;
+        if can_run {
+            self.local_inputs.insert(self.frame + self.delay, keys);
+            self.run_frame();
+        }
+
+        self.check_hashes()?;
+        self.send()?;
+        self.forget_old_frames();
+        Ok(can_run)
+    }
+
+    /// Runs the next frame with local and remote keys, predicting the remote ones if unknown.
+    /// The snapshot before the frame is taken first.
+    fn run_frame(&mut self) {
+        let slot = (self.frame % self.snapshots.len() as u32) as usize;
+        self.emulator.save_state(&mut self.snapshots[slot]);
+
+        let local = self.local_inputs.get(&self.frame).copied().unwrap_or(0);
+        let remote = match self.remote_inputs.get(&self.frame) {
+            Option::Some(keys) => *keys,
+            Option::None => {
+                // Keys of the peer are predicted to be the last known ones.
+                let keys = self
+                    .remote_next_expected
+                    .checked_sub(1)
+                    .and_then(|frame| self.remote_inputs.get(&frame).copied())
+                    .unwrap_or(0);
+                self.predictions.insert(self.frame, keys);
+                keys
+            }
+        };
+
+        let keys = local | remote;
+        for key in 0..KEYS_COUNT {
+            self.emulator.key_set(key, keys & (1 << key) != 0);
+        }
+        self.emulator.run_frame();
+        self.local_hashes
+            .insert(self.frame, state_hash(&self.emulator));
+        self.frame += 1;
+    }
+
+    /// Processes the messages received from the peer, rolling back on mispredictions.
+    fn receive(&mut self) -> Result<(), NetplayError> {
+        let mut data = [0u8; MAX_MESSAGE_SIZE];
+        let mut mispredicted = Option::None;
+        while let Option::Some(length) = self.transport.receive(&mut data)? {
+            let Option::Some(message) = Message::decode(&data[..length]) else {
+                continue;
+            };
+            self.local_acknowledged = self.local_acknowledged.max(message.next_expected);
+            for (offset, keys) in message.inputs.iter().enumerate() {
+                let frame = message.first_frame + offset as u32;
+                if frame < self.remote_next_expected {
+                    continue;
+                }
+                self.remote_inputs.insert(frame, *keys);
+                if let Option::Some(prediction) = self.predictions.remove(&frame) {
+                    if prediction != *keys {
+                        mispredicted =
+                            Option::Some(mispredicted.map_or(frame, |first: u32| first.min(frame)));
+                    }
+                }
+            }
+            while self.remote_inputs.contains_key(&self.remote_next_expected) {
+                self.remote_next_expected += 1;
+            }
+            if message.hash_frame != NO_FRAME {
+                self.remote_hashes.insert(message.hash_frame, message.hash);
+            }
+        }
+
+        if let Option::Some(first) = mispredicted {
+            self.rollback(first);
+        }
+        Ok(())
+    }
+
+    /// Restores the state before given `frame` and runs the frames again up to the current one.
+    fn rollback(&mut self, frame: u32) {
+        self.rollbacks += 1;
+        let target = self.frame;
+        let slot = (frame % self.snapshots.len() as u32) as usize;
+        self.emulator.load_state(&self.snapshots[slot]);
+        self.frame = frame;
+        // Predictions of the frames run again are made anew.
+        self.predictions.retain(|predicted, _| *predicted < frame);
+        while self.frame < target {
+            self.run_frame();
+        }
+    }
+
+    /// Returns the last frame run with the actual keys of both peers.
+    fn confirmed_frame(&self) -> Option<u32> {
+        self.remote_next_expected.min(self.frame).checked_sub(1)
+    }
+
+    /// Compares the hashes of the frames confirmed on both sides.
+    fn check_hashes(&mut self) -> Result<(), NetplayError> {
+        let Option::Some(confirmed) = self.confirmed_frame() else {
+            return Ok(());
+        };
+        let mut desync = Option::None;
+        self.remote_hashes.retain(|frame, hash| {
+            if *frame > confirmed {
+                return true;
+            }
+            if let Option::Some(local) = self.local_hashes.get(frame) {
+                if local != hash {
+                    desync = Option::Some(desync.map_or(*frame, |first: u32| first.min(*frame)));
+                }
+            }
+            false
+        });
+        match desync {
+            Option::Some(frame) => Err(NetplayError::Desync { frame }),
+            Option::None => Ok(()),
+        }
+    }
+
+    /// Sends the local keys the peer does not know yet, and the hash of the last confirmed frame.
+    fn send(&mut self) -> Result<(), NetplayError> {
+        let last = self.frame + self.delay;
+        let first_frame = self
+            .local_acknowledged
+            .max(last.saturating_sub(MAX_INPUTS_PER_MESSAGE as u32));
+        let (hash_frame, hash) = self
+            .confirmed_frame()
+            .and_then(|frame| Option::Some((frame, *self.local_hashes.get(&frame)?)))
+            .unwrap_or((NO_FRAME, 0));
+        let message = Message {
+            next_expected: self.remote_next_expected,
+            first_frame,
+            inputs: (first_frame..last)
+                .map(|frame| self.local_inputs.get(&frame).copied().unwrap_or(0))
+                .collect(),
+            hash_frame,
+            hash,
+        };
+        message.encode(&mut self.buffer);
+        self.transport.send(&self.buffer)?;
+        Ok(())
+    }
+
+    /// Forgets inputs and hashes no longer needed.
+    fn forget_old_frames(&mut self) {
+        let oldest = self
+            .local_acknowledged
+            .min(self.remote_next_expected)
+            .saturating_sub(self.max_rollback + self.delay + MAX_INPUTS_PER_MESSAGE as u32);
+        if oldest > 0 {
+            self.local_inputs.retain(|frame, _| *frame >= oldest);
+            self.remote_inputs.retain(|frame, _| *frame >= oldest);
+            self.local_hashes.retain(|frame, _| *frame >= oldest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::net::TcpListener;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::Chirp8Mode;
+
+    #[rustfmt::skip]
+    const ROM: [u8; 22] = [
+        0x61, 0x00, // 200 : v1 = 0
+        0xE1, 0xA1, // 202 : skip if key v1 not pressed
+        0x72, 0x01, // 204 : v2 += 1
+        0x71, 0x01, // 206 : v1 += 1
+        0x41, 0x10, // 208 : skip if v1 != 16
+        0x61, 0x00, // 20A : v1 = 0
+        0xC3, 0xFF, // 20C : v3 = random
+        0xA3, 0x00, // 20E : I = 0x300
+        0xF3, 0x55, // 210 : store v0 to v3
+        0xD2, 0x31, // 212 : draw v2 v3 1
+        0x12, 0x02, // 214 : jump 202
+    ];
+
+    /// Messages in flight, with the number of receive attempts left before their delivery.
+    type Queue = Rc<RefCell<VecDeque<(usize, Vec<u8>)>>>;
+
+    /// In-memory transport delivering messages after a number of receive attempts, and losing
+    /// some of them.
+    struct DelayedTransport {
+        outgoing: Queue,
+        incoming: Queue,
+        latency: usize,
+        /// Returns whether the n-th sent message is lost.
+        loss: fn(usize) -> bool,
+        sent: usize,
+    }
+
+    impl DelayedTransport {
+        fn pair(latency: usize) -> (Self, Self) {
+            Self::lossy_pair(latency, |_| false)
+        }
+
+        fn lossy_pair(latency: usize, loss: fn(usize) -> bool) -> (Self, Self) {
+            let (a, b) = (Rc::default(), Rc::default());
+            (
+                Self {
+                    outgoing: Rc::clone(&a),
+                    incoming: Rc::clone(&b),
+                    latency,
+                    loss,
+                    sent: 0,
+                },
+                Self {
+                    outgoing: b,
+                    incoming: a,
+                    latency,
+                    loss,
+                    sent: 0,
+                },
+            )
+        }
+    }
+
+    impl Transport for DelayedTransport {
+        fn send(&mut self, message: &[u8]) -> io::Result<()> {
+            self.sent += 1;
+            if !(self.loss)(self.sent) {
+                self.outgoing
+                    .borrow_mut()
+                    .push_back((self.latency, message.to_vec()));
+            }
+            Ok(())
+        }
+
+        fn receive(&mut self, buffer: &mut [u8]) -> io::Result<Option<usize>> {
+            let mut incoming = self.incoming.borrow_mut();
+            match incoming.front_mut() {
+                Option::Some((0, _)) => {
+                    let (_, message) = incoming.pop_front().unwrap();
+                    buffer[..message.len()].copy_from_slice(&message);
+                    Ok(Option::Some(message.len()))
+                }
+                Option::Some(_) => {
+                    for (wait, _) in incoming.iter_mut() {
+                        *wait = wait.saturating_sub(1);
+                    }
+                    Ok(Option::None)
+                }
+                Option::None => Ok(Option::None),
+            }
+        }
+    }
+
+    /// Keys pressed by a peer at given frame, varying often.
+    fn keys(peer: u32, frame: u32) -> u16 {
+        let value = (frame / 3)
+            .wrapping_mul(2654435761)
+            .wrapping_add(peer * 40503);
+        if (frame / 5 + peer) & 1 == 0 {
+            1 << (value % 16)
+        } else {
+            0
+        }
+    }
+
+    fn emulator(rom: &[u8]) -> Chirp8 {
+        let mut emulator = Chirp8::new(Chirp8Mode::SuperChipModern);
+        emulator.load_rom(rom);
+        emulator
+    }
+
+    /// Runs both sessions until they both ran `frames` frames and confirmed them.
+    fn play<T: Transport>(
+        first: &mut NetplaySession<T>,
+        second: &mut NetplaySession<T>,
+        frames: u32,
+    ) -> Result<(), NetplayError> {
+        let mut attempts = 0;
+        while first.confirmed_frame() < Option::Some(frames)
+            || second.confirmed_frame() < Option::Some(frames)
+        {
+            first.advance(keys(0, first.frame()))?;
+            second.advance(keys(1, second.frame()))?;
+            attempts += 1;
+            assert!(attempts < 100_000, "Peers are stuck");
+        }
+        Ok(())
+    }
+
+    /// Runs the frames of the sessions on a single emulator, as if both peers shared the keypad.
+    fn assert_same_as_local(session: &NetplaySession<impl Transport>, delay: u32, rom: &[u8]) {
+        let mut local = emulator(rom);
+        for frame in 0..session.frame() {
+            let keys = if frame < delay {
+                0
+            } else {
+                keys(0, frame - delay) | keys(1, frame - delay)
+            };
+            for key in 0..KEYS_COUNT {
+                local.key_set(key, keys & (1 << key) != 0);
+            }
+            local.run_frame();
+        }
+        assert_eq!(state_hash(&local), state_hash(session.emulator()));
+    }
+
+    #[test]
+    fn netplay_rollback() {
+        let (a, b) = DelayedTransport::pair(3);
+        let mut first = NetplaySession::new(emulator(&ROM), a, 1, 8);
+        let mut second = NetplaySession::new(emulator(&ROM), b, 1, 8);
+        play(&mut first, &mut second, 200).unwrap();
+        assert!(first.rollbacks() > 0 && second.rollbacks() > 0);
+
+        // Bring both peers to the same frame.
+        while first.frame() != second.frame() {
+            if first.frame() < second.frame() {
+                first.advance(keys(0, first.frame())).unwrap();
+            } else {
+                second.advance(keys(1, second.frame())).unwrap();
+            }
+        }
+        // Let the last predictions be corrected.
+        for _ in 0..20 {
+            first.receive().unwrap();
+            second.receive().unwrap();
+            first.send().unwrap();
+            second.send().unwrap();
+        }
+        assert_eq!(state_hash(first.emulator()), state_hash(second.emulator()));
+        assert_same_as_local(&first, 1, &ROM);
+    }
+
+    #[test]
+    fn netplay_lossy() {
+        // One message out of three is lost, and bursts of 40 messages are lost now and then.
+        let (a, b) = DelayedTransport::lossy_pair(8, |sent| sent % 3 == 0 || sent % 500 < 40);
+        let (delay, max_rollback) = (4, MAX_INPUT_WINDOW - 4);
+        let mut first = NetplaySession::new(emulator(&ROM), a, delay, max_rollback);
+        let mut second = NetplaySession::new(emulator(&ROM), b, delay, max_rollback);
+        play(&mut first, &mut second, 1000).unwrap();
+        assert!(first.rollbacks() > 0 && second.rollbacks() > 0);
+
+        // Every frame confirmed on both sides ran with the actual keys of both peers.
+        let confirmed = first
+            .confirmed_frame()
+            .unwrap()
+            .min(second.confirmed_frame().unwrap());
+        let mut local = emulator(&ROM);
+        for frame in 0..=confirmed {
+            let keys = if frame < delay {
+                0
+            } else {
+                keys(0, frame - delay) | keys(1, frame - delay)
+            };
+            for key in 0..KEYS_COUNT {
+                local.key_set(key, keys & (1 << key) != 0);
+            }
+            local.run_frame();
+        }
+        for session in [&first, &second] {
+            assert_eq!(
+                session.local_hashes.get(&confirmed),
+                Option::Some(&state_hash(&local))
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn netplay_window_too_large() {
+        let (a, _) = DelayedTransport::pair(1);
+        NetplaySession::new(emulator(&ROM), a, 4, MAX_INPUT_WINDOW - 3);
+    }
+
+    #[test]
+    fn netplay_lockstep() {
+        let (a, b) = DelayedTransport::pair(2);
+        let mut first = NetplaySession::new(emulator(&ROM), a, 2, 0);
+        let mut second = NetplaySession::new(emulator(&ROM), b, 2, 0);
+        play(&mut first, &mut second, 100).unwrap();
+        assert_eq!(first.rollbacks() + second.rollbacks(), 0);
+        assert!(first.frame().abs_diff(second.frame()) <= 2);
+    }
+
+    #[test]
+    fn netplay_desync() {
+        let mut other_rom = ROM;
+        other_rom[3] = 0x9E; // Skip if key pressed instead of not pressed.
+        let (a, b) = DelayedTransport::pair(1);
+        let mut first = NetplaySession::new(emulator(&ROM), a, 1, 4);
+        let mut second = NetplaySession::new(emulator(&other_rom), b, 1, 4);
+        assert!(matches!(
+            play(&mut first, &mut second, 100),
+            Err(NetplayError::Desync { .. })
+        ));
+    }
+
+    #[test]
+    fn netplay_tcp_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let mut first =
+            NetplaySession::new(emulator(&ROM), TcpTransport::new(server).unwrap(), 2, 4);
+        let mut second =
+            NetplaySession::new(emulator(&ROM), TcpTransport::new(client).unwrap(), 2, 4);
+        play(&mut first, &mut second, 60).unwrap();
+    }
+
+    #[test]
+    fn netplay_udp_loopback() {
+        let first_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let second_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        first_socket
+            .connect(second_socket.local_addr().unwrap())
+            .unwrap();
+        second_socket
+            .connect(first_socket.local_addr().unwrap())
+            .unwrap();
+        let mut first = NetplaySession::new(
+            emulator(&ROM),
+            UdpTransport::new(first_socket).unwrap(),
+            2,
+            4,
+        );
+        let mut second = NetplaySession::new(
+            emulator(&ROM),
+            UdpTransport::new(second_socket).unwrap(),
+            2,
+            4,
+        );
+        play(&mut first, &mut second, 60).unwrap();
+    }
+}
@@ -9,6 +9,7 @@ bitflags! {
     /// ```
     /// let quirks = chirp8::QuirkFlags::FLAG_RESET | chirp8::QuirkFlags::CLIP_SPRITES_HIRES;
     /// ```
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub struct QuirkFlags: u16 {
         /// The AND, OR and XOR opcodes (8xy1, 8xy2 and 8xy3) reset the flags register to zero.
         const FLAG_RESET = 1 << 0;
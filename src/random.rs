@@ -0,0 +1,87 @@
+/// Small pseudo-random numbers generator (xoshiro256++), used by the `CXNN` instruction.
+///
+/// It is seeded with PCG32 and yields bytes the same way as the `SmallRng` of `rand` did on
+/// 64-bits platforms, so that programs keep drawing the same numbers, but on every platform,
+/// which keeps save states and netplay sessions portable. Its whole state is four 64-bits words,
+/// cheap to save and restore.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Random {
+    state: [u64; 4],
+}
+
+impl Random {
+    /// Creates a generator from given `seed`.
+    pub(crate) const fn new(mut seed: u64) -> Self {
+        let mut state = [0; 4];
+        let mut i = 0;
+        while i < state.len() * 2 {
+            // PCG32, each word being made of two numbers, least significant first.
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(11634580027462260723);
+            let xorshifted = (((seed >> 18) ^ seed) >> 27) as u32;
+            let value = xorshifted.rotate_right((seed >> 59) as u32) as u64;
+            state[i / 2] |= value << (32 * (i % 2));
+            i += 1;
+        }
+        Self { state }
+    }
+
+    /// Creates a generator from its `state`, as returned by [Random::state]. The state must not
+    /// be all zeros.
+    pub(crate) const fn from_state(state: [u64; 4]) -> Self {
+        Self { state }
+    }
+
+    /// Returns the state of the generator, from which it can be recreated with
+    /// [Random::from_state].
+    pub(crate) const fn state(&self) -> [u64; 4] {
+        self.state
+    }
+
+    /// Returns the next pseudo-random 64-bits number.
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[0].wrapping_add(s[3]).rotate_left(23).wrapping_add(s[0]);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    /// Returns the next pseudo-random byte.
+    pub(crate) fn next_u8(&mut self) -> u8 {
+        // The lowest bits are weaker, take the lowest byte of the upper half.
+        (self.next_u64() >> 32) as u8
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn random_sequence() {
+        // Reference values of xoshiro256++.
+        let mut random = Random::from_state([1, 2, 3, 4]);
+        assert_eq!(random.next_u64(), 41943041);
+        assert_eq!(random.next_u64(), 58720359);
+        assert_eq!(random.next_u64(), 3588806011781223);
+
+        // Values of the SmallRng of rand 0.8 on 64-bits platforms.
+        let mut random = Random::new(1234567);
+        assert_eq!(random.next_u64(), 4453140853673219229);
+        assert_eq!(random.next_u64(), 9637258884336139626);
+        let mut random = Random::new(0xDEADCAFEDEADCAFE);
+        let bytes = [3, 43, 164, 141, 172, 10, 71, 13];
+        assert!(bytes.iter().all(|byte| random.next_u8() == *byte));
+
+        let mut copy = Random::from_state(random.state());
+        assert_eq!(copy.next_u8(), random.next_u8());
+        assert_eq!(copy, random);
+    }
+}
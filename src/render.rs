@@ -0,0 +1,320 @@
+use crate::{Bus, Chirp8, DisplayStorage};
+
+/// Colors of the display, the color `n` being used for the pixels lit on the planes set in `n`.
+/// Colors are given as `0xRRGGBB`.
+///
+/// Pixel values repeat their plane bits over the whole byte, see
+/// [crate::Chirp8::set_display_planes] : a palette indexes them with their lowest 4 bits, as
+/// with 4 planes. With fewer planes, [Palette::with_planes] gives the palette of their values,
+/// which the rendering functions of the crate use. With 2 planes, pixels are `0x00` when off,
+/// `0x55` when lit on the first plane only, `0xAA` on the second plane only and `0xFF` on both,
+/// using the colors 0, 1, 2 and 3. With a single plane, lit pixels are `0xFF` and use the color 1.
+///
+/// A custom palette is created as follow :
+/// ```
+/// let palette = chirp8::Palette::new([0x000000, 0xFF0000, 0x00FF00, 0xFFFFFF]);
+/// assert_eq!(palette.rgba8(0x55), [0xFF, 0x00, 0x00, 0xFF]);
+/// assert_eq!(palette.with_planes(1).rgba8(0xFF), [0xFF, 0x00, 0x00, 0xFF]);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    /// Color of each combination of planes, from off to lit on all 4 planes.
+    pub colors: [u32; 16],
+}
+
+impl Palette {
+    /// The default colors of Octo.
+    pub const OCTO: Palette = Palette::new([0x996600, 0xFFCC00, 0xFF6600, 0x662200]);
+    /// Shades of green of old LCD handhelds.
+    pub const LCD_GREEN: Palette = Palette::new([0x9BBC0F, 0x0F380F, 0x8BAC0F, 0x306230]);
+    /// Amber phosphor monitor.
+    pub const AMBER: Palette = Palette::new([0x1A0F00, 0xFFB000, 0x995C00, 0xFFE0A0]);
+    /// Black, white, yellow and cyan, easy to tell apart.
+    pub const HIGH_CONTRAST: Palette = Palette::new([0x000000, 0xFFFFFF, 0xFFFF00, 0x00FFFF]);
+
+    /// Creates a palette from the `colors` of each pixel value of 2 planes, from off to lit on
+    /// both planes. With 4 planes, the third and fourth planes have the colors of the first and
+    /// second ones.
+    pub const fn new(colors: [u32; 4]) -> Self {
+        let mut all_colors = [0; 16];
+        let mut planes = 0;
+        while planes < all_colors.len() {
+            all_colors[planes] = colors[(planes & 0b11) | (planes >> 2)];
+            planes += 1;
+        }
+        Self::new_16(all_colors)
+    }
+
+    /// Creates a palette of 16 `colors` for 4 planes, the color `n` being used for the pixels lit
+    /// on the planes set in `n`.
+    pub const fn new_16(colors: [u32; 16]) -> Self {
+        Self { colors }
+    }
+
+    /// Creates a palette of two colors, pixels being `on` when lit on any plane.
+    pub const fn monochrome(off: u32, on: u32) -> Self {
+        Self::new([off, on, on, on])
+    }
+
+    /// Returns the palette coloring the pixel values of a display of `planes` planes, see
+    /// [crate::Chirp8::display_planes], with the color `n` for the pixels lit on the planes
+    /// set in `n`. With 4 planes or more, the palette is unchanged.
+    pub const fn with_planes(&self, planes: usize) -> Self {
+        if planes >= 4 {
+            return *self;
+        }
+        let mask = (1 << planes) - 1;
+        let mut colors = [0; 16];
+        let mut value = 0;
+        while value < colors.len() {
+            colors[value] = self.colors[value & mask];
+            value += 1;
+        }
+        Self::new_16(colors)
+    }
+
+    /// Returns the color of given `pixel` value of 4 planes as `0xRRGGBB`, see
+    /// [Palette::with_planes] for fewer planes.
+    #[inline]
+    pub fn color(&self, pixel: u8) -> u32 {
+        // Plane bits are repeated over the whole byte.
+        self.colors[(pixel & 0b1111) as usize]
+    }
+
+    /// Returns the color of given `pixel` value as red, green, blue and alpha bytes.
+    #[inline]
+    pub fn rgba8(&self, pixel: u8) -> [u8; 4] {
+        let [_, red, green, blue] = self.color(pixel).to_be_bytes();
+        [red, green, blue, u8::MAX]
+    }
+
+    /// Returns the color of given `pixel` value in the 16-bits RGB565 format.
+    #[inline]
+    pub fn rgb565(&self, pixel: u8) -> u16 {
+        let [_, red, green, blue] = self.color(pixel).to_be_bytes();
+        ((red as u16 >> 3) << 11) | ((green as u16 >> 2) << 5) | (blue as u16 >> 3)
+    }
+
+    /// Returns the luminance of the color of given `pixel` value.
+    #[inline]
+    pub fn grayscale(&self, pixel: u8) -> u8 {
+        let [_, red, green, blue] = self.color(pixel).to_be_bytes();
+        ((red as u32 * 299 + green as u32 * 587 + blue as u32 * 114) / 1000) as u8
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::OCTO
+    }
+}
+
+/// Returns the width and height in pixels of the image of the `emulator`'s display at given
+/// integer `scale`, at least 1. In low resolution, the image is 64x32 times the scale,
+/// 128x64 times the scale in high resolution.
+pub fn render_size<B: Bus, D: DisplayStorage>(
+    emulator: &Chirp8<B, D>,
+    scale: usize,
+) -> (usize, usize) {
+    let (width, height) = emulator.resolution();
+    let scale = scale.max(1);
+    (width * scale, height * scale)
+}
+
+/// Writes the image of the `emulator`'s display to `output`, a pixel per element row after row,
+/// each pixel of the display being the `color` of its column and row repeated `scale` times in
+/// both directions. Returns false if `output` is too short, see [render_size].
+pub(crate) fn render<B: Bus, D: DisplayStorage, T: Copy>(
+    emulator: &Chirp8<B, D>,
+    scale: usize,
+    output: &mut [T],
+    color: impl Fn(usize, usize) -> T,
+) -> bool {
+    let scale = scale.max(1);
+    let (width, height) = render_size(emulator, scale);
+    if output.len() < width * height {
+        return false;
+    }
+    for y in 0..height {
+        let start = y * width;
+        if y % scale != 0 {
+            // Same row as above.
+            output.copy_within((start - width)..start, start);
+            continue;
+        }
+        for (x, pixel) in output[start..(start + width)].iter_mut().enumerate() {
+            *pixel = color(x / scale, y / scale);
+        }
+    }
+    true
+}
+
+/// Writes the image of the `emulator`'s display to `output` as red, green, blue and alpha bytes,
+/// with the colors of the `palette`, each pixel repeated `scale` times in both directions.
+/// Returns false if `output` is shorter than 4 bytes per pixel of the [render_size].
+/// ```
+/// let emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+/// let (width, height) = chirp8::render_size(&emulator, 4);
+/// let mut frame = vec![0; width * height * 4];
+/// assert!(chirp8::render_rgba8(&emulator, &chirp8::Palette::OCTO, 4, &mut frame));
+/// assert_eq!(frame[..4], [0x99, 0x66, 0x00, 0xFF]);
+/// ```
+pub fn render_rgba8<B: Bus, D: DisplayStorage>(
+    emulator: &Chirp8<B, D>,
+    palette: &Palette,
+    scale: usize,
+    output: &mut [u8],
+) -> bool {
+    let palette = palette.with_planes(emulator.display_planes());
+    render(emulator, scale, output.as_chunks_mut().0, |x, y| {
+        palette.rgba8(emulator.get_pixel(x, y))
+    })
+}
+
+/// Writes the image of the `emulator`'s display to `output` in the RGB565 format, with the
+/// colors of the `palette`, each pixel repeated `scale` times in both directions.
+/// Returns false if `output` is shorter than the [render_size].
+pub fn render_rgb565<B: Bus, D: DisplayStorage>(
+    emulator: &Chirp8<B, D>,
+    palette: &Palette,
+    scale: usize,
+    output: &mut [u16],
+) -> bool {
+    let palette = palette.with_planes(emulator.display_planes());
+    render(emulator, scale, output, |x, y| {
+        palette.rgb565(emulator.get_pixel(x, y))
+    })
+}
+
+/// Writes the image of the `emulator`'s display to `output` as a byte of luminance per pixel,
+/// from the colors of the `palette`, each pixel repeated `scale` times in both directions.
+/// Returns false if `output` is shorter than the [render_size].
+pub fn render_grayscale<B: Bus, D: DisplayStorage>(
+    emulator: &Chirp8<B, D>,
+    palette: &Palette,
+    scale: usize,
+    output: &mut [u8],
+) -> bool {
+    let palette = palette.with_planes(emulator.display_planes());
+    render(emulator, scale, output, |x, y| {
+        palette.grayscale(emulator.get_pixel(x, y))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chirp8::test::new_emulator;
+    use crate::Chirp8Mode;
+
+    #[test]
+    fn render_lores_scaled() {
+        #[rustfmt::skip]
+        let rom = [
+            0xA2, 0x06, // I = 206
+            0x60, 0x01, // v0 = 1
+            0xD0, 0x01, // Draw v0 v0 1
+            0xA0, 0x00, // Sprite
+        ];
+        let mut emulator = Chirp8::new(Chirp8Mode::CosmacChip8);
+        emulator.load_rom(&rom);
+        emulator.take_steps(3);
+
+        assert_eq!(render_size(&emulator, 0), (64, 32));
+        assert_eq!(render_size(&emulator, 3), (192, 96));
+        let palette = Palette::monochrome(0x000000, 0xFFFFFF);
+        let mut output = [0; 192 * 96];
+        assert!(!render_grayscale(&emulator, &palette, 3, &mut output[1..]));
+        assert!(render_grayscale(&emulator, &palette, 3, &mut output));
+        for y in 0..6 {
+            let row = &output[(y * 192)..(y * 192 + 7)];
+            if (3..6).contains(&y) {
+                assert_eq!(row, [0, 0, 0, 0xFF, 0xFF, 0xFF, 0]);
+            } else {
+                assert_eq!(row, [0; 7]);
+            }
+        }
+    }
+
+    #[test]
+    fn render_monochrome_first_plane_color() {
+        #[rustfmt::skip]
+        let rom = [
+            0xA2, 0x04, // I = 204
+            0xD0, 0x01, // Draw v0 v0 1
+            0xC0, 0x00, // Sprite
+        ];
+        let mut emulator = Chirp8::new(Chirp8Mode::CosmacChip8);
+        emulator.load_rom(&rom);
+        emulator.take_steps(2);
+        assert_eq!(emulator.display_planes(), 1);
+
+        // Lit pixels have the color of the first plane, off pixels the background color.
+        let mut rgba = [0; 64 * 32 * 4];
+        assert!(render_rgba8(&emulator, &Palette::OCTO, 1, &mut rgba));
+        assert_eq!(
+            rgba[..12],
+            [0xFF, 0xCC, 0x00, 0xFF, 0xFF, 0xCC, 0x00, 0xFF, 0x99, 0x66, 0x00, 0xFF]
+        );
+        assert_eq!(Palette::OCTO.with_planes(1).color(0xFF), 0xFFCC00);
+        assert_eq!(Palette::OCTO.with_planes(2).color(0xFF), 0x662200);
+    }
+
+    #[test]
+    fn render_planes_colors() {
+        #[rustfmt::skip]
+        let rom = [
+            0x00, 0xFF, // High resolution
+            0xF3, 0x01, // Select both planes
+            0xA2, 0x0A, // I = 20A
+            0xD0, 0x01, // Draw v0 v0 1 on both planes
+            0x12, 0x08, // Loop
+            0xF0, 0x30, // Plane 1 sprite, then plane 2 sprite
+        ];
+        let mut emulator = new_emulator(Chirp8Mode::XOChip);
+        emulator.load_rom(&rom);
+        emulator.take_steps(4);
+
+        let palette = Palette::HIGH_CONTRAST;
+        let mut rgba = [0; 128 * 64 * 4];
+        assert!(render_rgba8(&emulator, &palette, 1, &mut rgba));
+        assert_eq!(rgba[..4], [0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(rgba[8..12], [0x00, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(rgba[16..20], [0x00, 0x00, 0x00, 0xFF]);
+
+        let mut rgb565 = [0; 128 * 64];
+        assert!(render_rgb565(&emulator, &palette, 1, &mut rgb565));
+        assert_eq!(rgb565[..5], [0xFFFF, 0xFFFF, 0x07FF, 0x07FF, 0x0000]);
+        assert_eq!(Palette::HIGH_CONTRAST.rgb565(0xAA), 0xFFE0);
+    }
+
+    #[test]
+    fn palette_four_planes() {
+        let palette = Palette::HIGH_CONTRAST;
+        // Third and fourth planes alone look like the first and second ones.
+        assert_eq!(palette.color(0x44), 0xFFFFFF);
+        assert_eq!(palette.color(0x88), 0xFFFF00);
+        assert_eq!(palette.color(0x66), 0x00FFFF);
+        assert_eq!(palette.color(0xFF), 0x00FFFF);
+
+        let colors = core::array::from_fn(|planes| planes as u32 * 0x111111);
+        let palette = Palette::new_16(colors);
+        let mut emulator = new_emulator(Chirp8Mode::XOChip);
+        emulator.set_display_planes(4);
+        #[rustfmt::skip]
+        let rom = [
+            0xF9, 0x01, // Select planes 0 and 3
+            0xA2, 0x06, // I = 206
+            0xD0, 0x01, // Draw v0 v0 1
+            0x80, 0xC0, // Plane 0 sprite, then plane 3 sprite
+        ];
+        emulator.load_rom(&rom);
+        emulator.take_steps(3);
+        let mut rgba = [0; 64 * 32 * 4];
+        assert!(render_rgba8(&emulator, &palette, 1, &mut rgba));
+        assert_eq!(
+            rgba[..12],
+            [0x99, 0x99, 0x99, 0xFF, 0x88, 0x88, 0x88, 0xFF, 0, 0, 0, 0xFF]
+        );
+    }
+}
@@ -0,0 +1,847 @@
+use std::format;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::vec::Vec;
+
+use crate::deflate;
+use crate::{render_rgba8, render_size, Bus, Chirp8, DisplayStorage, Palette};
+
+/// Signature starting PNG files.
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+/// Size of the file header and of the info header of written BMP files.
+const BMP_HEADERS_SIZE: usize = 14 + 40;
+
+/// File formats of [Snapshot]s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Windows bitmap, written with 24 bits per pixel.
+    Bmp,
+    /// Portable Network Graphics, written with 8 bits per channel and alpha.
+    Png,
+    /// Netpbm bitmap, written in binary. Pixels darker than mid-gray are black, the others white.
+    Pbm,
+    /// Netpbm grayscale image, written in binary with the luminance of the pixels.
+    Pgm,
+}
+
+impl ImageFormat {
+    /// Returns the format of files with given `extension`, whatever its case, if known.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        [Self::Bmp, Self::Png, Self::Pbm, Self::Pgm]
+            .into_iter()
+            .find(|format| format.extension().eq_ignore_ascii_case(extension))
+    }
+
+    /// Returns the format of the file at given `path` according to its extension, if known.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Self::from_extension(path.extension()?.to_str()?)
+    }
+
+    /// Returns the usual extension of the files of this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Bmp => "bmp",
+            Self::Png => "png",
+            Self::Pbm => "pbm",
+            Self::Pgm => "pgm",
+        }
+    }
+}
+
+/// Returns an error about an invalid image.
+fn invalid_data<T>(message: &'static str) -> io::Result<T> {
+    Err(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+/// Returns the number of bytes of the pixels of an image of `width` by `height` pixels, or an
+/// error when the image is empty or too large to be held in memory.
+fn pixels_size(width: usize, height: usize) -> io::Result<usize> {
+    if width == 0 || height == 0 {
+        return invalid_data("Empty image");
+    }
+    let size = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(4));
+    match size {
+        Option::Some(size) if size <= isize::MAX as usize => Ok(size),
+        _ => invalid_data("Image too large"),
+    }
+}
+
+/// Returns the luminance of given `rgba` color, like [Palette::grayscale].
+fn luminance(rgba: &[u8]) -> u8 {
+    ((rgba[0] as u32 * 299 + rgba[1] as u32 * 587 + rgba[2] as u32 * 114) / 1000) as u8
+}
+
+/// Image of the display, made of red, green, blue and alpha bytes row after row, to save
+/// screenshots and to compare the display with golden images.
+/// ```
+/// let mut emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+/// emulator.load_rom(&[0xF0, 0x29, 0xD0, 0x05]); // Draw the font sprite of 0.
+/// emulator.take_steps(2);
+/// let palette = chirp8::Palette::monochrome(0x000000, 0xFFFFFF);
+/// let snapshot = chirp8::Snapshot::capture(&emulator, &palette, 2);
+/// assert_eq!((snapshot.width(), snapshot.height()), (128, 64));
+///
+/// let png = snapshot.encode(chirp8::ImageFormat::Png);
+/// let golden = chirp8::Snapshot::from_bytes(&png).unwrap();
+/// assert_eq!(snapshot.count_differences(&golden), Some(0));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Width in pixels.
+    width: usize,
+    /// Height in pixels.
+    height: usize,
+    /// 4 bytes per pixel.
+    pixels: Vec<u8>,
+}
+
+impl Snapshot {
+    /// Creates an image of `width` by `height` `pixels`, as red, green, blue and alpha bytes.
+    /// Returns `None` if the image is empty or if there are not 4 bytes per pixel.
+    pub fn new(width: usize, height: usize, pixels: Vec<u8>) -> Option<Self> {
+        if pixels_size(width, height).ok() != Option::Some(pixels.len()) {
+            return Option::None;
+        }
+        Option::Some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Captures the `emulator`'s display with the colors of the `palette`, each pixel repeated
+    /// `scale` times in both directions, like [render_rgba8].
+    pub fn capture<B: Bus, D: DisplayStorage>(
+        emulator: &Chirp8<B, D>,
+        palette: &Palette,
+        scale: usize,
+    ) -> Self {
+        let (width, height) = render_size(emulator, scale);
+        let mut pixels = std::vec![0; width * height * 4];
+        render_rgba8(emulator, palette, scale, &mut pixels);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Returns the width in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the pixels as red, green, blue and alpha bytes, row after row.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Returns the red, green, blue and alpha bytes of the pixel at column `x` and row `y`.
+    pub fn rgba8(&self, x: usize, y: usize) -> [u8; 4] {
+        let start = (y * self.width + x) * 4;
+        let mut rgba = [0; 4];
+        rgba.copy_from_slice(&self.pixels[start..(start + 4)]);
+        rgba
+    }
+
+    /// Returns the number of pixels of different colors in this image and `other`, or `None`
+    /// when their sizes differ.
+    pub fn count_differences(&self, other: &Snapshot) -> Option<usize> {
+        if (self.width, self.height) != (other.width, other.height) {
+            return Option::None;
+        }
+        let pixels = self
+            .pixels
+            .chunks_exact(4)
+            .zip(other.pixels.chunks_exact(4));
+        Option::Some(pixels.filter(|(pixel, other)| pixel != other).count())
+    }
+
+    /// Returns the image encoded in given `format`.
+    pub fn encode(&self, format: ImageFormat) -> Vec<u8> {
+        match format {
+            ImageFormat::Bmp => self.encode_bmp(),
+            ImageFormat::Png => self.encode_png(),
+            ImageFormat::Pbm => self.encode_pbm(),
+            ImageFormat::Pgm => self.encode_pgm(),
+        }
+    }
+
+    /// Writes the image encoded in given `format` to `writer`.
+    pub fn write<W: Write>(&self, format: ImageFormat, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.encode(format))
+    }
+
+    /// Saves the image to the file at given `path`, in the format of its extension.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let Option::Some(format) = ImageFormat::from_path(path) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Unknown image file extension",
+            ));
+        };
+        fs::write(path, self.encode(format))
+    }
+
+    /// Decodes an image from given `bytes`, in any [ImageFormat] recognized from its first
+    /// bytes. BMP images may have 1, 4, 8, 16, 24 or 32 bits per pixel without compression, PNG
+    /// images must not be interlaced and Netpbm images may be plain or binary.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.starts_with(b"BM") {
+            Self::decode_bmp(bytes)
+        } else if bytes.starts_with(&PNG_SIGNATURE) {
+            Self::decode_png(bytes)
+        } else if bytes.len() >= 2 && bytes[0] == b'P' && b"1245".contains(&bytes[1]) {
+            Self::decode_netpbm(bytes)
+        } else {
+            invalid_data("Unknown image format")
+        }
+    }
+
+    /// Reads an image from `reader`, see [Snapshot::from_bytes].
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Opens the image file at given `path`, see [Snapshot::from_bytes].
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    fn encode_bmp(&self) -> Vec<u8> {
+        let row_size = (self.width * 3).div_ceil(4) * 4;
+        let image_size = row_size * self.height;
+        let mut bytes = Vec::with_capacity(BMP_HEADERS_SIZE + image_size);
+        // File header.
+        bytes.extend_from_slice(b"BM");
+        bytes.extend_from_slice(&((BMP_HEADERS_SIZE + image_size) as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&(BMP_HEADERS_SIZE as u32).to_le_bytes());
+        // Info header, without compression.
+        bytes.extend_from_slice(&40u32.to_le_bytes());
+        bytes.extend_from_slice(&(self.width as i32).to_le_bytes());
+        bytes.extend_from_slice(&(self.height as i32).to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&24u16.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&(image_size as u32).to_le_bytes());
+        // 72 DPI.
+        bytes.extend_from_slice(&2835u32.to_le_bytes());
+        bytes.extend_from_slice(&2835u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 8]);
+        // Rows from the bottom, as blue, green and red bytes.
+        for row in self.pixels.chunks_exact(self.width * 4).rev() {
+            for pixel in row.chunks_exact(4) {
+                bytes.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+            }
+            bytes.resize(bytes.len() + row_size - self.width * 3, 0);
+        }
+        bytes
+    }
+
+    fn decode_bmp(bytes: &[u8]) -> io::Result<Self> {
+        let u16_at = |offset: usize| -> io::Result<u16> {
+            match bytes.get(offset..(offset + 2)) {
+                Option::Some(value) => Ok(u16::from_le_bytes([value[0], value[1]])),
+                Option::None => invalid_data("Truncated BMP image"),
+            }
+        };
+        let u32_at = |offset: usize| -> io::Result<u32> {
+            Ok(u16_at(offset)? as u32 | (u16_at(offset + 2)? as u32) << 16)
+        };
+        let data_offset = u32_at(10)? as usize;
+        let header_size = u32_at(14)? as usize;
+        if header_size < 40 {
+            return invalid_data("Unsupported BMP header");
+        }
+        let width = u32_at(18)? as i32;
+        let height = u32_at(22)? as i32;
+        let bits = u16_at(28)?;
+        let compression = u32_at(30)?;
+        if width <= 0 || height == 0 {
+            return invalid_data("Invalid BMP size");
+        }
+        let (width, top_down) = (width as usize, height < 0);
+        let height = height.unsigned_abs() as usize;
+        let size = pixels_size(width, height)?;
+
+        // Colors of the palette, or masks of the red, green, blue and alpha channels.
+        let mut palette = Vec::new();
+        let mut masks = match (bits, compression) {
+            (1 | 4 | 8, 0) => {
+                let colors = match u32_at(46)? {
+                    0 => 1 << bits,
+                    colors => colors as usize,
+                };
+                let start = 14 + header_size;
+                for color in 0..colors {
+                    let offset = start + color * 4;
+                    let [blue, green, red, _] = u32_at(offset)?.to_le_bytes();
+                    palette.push([red, green, blue, u8::MAX]);
+                }
+                [0; 4]
+            }
+            (16, 0) => [0x7C00, 0x03E0, 0x001F, 0],
+            (24 | 32, 0) => [0xFF_0000, 0xFF00, 0xFF, 0],
+            (16 | 32, 3) => [u32_at(54)?, u32_at(58)?, u32_at(62)?, 0],
+            _ => return invalid_data("Unsupported BMP pixel format"),
+        };
+        if compression == 3 && header_size >= 56 {
+            masks[3] = u32_at(66)?;
+        }
+
+        let row_size = width
+            .checked_mul(bits as usize)
+            .map(|bits| bits.div_ceil(32) * 4);
+        let end = row_size
+            .and_then(|row_size| row_size.checked_mul(height))
+            .and_then(|data_size| data_size.checked_add(data_offset));
+        let (Option::Some(row_size), Option::Some(end)) = (row_size, end) else {
+            return invalid_data("Image too large");
+        };
+        let Option::Some(data) = bytes.get(data_offset..end) else {
+            return invalid_data("Truncated BMP image");
+        };
+        let mut pixels = Vec::with_capacity(size);
+        for y in 0..height {
+            let row = if top_down { y } else { height - 1 - y };
+            let row = &data[(row * row_size)..((row + 1) * row_size)];
+            for x in 0..width {
+                let rgba = match bits {
+                    1 | 4 | 8 => {
+                        let bit = x * bits as usize;
+                        let shift = 8 - bits as usize - bit % 8;
+                        let index = (row[bit / 8] >> shift) as usize & ((1 << bits) - 1);
+                        match palette.get(index) {
+                            Option::Some(color) => *color,
+                            Option::None => return invalid_data("Invalid BMP palette index"),
+                        }
+                    }
+                    _ => {
+                        let size = bits as usize / 8;
+                        let mut value = [0; 4];
+                        value[..size].copy_from_slice(&row[(x * size)..((x + 1) * size)]);
+                        let value = u32::from_le_bytes(value);
+                        masks.map(|mask| {
+                            if mask == 0 {
+                                return u8::MAX;
+                            }
+                            let channel = (value & mask) >> mask.trailing_zeros();
+                            let maximum = mask >> mask.trailing_zeros();
+                            (channel as u64 * 255 / maximum as u64) as u8
+                        })
+                    }
+                };
+                pixels.extend_from_slice(&rgba);
+            }
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    fn encode_png(&self) -> Vec<u8> {
+        let mut bytes = PNG_SIGNATURE.to_vec();
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bits red, green, blue and alpha, not interlaced.
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+        write_png_chunk(&mut bytes, b"IHDR", &header);
+
+        // Rows without filter, matching the previous pixel or the row above.
+        let stride = self.width * 4 + 1;
+        let mut filtered = Vec::with_capacity(stride * self.height);
+        for row in self.pixels.chunks_exact(self.width * 4) {
+            filtered.push(0);
+            filtered.extend_from_slice(row);
+        }
+        write_png_chunk(
+            &mut bytes,
+            b"IDAT",
+            &deflate::compress(&filtered, &[4, stride]),
+        );
+        write_png_chunk(&mut bytes, b"IEND", &[]);
+        bytes
+    }
+
+    fn decode_png(bytes: &[u8]) -> io::Result<Self> {
+        let mut header = Option::None;
+        let mut palette = Vec::new();
+        let mut transparency = Vec::new();
+        let mut data = Vec::new();
+        let mut position = PNG_SIGNATURE.len();
+        loop {
+            let Option::Some(length) = bytes.get(position..(position + 4)) else {
+                return invalid_data("Truncated PNG image");
+            };
+            let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize;
+            let Option::Some(chunk) = bytes.get((position + 4)..(position + 12 + length)) else {
+                return invalid_data("Truncated PNG image");
+            };
+            let (chunk, crc) = chunk.split_at(4 + length);
+            if crc32(chunk).to_be_bytes() != crc {
+                return invalid_data("Invalid PNG chunk checksum");
+            }
+            let (kind, content) = chunk.split_at(4);
+            match kind {
+                b"IHDR" if content.len() == 13 => header = Option::Some(content),
+                b"PLTE" => palette = content.chunks_exact(3).collect(),
+                b"tRNS" => transparency = content.to_vec(),
+                b"IDAT" => data.extend_from_slice(content),
+                b"IEND" => break,
+                _ => {}
+            }
+            position += 12 + length;
+        }
+
+        let Option::Some(header) = header else {
+            return invalid_data("Missing PNG header");
+        };
+        let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let (depth, color_type) = (header[8] as usize, header[9]);
+        if header[12] != 0 {
+            return invalid_data("Interlaced PNG images are not supported");
+        }
+        let channels = match (color_type, depth) {
+            (0, 1 | 2 | 4 | 8 | 16) => 1,
+            (3, 1 | 2 | 4 | 8) => 1,
+            (4, 8 | 16) => 2,
+            (2, 8 | 16) => 3,
+            (6, 8 | 16) => 4,
+            _ => return invalid_data("Invalid PNG pixel format"),
+        };
+
+        let size = pixels_size(width, height)?;
+
+        // Bytes per row and per pixel, at least one for filters.
+        let row_size = width
+            .checked_mul(channels * depth)
+            .map(|bits| bits.div_ceil(8));
+        let data_size = row_size.and_then(|row_size| (row_size + 1).checked_mul(height));
+        let (Option::Some(row_size), Option::Some(data_size)) = (row_size, data_size) else {
+            return invalid_data("Image too large");
+        };
+        let pixel_size = (channels * depth / 8).max(1);
+        let data = deflate::decompress(&data, data_size)?;
+        if data.len() < data_size {
+            return invalid_data("Truncated PNG image data");
+        }
+        let mut previous = std::vec![0u8; row_size];
+        let mut row = std::vec![0u8; row_size];
+        let mut pixels = Vec::with_capacity(size);
+        for filtered in data.chunks_exact(row_size + 1).take(height) {
+            unfilter_png_row(filtered[0], &filtered[1..], &previous, &mut row, pixel_size)?;
+            let sample = |index: usize| -> u16 {
+                match depth {
+                    16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]) >> 8,
+                    8 => row[index] as u16,
+                    _ => {
+                        let bit = index * depth;
+                        (row[bit / 8] >> (8 - depth - bit % 8)) as u16 & ((1 << depth) - 1)
+                    }
+                }
+            };
+            for x in 0..width {
+                let rgba = match color_type {
+                    0 => {
+                        let gray = (sample(x) * 255 / ((1 << depth.min(8)) - 1)) as u8;
+                        [gray, gray, gray, u8::MAX]
+                    }
+                    3 => {
+                        let index = sample(x) as usize;
+                        let Option::Some(color) = palette.get(index) else {
+                            return invalid_data("Invalid PNG palette index");
+                        };
+                        let alpha = transparency.get(index).copied().unwrap_or(u8::MAX);
+                        [color[0], color[1], color[2], alpha]
+                    }
+                    4 => {
+                        let gray = sample(x * 2) as u8;
+                        [gray, gray, gray, sample(x * 2 + 1) as u8]
+                    }
+                    2 => [
+                        sample(x * 3) as u8,
+                        sample(x * 3 + 1) as u8,
+                        sample(x * 3 + 2) as u8,
+                        u8::MAX,
+                    ],
+                    _ => [
+                        sample(x * 4) as u8,
+                        sample(x * 4 + 1) as u8,
+                        sample(x * 4 + 2) as u8,
+                        sample(x * 4 + 3) as u8,
+                    ],
+                };
+                pixels.extend_from_slice(&rgba);
+            }
+            core::mem::swap(&mut previous, &mut row);
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    fn encode_pbm(&self) -> Vec<u8> {
+        let mut bytes = format!("P4\n{} {}\n", self.width, self.height).into_bytes();
+        for row in self.pixels.chunks_exact(self.width * 4) {
+            let mut bits = 0u8;
+            for (x, pixel) in row.chunks_exact(4).enumerate() {
+                // Dark pixels are set.
+                bits |= ((luminance(pixel) < 128) as u8) << (7 - x % 8);
+                if x % 8 == 7 || x == self.width - 1 {
+                    bytes.push(bits);
+                    bits = 0;
+                }
+            }
+        }
+        bytes
+    }
+
+    fn encode_pgm(&self) -> Vec<u8> {
+        let mut bytes = format!("P5\n{} {}\n255\n", self.width, self.height).into_bytes();
+        bytes.extend(self.pixels.chunks_exact(4).map(luminance));
+        bytes
+    }
+
+    fn decode_netpbm(bytes: &[u8]) -> io::Result<Self> {
+        let kind = bytes[1];
+        let mut position = 2;
+        // Reads the next number of the header or of a plain image, skipping comments.
+        let mut number = |digits: usize| -> io::Result<usize> {
+            loop {
+                match bytes.get(position) {
+                    Option::Some(b'#') => {
+                        while !matches!(bytes.get(position), Option::Some(b'\n') | Option::None) {
+                            position += 1;
+                        }
+                    }
+                    Option::Some(byte) if byte.is_ascii_whitespace() => position += 1,
+                    _ => break,
+                }
+            }
+            let start = position;
+            while position - start < digits
+                && bytes
+                    .get(position)
+                    .is_some_and(|byte| byte.is_ascii_digit())
+            {
+                position += 1;
+            }
+            match core::str::from_utf8(&bytes[start..position]).map(str::parse) {
+                Ok(Ok(value)) => Ok(value),
+                _ => invalid_data("Invalid Netpbm number"),
+            }
+        };
+        let width = number(usize::MAX)?;
+        let height = number(usize::MAX)?;
+        let bitmap = matches!(kind, b'1' | b'4');
+        let maximum = if bitmap { 1 } else { number(usize::MAX)? };
+        if maximum == 0 || maximum > u8::MAX as usize {
+            return invalid_data("Unsupported Netpbm maximum value");
+        }
+
+        let size = pixels_size(width, height)?;
+        let count = size / 4;
+        let mut samples = Vec::new();
+        match kind {
+            // Plain bitmaps may have digits without spaces.
+            b'1' | b'2' if count > bytes.len() => return invalid_data("Truncated Netpbm image"),
+            b'1' => {
+                for _ in 0..count {
+                    samples.push(number(1)?);
+                }
+            }
+            b'2' => {
+                for _ in 0..count {
+                    samples.push(number(usize::MAX)?);
+                }
+            }
+            _ => {
+                // Single whitespace before the binary data.
+                let start = position + 1;
+                let row_size = if bitmap { width.div_ceil(8) } else { width };
+                let Option::Some(data) = bytes.get(start..(start + row_size * height)) else {
+                    return invalid_data("Truncated Netpbm image");
+                };
+                samples.reserve(count);
+                for row in data.chunks_exact(row_size) {
+                    for x in 0..width {
+                        samples.push(if bitmap {
+                            (row[x / 8] >> (7 - x % 8)) as usize & 1
+                        } else {
+                            row[x] as usize
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut pixels = Vec::with_capacity(size);
+        for sample in samples {
+            if sample > maximum {
+                return invalid_data("Invalid Netpbm sample");
+            }
+            // Set bits are black.
+            let sample = if bitmap { 1 - sample } else { sample };
+            let gray = (sample * 255 / maximum) as u8;
+            pixels.extend_from_slice(&[gray, gray, gray, u8::MAX]);
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+/// Returns the CRC-32 checksum of PNG chunks.
+fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut index = 0;
+        while index < table.len() {
+            let mut crc = index as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 != 0 {
+                    0xEDB8_8320 ^ (crc >> 1)
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[index] = crc;
+            index += 1;
+        }
+        table
+    };
+    !data.iter().fold(u32::MAX, |crc, byte| {
+        TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Appends a PNG chunk of given `kind` and `content` to `bytes`.
+fn write_png_chunk(bytes: &mut Vec<u8>, kind: &[u8; 4], content: &[u8]) {
+    bytes.extend_from_slice(&(content.len() as u32).to_be_bytes());
+    let start = bytes.len();
+    bytes.extend_from_slice(kind);
+    bytes.extend_from_slice(content);
+    let crc = crc32(&bytes[start..]);
+    bytes.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Reverts the PNG `filter` of a `filtered` row to `row`, given the `previous` row and the
+/// number of bytes per pixel.
+fn unfilter_png_row(
+    filter: u8,
+    filtered: &[u8],
+    previous: &[u8],
+    row: &mut [u8],
+    pixel_size: usize,
+) -> io::Result<()> {
+    for index in 0..row.len() {
+        let left = if index >= pixel_size {
+            row[index - pixel_size]
+        } else {
+            0
+        };
+        let up = previous[index];
+        let up_left = if index >= pixel_size {
+            previous[index - pixel_size]
+        } else {
+            0
+        };
+        let predicted = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => {
+                // Paeth predictor.
+                let estimate = left as i16 + up as i16 - up_left as i16;
+                let (to_left, to_up, to_up_left) = (
+                    (estimate - left as i16).abs(),
+                    (estimate - up as i16).abs(),
+                    (estimate - up_left as i16).abs(),
+                );
+                if to_left <= to_up && to_left <= to_up_left {
+                    left
+                } else if to_up <= to_up_left {
+                    up
+                } else {
+                    up_left
+                }
+            }
+            _ => return invalid_data("Invalid PNG filter"),
+        };
+        row[index] = filtered[index].wrapping_add(predicted);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Chirp8Mode;
+
+    /// Returns a snapshot of the font sprite of 0 drawn in high resolution.
+    fn font_snapshot(palette: &Palette, scale: usize) -> Snapshot {
+        let mut emulator = Chirp8::new(Chirp8Mode::SuperChipModern);
+        emulator.load_rom(&[0x00, 0xFF, 0xF0, 0x29, 0x61, 0x01, 0xD1, 0x15]);
+        emulator.take_steps(4);
+        Snapshot::capture(&emulator, palette, scale)
+    }
+
+    #[test]
+    fn snapshot_round_trips() {
+        let snapshot = font_snapshot(&Palette::OCTO, 3);
+        assert_eq!((snapshot.width(), snapshot.height()), (384, 192));
+        assert_eq!(snapshot.rgba8(2, 2), [0x99, 0x66, 0x00, 0xFF]);
+        assert_eq!(snapshot.rgba8(3, 3), [0xFF, 0xCC, 0x00, 0xFF]);
+
+        for format in [ImageFormat::Bmp, ImageFormat::Png] {
+            let decoded = Snapshot::from_bytes(&snapshot.encode(format)).unwrap();
+            assert_eq!(decoded, snapshot);
+        }
+        let png = snapshot.encode(ImageFormat::Png);
+        assert!(png.len() < snapshot.pixels().len() / 50);
+
+        // Netpbm images are monochrome or grayscale.
+        let snapshot = font_snapshot(&Palette::monochrome(0xFFFFFF, 0x000000), 1);
+        let pbm = snapshot.encode(ImageFormat::Pbm);
+        assert_eq!(pbm[..11], *b"P4\n128 64\n\x00");
+        assert_eq!(pbm[(10 + 16)..(10 + 18)], [0b0111_1000, 0]);
+        for format in [ImageFormat::Pbm, ImageFormat::Pgm] {
+            let decoded = Snapshot::read(&snapshot.encode(format)[..]).unwrap();
+            assert_eq!(decoded.count_differences(&snapshot), Option::Some(0));
+        }
+        let gray = font_snapshot(&Palette::monochrome(0x000000, 0x808080), 2);
+        let decoded = Snapshot::from_bytes(&gray.encode(ImageFormat::Pgm)).unwrap();
+        assert_eq!(decoded.rgba8(2, 2), [0x80, 0x80, 0x80, 0xFF]);
+        assert_eq!(decoded.count_differences(&snapshot), Option::None);
+        assert_eq!(decoded.count_differences(&gray), Option::Some(0));
+    }
+
+    #[test]
+    fn snapshot_golden_bmp() {
+        // 1 bit per pixel with a version 5 header.
+        let golden = Snapshot::from_bytes(include_bytes!("../tests/ibm_logo.bmp")).unwrap();
+        assert_eq!((golden.width(), golden.height()), (128, 64));
+        assert_eq!(golden.rgba8(0, 0), [0x00, 0x00, 0x00, 0xFF]);
+        let lit = golden
+            .pixels()
+            .chunks_exact(4)
+            .filter(|pixel| pixel[0] != 0);
+        assert!(lit.clone().count() > 0);
+        assert!(lit.into_iter().all(|pixel| pixel == [0xFF; 4]));
+    }
+
+    #[test]
+    fn snapshot_other_encodings() {
+        // Plain Netpbm images with comments.
+        let plain = Snapshot::from_bytes(b"P1 # Bitmap\n3 2\n010\n1 1 0").unwrap();
+        let pixels = [0xFF, 0x00, 0xFF, 0x00, 0x00, 0xFF].map(|gray| [gray, gray, gray, 0xFF]);
+        assert_eq!(plain.pixels(), pixels.as_flattened());
+        let plain = Snapshot::from_bytes(b"P2\n2 1\n# Comment\n4\n0 2\n").unwrap();
+        assert_eq!(plain.pixels(), [0, 0, 0, 0xFF, 127, 127, 127, 0xFF]);
+        assert!(Snapshot::from_bytes(b"P2 1 1 4 5").is_err());
+        assert!(Snapshot::from_bytes(b"P5 2 1 255 ").is_err());
+        assert!(Snapshot::from_bytes(b"GIF89a").is_err());
+
+        // 2x2 palette PNG with 2 bits per pixel and filters, written by another encoder.
+        let mut png = PNG_SIGNATURE.to_vec();
+        write_png_chunk(&mut png, b"IHDR", &[0, 0, 0, 2, 0, 0, 0, 2, 2, 3, 0, 0, 0]);
+        write_png_chunk(&mut png, b"PLTE", &[0, 0, 0, 0xFF, 0, 0, 0, 0xFF, 0]);
+        write_png_chunk(&mut png, b"tRNS", &[0]);
+        let rows = [0, 0b0110_0000, 2, 0b1100_0000];
+        write_png_chunk(&mut png, b"IDAT", &deflate::compress(&rows, &[]));
+        write_png_chunk(&mut png, b"IEND", &[]);
+        let decoded = Snapshot::from_bytes(&png).unwrap();
+        #[rustfmt::skip]
+        assert_eq!(decoded.pixels(), [
+            0xFF, 0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0xFF,
+        ]);
+        let last = png.len() - 13;
+        png[last] ^= 1;
+        assert!(Snapshot::from_bytes(&png).is_err());
+    }
+
+    #[test]
+    fn snapshot_invalid_sizes() {
+        let too_large = |bytes: &[u8]| {
+            let error = Snapshot::from_bytes(bytes).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            format!("{error}")
+        };
+        assert_eq!(
+            too_large(b"P5\n99999999999 99999999999\n255\n"),
+            "Image too large"
+        );
+        assert_eq!(too_large(b"P1\n99999 99999\n0"), "Truncated Netpbm image");
+        assert_eq!(too_large(b"P4\n0 5\n"), "Empty image");
+
+        let snapshot = Snapshot::new(1, 1, std::vec![1, 2, 3, 4]).unwrap();
+        let mut bmp = snapshot.encode(ImageFormat::Bmp);
+        bmp[18..26].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0x7F, 0xFF, 0xFF, 0xFF, 0x7F]);
+        assert_eq!(too_large(&bmp), "Image too large");
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        write_png_chunk(
+            &mut png,
+            b"IHDR",
+            &[
+                0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 8, 6, 0, 0, 0,
+            ],
+        );
+        write_png_chunk(&mut png, b"IEND", &[]);
+        assert_eq!(too_large(&png), "Image too large");
+
+        // A 1x1 image whose data decompresses to much more than its size.
+        let mut png = PNG_SIGNATURE.to_vec();
+        write_png_chunk(&mut png, b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]);
+        let bomb = deflate::compress(&std::vec![0; 1 << 20], &[1]);
+        write_png_chunk(&mut png, b"IDAT", &bomb);
+        write_png_chunk(&mut png, b"IEND", &[]);
+        assert_eq!(too_large(&png), "Decompressed data too large");
+        assert!(Snapshot::new(usize::MAX, 2, Vec::new()).is_none());
+    }
+
+    #[test]
+    fn image_formats() {
+        assert_eq!(
+            ImageFormat::from_extension("PNG"),
+            Option::Some(ImageFormat::Png)
+        );
+        assert_eq!(
+            ImageFormat::from_path(Path::new("golden/screen.pgm")),
+            Option::Some(ImageFormat::Pgm)
+        );
+        assert_eq!(ImageFormat::from_path(Path::new("screen")), Option::None);
+        assert_eq!(ImageFormat::Bmp.extension(), "bmp");
+
+        let snapshot = Snapshot::new(1, 1, std::vec![1, 2, 3, 4]).unwrap();
+        let mut bmp = Vec::new();
+        snapshot.write(ImageFormat::Bmp, &mut bmp).unwrap();
+        assert_eq!(bmp.len(), BMP_HEADERS_SIZE + 4);
+        assert!(Snapshot::new(1, 1, Vec::new()).is_none());
+        assert!(snapshot.save("screen.gif").is_err());
+    }
+}
@@ -0,0 +1,449 @@
+use crate::stack::Stack;
+#[cfg(feature = "alloc")]
+use crate::{render_rgba8, render_size, Bus, Chirp8, DisplayStorage, Palette, PIXEL_OFF};
+
+/// Largest number of draw calls a [DrawLog] records per frame, and of sprites it keeps as
+/// visible. Further calls are counted by [DrawLog::dropped].
+pub const MAX_DRAW_CALLS: usize = 256;
+
+/// Offset basis of the 64 bits FNV-1a hash.
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+/// Prime of the 64 bits FNV-1a hash.
+const FNV_PRIME: u64 = 0x0100_0000_01B3;
+
+/// A sprite drawn by a `DXYN` instruction, recorded by a [DrawLog].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawCall {
+    /// Address of the sprite data in RAM, the index register.
+    pub address: u16,
+    /// Column of the top left pixel of the sprite, in the resolution it was drawn in.
+    pub x: u8,
+    /// Row of the top left pixel of the sprite, in the resolution it was drawn in.
+    pub y: u8,
+    /// Width of the sprite in pixels, 8, or 16 for large sprites.
+    pub width: u8,
+    /// Height of the sprite in pixels.
+    pub height: u8,
+    /// The planes drawn on, bit `n` being set for plane `n` like the `FN01` instruction.
+    pub planes: u8,
+    /// Number of bytes of sprite data read from `address`, for all planes drawn on.
+    pub length: u16,
+    /// True when the sprite was drawn in high resolution.
+    pub high_resolution: bool,
+    /// Value of VF after drawing, non zero when pixels have been erased.
+    pub collision: u8,
+    /// Hash of the sprite, see [sprite_hash].
+    pub hash: u64,
+}
+
+/// Returns the hash identifying a sprite of given `width` and `height` drawn from `data`, the
+/// bytes of all its planes, as recorded in [DrawCall::hash]. This is the 64 bits FNV-1a hash of
+/// the width, the height and the data.
+/// ```
+/// let first = chirp8::sprite_hash(8, 2, &[0xF0, 0x90]);
+/// assert_ne!(first, chirp8::sprite_hash(8, 2, &[0xF0, 0x91]));
+/// assert_ne!(first, chirp8::sprite_hash(8, 1, &[0xF0, 0x90]));
+/// ```
+pub fn sprite_hash(width: u8, height: u8, data: &[u8]) -> u64 {
+    hash_sprite_bytes(width, height, data.iter().copied())
+}
+
+/// Returns the [sprite_hash] of the sprite made of given `data` bytes.
+pub(crate) fn hash_sprite_bytes(width: u8, height: u8, data: impl Iterator<Item = u8>) -> u64 {
+    [width, height]
+        .into_iter()
+        .chain(data)
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+        })
+}
+
+/// Opt-in record of the sprites drawn, to rip sprites from games or to replace them with
+/// high-resolution images, see [ReplacementPack].
+///
+/// The log holds the draw calls of the current frame, forgotten when the next frame starts, and
+/// the sprites which are still visible on the display. A sprite stops being visible when drawn
+/// again at the same place, which erases it, or when the whole display changes, after clearing
+/// or scrolling it for instance.
+/// ```
+/// let mut emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+/// emulator.set_draw_log(Some(chirp8::DrawLog::new()));
+/// emulator.load_rom(&[0xF0, 0x29, 0xD0, 0x05]); // Draw the font sprite of 0.
+/// emulator.take_steps(2);
+/// let call = emulator.draw_log().unwrap().calls()[0];
+/// assert_eq!((call.x, call.y, call.height, call.collision), (0, 0, 5, 0));
+/// assert_eq!(emulator.draw_log().unwrap().visible(), [call]);
+/// ```
+pub struct DrawLog {
+    /// Draw calls of the current frame.
+    calls: Stack<DrawCall, MAX_DRAW_CALLS>,
+    /// Sprites drawn and not erased since, from the first drawn to the last.
+    visible: Stack<DrawCall, MAX_DRAW_CALLS>,
+    /// Number of draw calls of the current frame which have not been recorded.
+    dropped: usize,
+}
+
+impl DrawLog {
+    /// Creates an empty log.
+    pub fn new() -> Self {
+        Self {
+            calls: Stack::new(),
+            visible: Stack::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Returns the draw calls of the current frame, in the order they were executed.
+    pub fn calls(&self) -> &[DrawCall] {
+        self.calls.as_slice()
+    }
+
+    /// Returns the sprites still visible on the display, from the first drawn to the last.
+    pub fn visible(&self) -> &[DrawCall] {
+        self.visible.as_slice()
+    }
+
+    /// Returns the number of draw calls of the current frame which have not been recorded,
+    /// beyond [MAX_DRAW_CALLS].
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Forgets the draw calls of the current frame.
+    pub fn clear(&mut self) {
+        self.calls.clear();
+        self.dropped = 0;
+    }
+
+    /// Records given draw `call`, and updates the visible sprites.
+    pub(crate) fn record(&mut self, call: DrawCall) {
+        if self.calls.push(call).is_err() {
+            self.dropped += 1;
+        }
+        let erased = self.visible.as_slice().iter().rposition(|visible| {
+            DrawCall {
+                address: call.address,
+                collision: call.collision,
+                ..*visible
+            } == call
+        });
+        match erased {
+            Option::Some(index) => self.visible.remove(index),
+            Option::None => {
+                if self.visible.push(call).is_err() {
+                    // Keep the most recent sprites.
+                    self.visible.remove(0);
+                    let _ = self.visible.push(call);
+                }
+            }
+        }
+    }
+
+    /// Forgets the visible sprites, when the whole display changes.
+    pub(crate) fn clear_visible(&mut self) {
+        self.visible.clear();
+    }
+}
+
+impl Default for DrawLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An image replacing a sprite, made of red, green, blue and alpha bytes row after row.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpriteImage {
+    /// Width in pixels.
+    width: usize,
+    /// Height in pixels.
+    height: usize,
+    /// 4 bytes per pixel.
+    pixels: alloc::vec::Vec<u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl SpriteImage {
+    /// Creates an image of `width` by `height` `pixels`, as red, green, blue and alpha bytes.
+    /// Returns `None` if the image is empty or if there are not 4 bytes per pixel.
+    pub fn new(width: usize, height: usize, pixels: alloc::vec::Vec<u8>) -> Option<Self> {
+        if width == 0 || height == 0 || pixels.len() != width * height * 4 {
+            return Option::None;
+        }
+        Option::Some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Returns the width in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the height in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the pixels as red, green, blue and alpha bytes, row after row.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Returns the red, green, blue and alpha bytes of the pixel at column `x` and row `y`.
+    pub fn rgba8(&self, x: usize, y: usize) -> [u8; 4] {
+        let start = (y * self.width + x) * 4;
+        [
+            self.pixels[start],
+            self.pixels[start + 1],
+            self.pixels[start + 2],
+            self.pixels[start + 3],
+        ]
+    }
+}
+
+/// Images replacing sprites, identified by their [sprite_hash], to remaster games with
+/// high-resolution art.
+///
+/// The sprites visible on the display according to the emulator's [DrawLog] are drawn with
+/// their image, stretched over the pixels they cover.
+/// ```
+/// let mut emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+/// emulator.set_draw_log(Some(chirp8::DrawLog::new()));
+/// emulator.load_rom(&[0xF0, 0x29, 0xD0, 0x05]); // Draw the font sprite of 0.
+/// emulator.take_steps(2);
+///
+/// let mut pack = chirp8::ReplacementPack::new();
+/// let hash = emulator.draw_log().unwrap().calls()[0].hash;
+/// let red = chirp8::SpriteImage::new(1, 1, vec![0xFF, 0x00, 0x00, 0xFF]).unwrap();
+/// pack.insert(hash, red);
+/// let (width, height) = chirp8::render_size(&emulator, 4);
+/// let mut frame = vec![0; width * height * 4];
+/// assert!(pack.composite_rgba8(&emulator, &chirp8::Palette::OCTO, 4, &mut frame));
+/// assert_eq!(frame[..4], [0xFF, 0x00, 0x00, 0xFF]);
+/// ```
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, Default)]
+pub struct ReplacementPack {
+    /// Image of each sprite hash.
+    images: alloc::collections::BTreeMap<u64, SpriteImage>,
+}
+
+#[cfg(feature = "alloc")]
+impl ReplacementPack {
+    /// Creates a pack without any image.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Replaces the sprites of given `hash` with `image`, returning the image replaced before.
+    pub fn insert(&mut self, hash: u64, image: SpriteImage) -> Option<SpriteImage> {
+        self.images.insert(hash, image)
+    }
+
+    /// Stops replacing the sprites of given `hash`, returning their image.
+    pub fn remove(&mut self, hash: u64) -> Option<SpriteImage> {
+        self.images.remove(&hash)
+    }
+
+    /// Returns the image replacing the sprites of given `hash`, if any.
+    pub fn get(&self, hash: u64) -> Option<&SpriteImage> {
+        self.images.get(&hash)
+    }
+
+    /// Returns the image replacing the sprite drawn by given `call`, if any.
+    pub fn replacement(&self, call: &DrawCall) -> Option<&SpriteImage> {
+        self.get(call.hash)
+    }
+
+    /// Returns the number of replaced sprites.
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Indicates if no sprite is replaced.
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// Writes the image of the `emulator`'s display to `output` like [render_rgba8], then draws
+    /// the image of each visible sprite of its [DrawLog] over it. The area of a replaced sprite
+    /// is cleared to the color of unlit pixels, then its image is stretched over it and blended
+    /// according to its alpha. Sprites wrapping around the edges are clipped.
+    /// Returns false if `output` is too short.
+    pub fn composite_rgba8<B: Bus, D: DisplayStorage>(
+        &self,
+        emulator: &Chirp8<B, D>,
+        palette: &Palette,
+        scale: usize,
+        output: &mut [u8],
+    ) -> bool {
+        if !render_rgba8(emulator, palette, scale, output) {
+            return false;
+        }
+        let Option::Some(log) = emulator.draw_log() else {
+            return true;
+        };
+        let scale = scale.max(1);
+        let (width, height) = render_size(emulator, scale);
+        let background = palette.rgba8(PIXEL_OFF);
+        for call in log.visible() {
+            let Option::Some(image) = self.replacement(call) else {
+                continue;
+            };
+            let (left, top) = (call.x as usize * scale, call.y as usize * scale);
+            let sprite_width = call.width as usize * scale;
+            let sprite_height = call.height as usize * scale;
+            for y in top..(top + sprite_height).min(height) {
+                let image_y = (y - top) * image.height / sprite_height;
+                for x in left..(left + sprite_width).min(width) {
+                    let image_x = (x - left) * image.width / sprite_width;
+                    let source = image.rgba8(image_x, image_y);
+                    let alpha = source[3] as u32;
+                    let pixel = &mut output[((y * width + x) * 4)..][..4];
+                    for channel in 0..3 {
+                        pixel[channel] = ((source[channel] as u32 * alpha
+                            + background[channel] as u32 * (u8::MAX as u32 - alpha))
+                            / u8::MAX as u32) as u8;
+                    }
+                    pixel[3] = u8::MAX;
+                }
+            }
+        }
+        true
+    }
+}
+
+// The draw log is only attached to emulators with the `alloc` feature.
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::{Chirp8, Chirp8Mode};
+
+    #[test]
+    fn draw_log_calls_and_visible_sprites() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x05, // v0 = 5
+            0xA2, 0x0C, // I = 20C
+            0xD0, 0x02, // Draw v0 v0 2
+            0xD0, 0x02, // Erase it
+            0xD0, 0x12, // Draw v0 v1 2
+            0x12, 0x0A, // Loop
+            0xF0, 0x90, // Sprite
+        ];
+        let mut emulator = Chirp8::new(Chirp8Mode::SuperChipModern);
+        emulator.load_rom(&rom);
+        emulator.take_steps(3);
+        assert!(emulator.draw_log().is_none());
+
+        emulator.reset();
+        emulator.set_draw_log(Option::Some(DrawLog::new()));
+        emulator.take_steps(3);
+        let drawn = DrawCall {
+            address: 0x20C,
+            x: 5,
+            y: 5,
+            width: 8,
+            height: 2,
+            planes: 0b1,
+            length: 2,
+            high_resolution: false,
+            collision: 0,
+            hash: sprite_hash(8, 2, &[0xF0, 0x90]),
+        };
+        let log = emulator.draw_log().unwrap();
+        assert_eq!(log.calls(), [drawn]);
+        assert_eq!(log.visible(), [drawn]);
+
+        emulator.take_steps(2);
+        // Super-Chip counts the colliding rows.
+        let erased = DrawCall {
+            collision: 2,
+            ..drawn
+        };
+        let moved = DrawCall { y: 0, ..drawn };
+        let log = emulator.draw_log().unwrap();
+        assert_eq!(log.calls(), [drawn, erased, moved]);
+        assert_eq!(log.visible(), [moved]);
+        assert_eq!(log.dropped(), 0);
+
+        // The calls are forgotten at the next frame, the visible sprites are kept.
+        emulator.run_frame();
+        emulator.step();
+        let log = emulator.draw_log().unwrap();
+        assert!(log.calls().is_empty());
+        assert_eq!(log.visible(), [moved]);
+    }
+
+    #[test]
+    fn draw_log_planes_and_large_sprites() {
+        #[rustfmt::skip]
+        let rom = [
+            0x00, 0xFF, // High resolution
+            0xF3, 0x01, // Select both planes
+            0x60, 0x7C, // v0 = 124
+            0xD0, 0x10, // Draw large sprite v0 v1
+            0x00, 0xE0, // Clear
+        ];
+        let mut emulator = Chirp8::new(Chirp8Mode::XOChip);
+        emulator.set_draw_log(Option::Some(DrawLog::new()));
+        emulator.load_rom(&rom);
+        emulator.take_steps(4);
+        let call = emulator.draw_log().unwrap().calls()[0];
+        assert_eq!((call.x, call.y, call.width, call.height), (124, 0, 16, 16));
+        assert_eq!((call.planes, call.length), (0b11, 64));
+        assert!(call.high_resolution);
+        assert_eq!(emulator.draw_log().unwrap().visible(), [call]);
+
+        emulator.step();
+        assert!(emulator.draw_log().unwrap().visible().is_empty());
+        assert_eq!(emulator.draw_log().unwrap().calls(), [call]);
+    }
+
+    #[test]
+    fn replacement_pack_composite() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x01, // v0 = 1
+            0xA2, 0x06, // I = 206
+            0xD0, 0x01, // Draw v0 v0 1
+            0xFF, 0x00, // Sprite
+        ];
+        let mut emulator = Chirp8::new(Chirp8Mode::CosmacChip8);
+        emulator.set_draw_log(Option::Some(DrawLog::new()));
+        emulator.load_rom(&rom);
+        emulator.take_steps(3);
+
+        let hash = sprite_hash(8, 1, &[0xFF]);
+        let mut pack = ReplacementPack::new();
+        assert!(SpriteImage::new(2, 1, alloc::vec![0; 4]).is_none());
+        // Opaque red on the left half, transparent on the right half.
+        let pixels = alloc::vec![0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+        let image = SpriteImage::new(2, 1, pixels).unwrap();
+        assert!(pack.insert(hash, image.clone()).is_none());
+        assert_eq!(pack.len(), 1);
+        let call = emulator.draw_log().unwrap().calls()[0];
+        assert_eq!(pack.replacement(&call), Option::Some(&image));
+
+        let palette = Palette::monochrome(0x000000, 0xFFFFFF);
+        let mut output = alloc::vec![0; 128 * 64 * 4];
+        assert!(!pack.composite_rgba8(&emulator, &palette, 2, &mut output[1..]));
+        assert!(pack.composite_rgba8(&emulator, &palette, 2, &mut output));
+        let pixel = |x: usize, y: usize| output[((y * 128 + x) * 4)..][..4].to_vec();
+        assert_eq!(pixel(1, 1), [0x00, 0x00, 0x00, 0xFF]);
+        assert_eq!(pixel(2, 2), [0xFF, 0x00, 0x00, 0xFF]);
+        assert_eq!(pixel(9, 3), [0xFF, 0x00, 0x00, 0xFF]);
+        assert_eq!(pixel(10, 3), [0x00, 0x00, 0x00, 0xFF]);
+        assert_eq!(pixel(17, 2), [0x00, 0x00, 0x00, 0xFF]);
+        assert_eq!(pixel(18, 2), [0x00, 0x00, 0x00, 0xFF]);
+
+        // Without the pack, the sprite is white.
+        assert!(pack.remove(hash).is_some() && pack.is_empty());
+        assert!(pack.composite_rgba8(&emulator, &palette, 2, &mut output));
+        assert_eq!(output[((2 * 128 + 10) * 4)..][..4], [0xFF; 4]);
+    }
+}
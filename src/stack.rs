@@ -45,6 +45,29 @@ impl<T: Default + Copy, const N: usize> Stack<T, N> {
         }
     }
 
+    /// Returns the number of elements in the stack.
+    pub fn len(&self) -> usize {
+        self.ptr
+    }
+
+    /// Returns the elements in the stack, from bottom to top.
+    pub fn as_slice(&self) -> &[T] {
+        &self.data[..self.ptr]
+    }
+
+    /// Removes all elements.
+    pub fn clear(&mut self) {
+        self.ptr = 0;
+    }
+
+    /// Removes the element at given `index` from the bottom, moving the elements above it down.
+    pub fn remove(&mut self, index: usize) {
+        if index < self.ptr {
+            self.data[index..self.ptr].rotate_left(1);
+            self.ptr -= 1;
+        }
+    }
+
     pub fn pop(&mut self) -> Result<T, StackError> {
         if self.ptr > 0 {
             self.ptr -= 1;
@@ -0,0 +1,104 @@
+/// Sequential writer of the big endian fields of a save state.
+pub(crate) struct StateWriter<'a> {
+    data: &'a mut [u8],
+    position: usize,
+}
+
+impl<'a> StateWriter<'a> {
+    /// Creates a writer filling `data`, which must be large enough for all written fields.
+    pub(crate) fn new(data: &'a mut [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.data[self.position..self.position + bytes.len()].copy_from_slice(bytes);
+        self.position += bytes.len();
+    }
+
+    /// Returns the next `length` bytes, to be filled by the caller.
+    pub(crate) fn slice(&mut self, length: usize) -> &mut [u8] {
+        self.position += length;
+        &mut self.data[self.position - length..self.position]
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes(&[value]);
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    /// Writes up to 16 booleans as the bits of a 16 bits word, first one being the lowest bit.
+    pub(crate) fn bits(&mut self, values: &[bool]) {
+        self.u16(
+            values
+                .iter()
+                .enumerate()
+                .fold(0, |bits, (i, value)| bits | ((*value as u16) << i)),
+        );
+    }
+
+    /// Returns the number of bytes written so far.
+    pub(crate) fn position(&self) -> usize {
+        self.position
+    }
+}
+
+/// Sequential reader of the big endian fields of a save state.
+pub(crate) struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    /// Creates a reader of `data`, which must be large enough for all read fields.
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub(crate) fn bytes(&mut self, length: usize) -> &'a [u8] {
+        self.position += length;
+        &self.data[self.position - length..self.position]
+    }
+
+    pub(crate) fn u8(&mut self) -> u8 {
+        self.bytes(1)[0]
+    }
+
+    pub(crate) fn u16(&mut self) -> u16 {
+        u16::from_be_bytes(self.bytes(2).try_into().unwrap())
+    }
+
+    pub(crate) fn u32(&mut self) -> u32 {
+        u32::from_be_bytes(self.bytes(4).try_into().unwrap())
+    }
+
+    pub(crate) fn u64(&mut self) -> u64 {
+        u64::from_be_bytes(self.bytes(8).try_into().unwrap())
+    }
+
+    pub(crate) fn bool(&mut self) -> bool {
+        self.u8() != 0
+    }
+
+    /// Reads booleans written with [StateWriter::bits].
+    pub(crate) fn bits(&mut self, values: &mut [bool]) {
+        let bits = self.u16();
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = bits & (1 << i) != 0;
+        }
+    }
+}
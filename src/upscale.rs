@@ -0,0 +1,343 @@
+use crate::{render_size, Bus, Chirp8, DisplayStorage, Palette};
+
+/// Darkness of the scanlines of [Upscaler::Crt], out of 255.
+const CRT_SCANLINE_DARKNESS: u8 = 128;
+/// Darkness of the two dimmed channels of each column of [Upscaler::Crt], out of 255.
+const CRT_MASK_DARKNESS: u8 = 64;
+
+/// Filter enlarging the display for pixel art to look smooth or like on an old monitor.
+///
+/// Filters work on the pixels of the current resolution, 64x32 in low resolution and 128x64 in
+/// high resolution, and compare pixel values, so that any [Palette] can be used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Upscaler {
+    /// Each pixel is repeated `scale` times in both directions, like [crate::render_rgba8].
+    Nearest(usize),
+    /// Scale2x, also known as EPX : doubles the size, rounding the diagonal edges.
+    Scale2x,
+    /// Scale3x : triples the size, rounding the diagonal edges.
+    Scale3x,
+    /// Doubles the size like [Upscaler::Scale2x], in the manner of hq2x : the rounded edges
+    /// blend the colors of both sides instead of copying one of them.
+    Hq2x,
+    /// Each pixel is repeated `scale` times in both directions, the last of these rows being
+    /// darker by `darkness` out of 255, like the scanlines of a CRT.
+    Scanlines {
+        /// Size of each pixel, at least 2 for the scanlines to be visible.
+        scale: usize,
+        /// How much darker the scanlines are, out of 255.
+        darkness: u8,
+    },
+    /// Scanlines with the aperture grille of a CRT, the columns of the image showing mostly red,
+    /// green and blue in turn.
+    Crt {
+        /// Size of each pixel, at least 3 for the grille to be visible.
+        scale: usize,
+    },
+}
+
+impl Upscaler {
+    /// Returns the number of pixels of the image per pixel of the display, in both directions.
+    pub fn factor(&self) -> usize {
+        match *self {
+            Upscaler::Nearest(scale) => scale.max(1),
+            Upscaler::Scale2x | Upscaler::Hq2x => 2,
+            Upscaler::Scale3x => 3,
+            Upscaler::Scanlines { scale, .. } | Upscaler::Crt { scale } => scale.max(1),
+        }
+    }
+}
+
+/// Returns `color` darker by `darkness` out of 255, keeping its alpha.
+fn darken(color: [u8; 4], darkness: u8) -> [u8; 4] {
+    let keep = (u8::MAX - darkness) as u32;
+    let mut darker = color;
+    for channel in &mut darker[..3] {
+        *channel = (*channel as u32 * keep / 255) as u8;
+    }
+    darker
+}
+
+/// Returns the mix of three quarters of `color` and a quarter of `other`.
+fn blend(color: [u8; 4], other: [u8; 4]) -> [u8; 4] {
+    let mut mixed = color;
+    for (channel, other) in mixed.iter_mut().zip(other) {
+        *channel = ((*channel as u32 * 3 + other as u32) / 4) as u8;
+    }
+    mixed
+}
+
+/// Returns the values of the Scale2x block of the pixel `e` and its `neighbors`
+/// `[a, b, c, d, e, f, g, h, i]` row after row, along with whether each value replaces `e`.
+fn scale2x(neighbors: [u8; 9]) -> [(u8, bool); 4] {
+    let [_, b, _, d, e, f, _, h, _] = neighbors;
+    let corner = |value: u8, condition: bool| if condition { (value, true) } else { (e, false) };
+    [
+        corner(d, d == b && b != f && d != h),
+        corner(f, b == f && b != d && f != h),
+        corner(d, d == h && d != b && h != f),
+        corner(f, h == f && d != h && b != f),
+    ]
+}
+
+/// Returns the values of the Scale3x block of the pixel `e` and its `neighbors`
+/// `[a, b, c, d, e, f, g, h, i]`, row after row.
+fn scale3x(neighbors: [u8; 9]) -> [u8; 9] {
+    let [a, b, c, d, e, f, g, h, i] = neighbors;
+    let top_left = d == b && d != h && b != f;
+    let top_right = b == f && b != d && f != h;
+    let bottom_left = d == h && d != b && h != f;
+    let bottom_right = h == f && d != h && b != f;
+    let pick = |value: u8, condition: bool| if condition { value } else { e };
+    [
+        pick(d, top_left),
+        pick(b, (top_left && e != c) || (top_right && e != a)),
+        pick(f, top_right),
+        pick(d, (top_left && e != g) || (bottom_left && e != a)),
+        e,
+        pick(f, (top_right && e != i) || (bottom_right && e != c)),
+        pick(d, bottom_left),
+        pick(h, (bottom_left && e != i) || (bottom_right && e != g)),
+        pick(f, bottom_right),
+    ]
+}
+
+/// Returns the width and height in pixels of the image of the `emulator`'s display enlarged by
+/// given `upscaler`.
+pub fn upscale_size<B: Bus, D: DisplayStorage>(
+    emulator: &Chirp8<B, D>,
+    upscaler: &Upscaler,
+) -> (usize, usize) {
+    render_size(emulator, upscaler.factor())
+}
+
+/// Writes the image of the `emulator`'s display enlarged by the `upscaler` to `output` as red,
+/// green, blue and alpha bytes, with the colors of the `palette`.
+/// Returns false if `output` is shorter than 4 bytes per pixel of the [upscale_size].
+/// ```
+/// let emulator = chirp8::Chirp8::new(chirp8::Chirp8Mode::CosmacChip8);
+/// let upscaler = chirp8::Upscaler::Scale3x;
+/// let (width, height) = chirp8::upscale_size(&emulator, &upscaler);
+/// assert_eq!((width, height), (192, 96));
+/// let mut frame = vec![0; width * height * 4];
+/// assert!(chirp8::upscale_rgba8(&emulator, &chirp8::Palette::AMBER, &upscaler, &mut frame));
+/// ```
+pub fn upscale_rgba8<B: Bus, D: DisplayStorage>(
+    emulator: &Chirp8<B, D>,
+    palette: &Palette,
+    upscaler: &Upscaler,
+    output: &mut [u8],
+) -> bool {
+    let (width, height) = upscale_size(emulator, upscaler);
+    if output.len() < width * height * 4 {
+        return false;
+    }
+    let output: &mut [[u8; 4]] = output.as_chunks_mut().0;
+    let factor = upscaler.factor();
+    let (columns, rows) = (width / factor, height / factor);
+    // Pixels beyond the borders repeat the pixels of the borders.
+    let value = |x: usize, y: usize| emulator.get_pixel(x.min(columns - 1), y);
+    let palette = palette.with_planes(emulator.display_planes());
+
+    let mut block = [[0; 4]; 9];
+    for y in 0..rows {
+        let (above, below) = (y.saturating_sub(1), (y + 1).min(rows - 1));
+        for x in 0..columns {
+            let (left, right) = (x.saturating_sub(1), x + 1);
+            let neighbors = [
+                value(left, above),
+                value(x, above),
+                value(right, above),
+                value(left, y),
+                value(x, y),
+                value(right, y),
+                value(left, below),
+                value(x, below),
+                value(right, below),
+            ];
+            let color = palette.rgba8(neighbors[4]);
+            let block_color = |column: usize, row: usize| match *upscaler {
+                Upscaler::Scanlines { darkness, .. } if row == factor - 1 && factor > 1 => {
+                    darken(color, darkness)
+                }
+                Upscaler::Crt { .. } => {
+                    let mut color = color;
+                    if row == factor - 1 && factor > 1 {
+                        color = darken(color, CRT_SCANLINE_DARKNESS);
+                    }
+                    let dimmed = darken(color, CRT_MASK_DARKNESS);
+                    let channel = (x * factor + column) % 3;
+                    for other in (0..3).filter(|other| *other != channel) {
+                        color[other] = dimmed[other];
+                    }
+                    color
+                }
+                _ => color,
+            };
+            match upscaler {
+                Upscaler::Scale2x => {
+                    for (index, (value, _)) in scale2x(neighbors).into_iter().enumerate() {
+                        block[index] = palette.rgba8(value);
+                    }
+                }
+                Upscaler::Hq2x => {
+                    for (index, (value, replaced)) in scale2x(neighbors).into_iter().enumerate() {
+                        block[index] = if replaced {
+                            blend(palette.rgba8(value), color)
+                        } else {
+                            color
+                        };
+                    }
+                }
+                Upscaler::Scale3x => {
+                    for (index, value) in scale3x(neighbors).into_iter().enumerate() {
+                        block[index] = palette.rgba8(value);
+                    }
+                }
+                _ => {}
+            }
+            for row in 0..factor {
+                let start = (y * factor + row) * width + x * factor;
+                for (column, pixel) in output[start..(start + factor)].iter_mut().enumerate() {
+                    *pixel = match upscaler {
+                        Upscaler::Scale2x | Upscaler::Hq2x | Upscaler::Scale3x => {
+                            block[row * factor + column]
+                        }
+                        _ => block_color(column, row),
+                    };
+                }
+            }
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Chirp8Mode;
+
+    const X: u8 = 0xFF;
+
+    /// Returns an emulator in high resolution showing a diagonal of two pixels from 1, 1.
+    fn diagonal() -> Chirp8 {
+        #[rustfmt::skip]
+        let rom = [
+            0x00, 0xFF, // High resolution
+            0x60, 0x01, // v0 = 1
+            0xA2, 0x0A, // I = 20A
+            0xD0, 0x02, // Draw v0 v0 2
+            0x12, 0x08, // Loop
+            0x80, 0x40, // Sprite
+        ];
+        let mut emulator = Chirp8::new(Chirp8Mode::SuperChipModern);
+        emulator.load_rom(&rom);
+        emulator.take_steps(4);
+        emulator
+    }
+
+    /// Returns the red channel of the top left NxN pixels of the `upscaler` image of the
+    /// `emulator`'s display, in black and white.
+    fn top_left<const N: usize>(emulator: &Chirp8, upscaler: Upscaler) -> [[u8; N]; N] {
+        let palette = Palette::monochrome(0x000000, 0xFFFFFF);
+        let (width, height) = upscale_size(emulator, &upscaler);
+        let mut output = [0; 384 * 192 * 4];
+        let output = &mut output[..(width * height * 4)];
+        assert!(!upscale_rgba8(
+            emulator,
+            &palette,
+            &upscaler,
+            &mut output[1..]
+        ));
+        assert!(upscale_rgba8(emulator, &palette, &upscaler, output));
+        core::array::from_fn(|y| core::array::from_fn(|x| output[(y * width + x) * 4]))
+    }
+
+    #[test]
+    fn upscale_scale2x_and_hq2x() {
+        let emulator = diagonal();
+        assert_eq!(upscale_size(&emulator, &Upscaler::Scale2x), (256, 128));
+        #[rustfmt::skip]
+        assert_eq!(top_left(&emulator, Upscaler::Scale2x), [
+            [0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0],
+            [0, 0, X, X, 0, 0],
+            [0, 0, X, X, X, 0],
+            [0, 0, 0, X, X, X],
+            [0, 0, 0, 0, X, X],
+        ]);
+        const H: u8 = 0xBF;
+        #[rustfmt::skip]
+        assert_eq!(top_left(&emulator, Upscaler::Hq2x), [
+            [0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0],
+            [0, 0, X, X, 0, 0],
+            [0, 0, X, X, H, 0],
+            [0, 0, 0, H, X, X],
+            [0, 0, 0, 0, X, X],
+        ]);
+    }
+
+    #[test]
+    fn upscale_scale3x() {
+        let emulator = diagonal();
+        assert_eq!(upscale_size(&emulator, &Upscaler::Scale3x), (384, 192));
+        #[rustfmt::skip]
+        assert_eq!(top_left(&emulator, Upscaler::Scale3x), [
+            [0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, X, X, X, 0, 0, 0],
+            [0, 0, 0, X, X, X, 0, 0, 0],
+            [0, 0, 0, X, X, X, X, 0, 0],
+            [0, 0, 0, 0, 0, X, X, X, X],
+            [0, 0, 0, 0, 0, 0, X, X, X],
+            [0, 0, 0, 0, 0, 0, X, X, X],
+        ]);
+    }
+
+    #[test]
+    fn upscale_nearest_and_scanlines() {
+        let emulator = diagonal();
+        #[rustfmt::skip]
+        assert_eq!(top_left(&emulator, Upscaler::Nearest(2)), [
+            [0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0],
+            [0, 0, X, X, 0, 0],
+            [0, 0, X, X, 0, 0],
+            [0, 0, 0, 0, X, X],
+            [0, 0, 0, 0, X, X],
+        ]);
+        let upscaler = Upscaler::Scanlines {
+            scale: 2,
+            darkness: 0xFF,
+        };
+        #[rustfmt::skip]
+        assert_eq!(top_left(&emulator, upscaler), [
+            [0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0],
+            [0, 0, X, X, 0, 0],
+            [0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 0, X, X],
+            [0, 0, 0, 0, 0, 0],
+        ]);
+    }
+
+    #[test]
+    fn upscale_crt() {
+        let emulator = diagonal();
+        let palette = Palette::monochrome(0x000000, 0xFFFFFF);
+        let upscaler = Upscaler::Crt { scale: 3 };
+        let mut output = [0; 384 * 192 * 4];
+        assert!(upscale_rgba8(&emulator, &palette, &upscaler, &mut output));
+        let pixel = (3 * 384 + 3) * 4;
+        #[rustfmt::skip]
+        assert_eq!(output[pixel..(pixel + 12)], [
+            0xFF, 0xBF, 0xBF, 0xFF,
+            0xBF, 0xFF, 0xBF, 0xFF,
+            0xBF, 0xBF, 0xFF, 0xFF,
+        ]);
+        let scanline = (5 * 384 + 3) * 4;
+        assert_eq!(output[scanline..(scanline + 4)], [0x7F, 0x5F, 0x5F, 0xFF]);
+    }
+}
@@ -1,9 +1,12 @@
-use chirp8::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+// The golden images are compared with programs needing the whole memory of the mode.
+#![cfg(feature = "std")]
+
+use chirp8::{DisplayPixels, DISPLAY_HEIGHT, DISPLAY_WIDTH};
 
 fn print_display(buffer: &chirp8::DisplayBuffer) {
-    for row in buffer {
-        for pixel in row {
-            if *pixel != 0 {
+    for y in 0..DISPLAY_HEIGHT {
+        for x in 0..DISPLAY_WIDTH {
+            if buffer.pixel(x, y) != 0 {
                 print!("\u{25A0}");
             } else {
                 print!("-");
@@ -20,10 +23,10 @@ fn assert_screen_eq(buffer: &chirp8::DisplayBuffer, expected: &bmp::Image, compa
     for i in 0..DISPLAY_HEIGHT {
         for j in 0..DISPLAY_WIDTH {
             if compare_value {
-                assert_eq!(buffer[i][j], expected.get_pixel(j as u32, i as u32).r);
+                assert_eq!(buffer.pixel(j, i), expected.get_pixel(j as u32, i as u32).r);
             } else {
                 assert_eq!(
-                    buffer[i][j] == 0,
+                    buffer.pixel(j, i) == 0,
                     expected.get_pixel(j as u32, i as u32).r == 0
                 );
             }